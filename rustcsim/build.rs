@@ -0,0 +1,13 @@
+fn main() {
+    // Regenerate the header whenever the API changes, so it can't drift from the code
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir)).unwrap();
+    cbindgen::Builder::new()
+        .with_crate(&crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("Can't generate the C header")
+        .write_to_file(format!("{}/include/rustcsim.h", crate_dir));
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+}
@@ -0,0 +1,123 @@
+#ifndef RUSTCSIM_H
+#define RUSTCSIM_H
+
+/* Generated by cbindgen from src/lib.rs. Don't edit it by hand; rebuild the crate instead. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Bump this whenever a function or struct here changes in a way old callers would notice
+ */
+#define RUSTSIM_ABI_VERSION 1
+
+/**
+ * What happened, for every call that can fail
+ */
+typedef enum RustsimStatus {
+  RUSTSIM_STATUS_OK = 0,
+  /**
+   * A pointer argument was NULL
+   */
+  RUSTSIM_STATUS_NULL_POINTER = 1,
+  /**
+   * The simulator hit a bug. The simulation is still safe to destroy.
+   */
+  RUSTSIM_STATUS_PANICKED = 2,
+} RustsimStatus;
+
+/**
+ * A simulation's parameters. Only ever handled through a pointer.
+ */
+typedef struct RustsimSimulation RustsimSimulation;
+
+/**
+ * Counters and rates from one or many simulated years, as rustsim's Summary tuple
+ */
+typedef struct RustsimSummary {
+  uint64_t successful_transactions;
+  uint64_t successful_sales;
+  uint64_t failed_transactions;
+  uint64_t failed_sales;
+  /**
+   * 1.0 if there weren't any customers at all
+   */
+  double transaction_fill_rate;
+  /**
+   * 1.0 if there wasn't any demand at all
+   */
+  double unit_fill_rate;
+} RustsimSummary;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * The version of this API the library was built with, to check against RUSTSIM_ABI_VERSION
+ * in the header you compiled against
+ */
+uint32_t rustsim_abi_version(void);
+
+/**
+ * Create a simulation, like rustsim.Simulation() in Python
+ *
+ * A zipf exponent of zero or less means the default. `seed` may be NULL for different results
+ * every time; otherwise the same calls give the same results, on any platform, and match
+ * the Python module's. Returns NULL if lead_time or order_quantity is zero. Free the result
+ * with rustsim_simulation_free().
+ *
+ * # Safety
+ *
+ * `seed` must be NULL or point to a uint64_t.
+ */
+struct RustsimSimulation *rustsim_simulation_new(size_t safety_stock,
+                                                 size_t lead_time,
+                                                 size_t order_quantity,
+                                                 double job_lot_zipf,
+                                                 double itemwise_traffic_zipf,
+                                                 const uint64_t *seed);
+
+/**
+ * Destroy a simulation made by rustsim_simulation_new(). NULL is fine, and does nothing.
+ *
+ * # Safety
+ *
+ * `sim` must have come from rustsim_simulation_new(), and not been freed already.
+ */
+void rustsim_simulation_free(struct RustsimSimulation *sim);
+
+/**
+ * Simulate one year, writing the results to `out`
+ *
+ * # Safety
+ *
+ * `sim` must be a live simulation, and `out` must point to a RustsimSummary.
+ */
+enum RustsimStatus rustsim_simulate_demand(const struct RustsimSimulation *sim,
+                                           size_t starting_quantity,
+                                           struct RustsimSummary *out);
+
+/**
+ * Repeat the simulation `count` times and write the totals to `out`
+ *
+ * This runs on the calling thread. Separate simulations can run on separate threads at once,
+ * and so can separate calls on the same one, since nothing here changes it.
+ *
+ * # Safety
+ *
+ * `sim` must be a live simulation, and `out` must point to a RustsimSummary.
+ */
+enum RustsimStatus rustsim_repeat_simulate_demand(const struct RustsimSimulation *sim,
+                                                  size_t starting_quantity,
+                                                  size_t count,
+                                                  struct RustsimSummary *out);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* RUSTCSIM_H */
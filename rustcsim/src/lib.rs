@@ -0,0 +1,160 @@
+//! A C API for the simulator, so C, C++, Java (through JNI or Panama) and .NET (through
+//! P/Invoke) services can embed it directly
+//!
+//! The API is small on purpose: create a simulation, run it, read the results out of a plain
+//! struct, and destroy it. Nothing here panics across the boundary; every function that can
+//! fail returns a RustsimStatus instead. build.rs writes include/rustcsim.h from this file.
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+/// Bump this whenever a function or struct here changes in a way old callers would notice
+pub const RUSTSIM_ABI_VERSION: u32 = 1;
+
+/// What happened, for every call that can fail
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RustsimStatus {
+    Ok = 0,
+    /// A pointer argument was NULL
+    NullPointer = 1,
+    /// The simulator hit a bug. The simulation is still safe to destroy.
+    Panicked = 2,
+}
+
+/// Counters and rates from one or many simulated years, as rustsim's Summary tuple
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RustsimSummary {
+    pub successful_transactions: u64,
+    pub successful_sales: u64,
+    pub failed_transactions: u64,
+    pub failed_sales: u64,
+    /// 1.0 if there weren't any customers at all
+    pub transaction_fill_rate: f64,
+    /// 1.0 if there wasn't any demand at all
+    pub unit_fill_rate: f64,
+}
+
+impl From<rustsim::Summary> for RustsimSummary {
+    fn from((st, ss, ft, fs, tr, ur): rustsim::Summary) -> RustsimSummary {
+        RustsimSummary {
+            successful_transactions: st as u64,
+            successful_sales: ss as u64,
+            failed_transactions: ft as u64,
+            failed_sales: fs as u64,
+            transaction_fill_rate: tr,
+            unit_fill_rate: ur,
+        }
+    }
+}
+
+/// A simulation's parameters. Only ever handled through a pointer.
+pub struct RustsimSimulation {
+    sim: rustsim::Simulation,
+}
+
+/// The version of this API the library was built with, to check against RUSTSIM_ABI_VERSION
+/// in the header you compiled against
+#[no_mangle]
+pub extern "C" fn rustsim_abi_version() -> u32 {
+    RUSTSIM_ABI_VERSION
+}
+
+/// Create a simulation, like rustsim.Simulation() in Python
+///
+/// A zipf exponent of zero or less means the default. `seed` may be NULL for different results
+/// every time; otherwise the same calls give the same results, on any platform, and match
+/// the Python module's. Returns NULL if lead_time or order_quantity is zero. Free the result
+/// with rustsim_simulation_free().
+///
+/// # Safety
+///
+/// `seed` must be NULL or point to a uint64_t.
+#[no_mangle]
+pub unsafe extern "C" fn rustsim_simulation_new(
+    safety_stock: usize,
+    lead_time: usize,
+    order_quantity: usize,
+    job_lot_zipf: f64,
+    itemwise_traffic_zipf: f64,
+    seed: *const u64,
+) -> *mut RustsimSimulation {
+    if lead_time == 0 || order_quantity == 0 {
+        return std::ptr::null_mut();
+    }
+    let exponent = |x: f64| if x > 0.0 { Some(x) } else { None };
+    let seed = seed.as_ref().copied();
+    Box::into_raw(Box::new(RustsimSimulation {
+        sim: rustsim::Simulation::new(
+            safety_stock,
+            lead_time,
+            order_quantity,
+            exponent(job_lot_zipf),
+            exponent(itemwise_traffic_zipf),
+            seed,
+        ),
+    }))
+}
+
+/// Destroy a simulation made by rustsim_simulation_new(). NULL is fine, and does nothing.
+///
+/// # Safety
+///
+/// `sim` must have come from rustsim_simulation_new(), and not been freed already.
+#[no_mangle]
+pub unsafe extern "C" fn rustsim_simulation_free(sim: *mut RustsimSimulation) {
+    if !sim.is_null() {
+        drop(Box::from_raw(sim));
+    }
+}
+
+/// Simulate one year, writing the results to `out`
+///
+/// # Safety
+///
+/// `sim` must be a live simulation, and `out` must point to a RustsimSummary.
+#[no_mangle]
+pub unsafe extern "C" fn rustsim_simulate_demand(
+    sim: *const RustsimSimulation,
+    starting_quantity: usize,
+    out: *mut RustsimSummary,
+) -> RustsimStatus {
+    run(sim, out, |sim| {
+        sim.simulate_summary(starting_quantity, &mut sim.rng())
+    })
+}
+
+/// Repeat the simulation `count` times and write the totals to `out`
+///
+/// This runs on the calling thread. Separate simulations can run on separate threads at once,
+/// and so can separate calls on the same one, since nothing here changes it.
+///
+/// # Safety
+///
+/// `sim` must be a live simulation, and `out` must point to a RustsimSummary.
+#[no_mangle]
+pub unsafe extern "C" fn rustsim_repeat_simulate_demand(
+    sim: *const RustsimSimulation,
+    starting_quantity: usize,
+    count: usize,
+    out: *mut RustsimSummary,
+) -> RustsimStatus {
+    run(sim, out, |sim| sim.repeat_summary(starting_quantity, count))
+}
+
+/// Check the pointers, run `f`, and write its summary to `out`, catching any panic
+unsafe fn run<F>(sim: *const RustsimSimulation, out: *mut RustsimSummary, f: F) -> RustsimStatus
+where
+    F: FnOnce(&rustsim::Simulation) -> rustsim::Summary,
+{
+    let (sim, out) = match (sim.as_ref(), out.as_mut()) {
+        (Some(sim), Some(out)) => (sim, out),
+        _ => return RustsimStatus::NullPointer,
+    };
+    match catch_unwind(AssertUnwindSafe(|| f(&sim.sim))) {
+        Ok(summary) => {
+            *out = summary.into();
+            RustsimStatus::Ok
+        }
+        Err(_) => RustsimStatus::Panicked,
+    }
+}
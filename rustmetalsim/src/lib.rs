@@ -0,0 +1,469 @@
+use metal::{CompileOptions, ComputePipelineState, Device, MTLResourceOptions, MTLSize};
+use pyo3::basic::CompareOp;
+use pyo3::exceptions::{OverflowError, RuntimeError, ValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyDict};
+use pyo3::{PyNativeType, PyObjectProtocol};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rustsim::gpu::{self, work_item_seeds};
+use rustsim::Summary;
+use std::collections::hash_map::DefaultHasher;
+use std::convert::TryFrom;
+use std::ffi::c_void;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+/// The longest lead time the kernel has room for (TRUCK_SLOTS in simulation.metal)
+const MAX_LEAD_TIME: usize = 64;
+
+/// How many threads share the work, like the work items in rustoclsim
+const THREADS: usize = 1000;
+
+/// (starting quantity, safety stock, order quantity), the parameters the kernel takes per run
+type Policy = (usize, usize, usize);
+
+/// The scalar arguments, laid out exactly like Params in simulation.metal
+#[repr(C)]
+struct Params {
+    starting_quantity: i32,
+    lead_time: u32,
+    safety_stock: i32,
+    order_quantity: i32,
+    precomp_size: u32,
+    samples: u32,
+}
+
+/// Simulation parameters
+///
+/// This is the Metal twin of rustoclsim.Simulation, for Macs, where OpenCL is deprecated and
+/// often missing. It runs the same simulation with the same random streams, so a seeded run
+/// should agree with rustoclsim's exactly, given the same number of threads.
+#[pyclass(module = "rustmetalsim")]
+struct Simulation {
+    safety_stock: usize,
+    lead_time: usize,
+    order_quantity: usize,
+    job_lot_zipf: f64,
+    itemwise_traffic_zipf: f64,
+    /// Shared between the copies with_params() makes, since they're 64 MB each
+    job_lot_zipf_precomp: Arc<Vec<u32>>,
+    itemwise_traffic_zipf_precomp: Arc<Vec<u32>>,
+    /// If set, the zipf tables and the thread seeds come from a generator seeded with
+    /// this, so the same call on the same device gives identical results
+    #[pyo3(get)]
+    seed: Option<u64>,
+    /// What the zipf tables were drawn with: the seed, or a random one in its place
+    table_seed: u64,
+    /// (starting quantity, count, call seed) of the last repeat_simulate_demand
+    last_call: Mutex<Option<(usize, usize, u64)>>,
+}
+
+/// Simulation implementation
+///
+/// The following methods are all available from Python
+#[pymethods]
+impl Simulation {
+    /// Implementation of python Simulation.__init__() (wraps rust Simulation::checked())
+    ///
+    /// Every argument is a keyword with a default, like Simulation(safety_stock=20). Raises
+    /// ValueError naming the argument that's out of range, including a lead time longer than
+    /// the kernel can track. The zipf exponents default to 2.75 and 4.0.
+    #[new]
+    #[args("*", safety_stock = "10", lead_time = "3", order_quantity = "7", job_lot_zipf = "None",
+        itemwise_traffic_zipf = "None", seed = "None")]
+    fn init(
+        obj: &PyRawObject,
+        safety_stock: isize,
+        lead_time: isize,
+        order_quantity: isize,
+        job_lot_zipf: Option<f64>,
+        itemwise_traffic_zipf: Option<f64>,
+        seed: Option<u64>,
+    ) -> PyResult<()> {
+        let sim = Simulation::checked(safety_stock, lead_time, order_quantity, job_lot_zipf, itemwise_traffic_zipf, seed)
+            .map_err(ValueError::py_err)?;
+        obj.init(sim);
+        Ok(())
+    }
+
+    /// A copy with some parameters changed, given as keywords like the constructor's
+    ///
+    /// The zipf tables are only drawn again if an exponent or the seed changes.
+    #[args(params = "**")]
+    fn with_params(&self, params: Option<&PyDict>) -> PyResult<Simulation> {
+        let mut safety_stock = self.safety_stock as isize;
+        let mut lead_time = self.lead_time as isize;
+        let mut order_quantity = self.order_quantity as isize;
+        let mut job_lot_zipf = self.job_lot_zipf;
+        let mut itemwise_traffic_zipf = self.itemwise_traffic_zipf;
+        let mut seed = self.seed;
+        for (name, value) in params.into_iter().flatten() {
+            match name.extract()? {
+                "safety_stock" => safety_stock = value.extract()?,
+                "lead_time" => lead_time = value.extract()?,
+                "order_quantity" => order_quantity = value.extract()?,
+                "job_lot_zipf" => job_lot_zipf = value.extract()?,
+                "itemwise_traffic_zipf" => itemwise_traffic_zipf = value.extract()?,
+                "seed" => seed = value.extract()?,
+                name => return Err(ValueError::py_err(
+                    format!("with_params() got an unexpected keyword argument {:?}", name))),
+            }
+        }
+        if job_lot_zipf == self.job_lot_zipf && itemwise_traffic_zipf == self.itemwise_traffic_zipf && seed == self.seed {
+            let (safety_stock, lead_time, order_quantity) = Simulation::check(safety_stock, lead_time, order_quantity, job_lot_zipf, itemwise_traffic_zipf)
+                .map_err(ValueError::py_err)?;
+            return Ok(Simulation {
+                safety_stock,
+                lead_time,
+                order_quantity,
+                job_lot_zipf_precomp: self.job_lot_zipf_precomp.clone(),
+                itemwise_traffic_zipf_precomp: self.itemwise_traffic_zipf_precomp.clone(),
+                last_call: Mutex::new(None),
+                ..*self
+            });
+        }
+        Simulation::checked(safety_stock, lead_time, order_quantity, Some(job_lot_zipf), Some(itemwise_traffic_zipf), seed)
+            .map_err(ValueError::py_err)
+    }
+
+    /// Whether this simulation was given a seed, and so gives the same results every time
+    #[getter]
+    fn deterministic(&self) -> bool {
+        self.seed.is_some()
+    }
+
+    /// Everything needed to re-run this simulation: the backend, device, crate version, seed,
+    /// and every parameter, as a dict
+    ///
+    /// An unseeded simulation reports the seed its tables were drawn with, so
+    /// Simulation(seed=metadata["seed"], ...) draws the same ones.
+    fn metadata(&self, py: Python<'_>) -> PyResult<PyObject> {
+        Ok(self.describe(py)?.to_object(py))
+    }
+
+    /// metadata(), plus the "starting_quantity", "count" and "call_seed" of the last
+    /// repeat_simulate_demand, or None before the first
+    fn last_metadata(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let last_call = *self.last_call.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let (starting_quantity, count, seed) = match last_call {
+            Some(call) => call,
+            None => return Ok(py.None()),
+        };
+        let dict = self.describe(py)?;
+        dict.set_item("method", "repeat_simulate_demand")?;
+        dict.set_item("starting_quantity", starting_quantity)?;
+        dict.set_item("count", count)?;
+        dict.set_item("call_seed", seed)?;
+        Ok(dict.to_object(py))
+    }
+
+    /// Run `count` simulated years on the GPU and add them up
+    ///
+    /// The work is split evenly over 1000 threads, and whatever doesn't split evenly runs as
+    /// one more year on that many of them. `seed` seeds the threads in place of the
+    /// simulation's own seed, to re-run a call from its last_metadata(). Raises RuntimeError
+    /// if there's no Metal device or the kernel won't build, and OverflowError if the totals
+    /// don't fit in this platform's integers.
+    #[args(seed = "None")]
+    fn repeat_simulate_demand(&self, py: Python<'_>, starting_quantity: usize, count: usize, seed: Option<u64>) -> PyResult<Summary> {
+        let seed = seed.or(self.seed).unwrap_or_else(rand::random);
+        *self.last_call.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some((starting_quantity, count, seed));
+        let policy = (starting_quantity, self.safety_stock, self.order_quantity);
+        let totals = py.allow_threads(|| self.metal_run(&[policy], count, seed)).map_err(RuntimeError::py_err)?;
+        checked_summary(totals[0])
+    }
+
+    /// Like repeat_simulate_demand, once for each of `starting_quantities`, for an
+    /// initial-buy curve in one call
+    ///
+    /// Every starting quantity gets the same thread seeds, so they all see the same customers
+    /// and the differences along the curve are down to the stock rather than luck.
+    fn repeat_simulate_demand_batch(&self, py: Python<'_>, starting_quantities: Vec<usize>, count: usize) -> PyResult<Vec<Summary>> {
+        let policies: Vec<Policy> = starting_quantities.iter()
+            .map(|&starting_quantity| (starting_quantity, self.safety_stock, self.order_quantity))
+            .collect();
+        self.sweep(py, &policies, count)
+    }
+
+    /// Like repeat_simulate_demand, once for each of `policies`, a list of
+    /// (starting_quantity, safety_stock, order_quantity) tuples
+    ///
+    /// Like repeat_simulate_demand_batch, every policy sees the same customers. The lead time
+    /// and zipf exponents are this simulation's. Raises ValueError for a policy out of range.
+    fn repeat_simulate_demand_sweep(&self, py: Python<'_>, policies: Vec<(usize, isize, isize)>, count: usize) -> PyResult<Vec<Summary>> {
+        let policies = policies.into_iter()
+            .map(|(starting_quantity, safety_stock, order_quantity)| {
+                let (safety_stock, _, order_quantity) = Simulation::check(safety_stock, self.lead_time as isize, order_quantity, self.job_lot_zipf, self.itemwise_traffic_zipf)?;
+                Ok((starting_quantity, safety_stock, order_quantity))
+            })
+            .collect::<Result<Vec<Policy>, String>>()
+            .map_err(ValueError::py_err)?;
+        self.sweep(py, &policies, count)
+    }
+}
+
+#[pyproto]
+impl<'p> PyObjectProtocol<'p> for Simulation {
+    /// Simulations are equal when they have the same parameters and seed
+    fn __richcmp__(&self, other: &PyAny, op: CompareOp) -> PyResult<PyObject> {
+        let py = other.py();
+        let other = match other.downcast_ref::<Simulation>() {
+            Ok(other) => other,
+            Err(_) => return Ok(py.NotImplemented()),
+        };
+        Ok(match op {
+            CompareOp::Eq => (self.config() == other.config()).into_py(py),
+            CompareOp::Ne => (self.config() != other.config()).into_py(py),
+            _ => py.NotImplemented(),
+        })
+    }
+
+    fn __hash__(&self) -> PyResult<isize> {
+        let mut hasher = DefaultHasher::new();
+        self.config().hash(&mut hasher);
+        Ok(hasher.finish() as isize)
+    }
+}
+
+/// Simulation Implementation, continued
+///
+/// This group doesn't mention pymethods, and isn't visible from Python
+impl Simulation {
+    /// A simulation, or why these parameters can't be simulated here
+    fn checked(
+        safety_stock: isize,
+        lead_time: isize,
+        order_quantity: isize,
+        job_lot_zipf: Option<f64>,
+        itemwise_traffic_zipf: Option<f64>,
+        seed: Option<u64>,
+    ) -> Result<Simulation, String> {
+        let (safety_stock, lead_time, order_quantity) = Simulation::check(
+            safety_stock, lead_time, order_quantity,
+            job_lot_zipf.unwrap_or(2.75), itemwise_traffic_zipf.unwrap_or(4.0))?;
+        Ok(Simulation::new(safety_stock, lead_time, order_quantity, job_lot_zipf, itemwise_traffic_zipf, seed))
+    }
+
+    /// The counts, if rustsim would simulate these parameters and the kernel has room for
+    /// the lead time
+    fn check(
+        safety_stock: isize,
+        lead_time: isize,
+        order_quantity: isize,
+        job_lot_zipf: f64,
+        itemwise_traffic_zipf: f64,
+    ) -> Result<(usize, usize, usize), String> {
+        let count = |name: &str, value: isize| usize::try_from(value)
+            .map_err(|_| format!("{} must be >= 0, not {}", name, value));
+        let counts = (count("safety_stock", safety_stock)?, count("lead_time", lead_time)?, count("order_quantity", order_quantity)?);
+        let (safety_stock, lead_time, order_quantity) = counts;
+        rustsim::Simulation::new(safety_stock, lead_time, order_quantity, Some(job_lot_zipf), Some(itemwise_traffic_zipf), None)
+            .check_parameters()?;
+        if lead_time > MAX_LEAD_TIME {
+            return Err(format!("lead_time must be <= {}, not {}", MAX_LEAD_TIME, lead_time));
+        }
+        Ok(counts)
+    }
+
+    /// A simulation with the default zipf exponents for anything left as None, without
+    /// checking the parameters
+    fn new(
+        safety_stock: usize,
+        lead_time: usize,
+        order_quantity: usize,
+        job_lot_zipf: Option<f64>,
+        itemwise_traffic_zipf: Option<f64>,
+        seed: Option<u64>,
+    ) -> Simulation {
+        let job_lot_zipf = job_lot_zipf.unwrap_or(2.75);
+        let itemwise_traffic_zipf = itemwise_traffic_zipf.unwrap_or(4.0);
+        let table_seed = seed.unwrap_or_else(rand::random);
+        let mut rng = StdRng::seed_from_u64(table_seed);
+        Simulation {
+            safety_stock,
+            lead_time,
+            order_quantity,
+            job_lot_zipf,
+            itemwise_traffic_zipf,
+            job_lot_zipf_precomp: Arc::new(gpu::zipf_table(job_lot_zipf, &mut rng)),
+            itemwise_traffic_zipf_precomp: Arc::new(gpu::zipf_table(itemwise_traffic_zipf, &mut rng)),
+            seed,
+            table_seed,
+            last_call: Mutex::new(None),
+        }
+    }
+
+    /// Everything that makes two simulations the same, for == and hash()
+    fn config(&self) -> (usize, usize, usize, u64, u64, Option<u64>) {
+        (self.safety_stock, self.lead_time, self.order_quantity,
+            self.job_lot_zipf.to_bits(), self.itemwise_traffic_zipf.to_bits(), self.seed)
+    }
+
+    /// The dict behind metadata() and last_metadata()
+    fn describe<'p>(&self, py: Python<'p>) -> PyResult<&'p PyDict> {
+        let dict = PyDict::new(py);
+        dict.set_item("backend", "metal")?;
+        dict.set_item("device", Device::system_default().map(|device| device.name().to_string()))?;
+        dict.set_item("version", env!("CARGO_PKG_VERSION"))?;
+        dict.set_item("seed", self.table_seed)?;
+        let params = PyDict::new(py);
+        params.set_item("safety_stock", self.safety_stock)?;
+        params.set_item("lead_time", self.lead_time)?;
+        params.set_item("order_quantity", self.order_quantity)?;
+        params.set_item("job_lot_zipf", self.job_lot_zipf)?;
+        params.set_item("itemwise_traffic_zipf", self.itemwise_traffic_zipf)?;
+        dict.set_item("params", params)?;
+        Ok(dict)
+    }
+
+    /// Run every policy with the same seed, and summarize each
+    fn sweep(&self, py: Python<'_>, policies: &[Policy], count: usize) -> PyResult<Vec<Summary>> {
+        let seed = self.seed.unwrap_or_else(rand::random);
+        let totals = py.allow_threads(|| self.metal_run(policies, count, seed)).map_err(RuntimeError::py_err)?;
+        totals.into_iter().map(checked_summary).collect()
+    }
+
+    /// The Metal side of repeat_simulate_demand: successful transactions, successful sales,
+    /// failed transactions and failed sales, summed over every thread, for each policy
+    ///
+    /// This goes the same way as with OpenCL: compile the kernel, copy the inputs over, run
+    /// it, and copy the outputs back. Except that on Apple silicon the CPU and GPU share
+    /// memory, so with StorageModeShared buffers the "copies" are free. Every policy's
+    /// threads are seeded alike from `seed`, and draw the same seeds rustoclsim's work items
+    /// would: the main launch's, then the leftover launch's.
+    fn metal_run(&self, policies: &[Policy], count: usize, seed: u64) -> Result<Vec<[u128; 4]>, String> {
+        let device = Device::system_default().ok_or("There's no Metal device")?;
+        let library = device.new_library_with_source(include_str!("simulation.metal"), &CompileOptions::new())?;
+        let function = library.get_function("simulate_demand", None)?;
+        let pipeline = device.new_compute_pipeline_state_with_function(&function)?;
+        let job_lot_zipf_precomp = shared_buffer(&device, &self.job_lot_zipf_precomp);
+        let itemwise_traffic_zipf_precomp = shared_buffer(&device, &self.itemwise_traffic_zipf_precomp);
+        let tables = [&job_lot_zipf_precomp, &itemwise_traffic_zipf_precomp];
+
+        let (samples, remainder) = (count / THREADS, count % THREADS);
+        Ok(policies.iter().map(|&(starting_quantity, safety_stock, order_quantity)| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let params = |samples: usize| Params {
+                starting_quantity: starting_quantity as i32,
+                lead_time: self.lead_time as u32,
+                safety_stock: safety_stock as i32,
+                order_quantity: order_quantity as i32,
+                precomp_size: self.itemwise_traffic_zipf_precomp.len() as u32,
+                samples: samples as u32,
+            };
+            // The device has no randomness of its own, so each thread gets a seed from us
+            let mut totals = dispatch(&device, &pipeline, tables, &work_item_seeds(&mut rng, THREADS), &params(samples));
+            if remainder > 0 {
+                let seeds = work_item_seeds(&mut rng, THREADS);
+                let tail = dispatch(&device, &pipeline, tables, &seeds[..remainder], &params(1));
+                for (total, more) in totals.iter_mut().zip(&tail) {
+                    *total += more;
+                }
+            }
+            totals
+        }).collect())
+    }
+}
+
+/// A StorageModeShared buffer holding a copy of `values`
+fn shared_buffer(device: &Device, values: &[u32]) -> metal::Buffer {
+    device.new_buffer_with_data(
+        values.as_ptr() as *const c_void,
+        std::mem::size_of_val(values) as u64,
+        MTLResourceOptions::StorageModeShared)
+}
+
+/// Run one thread for each of `seeds`, wait for them, and add up their counters
+fn dispatch(device: &Device, pipeline: &ComputePipelineState, tables: [&metal::Buffer; 2], seeds: &[u32], params: &Params) -> [u128; 4] {
+    let threads = seeds.len();
+    let seeds = shared_buffer(device, seeds);
+    let outputs: Vec<metal::Buffer> = (0..4)
+        .map(|_| device.new_buffer((threads * std::mem::size_of::<u64>()) as u64, MTLResourceOptions::StorageModeShared))
+        .collect();
+
+    let queue = device.new_command_queue();
+    let commands = queue.new_command_buffer();
+    let encoder = commands.new_compute_command_encoder();
+    encoder.set_compute_pipeline_state(pipeline);
+    encoder.set_buffer(0, Some(&seeds), 0);
+    encoder.set_buffer(1, Some(tables[0]), 0);
+    encoder.set_buffer(2, Some(tables[1]), 0);
+    for (index, output) in outputs.iter().enumerate() {
+        encoder.set_buffer(3 + index as u64, Some(output), 0);
+    }
+    encoder.set_bytes(7, std::mem::size_of::<Params>() as u64, params as *const Params as *const c_void);
+    let group = pipeline.max_total_threads_per_threadgroup().min(threads as u64);
+    encoder.dispatch_threads(MTLSize::new(threads as u64, 1, 1), MTLSize::new(group, 1, 1));
+    encoder.end_encoding();
+    commands.commit();
+    commands.wait_until_completed();
+
+    // Add up in u128, for the same reason rustoclsim does: a thousand u64s might not fit
+    let mut totals = [0u128; 4];
+    for (total, output) in totals.iter_mut().zip(&outputs) {
+        // The command buffer is done, so nothing is writing to these any more
+        let values = unsafe { std::slice::from_raw_parts(output.contents() as *const u64, threads) };
+        *total = values.iter().map(|&x| u128::from(x)).sum();
+    }
+    totals
+}
+
+/// The totals as a Summary, or OverflowError rather than totals that are quietly wrong
+fn checked_summary(totals: [u128; 4]) -> PyResult<Summary> {
+    gpu::summarize_totals(totals).ok_or_else(|| OverflowError::py_err(
+        "The totals are too large for this platform's integers; run fewer repetitions per call"))
+}
+
+/// This module is a python module implemented in Rust.
+#[pymodule]
+fn rustmetalsim(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<Simulation>()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_check() {
+    // rustsim's checks, and the kernel's own limit on the lead time
+    assert!(Simulation::check(10, 3, 7, 2.75, 4.0).is_ok());
+    assert!(Simulation::check(-1, 3, 7, 2.75, 4.0).unwrap_err().contains("safety_stock"));
+    assert!(Simulation::check(10, 0, 7, 2.75, 4.0).unwrap_err().contains("lead_time"));
+    assert!(Simulation::check(10, 3, 0, 2.75, 4.0).unwrap_err().contains("order_quantity"));
+    assert!(Simulation::check(10, 3, 7, f64::NAN, 4.0).unwrap_err().contains("job_lot_zipf"));
+    assert!(Simulation::check(10, MAX_LEAD_TIME as isize + 1, 7, 2.75, 4.0).unwrap_err().contains("lead_time"));
+}
+
+#[test]
+fn test_seeded() {
+    // The same seed gives the same years, and another gives different ones
+    let sim = Simulation::new(10, 3, 7, None, None, Some(1));
+    let policy = [(10, 10, 7)];
+    let first = sim.metal_run(&policy, 10_000, 5).expect("Metal failed");
+    assert_eq!(first, sim.metal_run(&policy, 10_000, 5).expect("Metal failed"));
+    assert_ne!(first, sim.metal_run(&policy, 10_000, 6).expect("Metal failed"));
+}
+
+#[test]
+fn test_remainder() {
+    // Fewer years than threads still run, and a year over a multiple of them runs on top of
+    // the same main launch. Every day has customers, so every year has transactions.
+    let sim = Simulation::new(10, 3, 7, None, None, Some(1));
+    let few = sim.metal_run(&[(10, 10, 7)], 999, 5).expect("Metal failed")[0];
+    assert!(few[0] + few[2] > 0);
+    let even = sim.metal_run(&[(10, 10, 7)], 1000, 5).expect("Metal failed")[0];
+    let more = sim.metal_run(&[(10, 10, 7)], 1001, 5).expect("Metal failed")[0];
+    assert!(more.iter().zip(&even).all(|(more, even)| more >= even));
+    assert!(more[0] + more[2] > even[0] + even[2]);
+}
+
+#[test]
+fn test_matches_cpu() {
+    // Close to rustsim's fill rates, which aren't drawn the same way so can't match exactly
+    let sim = Simulation::new(10, 3, 7, None, None, Some(1));
+    let totals = sim.metal_run(&[(10, 10, 7)], 100_000, 2).expect("Metal failed")[0];
+    let (_, _, _, _, transaction_rate, unit_rate) = gpu::summarize_totals(totals).unwrap();
+    let cpu = rustsim::Simulation::new(10, 3, 7, None, None, Some(2)).repeat_summary(10, 100_000);
+    assert!((transaction_rate - cpu.4).abs() < 0.01, "{} vs {}", transaction_rate, cpu.4);
+    assert!((unit_rate - cpu.5).abs() < 0.01, "{} vs {}", unit_rate, cpu.5);
+}
@@ -0,0 +1,94 @@
+// The same simulation as rustoclsim's simulation.cl, in the Metal Shading Language.
+// The two should stay step for step alike, down to the random numbers.
+#include <metal_stdlib>
+using namespace metal;
+
+// Trucks we can keep track of at once, which is also the longest lead time we allow.
+// The host checks lead_time against this.
+#define TRUCK_SLOTS 64
+#define HORIZON 365
+
+// The scalars, packed in one struct so they can go in a single set_bytes
+struct Params {
+    int starting_quantity;
+    uint lead_time;
+    int safety_stock;
+    int order_quantity;
+    uint precomp_size;
+    uint samples;
+};
+
+// Completely by-the-book reference implementation of xorshift
+static uint xorshift32(thread uint& state)
+{
+    /* Algorithm "xor" from p. 4 of Marsaglia, "Xorshift RNGs" */
+    uint x = state;
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    state = x;
+    return x;
+}
+
+// Select an item at random from a buffer
+static uint random_select(thread uint& state, device const uint* precomp, uint len) {
+    return precomp[xorshift32(state) % len];
+}
+
+kernel void simulate_demand(
+    device const uint* seed [[buffer(0)]],
+    device const uint* job_lot_zipf_precomp [[buffer(1)]],
+    device const uint* itemwise_traffic_zipf_precomp [[buffer(2)]],
+    device ulong* all_successful_transactions [[buffer(3)]],
+    device ulong* all_successful_sales [[buffer(4)]],
+    device ulong* all_failed_transactions [[buffer(5)]],
+    device ulong* all_failed_sales [[buffer(6)]],
+    constant Params& params [[buffer(7)]],
+    uint me [[thread_position_in_grid]]
+) {
+    ulong successful_transactions = 0;
+    ulong successful_sales = 0;
+    ulong failed_transactions = 0;
+    ulong failed_sales = 0;
+    uint trucks[TRUCK_SLOTS];
+    uint state = seed[me];
+    uint cycle = params.lead_time;
+
+    for (uint sample=0; sample<params.samples; sample++) {
+        // Every year starts from scratch, like on the CPU
+        int stock = params.starting_quantity;
+        for (uint slot=0; slot<cycle; slot++) {
+            trucks[slot] = 0;
+        }
+        for (uint day=0; day<HORIZON; day++) {
+            // A truck arrived
+            stock += trucks[day % cycle];
+            // This many customers arrive
+            uint customer_count = random_select(state, itemwise_traffic_zipf_precomp, params.precomp_size);
+            for (uint _customer=0; _customer < customer_count; _customer++) {
+                // This customer wants this many
+                int request = random_select(state, job_lot_zipf_precomp, params.precomp_size);
+                if (stock >= request) {
+                    // There are enough.
+                    successful_transactions += 1;
+                    successful_sales += request;
+                    stock -= request;
+                } else {
+                    // There are not enough
+                    failed_transactions += 1;
+                    failed_sales += request;
+                }
+            }
+            // The day is over. Start making orders.
+            if (stock < params.safety_stock) {
+                int short_by = max(params.safety_stock - stock, 0);
+                int orders = (short_by + params.order_quantity - 1) / params.order_quantity;
+                trucks[(day + cycle - 1) % cycle] = orders * params.order_quantity;
+            }
+        }
+    }
+    all_successful_transactions[me] = successful_transactions;
+    all_successful_sales[me] = successful_sales;
+    all_failed_transactions[me] = failed_transactions;
+    all_failed_sales[me] = failed_sales;
+}
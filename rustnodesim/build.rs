@@ -0,0 +1,4 @@
+fn main() {
+    // Node loads the module itself, so leave its symbols for it to fill in
+    napi_build::setup();
+}
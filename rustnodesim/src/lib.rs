@@ -0,0 +1,119 @@
+use napi::bindgen_prelude::*;
+use napi::{Env, Task};
+use napi_derive::napi;
+
+/// Counters and rates from one or many simulated years, as rustsim's Summary tuple
+///
+/// JavaScript has no tuples, so the fields get names instead. The counters are plain numbers,
+/// which are exact up to 2^53, far more than any run will reach.
+#[napi(object)]
+pub struct Summary {
+    pub successful_transactions: i64,
+    pub successful_sales: i64,
+    pub failed_transactions: i64,
+    pub failed_sales: i64,
+    pub transaction_fill_rate: f64,
+    pub unit_fill_rate: f64,
+}
+
+impl From<rustsim::Summary> for Summary {
+    fn from((st, ss, ft, fs, tr, ur): rustsim::Summary) -> Summary {
+        Summary {
+            successful_transactions: st as i64,
+            successful_sales: ss as i64,
+            failed_transactions: ft as i64,
+            failed_sales: fs as i64,
+            transaction_fill_rate: tr,
+            unit_fill_rate: ur,
+        }
+    }
+}
+
+/// Simulation parameters, for Node.js
+///
+/// This wraps the same engine as the rustsim Python module, so a web backend gets the same
+/// answers as the notebook that designed the scenario, seed for seed, without spawning Python.
+#[napi]
+pub struct Simulation {
+    sim: rustsim::Simulation,
+}
+
+#[napi]
+impl Simulation {
+    /// Like rustsim.Simulation(); leave the zipf exponents undefined for the defaults
+    ///
+    /// The seed is a BigInt, since Python's seeds go all the way up to 2^64.
+    #[napi(constructor)]
+    pub fn new(
+        safety_stock: u32,
+        lead_time: u32,
+        order_quantity: u32,
+        job_lot_zipf: Option<f64>,
+        itemwise_traffic_zipf: Option<f64>,
+        seed: Option<BigInt>,
+    ) -> Result<Simulation> {
+        // Python would raise on these as soon as a year started; here it would take down Node
+        if lead_time == 0 || order_quantity == 0 {
+            return Err(Error::new(
+                Status::InvalidArg,
+                "lead_time and order_quantity must be at least 1".to_string(),
+            ));
+        }
+        Ok(Simulation {
+            sim: rustsim::Simulation::new(
+                safety_stock as usize,
+                lead_time as usize,
+                order_quantity as usize,
+                job_lot_zipf,
+                itemwise_traffic_zipf,
+                seed.map(|seed| seed.get_u64().1),
+            ),
+        })
+    }
+
+    /// Simulate one year
+    #[napi]
+    pub fn simulate_demand(&self, starting_quantity: u32) -> Summary {
+        let starting_quantity = starting_quantity as usize;
+        self.sim.simulate_summary(starting_quantity, &mut self.sim.rng()).into()
+    }
+
+    /// Repeat the simulation `count` times and add it all up
+    ///
+    /// This blocks the event loop until it's done; a server wants the async version.
+    #[napi]
+    pub fn repeat_simulate_demand(&self, starting_quantity: u32, count: u32) -> Summary {
+        self.sim.repeat_summary(starting_quantity as usize, count as usize).into()
+    }
+
+    /// Like repeat_simulate_demand, but on libuv's thread pool, returning a Promise
+    #[napi(ts_return_type = "Promise<Summary>")]
+    pub fn repeat_simulate_demand_async(&self, starting_quantity: u32, count: u32) -> AsyncTask<Repeat> {
+        AsyncTask::new(Repeat {
+            sim: self.sim.clone(),
+            starting_quantity: starting_quantity as usize,
+            count: count as usize,
+        })
+    }
+}
+
+/// One repeat_simulate_demand_async call, running off the main thread
+pub struct Repeat {
+    // A copy, so JavaScript can drop or reuse its Simulation while we run
+    sim: rustsim::Simulation,
+    starting_quantity: usize,
+    count: usize,
+}
+
+impl Task for Repeat {
+    type Output = rustsim::Summary;
+    type JsValue = Summary;
+
+    fn compute(&mut self) -> Result<rustsim::Summary> {
+        Ok(self.sim.repeat_summary(self.starting_quantity, self.count))
+    }
+
+    fn resolve(&mut self, _env: Env, output: rustsim::Summary) -> Result<Summary> {
+        Ok(output.into())
+    }
+}
@@ -0,0 +1,103 @@
+//! The OpenCL API, forwarded to a library loaded at run time on Linux
+//!
+//! This is rustoclsim's copy of cl-sys 0.4.3, patched in for the upstream crate. Upstream
+//! declares the API in `extern` blocks that link against libOpenCL, so a module built on it
+//! won't even load on a machine without one. Here, on Linux, each function is an ordinary Rust
+//! function instead, which looks itself up in whatever library set_library() was given and
+//! calls that. Nothing links against libOpenCL, and since the functions aren't `#[no_mangle]`,
+//! nothing else in the process can bind to them in place of the real library's.
+//!
+//! Elsewhere the `extern` blocks are as upstream has them: macOS always has its OpenCL
+//! framework, and on Windows, OpenCL.dll comes with every GPU driver.
+#[cfg(target_os = "linux")]
+use crate::{c_void, cl_int, CL_INVALID_PLATFORM};
+#[cfg(target_os = "linux")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The dlopen() handle of the library to forward to, or 0 before there is one
+#[cfg(target_os = "linux")]
+static LIBRARY: AtomicUsize = AtomicUsize::new(0);
+
+/// Forward every function to `handle`, a library dlopen() returned, from now on
+#[cfg(target_os = "linux")]
+pub fn set_library(handle: *mut c_void) {
+    LIBRARY.store(handle as usize, Ordering::Release);
+}
+
+/// The address of the function `name`, which ends in a NUL, or 0 if there's no library yet
+/// or it doesn't have one
+#[cfg(target_os = "linux")]
+#[doc(hidden)]
+pub fn symbol(name: &'static str) -> usize {
+    match LIBRARY.load(Ordering::Acquire) {
+        0 => 0,
+        handle => unsafe {
+            libc::dlsym(handle as *mut c_void, name.as_ptr() as *const libc::c_char) as usize
+        },
+    }
+}
+
+/// What a forwarded function returns when there's nothing to forward to. Nothing should call
+/// one then, but if something does, it gets an error rather than a crash.
+#[cfg(target_os = "linux")]
+#[doc(hidden)]
+pub trait Missing {
+    fn missing() -> Self;
+}
+
+#[cfg(target_os = "linux")]
+impl Missing for cl_int {
+    fn missing() -> cl_int {
+        // Not CL_PLATFORM_NOT_FOUND_KHR, which ocl waits on for the ICD to wake up
+        CL_INVALID_PLATFORM
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Missing for *mut c_void {
+    fn missing() -> *mut c_void {
+        std::ptr::null_mut()
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Missing for () {
+    fn missing() {}
+}
+
+/// Declare the functions, linked from the OpenCL library as upstream does, or on Linux,
+/// defined to look themselves up in the loaded library, once it has them, and call that
+macro_rules! api {
+    ($($(#[$meta:meta])* pub fn $name:ident($($arg:ident: $ty:ty),* $(,)?) $(-> $ret:ty)?;)*) => {
+        #[cfg(not(target_os = "linux"))]
+        #[cfg_attr(target_os = "macos", link(name = "OpenCL", kind = "framework"))]
+        #[cfg_attr(not(target_os = "macos"), link(name = "OpenCL"))]
+        extern "system" {
+            $(
+                $(#[$meta])*
+                pub fn $name($($arg: $ty),*) $(-> $ret)?;
+            )*
+        }
+
+        $(
+            #[cfg(target_os = "linux")]
+            #[allow(non_snake_case)]
+            $(#[$meta])*
+            pub unsafe extern "system" fn $name($($arg: $ty),*) $(-> $ret)? {
+                static ADDRESS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+                let mut address = ADDRESS.load(std::sync::atomic::Ordering::Relaxed);
+                if address == 0 {
+                    // Only found addresses are kept, so a call before set_library() doesn't
+                    // stop later ones from finding the function
+                    address = crate::loader::symbol(concat!(stringify!($name), "\0"));
+                    if address == 0 {
+                        return crate::loader::Missing::missing();
+                    }
+                    ADDRESS.store(address, std::sync::atomic::Ordering::Relaxed);
+                }
+                let function: unsafe extern "system" fn($($ty),*) $(-> $ret)? = std::mem::transmute(address);
+                function($($arg),*)
+            }
+        )*
+    };
+}
@@ -0,0 +1,105 @@
+# Type stubs for the rustoclsim extension module, for IDEs and mypy.
+#
+# maturin ships this next to the compiled module. It's kept by hand, so when a #[pymethods]
+# or #[pyfunction] signature changes in src/, change it here too.
+from typing import Any, Dict, Generator, List, Literal, Optional, Sequence, Tuple
+
+# (successful transactions, successful sales, failed transactions, failed sales,
+#  transaction fill rate, unit fill rate)
+Summary = Tuple[int, int, int, int, float, float]
+# (mean, variance)
+Moments = Tuple[float, float]
+# One work item's (successful transactions, successful sales, failed transactions, failed sales)
+WorkItemTotals = Tuple[int, int, int, int]
+Precision = Literal["fp32", "fp64", "auto"]
+TablePrecision = Literal["u32", "u16", "auto"]
+Kernel = Literal["lost_sales", "backorder", "s_S"]
+
+class Simulation:
+    def __init__(
+        self,
+        *,
+        safety_stock: int = 10,
+        lead_time: int = 3,
+        order_quantity: int = 7,
+        job_lot_zipf: Optional[float] = None,
+        itemwise_traffic_zipf: Optional[float] = None,
+        seed: Optional[int] = None,
+        kernel_source: Optional[str] = None,
+        precision: Optional[Precision] = None,
+        table_precision: Optional[TablePrecision] = None,
+        kernel: Optional[Kernel] = None,
+        policy: Optional[str] = None,
+    ) -> None: ...
+    def with_params(
+        self,
+        *,
+        safety_stock: int = ...,
+        lead_time: int = ...,
+        order_quantity: int = ...,
+        job_lot_zipf: float = ...,
+        itemwise_traffic_zipf: float = ...,
+        seed: Optional[int] = ...,
+        precision: Precision = ...,
+        table_precision: TablePrecision = ...,
+        kernel: Kernel = ...,
+        policy: Optional[str] = ...,
+    ) -> Simulation: ...
+    @property
+    def seed(self) -> Optional[int]: ...
+    @property
+    def deterministic(self) -> bool: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __ne__(self, other: object) -> bool: ...
+    def __hash__(self) -> int: ...
+    def metadata(self) -> Dict[str, Any]: ...
+    def last_metadata(self) -> Optional[Dict[str, Any]]: ...
+    def last_timings(self) -> Optional[Dict[str, float]]: ...
+    def warmup(self, background: bool = True) -> None: ...
+    def device_info(self) -> Dict[str, Any]: ...
+    def repeat_simulate_demand(
+        self, starting_quantity: int, count: int, seed: Optional[int] = None
+    ) -> Summary: ...
+    def repeat_simulate_demand_per_work_item(
+        self, starting_quantity: int, count: int
+    ) -> Tuple[Summary, List[WorkItemTotals]]: ...
+    def repeat_simulate_demand_metrics(
+        self, starting_quantity: int, count: int, metrics: List[str]
+    ) -> Tuple[Summary, Dict[str, float]]: ...
+    def repeat_simulate_demand_async(self, starting_quantity: int, count: int) -> PendingRun: ...
+    def repeat_simulate_demand_batch(
+        self, starting_quantities: Sequence[int], count: int
+    ) -> List[Summary]: ...
+    def repeat_simulate_demand_sweep(
+        self, policies: Sequence[Tuple[int, int, int]], count: int
+    ) -> List[Summary]: ...
+    def repeat_simulate_demand_stats(
+        self, starting_quantity: int, count: int
+    ) -> Tuple[Summary, Moments, Moments]: ...
+    def repeat_until(
+        self,
+        starting_quantity: int,
+        target_ci_width: float,
+        max_count: int = 100_000_000,
+        pipeline_depth: int = 2,
+    ) -> Tuple[Summary, int, float]: ...
+    def repeat_simulate_demand_each(self, starting_quantity: int, count: int) -> List[Summary]: ...
+    def autotune(self, force: bool = False) -> Dict[str, Optional[int]]: ...
+    def debug_replay(
+        self, starting_quantity: int, work_items: int = 64, repetitions: int = 1, draws: int = 65536
+    ) -> Dict[str, Any]: ...
+
+class PendingRun:
+    def result(self) -> Summary: ...
+    def done(self) -> bool: ...
+    # Awaiting needs a running asyncio event loop
+    def __await__(self) -> Generator[Any, None, Summary]: ...
+
+def validate(count: int = 10, seed: Optional[int] = None) -> int: ...
+def rng_selfcheck(
+    work_items: int = 1024, count: int = 4096, alpha: float = 0.001, seed: Optional[int] = None
+) -> Dict[str, Tuple[float, float]]: ...
+def doctor() -> List[Dict[str, Any]]: ...
+
+# False without the ocl feature, when it's all there is, or without an OpenCL library
+def has_gpu_support() -> bool: ...
@@ -0,0 +1,75 @@
+//! Spotting work items that came back wrong
+//!
+//! A driver that miscompiles the kernel, or a buffer the kernel never wrote, usually doesn't
+//! fail the run. It leaves a few work items with nonsense counters, which get added into the
+//! totals with everything else and bend them a little. The work items of one policy all run
+//! the same number of repetitions from their own streams, so they ought to look alike, and
+//! one that doesn't is worth a warning.
+//!
+//! Some things can't happen at all: every request is between 1 and 1000 units, so sales are
+//! between 1 and 1000 times transactions, and no work item sees more years with customers than
+//! it ran. Beyond that, each work item's customer count (its transactions, successful or not)
+//! is a sum over a great many days, so it's close to normal across work items, and one more
+//! than 6σ from the rest is flagged. Sales have too long a tail for that, so they're left out.
+//! The middle and spread come from the median and the median absolute deviation, so the
+//! outliers themselves can't drag them along.
+use crate::WorkItemTotals;
+
+/// How many standard deviations from the rest a work item's customers can be
+const THRESHOLD: f64 = 6.0;
+
+/// Fewer work items than this are too few to say what normal looks like
+const PEERS: usize = 8;
+
+/// The work items among `work_items` that look wrong, by their index there, and why
+///
+/// `rate_count` is how many years with customers each saw, out of `repetitions`.
+pub fn anomalies(work_items: &[WorkItemTotals], rate_count: &[u32], repetitions: usize) -> Vec<(usize, String)> {
+    let mut found = vec![];
+    for (item, (&(st, ss, ft, fs), &years)) in work_items.iter().zip(rate_count).enumerate() {
+        if !(st..=st.saturating_mul(1000)).contains(&ss) || !(ft..=ft.saturating_mul(1000)).contains(&fs) {
+            found.push((item, format!("its sales ({}, {}) can't come from its transactions ({}, {})", ss, fs, st, ft)));
+        } else if years as usize > repetitions {
+            found.push((item, format!("it counted {} years with customers, but only ran {}", years, repetitions)));
+        }
+    }
+    if work_items.len() < PEERS {
+        return found;
+    }
+    let customers: Vec<f64> = work_items.iter().map(|&(st, _, ft, _)| st as f64 + ft as f64).collect();
+    let middle = median(customers.clone());
+    // The MAD of a normal is 0.6745σ. Counts can't spread less than one customer.
+    let sigma = (median(customers.iter().map(|c| (c - middle).abs()).collect()) / 0.6745).max(1.0);
+    for (item, &count) in customers.iter().enumerate() {
+        let z = (count - middle) / sigma;
+        if z.abs() > THRESHOLD && !found.iter().any(|&(flagged, _)| flagged == item) {
+            found.push((item, format!("its {} customers are {:.1}σ from its peers' {}", count, z, middle)));
+        }
+    }
+    found.sort_by_key(|&(item, _)| item);
+    found
+}
+
+/// The median of `values`, which mustn't be empty
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let half = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[half - 1] + values[half]) / 2.0
+    } else {
+        values[half]
+    }
+}
+
+#[test]
+fn test_anomalies() {
+    // Work items alike but for the odd one out pass, and the odd ones don't
+    let mut work_items: Vec<WorkItemTotals> = (0..100).map(|i| (400 + i % 7, 600 + i % 11, 20 + i % 3, 40 + i % 5)).collect();
+    let mut rate_count = vec![1000; 100];
+    assert!(anomalies(&work_items, &rate_count, 1000).is_empty());
+    work_items[3].0 += 100;
+    work_items[5].3 = 0;
+    rate_count[7] = 0xDEAD_BEEF;
+    let flagged: Vec<usize> = anomalies(&work_items, &rate_count, 1000).into_iter().map(|(item, _)| item).collect();
+    assert_eq!(flagged, vec![3, 5, 7]);
+}
@@ -0,0 +1,66 @@
+//! The kernel as one of rustsim's SimBackends, next to the scalar, rayon and SIMD engines
+//!
+//! Anything written against rustsim::backend (batching, seeding, comparing engines) runs on
+//! the default device through this, without going through Python.
+use crate::{default_device, OrderingPolicy, Phase, Simulation};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rustsim::backend::{Counts, SimBackend};
+use rustsim::schema::Config;
+
+/// The kernel on the default device
+#[derive(Default)]
+pub struct OpenCl {
+    sim: Option<Simulation>,
+}
+
+impl OpenCl {
+    /// Where the time went in the last batch, phase by phase, named as
+    /// Simulation.last_timings() names them, or None before the first
+    pub fn last_timings(&self) -> Option<Vec<(&'static str, f64)>> {
+        let breakdown = self.sim.as_ref()?.timings.get()?;
+        Some(Phase::ALL.iter().map(|&(name, phase)| (name, breakdown.seconds(phase))).collect())
+    }
+}
+
+/// The default device's global memory in bytes, or why there's no device to ask
+///
+/// That's what it has, not what's in use: OpenCL has no way to ask how much is free.
+pub fn device_memory() -> Result<u64, String> {
+    let (_, device) = default_device().map_err(|error| error.to_string())?;
+    device_info!(device, GlobalMemSize).ok_or_else(|| "The device won't say how much memory it has".to_string())
+}
+
+impl SimBackend for OpenCl {
+    fn name(&self) -> &'static str {
+        "opencl"
+    }
+
+    /// Build the zipf tables for `sim`, unless they're the ones we already have, since those
+    /// are the slow part and a sweep over policies only changes the rest
+    fn configure(&mut self, sim: &rustsim::Simulation) -> Result<(), String> {
+        crate::loader::available()?;
+        sim.check_parameters()?;
+        let config = Config::from(sim.clone());
+        let (job_lot_zipf, itemwise_traffic_zipf) = (config.job_lot_zipf.unwrap_or(2.75), config.itemwise_traffic_zipf.unwrap_or(4.0));
+        let ocl_sim = match self.sim.take() {
+            Some(current) if current.job_lot_zipf == job_lot_zipf && current.itemwise_traffic_zipf == itemwise_traffic_zipf && current.seed == config.seed => Simulation {
+                safety_stock: config.safety_stock,
+                lead_time: config.lead_time,
+                order_quantity: config.order_quantity,
+                ..current
+            },
+            _ => Simulation::new(config.safety_stock, config.lead_time, config.order_quantity, Some(job_lot_zipf), Some(itemwise_traffic_zipf), config.seed),
+        };
+        let policy = config.policy.as_deref().map(OrderingPolicy::parse).transpose()?;
+        self.sim = Some(Simulation { policy, ..ocl_sim });
+        Ok(())
+    }
+
+    fn run_batch(&mut self, starting_quantity: usize, count: usize, seed: u64) -> Result<Counts, String> {
+        let sim = self.sim.as_ref().ok_or_else(|| "The opencl backend has to be configured first".to_string())?;
+        let run = sim.ocl_run(starting_quantity, count, false, &mut StdRng::seed_from_u64(seed)).map_err(|error| error.to_string())?;
+        let (st, ss, ft, fs, _, _) = run.summary().ok_or_else(|| "The totals are too large for this platform's integers".to_string())?;
+        Ok((st, ss, ft, fs))
+    }
+}
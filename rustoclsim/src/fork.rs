@@ -0,0 +1,70 @@
+//! Process-wide caches that a fork() child starts over with
+//!
+//! fork() copies only the thread that called it, e.g. into a multiprocessing.Pool worker. A
+//! lock another thread held at that moment, say while compiling a program or tuning a device,
+//! stays held in the child for good, and the first call there that wants it hangs. Whatever
+//! the lock guarded belongs to the parent anyway: OpenCL contexts don't survive fork(), so
+//! its compiled programs are no use here. So each cache notes the process that made it, and a
+//! process that finds another pid there makes its own.
+//!
+//! The parent's copy is leaked rather than dropped. Dropping it would release the parent's
+//! OpenCL handles from here, and would wait for a lock that nobody is left to release.
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+/// A value behind a lock, made afresh in each process that uses it
+pub struct PerProcess<T> {
+    /// The pid that made it and the value, never freed once set
+    current: AtomicPtr<(u32, Mutex<T>)>,
+    init: fn() -> T,
+    /// Shared between threads only if a Mutex<T> could be
+    _value: PhantomData<Mutex<T>>,
+}
+
+impl<T> PerProcess<T> {
+    pub const fn new(init: fn() -> T) -> PerProcess<T> {
+        PerProcess { current: AtomicPtr::new(std::ptr::null_mut()), init, _value: PhantomData }
+    }
+
+    /// Lock this process's value, making it first if this process hasn't yet
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        let pid = std::process::id();
+        let mut current = self.current.load(Ordering::Acquire);
+        // Safe to read, since anything stored here is never freed
+        while current.is_null() || unsafe { (*current).0 } != pid {
+            let fresh = Box::into_raw(Box::new((pid, Mutex::new((self.init)()))));
+            match self.current.compare_exchange(current, fresh, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => current = fresh,
+                // Another thread got there first, so use its value instead, unless it's stale too
+                Err(theirs) => {
+                    drop(unsafe { Box::from_raw(fresh) });
+                    current = theirs;
+                }
+            }
+        }
+        unsafe { &(*current).1 }.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_fork() {
+    // A fork() child finds the parent's value stale, however it was left, and gets a fresh one
+    static COUNT: PerProcess<usize> = PerProcess::new(|| 0);
+    *COUNT.lock() += 1;
+    let held = COUNT.lock();
+    match unsafe { libc::fork() } {
+        0 => {
+            let fresh = *COUNT.lock() == 0;
+            unsafe { libc::_exit(if fresh { 0 } else { 1 }) };
+        }
+        child => {
+            drop(held);
+            let mut status = 0;
+            assert_eq!(unsafe { libc::waitpid(child, &mut status, 0) }, child);
+            assert!(libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0);
+            assert_eq!(*COUNT.lock(), 1);
+        }
+    }
+}
@@ -1,8 +1,172 @@
+// The code generated by #[pyclass] in pyo3 0.8 rounds up sizes by hand
+#![allow(clippy::manual_div_ceil)]
+
 use pyo3::prelude::*;
-use rand::distributions::Distribution;
-use std::convert::TryInto;
+use pyo3::wrap_pyfunction;
+// Everything else here is for the device, so it's only there with the ocl feature
+#[cfg(feature = "ocl")]
+use pyo3::exceptions::{AssertionError, OverflowError, RuntimeError, ValueError};
+#[cfg(feature = "ocl")]
+use pyo3::{PyAsyncProtocol, PyNativeType, PyObjectProtocol};
+#[cfg(feature = "ocl")]
+use pyo3::basic::CompareOp;
+#[cfg(feature = "ocl")]
+use pyo3::types::{PyAny, PyDict};
+#[cfg(feature = "ocl")]
+use rand::rngs::StdRng;
+#[cfg(feature = "ocl")]
+use rand::{Rng, SeedableRng};
+#[cfg(feature = "ocl")]
 use ocl::ProQue;
+// Not to be confused with the (starting quantity, safety stock, order quantity) Policy of a sweep
+#[cfg(feature = "ocl")]
+use rustsim::metric::Metric;
+// The tables and seeds every GPU backend hands its kernel
+#[cfg(feature = "ocl")]
+use rustsim::gpu::{self, work_item_seeds};
+#[cfg(feature = "ocl")]
+use rustsim::welford::Welford;
+#[cfg(feature = "ocl")]
+use rustsim::policy::{Inputs, Policy as OrderingPolicy};
+#[cfg(feature = "ocl")]
 use failure::Fallible;
+#[cfg(feature = "ocl")]
+use std::collections::HashMap;
+#[cfg(feature = "ocl")]
+use std::collections::hash_map::DefaultHasher;
+#[cfg(feature = "ocl")]
+use std::hash::{Hash, Hasher};
+#[cfg(feature = "ocl")]
+use std::sync::{Arc, Mutex};
+
+/// One piece of information about a device, or None if the driver won't say
+#[cfg(feature = "ocl")]
+macro_rules! device_info {
+    ($device:expr, $kind:ident) => {
+        match $device.info(ocl::enums::DeviceInfo::$kind) {
+            Ok(ocl::enums::DeviceInfoResult::$kind(value)) => Some(value),
+            _ => None,
+        }
+    };
+}
+
+#[cfg(feature = "ocl")]
+#[macro_use]
+mod logging;
+// This one has tests that don't need a device, so it's there for tests without ocl too
+#[cfg(any(feature = "ocl", test))]
+mod anomaly;
+#[cfg(feature = "ocl")]
+pub mod backend;
+// Likewise, for forking in the test
+#[cfg(any(feature = "ocl", test))]
+mod fork;
+#[cfg(feature = "ocl")]
+use fork::PerProcess;
+#[cfg(feature = "ocl")]
+mod loader;
+// Likewise, but for Budget::of
+#[cfg(any(feature = "ocl", test))]
+mod memory;
+#[cfg(feature = "ocl")]
+mod replay;
+#[cfg(feature = "svm")]
+mod svm;
+#[cfg(feature = "ocl")]
+mod tables;
+#[cfg(feature = "ocl")]
+use tables::ZipfTable;
+#[cfg(feature = "ocl")]
+mod timings;
+#[cfg(feature = "ocl")]
+use timings::{Breakdown, LastCall, LastTimings, Phase, Stopwatch};
+#[cfg(feature = "ocl")]
+mod tuning;
+#[cfg(feature = "ocl")]
+use tuning::Shape;
+
+/// Counters and rates from many simulated years:
+/// (successful transactions, successful sales, failed transactions, failed sales,
+///  transaction fill rate, unit fill rate)
+///
+/// With no customers at all, the rates are 1.0, since nobody was turned away. The counters
+/// (all zero) are the flag for that case.
+#[cfg(feature = "ocl")]
+type Summary = (usize, usize, usize, usize, f64, f64);
+
+/// (mean, variance) of something measured once per repetition
+#[cfg(feature = "ocl")]
+type Moments = (f64, f64);
+
+/// Fraction of the demand we met, or 1.0 if there wasn't any
+#[cfg(feature = "ocl")]
+fn fill_rate(filled: usize, missed: usize) -> f64 {
+    if filled + missed == 0 { 1.0 } else { filled as f64 / (filled + missed) as f64 }
+}
+
+/// Everything one trip to the device brings back
+#[cfg(feature = "ocl")]
+struct OclRun {
+    /// Successful transactions, successful sales, failed transactions and failed sales.
+    /// These add up faster than you'd think, so they're kept wider than any counter in Summary.
+    totals: [u128; 4],
+    /// Per-repetition transaction fill rate
+    transaction_rate: Welford,
+    /// Per-repetition unit fill rate
+    unit_rate: Welford,
+    /// Every repetition's counters, if we asked for them
+    samples: Vec<Summary>,
+    /// Each work item's own totals, in the same order as `totals`
+    work_items: Vec<WorkItemTotals>,
+    /// Each custom metric, added up over the repetitions
+    metrics: Vec<f64>,
+    /// Work items whose counters look wrong next to their peers', by their place in
+    /// `work_items`, and why (see anomaly.rs)
+    anomalies: Vec<(usize, String)>,
+    /// Where the time went
+    timings: Breakdown,
+}
+
+/// What one work item added up over all of its repetitions: successful transactions,
+/// successful sales, failed transactions and failed sales
+#[cfg(any(feature = "ocl", test))]
+type WorkItemTotals = (u64, u64, u64, u64);
+
+/// (starting quantity, safety stock, order quantity): what ocl_simulate_demand_sweep lets
+/// differ from one work item to the next
+#[cfg(feature = "ocl")]
+type Policy = (usize, usize, usize);
+
+#[cfg(feature = "ocl")]
+impl OclRun {
+    /// Fold the results of another run into this one
+    fn merge(&mut self, other: &OclRun) {
+        for (total, more) in self.totals.iter_mut().zip(&other.totals) {
+            *total += more;
+        }
+        self.transaction_rate.merge(&other.transaction_rate);
+        self.unit_rate.merge(&other.unit_rate);
+        self.samples.extend_from_slice(&other.samples);
+        let offset = self.work_items.len();
+        self.anomalies.extend(other.anomalies.iter().map(|(item, why)| (offset + item, why.clone())));
+        self.work_items.extend_from_slice(&other.work_items);
+        for (total, more) in self.metrics.iter_mut().zip(&other.metrics) {
+            *total += more;
+        }
+        self.timings.merge(&other.timings);
+    }
+
+    /// The totals as a Summary, or None if they overflowed usize
+    fn summary(&self) -> Option<Summary> {
+        gpu::summarize_totals(self.totals)
+    }
+
+    /// Like summary, but raises OverflowError instead of giving totals that are quietly wrong
+    fn checked_summary(&self) -> PyResult<Summary> {
+        self.summary().ok_or_else(|| OverflowError::py_err(
+            "The totals are too large for this platform's integers; run fewer repetitions per call"))
+    }
+}
 
 /// Simulation parameters
 /// 
@@ -11,49 +175,691 @@ use failure::Fallible;
 /// Simulation has two natures. It lives in the Python world and has an impl accessible there.
 /// It also lives in the Rust world. Different methods are used here too. We need that so that
 /// it is easier to test it.
-#[pyclass(module = "rustsim")]
+#[cfg(feature = "ocl")]
+#[pyclass(module = "rustoclsim")]
+#[derive(Clone)]
 struct Simulation {
     safety_stock: usize,
     lead_time: usize,
     order_quantity: usize,
-    job_lot_zipf_precomp: Vec<u32>,
-    itemwise_traffic_zipf_precomp: Vec<u32>,
+    job_lot_zipf: f64,
+    itemwise_traffic_zipf: f64,
+    /// Shared by copies from with_params(), since they're big and slow to make
+    job_lot_zipf_precomp: Arc<ZipfTable>,
+    itemwise_traffic_zipf_precomp: Arc<ZipfTable>,
+    /// If set, the zipf tables and the work item seeds come from a generator seeded with
+    /// this, so the same call on the same device gives identical results
+    #[pyo3(get)]
+    seed: Option<u64>,
+    /// The seed the zipf tables came from: `seed`, or one picked at random if there isn't
+    /// one, so even an unseeded simulation's tables can be drawn again
+    table_seed: u64,
+    /// OpenCL source to use instead of simulation.cl
+    kernel_source: Option<String>,
+    /// An ordering policy compiled into simulation.cl in place of the built-in rule
+    policy: Option<OrderingPolicy>,
+    /// Custom metrics compiled into simulation.cl, only ever set on the copy that
+    /// repeat_simulate_demand_metrics runs
+    metrics: Vec<Metric>,
+    /// How the device accumulates the fill rate statistics
+    precision: Precision,
+    /// How wide the zipf tables' entries are on the device
+    table_precision: TablePrecision,
+    /// Which inventory model simulation.cl is built for
+    kernel: Kernel,
+    /// Where the time went in the last trip to the device
+    timings: LastTimings,
+    /// What the last repeat_simulate_demand was asked for, for last_metadata()
+    last_call: LastCall,
+}
+
+/// Floating point precision for the on-device statistics
+#[cfg(feature = "ocl")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Precision {
+    /// Single precision, which every device has
+    Fp32,
+    /// Double precision, or an error on devices without it
+    Fp64,
+    /// Double precision where the device has it, single otherwise
+    Auto,
+}
+
+#[cfg(feature = "ocl")]
+impl Precision {
+    fn parse(name: &str) -> Option<Precision> {
+        match name {
+            "fp32" => Some(Precision::Fp32),
+            "fp64" => Some(Precision::Fp64),
+            "auto" => Some(Precision::Auto),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Precision::Fp32 => "fp32",
+            Precision::Fp64 => "fp64",
+            Precision::Auto => "auto",
+        }
+    }
+
+    /// Whether to build for double precision on `device`, given whether it `supports` it
+    ///
+    /// Fails for fp64 on a device without it, rather than quietly running in single.
+    fn double(self, device: &str, supports: bool) -> Fallible<bool> {
+        match self {
+            Precision::Fp32 => Ok(false),
+            Precision::Fp64 if !supports => failure::bail!("precision=\"fp64\", but {} has no double precision", device),
+            Precision::Fp64 => Ok(true),
+            Precision::Auto if !supports => {
+                info!("{} has no double precision, so precision=\"auto\" falls back to fp32", device);
+                Ok(false)
+            }
+            Precision::Auto => Ok(true),
+        }
+    }
+}
+
+/// How wide the zipf tables' entries are on the device
+#[cfg(feature = "ocl")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum TablePrecision {
+    /// 32 bits, as they're built
+    U32,
+    /// 16 bits, which hold every entry exactly, and halve the table reads' memory traffic
+    U16,
+    /// 16 bits where that's measurably faster on the device, which is timed the first time
+    Auto,
+}
+
+#[cfg(feature = "ocl")]
+impl TablePrecision {
+    fn parse(name: &str) -> Option<TablePrecision> {
+        match name {
+            "u32" => Some(TablePrecision::U32),
+            "u16" => Some(TablePrecision::U16),
+            "auto" => Some(TablePrecision::Auto),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            TablePrecision::U32 => "u32",
+            TablePrecision::U16 => "u16",
+            TablePrecision::Auto => "auto",
+        }
+    }
+}
+
+/// An inventory model, which simulation.cl is built for one at a time
+#[cfg(feature = "ocl")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Kernel {
+    /// Customers who can't be served go away, and stock below safety_stock is topped up in
+    /// multiples of order_quantity
+    LostSales,
+    /// Like LostSales, but customers who can't be served wait, and stock goes negative
+    Backorder,
+    /// Lost sales, ordering up to safety_stock + order_quantity whenever stock and trucks
+    /// come to less than safety_stock
+    SS,
+}
+
+#[cfg(feature = "ocl")]
+impl Kernel {
+    fn parse(name: &str) -> Option<Kernel> {
+        match name {
+            "lost_sales" => Some(Kernel::LostSales),
+            "backorder" => Some(Kernel::Backorder),
+            "s_S" => Some(Kernel::SS),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Kernel::LostSales => "lost_sales",
+            Kernel::Backorder => "backorder",
+            Kernel::SS => "s_S",
+        }
+    }
+
+    /// What simulation.cl needs defined to build this model, if anything
+    fn define(self) -> Option<&'static str> {
+        match self {
+            Kernel::LostSales => None,
+            Kernel::Backorder => Some("MODEL_BACKORDER"),
+            Kernel::SS => Some("MODEL_S_S"),
+        }
+    }
 }
 
 /// Simulation implementation
 /// 
 /// The following methods are all available from Python
+#[cfg(feature = "ocl")]
 #[pymethods]
 impl Simulation {
-    /// Implementation of python Simulation.__init__() (just wraps rust Simulation::new())
+    /// Implementation of python Simulation.__init__() (wraps rust Simulation::new())
+    ///
+    /// Every argument is a keyword with a default, like Simulation(safety_stock=20), and
+    /// ValueError says which one is out of range. The zipf exponents default to 2.75 and 4.0.
+    ///
+    /// `kernel_source` replaces simulation.cl, either as a path to a file or as the source
+    /// itself. It has to define the ocl_simulate_demand kernel with the same parameters as
+    /// simulation.cl, so the buffers and reductions here still fit, but what it does with them
+    /// (say, a different ordering policy) is up to you.
+    ///
+    /// `policy` is the easier way to a different ordering policy: an expression for how many
+    /// units to order at the end of each day, like
+    /// "max(0, 2 * forecast * lead_time + safety_stock - on_hand - pipeline)", in the language
+    /// rustsim's Simulation(policy=...) interprets (see rustsim's policy.rs). It's compiled into
+    /// simulation.cl, so it runs as fast as the built-in rule, and orders exactly as rustsim's
+    /// does. It can't go with kernel_source, which has no place to put it.
+    ///
+    /// `precision` is how the device accumulates the running mean and variance of the
+    /// per-repetition fill rates: "fp32" (the default), "fp64", or "auto" for fp64 where the
+    /// device has it. The totals are integers and exact either way, and so are the summaries
+    /// built from them. In fp32, each work item's mean is good to about 1e-6, but its variance
+    /// drifts as it runs more repetitions, to something like 0.1% relative error at a million
+    /// per work item, which is what repeat_simulate_demand_stats and repeat_until report.
+    /// Consumer GPUs often lack fp64, or run it much slower, which is why it isn't the default.
+    ///
+    /// `table_precision` is how wide the zipf tables' entries are on the device: "u32" (the
+    /// default), "u16", or "auto" for u16 where a quick timing the first time on each device
+    /// says it's at least 5% faster. Every entry is a count between 1 and 1000, so 16 bits
+    /// hold it exactly and the results are identical to the last unit; all it changes is how
+    /// many bytes each random pick reads, which is most of the kernel's memory traffic, and
+    /// how much of the tables fits in local memory and on small devices. Whether that's faster
+    /// is up to the device: some read 16 bit values no faster than 32 bit ones. A big-endian
+    /// device, or kernel_source, which reads the tables as uint, always gets u32. There are no
+    /// other sampling arrays in the kernel to narrow.
+    ///
+    /// `kernel` is the inventory model simulation.cl is built for: "lost_sales" (the default),
+    /// where customers who can't be served go away; "backorder", where they wait, so stock can
+    /// go negative until trucks catch up (they still count as failed); or "s_S", lost sales
+    /// under an (s, S) policy, which orders up to safety_stock + order_quantity whenever stock
+    /// plus trucks falls below safety_stock. Each is its own program, chosen when it's built,
+    /// so none of them slows the others down. A `policy` replaces the ordering rule of
+    /// "lost_sales" or "backorder", but "s_S" is an ordering rule already, so it takes none.
     #[new]
+    #[args("*", safety_stock = "10", lead_time = "3", order_quantity = "7", job_lot_zipf = "None",
+        itemwise_traffic_zipf = "None", seed = "None", kernel_source = "None", precision = "None", policy = "None",
+        table_precision = "None", kernel = "None")]
+    #[allow(clippy::too_many_arguments)]
     fn init(
         obj: &PyRawObject,
-        safety_stock: usize,
-        lead_time: usize,
-        order_quantity: usize,
+        safety_stock: isize,
+        lead_time: isize,
+        order_quantity: isize,
         job_lot_zipf: Option<f64>,
         itemwise_traffic_zipf: Option<f64>,
-    ) {
-        obj.init(Simulation::new(
+        seed: Option<u64>,
+        kernel_source: Option<&str>,
+        precision: Option<&str>,
+        policy: Option<&str>,
+        table_precision: Option<&str>,
+        kernel: Option<&str>,
+    ) -> PyResult<()> {
+        // Before anything slow, so a machine without OpenCL finds out straight away
+        loader::available().map_err(RuntimeError::py_err)?;
+        if kernel_source.is_some() && policy.is_some() {
+            return Err(ValueError::py_err("policy only works with simulation.cl, not kernel_source"));
+        }
+        let kernel = kernel.map(parse_kernel).transpose()?.unwrap_or(Kernel::LostSales);
+        if kernel == Kernel::SS && policy.is_some() {
+            return Err(ValueError::py_err("kernel=\"s_S\" has its own ordering rule, so it takes no policy"));
+        }
+        let table_precision = table_precision.map(parse_table_precision).transpose()?.unwrap_or(TablePrecision::U32);
+        if kernel_source.is_some() && table_precision == TablePrecision::U16 {
+            return Err(ValueError::py_err("table_precision=\"u16\" only works with simulation.cl, not kernel_source"));
+        }
+        let policy = policy.map(OrderingPolicy::parse).transpose().map_err(ValueError::py_err)?;
+        // Check everything before Simulation::new(), which spends a while on the zipf tables
+        // and panics on an exponent they can't have
+        let (safety_stock, lead_time, order_quantity) =
+            check_parameters(safety_stock, lead_time, order_quantity, job_lot_zipf, itemwise_traffic_zipf)?;
+        let mut sim = Simulation::new(
             safety_stock,
             lead_time,
             order_quantity,
             job_lot_zipf,
-            itemwise_traffic_zipf
-        ));
+            itemwise_traffic_zipf,
+            seed
+        );
+        if let Some(kernel_source) = kernel_source {
+            let source = if std::path::Path::new(kernel_source).is_file() {
+                std::fs::read_to_string(kernel_source)?
+            } else {
+                kernel_source.to_string()
+            };
+            check_kernel_signature(&source).map_err(ValueError::py_err)?;
+            sim.kernel_source = Some(source);
+        }
+        sim.policy = policy;
+        if let Some(precision) = precision {
+            sim.precision = Precision::parse(precision).ok_or_else(|| ValueError::py_err(
+                format!("precision must be \"fp32\", \"fp64\" or \"auto\", not {:?}", precision)))?;
+        }
+        sim.table_precision = table_precision;
+        sim.kernel = kernel;
+        obj.init(sim);
+        Ok(())
+    }
+
+    /// A copy of this simulation with some parameters changed, like
+    /// sim.with_params(safety_stock=20)
+    ///
+    /// Takes the same keywords as the constructor, but for kernel_source, and checks them the
+    /// same way. The copy shares this simulation's zipf tables (16M entries each, and the slow
+    /// part of making a Simulation) unless an exponent or the seed changes, since those are
+    /// what the tables are drawn from. Compiled programs are shared by every simulation with
+    /// the same lead time already. So sweeps written as Python loops can make one per point.
+    #[args(params = "**")]
+    fn with_params(&self, params: Option<&PyDict>) -> PyResult<Simulation> {
+        let mut safety_stock = self.safety_stock as isize;
+        let mut lead_time = self.lead_time as isize;
+        let mut order_quantity = self.order_quantity as isize;
+        let mut job_lot_zipf = self.job_lot_zipf;
+        let mut itemwise_traffic_zipf = self.itemwise_traffic_zipf;
+        let mut seed = self.seed;
+        let mut precision = self.precision;
+        let mut table_precision = self.table_precision;
+        let mut kernel = self.kernel;
+        let mut policy = self.policy.clone();
+        for (name, value) in params.into_iter().flatten() {
+            match name.extract()? {
+                "safety_stock" => safety_stock = value.extract()?,
+                "lead_time" => lead_time = value.extract()?,
+                "order_quantity" => order_quantity = value.extract()?,
+                "job_lot_zipf" => job_lot_zipf = value.extract()?,
+                "itemwise_traffic_zipf" => itemwise_traffic_zipf = value.extract()?,
+                "seed" => seed = value.extract()?,
+                "precision" => {
+                    let name: &str = value.extract()?;
+                    precision = Precision::parse(name).ok_or_else(|| ValueError::py_err(
+                        format!("precision must be \"fp32\", \"fp64\" or \"auto\", not {:?}", name)))?;
+                }
+                "table_precision" => {
+                    table_precision = parse_table_precision(value.extract()?)?;
+                    if table_precision == TablePrecision::U16 && self.kernel_source.is_some() {
+                        return Err(ValueError::py_err("table_precision=\"u16\" only works with simulation.cl, not kernel_source"));
+                    }
+                }
+                "kernel" => kernel = parse_kernel(value.extract()?)?,
+                "policy" => {
+                    let source: Option<&str> = value.extract()?;
+                    policy = source.map(OrderingPolicy::parse).transpose().map_err(ValueError::py_err)?;
+                    if policy.is_some() && self.kernel_source.is_some() {
+                        return Err(ValueError::py_err("policy only works with simulation.cl, not kernel_source"));
+                    }
+                }
+                name => return Err(ValueError::py_err(
+                    format!("with_params() got an unexpected keyword argument {:?}", name))),
+            }
+        }
+        if kernel == Kernel::SS && policy.is_some() {
+            return Err(ValueError::py_err("kernel=\"s_S\" has its own ordering rule, so it takes no policy"));
+        }
+        let (safety_stock, lead_time, order_quantity) =
+            check_parameters(safety_stock, lead_time, order_quantity, Some(job_lot_zipf), Some(itemwise_traffic_zipf))?;
+        let mut sim = if job_lot_zipf == self.job_lot_zipf && itemwise_traffic_zipf == self.itemwise_traffic_zipf && seed == self.seed {
+            Simulation { safety_stock, lead_time, order_quantity, ..self.clone() }
+        } else {
+            let sim = Simulation::new(safety_stock, lead_time, order_quantity, Some(job_lot_zipf), Some(itemwise_traffic_zipf), seed);
+            Simulation { kernel_source: self.kernel_source.clone(), ..sim }
+        };
+        sim.precision = precision;
+        sim.table_precision = table_precision;
+        sim.kernel = kernel;
+        sim.policy = policy;
+        Ok(sim)
+    }
+
+    /// Whether this simulation was given a seed, and so gives the same results every time
+    #[getter]
+    fn deterministic(&self) -> bool {
+        self.seed.is_some()
+    }
+
+    /// Everything needed to re-run this simulation: the backend, device, crate version, seed,
+    /// and every parameter, as a dict
+    ///
+    /// The "seed" is the one the zipf tables came from, even if it was picked at random, so
+    /// passing it back to the constructor draws the same tables. Raises RuntimeError if
+    /// there is no OpenCL device.
+    fn metadata(&self, py: Python<'_>) -> PyResult<PyObject> {
+        Ok(self.describe(py)?.to_object(py))
+    }
+
+    /// metadata(), plus the "starting_quantity", "count" and "call_seed" of the last
+    /// repeat_simulate_demand, or None before the first
+    ///
+    /// The call seed is drawn at random for an unseeded simulation, so
+    /// Simulation(seed=metadata["seed"], ...).repeat_simulate_demand(starting_quantity, count,
+    /// seed=metadata["call_seed"]) runs the same call again.
+    fn last_metadata(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let (starting_quantity, count, seed) = match self.last_call.get() {
+            Some(call) => call,
+            None => return Ok(py.None()),
+        };
+        let dict = self.describe(py)?;
+        dict.set_item("method", "repeat_simulate_demand")?;
+        dict.set_item("starting_quantity", starting_quantity)?;
+        dict.set_item("count", count)?;
+        dict.set_item("call_seed", seed)?;
+        Ok(dict.to_object(py))
+    }
+
+    /// Where the time went in the last call that ran on the device, or None before the first
+    ///
+    /// repeat_simulate_demand_async isn't counted, since its PendingRun is waited on later.
+    ///
+    /// Returns a dict of seconds spent in "precompute" (getting the program ready, which
+    /// means compiling it the first time), "seed_generation", "upload", "kernel", "readback"
+    /// and "reduction" (adding up what came back), in that order, as rustsim reports them.
+    /// The phases are timed by waiting on each in turn, so a batch split by the watchdog
+    /// counts as one kernel, and repeat_until's overlapping batches add up to more than the
+    /// wall-clock time. Some drivers only copy the inputs when the kernel first needs them,
+    /// in which case the upload shows up as kernel time.
+    fn last_timings(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let breakdown = match self.timings.get() {
+            Some(breakdown) => breakdown,
+            None => return Ok(py.None()),
+        };
+        let dict = PyDict::new(py);
+        for &(name, phase) in &Phase::ALL {
+            dict.set_item(name, breakdown.seconds(phase))?;
+        }
+        Ok(dict.to_object(py))
+    }
+
+    /// Get the default device ready ahead of the first real call: tune the layout if it
+    /// hasn't been tuned, and compile the program
+    ///
+    /// Those take seconds the first time in a process and nothing after, so the first
+    /// repeat_simulate_demand is otherwise much slower than the rest. The zipf tables are
+    /// already built by the time the constructor returns. With `background=True` (the
+    /// default) this returns at once and does the work in a thread, logging a warning if it
+    /// fails; a call that needs the program meanwhile waits for it rather than compiling it
+    /// again. With `background=False` it waits, and raises RuntimeError if it fails.
+    /// (The keyword can't be called `async`, which Python reserves.)
+    #[args(background = "true")]
+    fn warmup(&self, py: Python<'_>, background: bool) -> PyResult<()> {
+        let sim = self.clone();
+        if background {
+            std::thread::spawn(move || {
+                if let Err(error) = sim.warm_up() {
+                    warning!("Couldn't warm up the default device: {}", error);
+                }
+                // Nothing's locked by now, so it's safe to wait for the GIL to send what we logged
+                logging::flush(Python::acquire_gil().python());
+            });
+            return Ok(());
+        }
+        logging::allow_threads(py, || sim.warm_up()).map_err(|error| RuntimeError::py_err(error.to_string()))
+    }
+
+    /// What the default device is and what it can do, for making sense of (and reporting)
+    /// how fast it runs
+    ///
+    /// Returns a dict with "platform", "name", "vendor", "driver_version", "global_memory"
+    /// and "local_memory" (in bytes), "compute_units", "max_work_group_size", and "fp64"
+    /// (whether it can do double precision). Anything the driver won't tell us is None.
+    /// Raises RuntimeError if there is no OpenCL device.
+    fn device_info(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let (platform, device) = default_device().map_err(|error| RuntimeError::py_err(error.to_string()))?;
+        Ok(describe_device(py, &platform, &device)?.to_object(py))
     }
 
     /// Calls the appropriate OpenCL function
-    fn repeat_simulate_demand(&self, starting_quantity: usize, count: usize) -> (usize, usize, usize, usize, f64, f64) {
-        self.ocl_repeat_simulate_demand(starting_quantity, count).unwrap()
+    ///
+    /// `seed` seeds the work items in place of the simulation's own seed, to re-run a call
+    /// from its last_metadata(). Raises RuntimeError if OpenCL fails, and OverflowError if
+    /// the totals don't fit in this platform's integers.
+    #[args(seed = "None")]
+    fn repeat_simulate_demand(&self, py: Python<'_>, starting_quantity: usize, count: usize, seed: Option<u64>) -> PyResult<Summary> {
+        let seed = seed.or(self.seed).unwrap_or_else(rand::random);
+        self.last_call.set(starting_quantity, count, seed);
+        logging::allow_threads(py, || self.ocl_run(starting_quantity, count, false, &mut StdRng::seed_from_u64(seed)))
+            .map_err(|error| RuntimeError::py_err(error.to_string()))?
+            .checked_summary()
+    }
+
+    /// Like repeat_simulate_demand, plus what each work item added up on its own
+    ///
+    /// Returns (summary, work_items), where work_items has one (successful transactions,
+    /// successful sales, failed transactions, failed sales) tuple per work item. They all ran
+    /// the same number of repetitions, so they should look alike; the spread between them
+    /// is a cheap variance estimate, and one far off from the rest points at a bad random
+    /// stream.
+    fn repeat_simulate_demand_per_work_item(&self, py: Python<'_>, starting_quantity: usize, count: usize) -> PyResult<(Summary, Vec<WorkItemTotals>)> {
+        let run = logging::allow_threads(py, || self.ocl_repeat_simulate_demand(starting_quantity, count))
+            .map_err(|error| RuntimeError::py_err(error.to_string()))?;
+        Ok((run.checked_summary()?, run.work_items))
+    }
+
+    /// Like repeat_simulate_demand, plus the average year of each of `metrics`
+    ///
+    /// A metric is an aggregate of the day's state, like "max(stock)", "mean(lost)" or
+    /// "days_below(5)"; rustsim's repeat_simulate_demand_metrics takes the same ones. They're
+    /// compiled into the kernel and added up as it goes, so nothing per day comes back from
+    /// the device. Returns (summary, metrics), where metrics maps each one as it was written
+    /// to its mean over the years. Raises ValueError if one doesn't parse.
+    fn repeat_simulate_demand_metrics(&self, py: Python<'_>, starting_quantity: usize, count: usize, metrics: Vec<&str>) -> PyResult<(Summary, PyObject)> {
+        if self.kernel_source.is_some() {
+            return Err(ValueError::py_err("Metrics are compiled into simulation.cl, so they can't be used with kernel_source"));
+        }
+        let parsed = metrics.iter()
+            .map(|source| Metric::parse(source))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(ValueError::py_err)?;
+        let sim = Simulation { metrics: parsed, ..self.clone() };
+        let run = logging::allow_threads(py, || sim.ocl_repeat_simulate_demand(starting_quantity, count))
+            .map_err(|error| RuntimeError::py_err(error.to_string()))?;
+        let dict = PyDict::new(py);
+        for (metric, total) in sim.metrics.iter().zip(&run.metrics) {
+            dict.set_item(metric.source(), total / count.max(1) as f64)?;
+        }
+        Ok((run.checked_summary()?, dict.to_object(py)))
+    }
+
+    /// Like repeat_simulate_demand, but returns as soon as the work is queued on the device
+    ///
+    /// The PendingRun it returns gives the same summary from result(), which waits for the
+    /// device if it isn't done yet. It can also be awaited from asyncio, which polls it
+    /// without blocking the event loop.
+    fn repeat_simulate_demand_async(&self, py: Python<'_>, starting_quantity: usize, count: usize) -> PyResult<PendingRun> {
+        let batch = logging::allow_threads(py, || -> Fallible<InFlight> {
+            let shape = self.shape(None)?;
+            let pro_que = self.program(None, shape.work_items)?;
+            self.ocl_launch(&pro_que, pro_que.queue(), shape, starting_quantity, count, false, &mut self.rng())
+        }).map_err(|error| RuntimeError::py_err(error.to_string()))?;
+        Ok(PendingRun { state: Arc::new(Mutex::new(Pending { batch: Some(batch), run: None, pid: std::process::id() })) })
+    }
+
+    /// Like repeat_simulate_demand, once for each of `starting_quantities` (a 1-D numpy array
+    /// or a list), for an initial-buy curve in one call
+    ///
+    /// All of them run in a single kernel launch, as in repeat_simulate_demand_sweep, so
+    /// every starting quantity sees the same customers and the differences along the curve
+    /// are down to the stock rather than luck. Returns one summary per starting quantity, in
+    /// order, which `numpy.asarray()` turns into a matrix.
+    fn repeat_simulate_demand_batch(&self, py: Python<'_>, starting_quantities: Vec<usize>, count: usize) -> PyResult<Vec<Summary>> {
+        let policies: Vec<Policy> = starting_quantities.iter()
+            .map(|&starting_quantity| (starting_quantity, self.safety_stock, self.order_quantity))
+            .collect();
+        self.sweep(py, &policies, count)
+    }
+
+    /// Like repeat_simulate_demand, once for each of `policies`, all in a single kernel launch
+    ///
+    /// Each policy is a (starting_quantity, safety_stock, order_quantity) tuple, and gets
+    /// its own copy of the usual work items, each seeded like the same work item of every
+    /// other copy. So all the policies see the same customers, and a whole parameter sweep
+    /// costs one round trip to the device instead of one per policy. The lead time and zipf
+    /// exponents are this simulation's, since they're compiled into the program and decide
+    /// the tables. Returns one summary per policy, in order.
+    ///
+    /// With kernel_source, the source has to define ocl_simulate_demand_sweep as well, the
+    /// way simulation.cl does.
+    fn repeat_simulate_demand_sweep(&self, py: Python<'_>, policies: Vec<(usize, isize, isize)>, count: usize) -> PyResult<Vec<Summary>> {
+        let policies = policies.into_iter()
+            .map(|(starting_quantity, safety_stock, order_quantity)| {
+                let (safety_stock, _, order_quantity) =
+                    check_parameters(safety_stock, self.lead_time as isize, order_quantity, None, None)?;
+                Ok((starting_quantity, safety_stock, order_quantity))
+            })
+            .collect::<PyResult<Vec<Policy>>>()?;
+        self.sweep(py, &policies, count)
+    }
+
+    /// Like repeat_simulate_demand, plus the (mean, variance) of the per-repetition
+    /// transaction fill rate and unit fill rate.
+    ///
+    /// Each work item keeps a running mean and variance of its own repetitions, and we merge
+    /// those on the host, so nothing per-repetition is ever stored.
+    fn repeat_simulate_demand_stats(&self, py: Python<'_>, starting_quantity: usize, count: usize) -> PyResult<(Summary, Moments, Moments)> {
+        let run = logging::allow_threads(py, || self.ocl_run(starting_quantity, count, false, &mut self.rng()))
+            .map_err(|error| RuntimeError::py_err(error.to_string()))?;
+        Ok((run.checked_summary()?,
+            (run.transaction_rate.mean(), run.transaction_rate.variance()),
+            (run.unit_rate.mean(), run.unit_rate.variance())))
+    }
+
+    /// Launch batches of repetitions until we're confident enough about the unit fill rate
+    ///
+    /// Stops once the 95% confidence interval of the mean per-repetition unit fill rate is
+    /// narrower than `target_ci_width`, or after `max_count` repetitions, whichever is first.
+    /// Returns the totals (as in repeat_simulate_demand), how many repetitions it took,
+    /// and the final interval width.
+    ///
+    /// Up to `pipeline_depth` batches are in flight at once, each on its own queue, so one
+    /// batch's upload, kernel and readback can overlap with the next. Set it to 1 to run
+    /// the batches strictly one after another.
+    #[args(max_count = "100_000_000", pipeline_depth = "2")]
+    fn repeat_until(&self, py: Python<'_>, starting_quantity: usize, target_ci_width: f64, max_count: usize, pipeline_depth: usize) -> PyResult<(Summary, usize, f64)> {
+        if pipeline_depth == 0 {
+            return Err(ValueError::py_err("pipeline_depth must be at least 1"));
+        }
+        let (total, done) = logging::allow_threads(py, || self.run_until(starting_quantity, target_ci_width, max_count, pipeline_depth))
+            .map_err(|error| RuntimeError::py_err(error.to_string()))?;
+        Ok((total.checked_summary()?, done, total.unit_rate.ci_width()))
+    }
+
+
+    /// Like repeat_simulate_demand, but returns every repetition's result, for checking the
+    /// device against the CPU implementation
+    ///
+    /// The work is split evenly over the work items, and whatever doesn't divide evenly runs
+    /// as a second, smaller launch, so there are exactly `count` of them.
+    fn repeat_simulate_demand_each(&self, py: Python<'_>, starting_quantity: usize, count: usize) -> PyResult<Vec<Summary>> {
+        let run = logging::allow_threads(py, || self.ocl_run(starting_quantity, count, true, &mut self.rng()))
+            .map_err(|error| RuntimeError::py_err(error.to_string()))?;
+        Ok(run.samples)
+    }
+
+    /// Find the fastest way to lay out a batch on the default device
+    ///
+    /// This happens by itself the first time a device is used, and the choice is saved (in
+    /// $RUSTOCLSIM_CACHE_DIR, or ~/.cache/rustoclsim) for later processes. Pass `force` to
+    /// measure again anyway, say after a driver update. Set RUSTOCLSIM_AUTOTUNE=0 to skip
+    /// tuning altogether.
+    ///
+    /// Returns a dict of "work_items", "local_size" (None if the driver picks) and
+    /// "chunk_size" (repetitions per work item per batch).
+    #[args(force = "false")]
+    fn autotune(&self, py: Python<'_>, force: bool) -> PyResult<PyObject> {
+        let shape = logging::allow_threads(py, || -> Fallible<Shape> {
+            if !force {
+                return self.shape(None);
+            }
+            let (platform, device) = default_device()?;
+            let shape = self.tune(None)?;
+            tuning::remember(&tuning::key(&platform, &device, self.program_hash()), shape);
+            Ok(shape)
+        }).map_err(|error| RuntimeError::py_err(error.to_string()))?;
+        let dict = PyDict::new(py);
+        dict.set_item("work_items", shape.work_items)?;
+        dict.set_item("local_size", shape.local_size)?;
+        dict.set_item("chunk_size", shape.chunk_size)?;
+        Ok(dict.to_object(py))
+    }
+
+    /// Run the kernel and the CPU reference on the same random draws, and compare them day
+    /// by day, to tell a bug in the kernel's logic from a difference in its random numbers
+    ///
+    /// The draws are made on the host, `draws` of them for each of `work_items` work items,
+    /// and uploaded, and the kernel reads them in place of its xorshift32, wrapping around if
+    /// a work item uses them all up. Each work item runs `repetitions` years. The draws come
+    /// from the simulation's seed if it has one, so a replay can be repeated exactly.
+    ///
+    /// Returns a dict with "ok" (whether everything matched), "first_difference" (None, or
+    /// (work_item, repetition, day) where the closing stock first differs), "totals" and
+    /// "reference_totals" (as in doctor()), and "trace" and "reference_trace", each a list of
+    /// every year's closing stock, day by day, for each repetition of each work item in turn.
+    #[args(work_items = "64", repetitions = "1", draws = "65536")]
+    fn debug_replay(&self, py: Python<'_>, starting_quantity: usize, work_items: usize, repetitions: usize, draws: usize) -> PyResult<PyObject> {
+        if self.kernel_source.is_some() {
+            return Err(ValueError::py_err("Replaying draws is compiled into simulation.cl, so it can't be used with kernel_source"));
+        }
+        if work_items == 0 || repetitions == 0 || draws == 0 {
+            return Err(ValueError::py_err("work_items, repetitions and draws must all be at least 1"));
+        }
+        if work_items.checked_mul(draws).is_none_or(|total| total > i32::MAX as usize) {
+            return Err(ValueError::py_err("work_items * draws has to fit in the kernel's 32 bit indices"));
+        }
+        let draws = replay::draws(&mut self.rng(), work_items, draws);
+        let (trace, reference) = logging::allow_threads(py, || -> Fallible<_> {
+            let (_, device) = default_device()?;
+            self.settle_table_precision(None)?;
+            let table_len = memory::Budget::of(&device).table_len(self.itemwise_traffic_zipf_precomp.len(), self.table_entry_bytes(&device));
+            let trace = self.replay_on_device(starting_quantity, work_items, repetitions, &draws, table_len)?;
+            Ok((trace, self.replay_reference(starting_quantity, work_items, repetitions, &draws, table_len)))
+        }).map_err(|error| RuntimeError::py_err(error.to_string()))?;
+        let dict = PyDict::new(py);
+        dict.set_item("ok", trace == reference)?;
+        dict.set_item("first_difference", trace.first_difference(&reference, repetitions))?;
+        dict.set_item("totals", trace.totals.iter().map(|&x| x as u64).collect::<Vec<u64>>())?;
+        dict.set_item("reference_totals", reference.totals.iter().map(|&x| x as u64).collect::<Vec<u64>>())?;
+        dict.set_item("trace", trace.years)?;
+        dict.set_item("reference_trace", reference.years)?;
+        Ok(dict.to_object(py))
     }
 
 }
 
+/// Simulations compare by their parameters, seed, kernel source and precision, not by
+/// identity, so they work as dict keys and in sets
+#[cfg(feature = "ocl")]
+#[pyproto]
+impl PyObjectProtocol for Simulation {
+    fn __richcmp__(&self, other: &PyAny, op: CompareOp) -> PyResult<PyObject> {
+        let py = other.py();
+        let other = match other.downcast_ref::<Simulation>() {
+            Ok(other) => other,
+            Err(_) => return Ok(py.NotImplemented()),
+        };
+        Ok(match op {
+            CompareOp::Eq => (self.config() == other.config()).into_py(py),
+            CompareOp::Ne => (self.config() != other.config()).into_py(py),
+            _ => py.NotImplemented(),
+        })
+    }
+
+    fn __hash__(&self) -> PyResult<isize> {
+        let mut hasher = DefaultHasher::new();
+        self.config().hash(&mut hasher);
+        Ok(hasher.finish() as isize)
+    }
+}
+
 /// Simulation Implementation, continued
 /// 
 /// This group doesn't mention pymethods, and isn't visible from Python
+#[cfg(feature = "ocl")]
 impl Simulation {
     fn new(
         safety_stock: usize,
@@ -61,15 +867,273 @@ impl Simulation {
         order_quantity: usize,
         job_lot_zipf: Option<f64>,
         itemwise_traffic_zipf: Option<f64>,
+        seed: Option<u64>,
     ) -> Simulation {
         let job_lot_zipf = job_lot_zipf.unwrap_or(2.75);
         let itemwise_traffic_zipf = itemwise_traffic_zipf.unwrap_or(4.0);
+        let table_seed = seed.unwrap_or_else(rand::random);
+        let cached = seed.filter(|_| tables::enabled())
+            .and_then(|seed| tables::load(seed, job_lot_zipf, itemwise_traffic_zipf));
+        let (job_lot_zipf_precomp, itemwise_traffic_zipf_precomp) = match cached {
+            Some(tables) => tables,
+            None => {
+                let mut rng = StdRng::seed_from_u64(table_seed);
+                let job_lot = gpu::zipf_table(job_lot_zipf, &mut rng);
+                let traffic = gpu::zipf_table(itemwise_traffic_zipf, &mut rng);
+                if let Some(seed) = seed.filter(|_| tables::enabled()) {
+                    tables::save(seed, job_lot_zipf, itemwise_traffic_zipf, &job_lot, &traffic);
+                }
+                (job_lot.into(), traffic.into())
+            }
+        };
         Simulation {
             safety_stock,
             lead_time,
             order_quantity,
-            job_lot_zipf_precomp: precompute_zipf_buffer(1000, job_lot_zipf),
-            itemwise_traffic_zipf_precomp: precompute_zipf_buffer(1000, itemwise_traffic_zipf)
+            job_lot_zipf,
+            itemwise_traffic_zipf,
+            job_lot_zipf_precomp: Arc::new(job_lot_zipf_precomp),
+            itemwise_traffic_zipf_precomp: Arc::new(itemwise_traffic_zipf_precomp),
+            seed,
+            table_seed,
+            kernel_source: None,
+            policy: None,
+            metrics: vec![],
+            precision: Precision::Fp32,
+            table_precision: TablePrecision::U32,
+            kernel: Kernel::LostSales,
+            timings: LastTimings::default(),
+            last_call: LastCall::default(),
+        }
+    }
+
+    /// Everything that makes two simulations the same, for == and hash()
+    ///
+    /// The zipf tables are left out, since the exponents and seed decide them. The exponents
+    /// are compared by their bits, which is the same as comparing them for every exponent a
+    /// Simulation can have, since NaN and -0.0 aren't positive.
+    #[allow(clippy::type_complexity)]
+    fn config(&self) -> (usize, usize, usize, u64, u64, Option<u64>, Option<&str>, Option<&str>, Precision, TablePrecision, Kernel) {
+        (self.safety_stock, self.lead_time, self.order_quantity, self.job_lot_zipf.to_bits(),
+            self.itemwise_traffic_zipf.to_bits(), self.seed, self.kernel_source.as_deref(),
+            self.policy.as_ref().map(OrderingPolicy::source), self.precision, self.table_precision, self.kernel)
+    }
+
+    /// The parameters baked into the program with -D, so the compiler can specialize on them,
+    /// and the inventory model it's for
+    fn defines(&self) -> Vec<(&'static str, i32)> {
+        let mut defines = vec![("LEAD_TIME", self.lead_time as i32), ("HORIZON", 365)];
+        defines.extend(self.kernel.define().map(|name| (name, 1)));
+        defines
+    }
+
+    /// A hash of this simulation's program, as far as it doesn't depend on the device: the
+    /// source and the defines
+    fn program_hash(&self) -> u64 {
+        let generated = self.generated_source();
+        let source = generated.as_deref().or(self.kernel_source.as_deref()).unwrap_or(include_str!("simulation.cl"));
+        let mut hasher = DefaultHasher::new();
+        (source, self.defines()).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// The compiled program and queue for this simulation's source and defines on `device`
+    ///
+    /// Compiling takes a while, so each combination is only compiled once per process.
+    fn program(&self, device: Option<(ocl::Platform, ocl::Device)>, work_items: usize) -> Fallible<ProQue> {
+        self.program_with(device, work_items, &[])
+    }
+
+    /// Like program, with `extra` defines on top of the simulation's own
+    fn program_with(&self, device: Option<(ocl::Platform, ocl::Device)>, work_items: usize, extra: &[(&'static str, i32)]) -> Fallible<ProQue> {
+        let generated = self.generated_source();
+        let source = generated.as_deref().or(self.kernel_source.as_deref()).unwrap_or(include_str!("simulation.cl"));
+        let (_, resolved) = match device {
+            Some(device) => device,
+            None => default_device()?,
+        };
+        let mut defines = self.defines();
+        defines.extend_from_slice(extra);
+        self.settle_table_precision(device)?;
+        if self.short_tables(&resolved) {
+            defines.push(("TABLE_USHORT", 1));
+        }
+        match local_prefix(&resolved, self.job_lot_zipf_precomp.len(), self.table_entry_bytes(&resolved)) {
+            Some(prefix) => defines.push(("LOCAL_PREFIX", prefix as i32)),
+            None => debug!("{} has too little local memory to cache the zipf tables", resolved.name().unwrap_or_default()),
+        }
+        if self.precision.double(&resolved.name().unwrap_or_default(), supports_fp64(&resolved))? {
+            defines.push(("STAT_DOUBLE", 1));
+        }
+        let key: ProgramKey = (device.map(|(_, device)| device), defines, source.to_string(), work_items);
+        let mut programs = PROGRAMS.lock();
+        if let Some(pro_que) = programs.get(&key) {
+            return Ok(pro_que.clone());
+        }
+        let mut program = ocl::Program::builder();
+        program.src(source);
+        for &(name, value) in &key.1 {
+            program.cmplr_def(name, value);
+        }
+        if generated.is_some() {
+            // The CPU divides exactly, and the policy has to order the same there
+            program.cmplr_opt("-cl-fp32-correctly-rounded-divide-sqrt");
+        }
+        let mut builder = ProQue::builder();
+        builder.prog_bldr(program).dims(work_items);
+        if let Some((platform, device)) = device {
+            builder.platform(platform).device(device);
+        }
+        let started = std::time::Instant::now();
+        let pro_que = builder.build()?;
+        let compiled = format!("Compiled ocl_simulate_demand for {} with {:?} in {:.2}s",
+            resolved.name().unwrap_or_default(), key.1, started.elapsed().as_secs_f64());
+        programs.insert(key, pro_que.clone());
+        // Not logged under the lock, where a thread with the GIL might be waiting for it
+        drop(programs);
+        info!("{}", compiled);
+        Ok(pro_que)
+    }
+
+    /// simulation.cl with the policy in place of the built-in ordering rule, and the custom
+    /// metrics worked out along the way, or None if there are neither
+    ///
+    /// The kernel reads ORDER_POLICY and POLICY_FORECAST, and METRIC_COUNT and the rest, if
+    /// they're defined. simulation.cl turns off contracting a * b + c into an fma before any
+    /// of them are expanded, since that would round differently from the CPU.
+    fn generated_source(&self) -> Option<String> {
+        if self.policy.is_none() && self.metrics.is_empty() {
+            return None;
+        }
+        let mut source = String::new();
+        if let Some(policy) = &self.policy {
+            let forecast = rustsim::policy::forecast(self.job_lot_zipf, self.itemwise_traffic_zipf);
+            source += &format!("#define ORDER_POLICY {}\n#define POLICY_FORECAST {:?}f\n", policy.to_opencl(), forecast);
+        }
+        if !self.metrics.is_empty() {
+            let each = |statement: &dyn Fn(usize, &Metric) -> String| -> String {
+                self.metrics.iter().enumerate().map(|(m, metric)| statement(m, metric)).collect::<Vec<_>>().join(" ")
+            };
+            source += &format!("#define METRIC_COUNT {}\n", self.metrics.len());
+            source += &format!("#define METRIC_START {}\n",
+                each(&|m, metric| format!("metric_year[{}] = {};", m, metric.opencl_start())));
+            source += &format!("#define METRIC_DAY {}\n",
+                each(&|m, metric| format!("metric_year[{}] = {};", m, metric.opencl_day(&format!("metric_year[{}]", m)))));
+            source += &format!("#define METRIC_FINISH {}\n",
+                each(&|m, metric| format!("metric_total[{}] += {};", m, metric.opencl_finish(&format!("metric_year[{}]", m), "HORIZON"))));
+        }
+        Some(source + include_str!("simulation.cl"))
+    }
+
+    /// Whether the tables go to `device` as 16 bit entries
+    ///
+    /// For table_precision="auto", that's whatever settle_table_precision found, and u32
+    /// until it has.
+    fn short_tables(&self, device: &ocl::Device) -> bool {
+        let little_endian = device_info!(device, EndianLittle).unwrap_or(false);
+        let short = match self.table_precision {
+            TablePrecision::U32 => false,
+            TablePrecision::U16 => true,
+            TablePrecision::Auto => SHORT_TABLES.lock().get(device).copied().unwrap_or(false),
+        };
+        // Two entries are packed into each u32 in the host's byte order, which only a
+        // little-endian device reads back the right way around
+        short && little_endian && self.kernel_source.is_none()
+    }
+
+    /// Bytes per table entry on `device`
+    fn table_entry_bytes(&self, device: &ocl::Device) -> usize {
+        if self.short_tables(device) { std::mem::size_of::<u16>() } else { std::mem::size_of::<u32>() }
+    }
+
+    /// For table_precision="auto", time a batch with each width on `device` (or the default
+    /// one), unless that's been done already in this process, and remember which was faster
+    fn settle_table_precision(&self, device: Option<(ocl::Platform, ocl::Device)>) -> Fallible<()> {
+        if self.table_precision != TablePrecision::Auto || self.kernel_source.is_some() {
+            return Ok(());
+        }
+        let (_, resolved) = match device {
+            Some(device) => device,
+            None => default_device()?,
+        };
+        if SHORT_TABLES.lock().contains_key(&resolved) {
+            return Ok(());
+        }
+        let starting_quantity = self.safety_stock + self.order_quantity;
+        let time = |table_precision: TablePrecision| -> Fallible<f64> {
+            let sim = Simulation { table_precision, ..self.clone() };
+            let shape = Shape::DEFAULT;
+            let pro_que = sim.program(device, shape.work_items)?;
+            // The first launch pays for the upload into a fresh context, so only time the second
+            sim.ocl_launch(&pro_que, pro_que.queue(), shape, starting_quantity, shape.batch_size(), false, &mut sim.rng())?.finish()?;
+            let started = std::time::Instant::now();
+            sim.ocl_launch(&pro_que, pro_que.queue(), shape, starting_quantity, shape.batch_size(), false, &mut sim.rng())?.finish()?;
+            Ok(started.elapsed().as_secs_f64())
+        };
+        let (wide, narrow) = (time(TablePrecision::U32)?, time(TablePrecision::U16)?);
+        let short = narrow < 0.95 * wide;
+        info!("{} ran a batch in {:.3}s with u32 tables and {:.3}s with u16, so table_precision=\"auto\" picks {}",
+            resolved.name().unwrap_or_default(), wide, narrow, if short { "u16" } else { "u32" });
+        SHORT_TABLES.lock().insert(resolved, short);
+        Ok(())
+    }
+
+    /// The first `table_len` entries of each table, uploaded as `device` stores them: one to a
+    /// u32, or two if the tables are short
+    fn table_buffers(&self, pro_que: &ProQue, device: &ocl::Device, table_len: usize) -> Fallible<(ocl::Buffer<u32>, ocl::Buffer<u32>)> {
+        let short = self.short_tables(device);
+        let upload = |table: &[u32]| -> ocl::Result<ocl::Buffer<u32>> {
+            if short {
+                let packed = memory::pack_u16(table);
+                pro_que.buffer_builder().len(packed.len()).copy_host_slice(&packed).build()
+            } else {
+                pro_que.buffer_builder().len(table.len()).copy_host_slice(table).build()
+            }
+        };
+        Ok((upload(&self.job_lot_zipf_precomp[..table_len])?, upload(&self.itemwise_traffic_zipf_precomp[..table_len])?))
+    }
+
+    /// What the policy sees at the end of `day`, as the kernel works it out
+    fn policy_inputs(&self, on_hand: i64, pipeline: i64, day: usize) -> Inputs {
+        Inputs {
+            on_hand: on_hand as f32,
+            pipeline: pipeline as f32,
+            day: day as f32,
+            forecast: rustsim::policy::forecast(self.job_lot_zipf, self.itemwise_traffic_zipf),
+            safety_stock: self.safety_stock as f32,
+            order_quantity: self.order_quantity as f32,
+            lead_time: self.lead_time as f32,
+        }
+    }
+
+    /// Reproducibility metadata for this simulation, as in metadata()
+    fn describe<'p>(&self, py: Python<'p>) -> PyResult<&'p PyDict> {
+        let (_, device) = default_device().map_err(|error| RuntimeError::py_err(error.to_string()))?;
+        let dict = PyDict::new(py);
+        dict.set_item("backend", "opencl")?;
+        dict.set_item("device", device.name().ok())?;
+        dict.set_item("version", env!("CARGO_PKG_VERSION"))?;
+        dict.set_item("seed", self.table_seed)?;
+        let params = PyDict::new(py);
+        params.set_item("safety_stock", self.safety_stock)?;
+        params.set_item("lead_time", self.lead_time)?;
+        params.set_item("order_quantity", self.order_quantity)?;
+        params.set_item("job_lot_zipf", self.job_lot_zipf)?;
+        params.set_item("itemwise_traffic_zipf", self.itemwise_traffic_zipf)?;
+        params.set_item("precision", self.precision.name())?;
+        params.set_item("table_precision", self.table_precision.name())?;
+        params.set_item("kernel", self.kernel.name())?;
+        params.set_item("policy", self.policy.as_ref().map(OrderingPolicy::source))?;
+        dict.set_item("params", params)?;
+        Ok(dict)
+    }
+
+    /// A random number generator for one call, which starts from the same place every time
+    /// if the simulation has a seed
+    fn rng(&self) -> StdRng {
+        match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
         }
     }
 
@@ -83,116 +1147,1601 @@ impl Simulation {
     /// 2. The source code for the inner simulation in OpenCL is in simulation.cl. We read it
     ///    into this program at compile time. using include_str!(filename)
     /// 
-    fn ocl_repeat_simulate_demand(&self, starting_quantity: usize, simulation_samples: usize) -> Fallible<(usize, usize, usize, usize, f64, f64)> {
-        let chunk_size = simulation_samples / 1000;
-        let chunk_count = 1000;
+    fn ocl_repeat_simulate_demand(&self, starting_quantity: usize, simulation_samples: usize) -> Fallible<OclRun> {
+        self.ocl_run(starting_quantity, simulation_samples, false, &mut self.rng())
+    }
+
+    /// The part of ocl_repeat_simulate_demand that actually talks to the device
+    ///
+    /// If `record` is set, the device also writes out the counters of every repetition.
+    /// The work items are seeded from `rng`.
+    fn ocl_run(&self, starting_quantity: usize, simulation_samples: usize, record: bool, rng: &mut StdRng) -> Fallible<OclRun> {
+        let shape = self.shape(None)?;
+        self.ocl_run_on(None, shape, starting_quantity, simulation_samples, record, rng)
+    }
 
+    /// Like ocl_run, on a particular device rather than the first one we find, laid out
+    /// as `shape` says
+    ///
+    /// Recording more repetitions than the device has room for takes several launches.
+    fn ocl_run_on(&self, device: Option<(ocl::Platform, ocl::Device)>, shape: Shape, starting_quantity: usize, simulation_samples: usize, record: bool, rng: &mut StdRng) -> Fallible<OclRun> {
+        let started = std::time::Instant::now();
         // Think of this program queue as your connection to the device
-        let pro_que = ProQue::builder()
-            .src(include_str!("simulation.cl"))
-            .dims(chunk_count)
-            .build()?;
+        let pro_que = self.program(device, shape.work_items)?;
+        let precompute = started.elapsed();
+        let per_launch = if record {
+            let device = pro_que.queue().device();
+            let (budget, entry_bytes) = (memory::Budget::of(&device), self.table_entry_bytes(&device));
+            budget.recorded_repetitions(budget.table_len(self.itemwise_traffic_zipf_precomp.len(), entry_bytes), entry_bytes, shape.work_items)
+        } else {
+            simulation_samples
+        };
+        if per_launch < simulation_samples {
+            warning!("Recording {} repetitions takes more memory than the device has, so they're split into launches of {}",
+                simulation_samples, per_launch);
+        }
+        let mut run = self.ocl_launch(&pro_que, pro_que.queue(), shape, starting_quantity, per_launch.min(simulation_samples), record, rng)?.finish()?;
+        let mut left = simulation_samples - per_launch.min(simulation_samples);
+        while left > 0 {
+            let batch = per_launch.min(left);
+            run.merge(&self.ocl_launch(&pro_que, pro_que.queue(), shape, starting_quantity, batch, record, rng)?.finish()?);
+            left -= batch;
+        }
+        run.timings.add(Phase::Precompute, precompute);
+        self.timings.set(run.timings);
+        Ok(run)
+    }
 
-        // These two are precomputed zipf distributions, to make sampling from these distributions
-        // faster and simpler to implement. A lot of the latency comes from precomputing these
-        // so in an ideal world you may do this in opencl too.
-        let job_lot_zipf_precomp = pro_que.buffer_builder()
-            .len(self.job_lot_zipf_precomp.len())
-            .copy_host_slice(&self.job_lot_zipf_precomp[..])
-            .build()?;
-        let itemwise_traffic_zipf_precomp = pro_que.buffer_builder()
-            .len(self.itemwise_traffic_zipf_precomp.len())
-            .copy_host_slice(&self.itemwise_traffic_zipf_precomp[..])
-            .build()?;
+    /// What repeat_until() does once the arguments are checked: the merged run and how
+    /// many repetitions went into it
+    fn run_until(&self, starting_quantity: usize, target_ci_width: f64, max_count: usize, pipeline_depth: usize) -> Fallible<(OclRun, usize)> {
+        let started = std::time::Instant::now();
+        let shape = self.shape(None)?;
+        // Each launch has some overhead, so the batches are a lot bigger than on the CPU
+        let batch_size = shape.batch_size();
+        let mut rng = self.rng();
+        let pro_que = self.program(None, shape.work_items)?;
+        let queues = (0..pipeline_depth)
+            .map(|_| ocl::Queue::new(pro_que.context(), pro_que.queue().device(), None))
+            .collect::<ocl::Result<Vec<_>>>()?;
+        let precompute = started.elapsed();
+        let first = batch_size.min(max_count);
+        let mut total = self.ocl_launch(&pro_que, &queues[0], shape, starting_quantity, first, false, &mut rng)?.finish()?;
+        let mut done = first;
+        // Keep the device busy while we merge: the next batches are already queued by the
+        // time we look at this one. If that turns out to be enough, the extra batches aren't
+        // wasted, they just narrow the interval a bit more.
+        let mut in_flight = std::collections::VecDeque::new();
+        let mut launched = 1;
+        loop {
+            while in_flight.len() < pipeline_depth && done < max_count && total.unit_rate.ci_width() > target_ci_width {
+                let batch = batch_size.min(max_count - done);
+                let queue = &queues[launched % pipeline_depth];
+                in_flight.push_back(self.ocl_launch(&pro_que, queue, shape, starting_quantity, batch, false, &mut rng)?);
+                done += batch;
+                launched += 1;
+            }
+            match in_flight.pop_front() {
+                Some(batch) => total.merge(&batch.finish()?),
+                None => break,
+            }
+        }
+        total.timings.add(Phase::Precompute, precompute);
+        self.timings.set(total.timings);
+        Ok((total, done))
+    }
+
+    /// What warmup() does: the layout for the default device, and the program for it
+    fn warm_up(&self) -> Fallible<()> {
+        let started = std::time::Instant::now();
+        let shape = self.shape(None)?;
+        self.program(None, shape.work_items)?;
+        debug!("Warmed up the default device in {:.2}s", started.elapsed().as_secs_f64());
+        Ok(())
+    }
+
+    /// The layout to use on `device` (or the default one): whatever was tuned for it before,
+    /// or else tune it now
+    ///
+    /// Each work item draws from its own stream, so the layout decides which repetitions
+    /// come out of which stream. A seeded simulation always uses Shape::DEFAULT, so its
+    /// results don't change when another process tunes the device differently.
+    fn shape(&self, device: Option<(ocl::Platform, ocl::Device)>) -> Fallible<Shape> {
+        if !tuning::enabled() || self.seed.is_some() {
+            return Ok(Shape::DEFAULT);
+        }
+        let (platform, resolved) = match device {
+            Some(device) => device,
+            None => default_device()?,
+        };
+        let key = tuning::key(&platform, &resolved, self.program_hash());
+        // One tuning per device at a time, so a call made during warmup() waits for its result.
+        // What tuning logs is sent once the lock is dropped.
+        logging::holding(|| {
+            let _tuning = TUNING.lock();
+            if let Some(shape) = tuning::recall(&key) {
+                return Ok(shape);
+            }
+            info!("Tuning the layout for {}, which only happens once per device", resolved.name().unwrap_or_default());
+            let shape = self.tune(device)?;
+            tuning::remember(&key, shape);
+            Ok(shape)
+        })
+    }
+
+    /// Time each candidate layout on `device`, and return the fastest
+    ///
+    /// First we pick the work items and work-group size by throughput on a fixed amount of
+    /// work per work item. Then, for that layout, we pick the smallest chunk size that gets
+    /// within 10% of the best throughput, since smaller batches let repeat_until stop sooner.
+    /// Once tuning::budget() runs out, we stop trying and take the best so far.
+    fn tune(&self, device: Option<(ocl::Platform, ocl::Device)>) -> Fallible<Shape> {
+        let (_, resolved) = match device {
+            Some(device) => device,
+            None => default_device()?,
+        };
+        let deadline = std::time::Instant::now() + tuning::budget();
+        let starting_quantity = self.safety_stock + self.order_quantity;
+        let mut rng = self.rng();
+        let mut time = |shape: Shape| -> Fallible<f64> {
+            let pro_que = self.program(device, shape.work_items)?;
+            let started = std::time::Instant::now();
+            self.ocl_launch(&pro_que, pro_que.queue(), shape, starting_quantity, shape.batch_size(), false, &mut rng)?.finish()?;
+            Ok(shape.batch_size() as f64 / started.elapsed().as_secs_f64())
+        };
+
+        let mut best = (Shape::DEFAULT, 0.0);
+        for candidate in tuning::candidates(&resolved)? {
+            if best.1 > 0.0 && std::time::Instant::now() > deadline {
+                debug!("Out of time for tuning, so settling for {:?}", best.0);
+                break;
+            }
+            let candidate = Shape { chunk_size: tuning::CHUNK_SIZES[0], ..candidate };
+            // Some layouts just won't launch on some devices, which only rules them out
+            match time(candidate) {
+                Ok(throughput) if throughput > best.1 => best = (candidate, throughput),
+                Ok(_) => {}
+                Err(error) => debug!("{:?} won't launch on {}: {}", candidate, resolved.name().unwrap_or_default(), error),
+            }
+        }
+        let mut throughputs = vec![];
+        for &chunk_size in &tuning::CHUNK_SIZES {
+            if !throughputs.is_empty() && std::time::Instant::now() > deadline {
+                break;
+            }
+            let candidate = Shape { chunk_size, ..best.0 };
+            throughputs.push((candidate, time(candidate)?));
+        }
+        let fastest = throughputs.iter().map(|&(_, throughput)| throughput).fold(0.0, f64::max);
+        let shape = throughputs.into_iter()
+            .find(|&(_, throughput)| throughput >= 0.9 * fastest)
+            .map_or(Shape::DEFAULT, |(shape, _)| shape);
+        info!("Tuned {}: {:?}, at {:.0} repetitions per second", resolved.name().unwrap_or_default(), shape, best.1);
+        Ok(shape)
+    }
+
+    /// The summaries of `policies`, `count` repetitions each, from one launch
+    fn sweep(&self, py: Python<'_>, policies: &[Policy], count: usize) -> PyResult<Vec<Summary>> {
+        if policies.is_empty() {
+            return Ok(vec![]);
+        }
+        if policies.len() > 1 && self.kernel_source.as_ref().is_some_and(|source| !source.contains("__kernel void ocl_simulate_demand_sweep")) {
+            return Err(ValueError::py_err("The kernel source doesn't define __kernel void ocl_simulate_demand_sweep"));
+        }
+        let runs = logging::allow_threads(py, || -> Fallible<Vec<OclRun>> {
+            let started = std::time::Instant::now();
+            let shape = self.shape(None)?;
+            let pro_que = self.program(None, shape.work_items)?;
+            let precompute = started.elapsed();
+            let mut runs = self.ocl_launch_policies(&pro_que, pro_que.queue(), shape, policies, count, false, &mut self.rng())?.finish_each()?;
+            runs[0].timings.add(Phase::Precompute, precompute);
+            self.timings.set(runs[0].timings);
+            Ok(runs)
+        }).map_err(|error| RuntimeError::py_err(error.to_string()))?;
+        runs.iter().map(OclRun::checked_summary).collect()
+    }
+
+    /// Queue up one batch of this simulation's policy on the device, starting from
+    /// `starting_quantity`, and return without waiting for it
+    #[allow(clippy::too_many_arguments)]
+    fn ocl_launch(&self, pro_que: &ProQue, queue: &ocl::Queue, shape: Shape, starting_quantity: usize, simulation_samples: usize, record: bool, rng: &mut StdRng) -> Fallible<InFlight> {
+        let policy = (starting_quantity, self.safety_stock, self.order_quantity);
+        self.ocl_launch_policies(pro_que, queue, shape, &[policy], simulation_samples, record, rng)
+    }
+
+    /// Queue up one batch on the device for each of `policies` and return without waiting
+    ///
+    /// One policy runs ocl_simulate_demand on `shape`'s work items. More than one run in a
+    /// single launch of ocl_simulate_demand_sweep, with a copy of the layout for each, and
+    /// every copy seeded the same, so each policy sees the same customers.
+    ///
+    /// The results go into pinned host memory, which is mapped without blocking once the
+    /// kernel is done, so the caller can queue the next batch, or merge the last one, while
+    /// this one runs. The kernel and the maps go on `queue`, so batches on different queues
+    /// can overlap.
+    /// With the "svm" feature, on a device that supports fine-grained shared virtual memory,
+    /// the kernel writes its results straight into host-visible memory instead, and there
+    /// are no reads at all.
+    ///
+    /// The work items can't split `simulation_samples` evenly unless it's a multiple of
+    /// them, so what's left over runs as a second launch of one repetition per work item,
+    /// and only as many of those work items as there are repetitions left count.
+    #[allow(clippy::too_many_arguments)]
+    fn ocl_launch_policies(&self, pro_que: &ProQue, queue: &ocl::Queue, shape: Shape, policies: &[Policy], simulation_samples: usize, record: bool, rng: &mut StdRng) -> Fallible<InFlight> {
+        let (chunk_size, remainder) = (simulation_samples / shape.work_items, simulation_samples % shape.work_items);
+        if chunk_size == 0 && remainder > 0 {
+            return self.ocl_launch_chunks(pro_que, queue, shape, policies, 1, remainder, record, rng);
+        }
+        let mut batch = self.ocl_launch_chunks(pro_que, queue, shape, policies, chunk_size, shape.work_items, record, rng)?;
+        if remainder > 0 {
+            batch.tail = Some(Box::new(self.ocl_launch_chunks(pro_que, queue, shape, policies, 1, remainder, record, rng)?));
+        }
+        Ok(batch)
+    }
+
+    /// Queue up `chunk_size` repetitions on each of `shape`'s work items for each of
+    /// `policies`, of which only the first `used` work items of each policy count
+    #[allow(clippy::too_many_arguments)]
+    fn ocl_launch_chunks(&self, pro_que: &ProQue, queue: &ocl::Queue, shape: Shape, policies: &[Policy], chunk_size: usize, used: usize, record: bool, rng: &mut StdRng) -> Fallible<InFlight> {
+        let mut stopwatch = Stopwatch::start();
+        let chunk_count = shape.work_items * policies.len();
 
         // We also need to seed the simple uniform random number generator on ocl because it has no randomness of its own
         // So first we compute it on the CPU (the Host)
-        let seed : Vec<u32> = (0..chunk_count).into_iter().map(|_| rand::random()).collect();
-        // Then send it to the device
+        let seed = work_item_seeds(rng, shape.work_items).repeat(policies.len());
+        stopwatch.lap(Phase::Seeds);
+
+        // These two are precomputed zipf distributions, to make sampling from these distributions
+        // faster and simpler to implement. A lot of the latency comes from precomputing these
+        // so in an ideal world you may do this in opencl too. On a device too small for them,
+        // only the start of each goes (see memory.rs).
+        // With table_precision, two entries may share each u32.
+        let device = queue.device();
+        let table_len = memory::Budget::of(&device).table_len(self.itemwise_traffic_zipf_precomp.len(), self.table_entry_bytes(&device));
+        let (job_lot_zipf_precomp, itemwise_traffic_zipf_precomp) = self.table_buffers(pro_que, &device, table_len)?;
+
+        // Then send the seeds to the device
         let seed = pro_que.buffer_builder::<u32>()
             .len(chunk_count)
             .copy_host_slice(&seed[..])
             .build()?;
 
-        // These four are the resulting statistics, to be filled in by the device
-        let successful_transactions = pro_que.create_buffer::<u64>()?;
-        let successful_sales        = pro_que.create_buffer::<u64>()?;
-        let failed_transactions     = pro_que.create_buffer::<u64>()?;
-        let failed_sales            = pro_que.create_buffer::<u64>()?;
+        // Four counters per repetition. OpenCL has no empty buffers, so keep one around anyway.
+        let samples_len = if record { (4 * chunk_size * chunk_count).max(1) } else { 1 };
+
+        // Have the kernel write its results into shared memory, if we can, so there's nothing
+        // to read back at all
+        #[cfg(feature = "svm")]
+        let shared = if svm::supported(&device) {
+            svm::Results::new(pro_que.context(), chunk_count, samples_len)
+        } else {
+            None
+        };
+        #[cfg(feature = "svm")]
+        if shared.is_none() {
+            debug!("No fine-grained shared virtual memory here, so reading the results back instead");
+        }
+        #[cfg(feature = "svm")]
+        let copy_back = shared.is_none();
+        #[cfg(not(feature = "svm"))]
+        let copy_back = true;
 
+        // Otherwise they go in buffers, which only go one way, device to host. Asking for
+        // host-accessible memory means we can map them once the kernel is done and read them
+        // right there, rather than have the driver copy them into our own vectors.
+        let outputs = if copy_back { Some(Outputs::new(pro_que, chunk_count, samples_len)?) } else { None };
 
-        let kernel = pro_que.kernel_builder("ocl_simulate_demand")
+        // Three ints per policy, for the sweep kernel
+        let policy_buffer = match policies {
+            [_] => None,
+            _ => {
+                let flat: Vec<i32> = policies.iter()
+                    .flat_map(|&(starting_quantity, safety_stock, order_quantity)|
+                        [starting_quantity as i32, safety_stock as i32, order_quantity as i32])
+                    .collect();
+                Some(pro_que.buffer_builder::<i32>().len(flat.len()).copy_host_slice(&flat[..]).build()?)
+            }
+        };
+        // Each work item's total of each custom metric, if there are any
+        let metrics = match self.metrics.len() {
+            0 => None,
+            count => Some(pro_que.buffer_builder::<f32>().flags(Outputs::flags()).len(chunk_count * count).build()?),
+        };
+        // Drivers may put off the actual copies until the kernel needs them, in which case
+        // they count as kernel time
+        stopwatch.lap(Phase::Upload);
+
+        let mut builder = pro_que.kernel_builder(if policy_buffer.is_some() { "ocl_simulate_demand_sweep" } else { "ocl_simulate_demand" });
+        builder
+            .global_work_size(chunk_count)
+            .local_work_size(shape.local_size.map_or(ocl::SpatialDims::Unspecified, ocl::SpatialDims::from))
             .arg(&seed)
             .arg(&job_lot_zipf_precomp)
-            .arg(&itemwise_traffic_zipf_precomp)
-            .arg(&successful_transactions)
-            .arg(&successful_sales)
-            .arg(&failed_transactions)
-            .arg(&failed_sales)
-            .arg(starting_quantity)
-            .arg(self.lead_time.min(10))
-            .arg(self.safety_stock as i32)
-            .arg(self.order_quantity as i32)
-            .arg(self.itemwise_traffic_zipf_precomp.len())
-            .arg(chunk_size)
-            .build()?;
+            .arg(&itemwise_traffic_zipf_precomp);
+        // With shared memory these start out null, and point at it once the kernel is built
+        for index in 0..4 {
+            builder.arg(outputs.as_ref().map(|outputs| &outputs.counters[index]));
+        }
+        builder.arg(outputs.as_ref().map(|outputs| &outputs.rate_count));
+        for index in 0..4 {
+            builder.arg(outputs.as_ref().map(|outputs| &outputs.rates[index]));
+        }
+        builder
+            .arg(outputs.as_ref().map(|outputs| &outputs.samples))
+            .arg(record as u32);
+        // The scalars have to match the kernel's C types exactly. The two kernels only differ
+        // in how they get the policy, but that moves where the slice arguments below are.
+        let samples_arg = match (&policy_buffer, policies) {
+            (Some(policy_buffer), _) => {
+                builder.arg(policy_buffer).arg(shape.work_items as u32).arg(self.lead_time as u32);
+                18
+            }
+            (None, &[(starting_quantity, safety_stock, order_quantity)]) => {
+                builder
+                    .arg(starting_quantity as i32)
+                    .arg(self.lead_time as u32)
+                    .arg(safety_stock as i32)
+                    .arg(order_quantity as i32);
+                19
+            }
+            (None, _) => unreachable!(),
+        };
+        builder
+            .arg(table_len as u32)
+            .arg(chunk_size as u32)
+            // Where this launch starts, and how many there are in all; see below
+            .arg(0u32)
+            .arg(chunk_size as u32);
+        if let Some(metrics) = &metrics {
+            builder.arg(metrics);
+        }
+        let kernel = builder.build()?;
+
+        #[cfg(feature = "svm")]
+        if let Some(shared) = &shared {
+            shared.set_args(&kernel, 3)?;
+        }
+
+        // On a device with a display watchdog, each launch only runs a slice of every work
+        // item's repetitions, so that none of them takes long enough to be killed. The kernel
+        // leaves its counters and random state in global memory for the next one. The queue
+        // runs them in order, so the last one finishing means they all have.
+        let slice = match watchdog_slice(&queue.device()) {
+            Some(slice) if slice < chunk_size => {
+                debug!("Splitting each launch into slices of {} repetitions per work item, for the watchdog", slice);
+                slice
+            }
+            _ => chunk_size,
+        };
+        let mut first = 0;
+        let kernel_done = loop {
+            let count = slice.min(chunk_size - first);
+            kernel.set_arg(samples_arg, count as u32)?;
+            kernel.set_arg(samples_arg + 1, first as u32)?;
+            let mut event = ocl::Event::empty();
+            unsafe { kernel.cmd().queue(queue).enew(&mut event).enq()?; }
+            first += count;
+            if first >= chunk_size {
+                break event;
+            }
+        };
 
-        unsafe { kernel.enq()?; }
+        let mut batch = InFlight {
+            chunk_size,
+            chunk_count,
+            used,
+            policies: policies.len(),
+            record,
+            kernel_done: kernel_done.clone(),
+            stopwatch,
+            events: vec![],
+            mapped: Mapped::default(),
+            tail: None,
+            #[cfg(feature = "svm")]
+            shared,
+        };
+        if let Some(outputs) = &outputs {
+            // Now queue up the maps. None of these wait: each one starts once the kernel is
+            // done, and leaves behind an event we can wait on later. finish() reads straight
+            // out of the mappings.
+            for buffer in &outputs.counters {
+                let map = map_after(buffer, queue, &kernel_done, &mut batch.events)?;
+                batch.mapped.counters.push(map);
+            }
+            batch.mapped.rate_count = Some(map_after(&outputs.rate_count, queue, &kernel_done, &mut batch.events)?);
+            for buffer in &outputs.rates {
+                let map = map_after(buffer, queue, &kernel_done, &mut batch.events)?;
+                batch.mapped.rates.push(map);
+            }
+            if record {
+                batch.mapped.samples = Some(map_after(&outputs.samples, queue, &kernel_done, &mut batch.events)?);
+            }
+        } else {
+            // The results are already where we can see them once the kernel is done
+            batch.events.push(kernel_done.clone());
+        }
+        if let Some(metrics) = &metrics {
+            // There's no shared memory for these, so they're mapped either way
+            batch.mapped.metrics = Some(map_after(metrics, queue, &kernel_done, &mut batch.events)?);
+        }
+        // Hand it all to the device now, rather than whenever the driver gets around to it
+        queue.flush()?;
+        // Dropping the device buffers here is fine: OpenCL keeps them alive until the
+        // commands that use them are finished, and the mappings hold on to them after that.
+        Ok(batch)
+    }
+
+}
+
+/// Map `buffer` for reading once `after` is done, without waiting for it, and add the map's
+/// event to `events`
+#[cfg(feature = "ocl")]
+fn map_after<T: ocl::OclPrm>(buffer: &ocl::Buffer<T>, queue: &ocl::Queue, after: &ocl::Event, events: &mut Vec<ocl::Event>) -> Fallible<ocl::FutureMemMap<T>> {
+    let mut event = ocl::Event::empty();
+    let map = unsafe { buffer.map().read().queue(queue).ewait(after).enew(&mut event).enq_async()? };
+    events.push(event);
+    Ok(map)
+}
+
+/// The buffers the kernel writes its results into, when it can't use shared memory
+///
+/// They're in the same order as the kernel's parameters.
+#[cfg(feature = "ocl")]
+struct Outputs {
+    /// Successful transactions, successful sales, failed transactions and failed sales
+    counters: [ocl::Buffer<u64>; 4],
+    /// And the running (count, mean, M2) of each work item's fill rates
+    rate_count: ocl::Buffer<u32>,
+    rates: [ocl::Buffer<f32>; 4],
+    samples: ocl::Buffer<u32>,
+}
+
+#[cfg(feature = "ocl")]
+impl Outputs {
+    /// Write-only for the device, and somewhere the host can map
+    fn flags() -> ocl::MemFlags {
+        ocl::MemFlags::new().write_only().alloc_host_ptr()
+    }
+
+    fn new(pro_que: &ProQue, work_items: usize, samples: usize) -> Fallible<Outputs> {
+        let n = work_items;
+        Ok(Outputs {
+            counters: [Outputs::buffer(pro_que, n)?, Outputs::buffer(pro_que, n)?, Outputs::buffer(pro_que, n)?, Outputs::buffer(pro_que, n)?],
+            rate_count: Outputs::buffer(pro_que, n)?,
+            rates: [Outputs::buffer(pro_que, n)?, Outputs::buffer(pro_que, n)?, Outputs::buffer(pro_que, n)?, Outputs::buffer(pro_que, n)?],
+            samples: Outputs::buffer(pro_que, samples)?,
+        })
+    }
+
+    fn buffer<T: ocl::OclPrm>(pro_que: &ProQue, len: usize) -> ocl::Result<ocl::Buffer<T>> {
+        pro_que.buffer_builder().flags(Outputs::flags()).len(len).build()
+    }
+}
+
+/// The pinned result buffers of a batch, mapped (or about to be) for reading
+///
+/// Each mapping is only readable once its event in InFlight.events has completed. A mapping
+/// that never got waited on stays mapped, so finish() resolves them and reads them in place,
+/// and dropping the batch unmaps them too.
+#[cfg(feature = "ocl")]
+#[derive(Default)]
+struct Mapped {
+    counters: Vec<ocl::FutureMemMap<u64>>,
+    rate_count: Option<ocl::FutureMemMap<u32>>,
+    rates: Vec<ocl::FutureMemMap<f32>>,
+    samples: Option<ocl::FutureMemMap<u32>>,
+    metrics: Option<ocl::FutureMemMap<f32>>,
+}
+
+#[cfg(feature = "ocl")]
+impl Mapped {
+    /// The mappings, ready to read, once the maps are done
+    ///
+    /// Each one unmaps itself when it's dropped. Whatever wasn't mapped, like the counters
+    /// when shared memory held them, stays empty.
+    fn resolve(self) -> Fallible<Resolved> {
+        Ok(Resolved {
+            counters: self.counters.into_iter().map(|map| map.wait()).collect::<Result<_, _>>()?,
+            rate_count: self.rate_count.map(|map| map.wait()).transpose()?,
+            rates: self.rates.into_iter().map(|map| map.wait()).collect::<Result<_, _>>()?,
+            samples: self.samples.map(|map| map.wait()).transpose()?,
+            metrics: self.metrics.map(|map| map.wait()).transpose()?,
+        })
+    }
+
+    /// Unmap everything without looking at it, once the maps are done
+    fn unmap(self) {
+        // Waiting turns each into a MemMap, which unmaps itself when it's dropped
+        self.counters.into_iter().for_each(|map| drop(map.wait()));
+        self.rate_count.into_iter().chain(self.samples).for_each(|map| drop(map.wait()));
+        self.rates.into_iter().chain(self.metrics).for_each(|map| drop(map.wait()));
+    }
+}
+
+/// Mapped in Mapped, now that the maps are done
+#[cfg(feature = "ocl")]
+struct Resolved {
+    counters: Vec<ocl::MemMap<u64>>,
+    rate_count: Option<ocl::MemMap<u32>>,
+    rates: Vec<ocl::MemMap<f32>>,
+    samples: Option<ocl::MemMap<u32>>,
+    metrics: Option<ocl::MemMap<f32>>,
+}
+
+/// A batch's results, wherever they ended up, for adding up in place
+#[cfg(feature = "ocl")]
+struct Results<'a> {
+    /// Successful transactions, successful sales, failed transactions and failed sales,
+    /// per work item
+    counters: [&'a [u64]; 4],
+    /// How many fill rates each work item saw
+    rate_count: &'a [u32],
+    /// Transaction rate mean and M2, then unit rate mean and M2, per work item
+    rates: [&'a [f32]; 4],
+    /// Four counters per repetition, if we asked for them
+    samples: &'a [u32],
+    /// Each work item's total of each custom metric, work item by work item
+    metrics: &'a [f32],
+}
 
-        // Copy the statistics back. It doesn't have to be this hard.
-        // But I want to explain it all in detail because I figure you'll spend a lot of your time
-        // doing exactly this.
-        
-        // I did it by making a single vector, which the closure will take control of (hence "move")
-        let mut vec = vec![0u64; chunk_count];
-        let mut get_sum = move |buffer: &ocl::Buffer<u64>| -> ocl::Result<usize> {
-            // This copies the device buffer into our host vector.
-            buffer.read(&mut vec).enq()?;
-            // This iterates over it and sums it into a u64.
-            // It would be a good idea to keep it as u64 because - who knows - maybe we want to
-            // sell more than 4 billion widgets. But they are purposely inconvenient to work with
-            // because they are also inconvenient for some computers to work with and they will
-            // slow you down on the GPU. Usize, however, is whichever size numbers your computer
-            // naturally uses. So we convert it to that and ignore the possible tragedy. We'll
-            // just show the max we can if we are limited. Good? No. But easy and maybe good enough
-            Ok(vec.iter().copied().sum::<u64>().try_into().unwrap_or(::std::usize::MAX))
+#[cfg(feature = "ocl")]
+impl Resolved {
+    /// The mapped results. Those in shared memory instead come from `shared`.
+    fn results<'a>(&'a self, #[cfg(feature = "svm")] shared: Option<&'a svm::Results>) -> Results<'a> {
+        #[cfg(feature = "svm")]
+        if let Some(shared) = shared {
+            // The kernel is done with these, so the host can read them
+            let (counters, rate_count, rates, samples) = unsafe { shared.slices() };
+            return Results {
+                counters,
+                rate_count,
+                rates,
+                samples,
+                metrics: self.metrics.as_deref().unwrap_or_default(),
+            };
+        }
+        fn four<T: ocl::OclPrm>(maps: &[ocl::MemMap<T>]) -> [&[T]; 4] {
+            [&maps[0], &maps[1], &maps[2], &maps[3]]
+        }
+        Results {
+            counters: four(&self.counters),
+            rate_count: self.rate_count.as_deref().unwrap_or_default(),
+            rates: four(&self.rates),
+            samples: self.samples.as_deref().unwrap_or_default(),
+            metrics: self.metrics.as_deref().unwrap_or_default(),
+        }
+    }
+}
+
+/// A batch the device is still working on
+///
+/// The results are in pinned buffers being mapped (or in shared memory the kernel is
+/// writing), so nobody may look at them until every event has completed. finish() waits for
+/// that and reads them where they are, and dropping the batch waits too.
+#[cfg(feature = "ocl")]
+struct InFlight {
+    chunk_size: usize,
+    chunk_count: usize,
+    /// How many of each policy's work items count; the rest ran repetitions nobody asked for
+    used: usize,
+    /// How many policies share the work items, in equal consecutive runs
+    policies: usize,
+    record: bool,
+    /// One per map, or just the kernel's if the results are in shared memory
+    events: Vec<ocl::Event>,
+    /// The pinned buffers the results are in, until finish() reads them
+    mapped: Mapped,
+    /// The last kernel launch's, which the reads wait on
+    kernel_done: ocl::Event,
+    /// Timing the batch, from launch to finish
+    stopwatch: Stopwatch,
+    /// The launch with the repetitions left over after the work items split the rest evenly
+    tail: Option<Box<InFlight>>,
+    /// Where the kernel is writing its results instead, if the device can share memory
+    #[cfg(feature = "svm")]
+    shared: Option<svm::Results>,
+}
+
+#[cfg(feature = "ocl")]
+impl InFlight {
+    /// Whether the device is finished with the batch and its tail
+    fn done(&self) -> bool {
+        self.events.iter().all(|event| event.is_complete().unwrap_or(true))
+            && self.tail.as_ref().is_none_or(|tail| tail.done())
+    }
+
+    /// Wait for the device, then add everything up
+    fn finish(self) -> Fallible<OclRun> {
+        let mut runs = self.finish_each()?.into_iter();
+        let mut total = runs.next().ok_or_else(|| failure::err_msg("the batch had no policies"))?;
+        for run in runs {
+            total.merge(&run);
+        }
+        Ok(total)
+    }
+
+    /// Like finish, but adding up each policy's work items on their own, in order
+    ///
+    /// The batch's timings go with the first run, so merging them all counts them once.
+    fn finish_each(mut self) -> Fallible<Vec<OclRun>> {
+        self.kernel_done.wait_for()?;
+        self.stopwatch.lap(Phase::Kernel);
+        for event in std::mem::take(&mut self.events) {
+            event.wait_for()?;
+        }
+        let resolved = std::mem::take(&mut self.mapped).resolve()?;
+        #[cfg(feature = "svm")]
+        let results = resolved.results(self.shared.as_ref());
+        #[cfg(not(feature = "svm"))]
+        let results = resolved.results();
+        self.stopwatch.lap(Phase::Readback);
+        let per_policy = self.chunk_count / self.policies;
+        let mut runs: Vec<OclRun> = (0..self.policies)
+            .map(|policy| self.add_up(&results, policy * per_policy..policy * per_policy + self.used))
+            .collect();
+        for run in runs.iter().filter(|run| !run.anomalies.is_empty()) {
+            let described: Vec<String> = run.anomalies.iter().take(5).map(|(item, why)| format!("work item {}: {}", item, why)).collect();
+            warning!("{} of {} work items came back looking wrong, which usually means the device isn't running the kernel faithfully; \
+                try rustoclsim.doctor(). {}", run.anomalies.len(), self.used, described.join("; "));
+        }
+        self.stopwatch.lap(Phase::Reduction);
+        runs[0].timings = self.stopwatch.breakdown();
+        if let Some(tail) = self.tail.take() {
+            for (run, more) in runs.iter_mut().zip(tail.finish_each()?) {
+                run.merge(&more);
+            }
+        }
+        Ok(runs)
+    }
+
+    /// The work items in `items`, added up
+    fn add_up(&self, results: &Results, items: std::ops::Range<usize>) -> OclRun {
+        // Each work item's u64 is safe enough, but a thousand of them added up might not be,
+        // and a billion-scale run merged over many batches even less so. So we add them up
+        // in u128, which can't realistically overflow, and only squeeze the result into a
+        // usize at the very end, where we can complain if it doesn't fit.
+        let mut totals = [0u128; 4];
+        for (total, counter) in totals.iter_mut().zip(results.counters) {
+            *total = counter[items.clone()].iter().map(|&x| u128::from(x)).sum();
+        }
+        let [st, ss, ft, fs] = results.counters;
+        let (st, ss, ft, fs) = (&st[items.clone()], &ss[items.clone()], &ft[items.clone()], &fs[items.clone()]);
+        let work_items: Vec<WorkItemTotals> = st.iter().zip(ss).zip(ft).zip(fs)
+            .map(|(((&st, &ss), &ft), &fs)| (st, ss, ft, fs))
+            .collect();
+
+        // Each work item summarized its own repetitions, so merge those summaries together
+        let get_welford = |mean: &[f32], m2: &[f32]| -> Welford {
+            let mut total = Welford::new();
+            for ((&count, &mean), &m2) in results.rate_count[items.clone()].iter().zip(&mean[items.clone()]).zip(&m2[items.clone()]) {
+                total.merge(&Welford::from_parts(count.into(), mean.into(), m2.into()));
+            }
+            total
+        };
+        let transaction_rate = get_welford(results.rates[0], results.rates[1]);
+        let unit_rate = get_welford(results.rates[2], results.rates[3]);
+
+        let samples = if self.record {
+            results.samples.chunks_exact(4).skip(self.chunk_size * items.start).take(self.chunk_size * items.len()).map(|c| {
+                let (st, ss, ft, fs) = (c[0] as usize, c[1] as usize, c[2] as usize, c[3] as usize);
+                (st, ss, ft, fs, fill_rate(st, ft), fill_rate(ss, fs))
+            }).collect()
+        } else {
+            vec![]
         };
-        let st = get_sum(&successful_transactions)?;
-        let ss = get_sum(&successful_sales)?;
-        let ft = get_sum(&failed_transactions)?;
-        let fs = get_sum(&failed_sales)?;
 
-        Ok((st, ss, ft, fs,
-            st as f64 / (st as f64 + ft as f64),
-            ss as f64 / (ss as f64 + fs as f64)))
+        let count = results.metrics.len() / self.chunk_count;
+        let metrics = (0..count)
+            .map(|m| results.metrics[count * items.start..count * items.end].iter().skip(m).step_by(count).map(|&x| x as f64).sum())
+            .collect();
+
+        let anomalies = anomaly::anomalies(&work_items, &results.rate_count[items.clone()], self.chunk_size);
+        OclRun {
+            totals,
+            transaction_rate,
+            unit_rate,
+            samples,
+            work_items,
+            metrics,
+            anomalies,
+            timings: Breakdown::default(),
+        }
+    }
+}
+
+#[cfg(feature = "ocl")]
+impl Drop for InFlight {
+    /// The device may still be writing into our buffers, so don't free them out from under it
+    fn drop(&mut self) {
+        for event in &self.events {
+            let _ = event.wait_for();
+        }
+        std::mem::take(&mut self.mapped).unmap();
+    }
+}
+
+/// A repeat_simulate_demand_async that may still be running on the device
+///
+/// Call result() to wait for the summary, done() to check without waiting, or await it. A run
+/// that's still going can only be waited for in the process that started it: after fork(),
+/// the child gets RuntimeError.
+#[cfg(feature = "ocl")]
+#[pyclass(module = "rustoclsim")]
+struct PendingRun {
+    // Shared with the iterators __await__ hands out, which need to see the same batch
+    state: Arc<Mutex<Pending>>,
+}
+
+/// The batch behind a PendingRun, until it's done, and then what it came to
+#[cfg(feature = "ocl")]
+struct Pending {
+    batch: Option<InFlight>,
+    // What the batch came to, or why OpenCL couldn't finish it
+    run: Option<Result<OclRun, String>>,
+    // The process that started the batch, which is the only one that can wait for it
+    pid: u32,
+}
+
+#[cfg(feature = "ocl")]
+impl Pending {
+    /// Raise RuntimeError if the batch is still running in the process we were forked from
+    ///
+    /// Its events and buffers live in the parent's context, so waiting on them here could hang
+    /// or corrupt the driver. A finished run is plain numbers, and fine anywhere.
+    fn check_process(&mut self) -> PyResult<()> {
+        if self.batch.is_none() || self.pid == std::process::id() {
+            return Ok(());
+        }
+        // Leaked rather than dropped, since dropping waits on the parent's events
+        std::mem::forget(self.batch.take());
+        Err(RuntimeError::py_err(format!(
+            "this PendingRun was started in process {} and didn't finish before fork(); start the run again in this process",
+            self.pid)))
+    }
+
+    /// Wait for the batch, if we haven't already
+    ///
+    /// This doesn't touch Python, so it can run with the GIL released.
+    fn finish(&mut self) {
+        if let Some(batch) = self.batch.take() {
+            self.run = Some(batch.finish().map_err(|error| error.to_string()));
+        }
+    }
+
+    /// What the batch came to, waiting for it if need be
+    ///
+    /// Raises RuntimeError if OpenCL failed, or if the batch was left behind by fork().
+    fn wait(&mut self) -> PyResult<&OclRun> {
+        self.finish();
+        match &self.run {
+            Some(Ok(run)) => Ok(run),
+            Some(Err(error)) => Err(RuntimeError::py_err(error.clone())),
+            None => Err(RuntimeError::py_err(format!(
+                "this PendingRun was started in process {} and didn't finish before fork(); start the run again in this process",
+                self.pid))),
+        }
+    }
+
+    /// Whether the device is finished with the batch, so waiting won't block
+    fn done(&self) -> bool {
+        match &self.batch {
+            Some(batch) => batch.done(),
+            None => true,
+        }
+    }
+}
+
+#[cfg(feature = "ocl")]
+impl Drop for Pending {
+    /// A fork() child that never looked at an inherited run mustn't wait on it on the way out
+    fn drop(&mut self) {
+        if self.pid != std::process::id() {
+            std::mem::forget(self.batch.take());
+        }
+    }
+}
+
+#[cfg(feature = "ocl")]
+#[pymethods]
+impl PendingRun {
+    /// The summary, as repeat_simulate_demand would have returned it, waiting if need be
+    ///
+    /// Other Python threads keep running while we wait. Raises RuntimeError if OpenCL fails,
+    /// and OverflowError if the totals don't fit in this platform's integers.
+    fn result(&self, py: Python<'_>) -> PyResult<Summary> {
+        let state = &self.state;
+        state.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).check_process()?;
+        logging::allow_threads(py, || state.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).finish());
+        state.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).wait()?.checked_summary()
+    }
+
+    /// Whether result() would return right away
+    fn done(&self) -> PyResult<bool> {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        state.check_process()?;
+        Ok(state.done())
+    }
+}
+
+#[cfg(feature = "ocl")]
+#[pyproto]
+impl<'p> PyAsyncProtocol<'p> for PendingRun {
+    /// Await a future on the running event loop, which a thread of our own settles once the
+    /// device is done
+    ///
+    /// The loop has nothing to do with the run meanwhile, rather than polling it. Raises
+    /// RuntimeError outside a running event loop.
+    fn __await__(&'p self) -> PyResult<PyObject> {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).check_process()?;
+        let event_loop = py.import("asyncio")?.call0("get_running_loop")?;
+        let future = event_loop.call_method0("create_future")?;
+        let run = Py::new(py, PendingRun { state: self.state.clone() })?;
+        let (state, event_loop_ref, future_ref) = (self.state.clone(), event_loop.to_object(py), future.to_object(py));
+        std::thread::spawn(move || {
+            // Waiting doesn't touch Python, so this only needs the GIL to hand over the result
+            state.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).finish();
+            let gil = Python::acquire_gil();
+            let py = gil.python();
+            logging::flush(py);
+            let settle = wrap_pyfunction!(settle_future)(py);
+            if let Err(error) = event_loop_ref.call_method1(py, "call_soon_threadsafe", (settle, future_ref, run)) {
+                // Most likely the loop closed first, and then nobody is waiting any more
+                debug!("Couldn't hand a finished run back to its event loop: {:?}", error);
+            }
+        });
+        Ok(future.call_method0("__await__")?.to_object(py))
+    }
+}
+
+/// Settle `future` with what `run` came to, unless it was cancelled meanwhile
+///
+/// PendingRun.__await__ has the event loop call this once the run is finished, since futures
+/// can only be touched from the loop's own thread. It isn't part of the module.
+#[cfg(feature = "ocl")]
+#[pyfunction]
+fn settle_future(py: Python<'_>, future: &PyAny, run: &PendingRun) -> PyResult<()> {
+    if future.call_method0("done")?.extract()? {
+        return Ok(());
+    }
+    match run.result(py) {
+        Ok(summary) => future.call_method1("set_result", (summary,))?,
+        Err(error) => future.call_method1("set_exception", (error.to_object(py),))?,
+    };
+    Ok(())
+}
+
+/// What a compiled program depends on: the device (None for the default), the defines, the
+/// source, and the number of work items
+#[cfg(feature = "ocl")]
+type ProgramKey = (Option<ocl::Device>, Vec<(&'static str, i32)>, String, usize);
+
+/// Every program compiled so far in this process
+///
+/// OpenCL contexts belong to the process that made them, so a fork() child starts over
+/// rather than using the parent's (see fork.rs).
+#[cfg(feature = "ocl")]
+static PROGRAMS: PerProcess<HashMap<ProgramKey, ProQue>> = PerProcess::new(HashMap::new);
+
+/// What table_precision="auto" settled on for each device in this process: whether its
+/// tables are 16 bit
+#[cfg(feature = "ocl")]
+static SHORT_TABLES: PerProcess<HashMap<ocl::Device, bool>> = PerProcess::new(HashMap::new);
+
+/// Held while a device's layout is looked up or tuned, so two threads don't tune at once
+#[cfg(feature = "ocl")]
+static TUNING: PerProcess<()> = PerProcess::new(|| ());
+
+/// The parameter types of ocl_simulate_demand, which the host code depends on
+#[cfg(feature = "ocl")]
+const KERNEL_PARAMETERS: [&str; 22] = [
+    "__global uint*", "__global uint*", "__global uint*",
+    "__global ulong*", "__global ulong*", "__global ulong*", "__global ulong*",
+    "__global uint*",
+    "__global float*", "__global float*", "__global float*", "__global float*",
+    "__global uint*", "uint",
+    "int", "uint", "int", "int", "uint", "uint", "uint", "uint",
+];
+
+/// Make sure custom kernel source defines ocl_simulate_demand with the parameters we pass it
+///
+/// This only reads the declaration; whether the rest compiles is up to the OpenCL compiler.
+#[cfg(feature = "ocl")]
+fn check_kernel_signature(source: &str) -> Result<(), String> {
+    let start = source.find("__kernel void ocl_simulate_demand")
+        .ok_or("The kernel source doesn't define __kernel void ocl_simulate_demand")?;
+    let open = start + source[start..].find('(').ok_or("Can't find ocl_simulate_demand's parameters")?;
+    let close = open + source[open..].find(')').ok_or("Can't find ocl_simulate_demand's parameters")?;
+    // Leave out anything conditional, like the metrics buffer, which only simulation.cl needs
+    let mut depth = 0;
+    let parameters: String = source[open + 1..close].lines().filter(|line| {
+        let line = line.trim();
+        if line.starts_with("#if") {
+            depth += 1;
+        } else if line.starts_with("#endif") {
+            depth -= 1;
+            return false;
+        }
+        depth == 0
+    }).collect::<Vec<_>>().join("\n");
+    let types: Vec<String> = parameters.split(',').map(|parameter| {
+        // Everything but the name, with the pointer star next to the type wherever it was written
+        let words: Vec<&str> = parameter.split_whitespace().collect();
+        let (name, rest) = words.split_last().unwrap_or((&"", &[]));
+        let stars = "*".repeat(name.chars().take_while(|&c| c == '*').count());
+        format!("{}{}", rest.join(" "), stars).replace(" *", "*")
+    }).collect();
+    if types != KERNEL_PARAMETERS {
+        return Err(format!(
+            "ocl_simulate_demand must take parameters of types {:?}, not {:?}", KERNEL_PARAMETERS, types));
     }
+    Ok(())
+}
 
+/// The same xorshift the kernel uses
+#[cfg(feature = "ocl")]
+fn xorshift32(state: &mut u32) -> u32 {
+    let mut x = *state;
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    *state = x;
+    x
 }
 
-/// Precompute some values for a zipf distribution
-/// Used by Simulation but not intended to be visible to Python.
-fn precompute_zipf_buffer(num_elements: usize, exponent: f64) -> Vec<u32> {
-    let z = zipf::ZipfDistribution::new(num_elements, exponent).unwrap();
-    let mut rng = rand::thread_rng();
-    (0..(16 << 20)).into_iter().map(|_| z.sample(&mut rng) as u32).collect()
+/// Statistical checks of the kernel's generator, as (test, statistic, p-value)
+///
+/// Each of `seeds` starts a work item's stream of `count` draws, made into table indices the
+/// way random_select() makes them, as a remainder of TABLE_LEN. A generator that favours some
+/// indices, or whose draws follow each other, hands the kernel some customers and job lots more
+/// often than their zipfs say, and the fill rates come out wrong with nothing to show for it.
+///
+/// - table_index_chi_squared: how evenly the indices land in 1024 bins
+/// - pair_chi_squared: how evenly each stream's non-overlapping pairs of draws land on a 32x32 grid
+/// - serial_correlation: the correlation of each draw with the next in its stream, as a z score
+/// - cross_stream_correlation: the same between neighbouring work items at the same step,
+///   which is where poorly spread seeds would show
+#[cfg(feature = "ocl")]
+fn rng_quality(seeds: &[u32], count: usize) -> Vec<(&'static str, f64, f64)> {
+    const BINS: usize = 1024;
+    const GRID: usize = 32;
+    let table_len = tables::TABLE_LEN as f64;
+    let mut bins = vec![0.0; BINS];
+    let mut pairs = vec![0.0; GRID * GRID];
+    // Each draw as a uniform on (-0.5, 0.5), so products of independent ones average 0
+    let (mut serial, mut cross) = (0.0, 0.0);
+    let mut previous_stream: Vec<f64> = vec![];
+    let mut stream = Vec::with_capacity(count);
+    for &seed in seeds {
+        let mut state = seed;
+        stream.clear();
+        for _ in 0..count {
+            let index = xorshift32(&mut state) as usize % tables::TABLE_LEN;
+            bins[index * BINS / tables::TABLE_LEN] += 1.0;
+            stream.push((index as f64 + 0.5) / table_len - 0.5);
+        }
+        for pair in stream.chunks_exact(2) {
+            let cell = |u: f64| (((u + 0.5) * GRID as f64) as usize).min(GRID - 1);
+            pairs[cell(pair[0]) * GRID + cell(pair[1])] += 1.0;
+        }
+        serial += stream.windows(2).map(|w| w[0] * w[1]).sum::<f64>();
+        cross += previous_stream.iter().zip(&stream).map(|(a, b)| a * b).sum::<f64>();
+        std::mem::swap(&mut previous_stream, &mut stream);
+    }
+    let chi_squared = |name, cells: &[f64]| {
+        let expected = cells.iter().sum::<f64>() / cells.len() as f64;
+        let statistic: f64 = cells.iter().map(|observed| (observed - expected).powi(2) / expected).sum();
+        (name, statistic, rustsim::hypothesis::gamma_q((cells.len() - 1) as f64 / 2.0, statistic / 2.0))
+    };
+    // A product of two independent uniforms on (-0.5, 0.5) has variance 1/144
+    let correlation = |name, sum: f64, products: usize| {
+        let z = 12.0 * sum / (products.max(1) as f64).sqrt();
+        (name, z, 2.0 * (1.0 - rustsim::normal::cdf(z.abs())))
+    };
+    vec![
+        chi_squared("table_index_chi_squared", &bins),
+        chi_squared("pair_chi_squared", &pairs),
+        correlation("serial_correlation", serial, seeds.len() * count.saturating_sub(1)),
+        correlation("cross_stream_correlation", cross, seeds.len().saturating_sub(1) * count),
+    ]
+}
+
+#[cfg(feature = "ocl")]
+impl Simulation {
+    /// Exactly what the kernel computes, step for step, but on the CPU
+    ///
+    /// Given the same seeds, the totals should match the device's to the last unit, so any
+    /// difference means the device (or its compiler) isn't running the kernel faithfully.
+    /// It's slow, so it's only meant for a handful of repetitions.
+    ///
+    /// Only the first `table_len` entries of each table are used, as on a device too small
+    /// for all of them.
+    fn reference_totals(&self, starting_quantity: usize, seeds: &[u32], samples: usize, table_len: usize) -> [u128; 4] {
+        let mut totals = [0u128; 4];
+        for &seed in seeds {
+            let mut state = seed;
+            self.reference_work_item(starting_quantity, samples, table_len, &mut || xorshift32(&mut state), &mut totals, None);
+        }
+        totals
+    }
+
+    /// One work item's `samples` repetitions, as the kernel runs them, taking its draws from
+    /// `next` and its entries from the first `table_len` of each table
+    ///
+    /// Adds its counters to `totals`, and pushes each day's closing stock onto `trace` if
+    /// there is one.
+    #[allow(clippy::too_many_arguments)]
+    fn reference_work_item(&self, starting_quantity: usize, samples: usize, table_len: usize, next: &mut dyn FnMut() -> u32, totals: &mut [u128; 4], mut trace: Option<&mut Vec<i32>>) {
+        let lead_time = self.lead_time;
+        let (safety_stock, order_quantity) = (self.safety_stock as i64, self.order_quantity as i64);
+        let mut select = |precomp: &[u32]| precomp[(next() % table_len as u32) as usize] as i64;
+        for _ in 0..samples {
+            let mut stock = starting_quantity as i64;
+            let mut trucks = vec![0i64; lead_time];
+            for day in 0..365 {
+                stock += trucks[day % lead_time];
+                for _customer in 0..select(&self.itemwise_traffic_zipf_precomp) {
+                    let request = select(&self.job_lot_zipf_precomp);
+                    if stock >= request {
+                        totals[0] += 1;
+                        totals[1] += request as u128;
+                        stock -= request;
+                    } else {
+                        totals[2] += 1;
+                        totals[3] += request as u128;
+                        if self.kernel == Kernel::Backorder {
+                            stock -= request;
+                        }
+                    }
+                }
+                let pipeline: i64 = trucks.iter().sum();
+                let ordered = match &self.policy {
+                    Some(policy) => policy.order(&self.policy_inputs(stock, pipeline, day)) as i64,
+                    None if self.kernel == Kernel::SS && stock + pipeline < safety_stock => safety_stock + order_quantity - stock - pipeline,
+                    None if self.kernel != Kernel::SS && stock < safety_stock => (safety_stock - stock + order_quantity - 1) / order_quantity * order_quantity,
+                    None => 0,
+                };
+                if ordered > 0 {
+                    trucks[(day + lead_time - 1) % lead_time] = ordered;
+                }
+                if let Some(trace) = trace.as_deref_mut() {
+                    trace.push(stock as i32);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "ocl")]
+impl Simulation {
+    /// Run 1000 repetitions on the device, and check that everything adds up
+    ///
+    /// Every repetition's counters must add up to the totals, its fill rates must be between
+    /// 0 and 1, and the totals must match the CPU reference exactly, which covers sales never
+    /// exceeding demand and stock never going negative, but for kernel="backorder", since the
+    /// reference can't do either otherwise.
+    /// Returns a description of the first thing that doesn't hold.
+    fn check_invariants(&self, starting_quantity: usize, seed: u64) -> Result<(), String> {
+        // The reference follows the default layout seed for seed, so use that, tuned or not
+        let run = self.ocl_run_on(None, Shape::DEFAULT, starting_quantity, 1000, true, &mut StdRng::seed_from_u64(seed))
+            .map_err(|error| format!("The kernel failed: {}", error))?;
+        let mut sums = [0u128; 4];
+        for &(st, ss, ft, fs, transaction_rate, unit_rate) in &run.samples {
+            for (sum, x) in sums.iter_mut().zip(&[st, ss, ft, fs]) {
+                *sum += *x as u128;
+            }
+            if !(0.0..=1.0).contains(&transaction_rate) || !(0.0..=1.0).contains(&unit_rate) {
+                return Err(format!("Fill rates {} and {} aren't both in [0, 1]", transaction_rate, unit_rate));
+            }
+        }
+        if sums != run.totals {
+            return Err(format!("The repetitions add up to {:?}, but the totals are {:?}", sums, run.totals));
+        }
+        let (_, device) = default_device().map_err(|error| format!("The kernel failed: {}", error))?;
+        let table_len = memory::Budget::of(&device).table_len(self.itemwise_traffic_zipf_precomp.len(), self.table_entry_bytes(&device));
+        let reference = self.reference_totals(starting_quantity, &work_item_seeds(&mut StdRng::seed_from_u64(seed), 1000), 1, table_len);
+        if run.totals != reference {
+            return Err(format!("The totals are {:?}, but the CPU reference got {:?}", run.totals, reference));
+        }
+        Ok(())
+    }
+}
+
+/// Check the kernel's invariants over `count` random configurations
+///
+/// Each configuration has random parameters and a random starting quantity, and gets the
+/// checks described in check_invariants. Worth running after any change to simulation.cl.
+/// Each configuration builds its own zipf tables, which takes a moment, so `count` is small.
+///
+/// Returns the number of configurations checked, or raises AssertionError describing the
+/// first one that broke an invariant. With a `seed`, the configurations are the same every
+/// time.
+#[cfg(feature = "ocl")]
+#[pyfunction(count = "10", seed = "None")]
+fn validate(count: usize, seed: Option<u64>) -> PyResult<usize> {
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    for _ in 0..count {
+        let (safety_stock, lead_time, order_quantity) = (rng.gen_range(0, 200), rng.gen_range(1, 11), rng.gen_range(1, 100));
+        let (job_lot_zipf, itemwise_traffic_zipf) = (rng.gen_range(1.5, 5.0), rng.gen_range(1.5, 5.0));
+        let starting_quantity = rng.gen_range(0, 300);
+        let sim = Simulation::new(safety_stock, lead_time, order_quantity, Some(job_lot_zipf), Some(itemwise_traffic_zipf), Some(rng.gen()));
+        if let Err(problem) = sim.check_invariants(starting_quantity, rng.gen()) {
+            return Err(AssertionError::py_err(format!(
+                "{} (safety_stock={}, lead_time={}, order_quantity={}, job_lot_zipf={}, itemwise_traffic_zipf={}, starting_quantity={})",
+                problem, safety_stock, lead_time, order_quantity, job_lot_zipf, itemwise_traffic_zipf, starting_quantity)));
+        }
+    }
+    Ok(count)
+}
+
+/// Check the statistical quality of the kernel's random numbers
+///
+/// The kernel draws every customer count and job lot with xorshift32, one stream per work
+/// item, and picks table entries by remainder. This runs the same generator on the CPU, from
+/// `work_items` streams seeded the way a launch seeds them, `count` draws each, and tests the
+/// table indices they make (see rng_quality for the tests).
+///
+/// Returns a dict of test name to (statistic, p-value), or raises AssertionError naming any
+/// test with a p-value under `alpha`. With a `seed`, the streams are the same every time.
+#[cfg(feature = "ocl")]
+#[pyfunction(work_items = "1024", count = "4096", alpha = "0.001", seed = "None")]
+fn rng_selfcheck(py: Python<'_>, work_items: usize, count: usize, alpha: f64, seed: Option<u64>) -> PyResult<PyObject> {
+    if work_items < 2 || count < 2 {
+        return Err(ValueError::py_err("rng_selfcheck needs at least 2 work_items and a count of at least 2"));
+    }
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let results = logging::allow_threads(py, || rng_quality(&work_item_seeds(&mut rng, work_items), count));
+    let failed: Vec<String> = results.iter().filter(|&&(_, _, p)| p < alpha).map(|(name, statistic, p)| format!("{} (statistic {}, p {})", name, statistic, p)).collect();
+    if !failed.is_empty() {
+        return Err(AssertionError::py_err(format!("The kernel's generator failed {}", failed.join(", "))));
+    }
+    let dict = PyDict::new(py);
+    for (name, statistic, p) in results {
+        dict.set_item(name, (statistic, p))?;
+    }
+    Ok(dict.to_object(py))
+}
+
+/// Check every OpenCL device we can find, and report what works
+///
+/// For each device on each platform, we compile the kernel, run a tiny simulation (1000
+/// repetitions), and compare the totals to a CPU reference that follows the kernel step for
+/// step with the same random seeds. They should agree exactly.
+///
+/// Returns a list of dicts, one per device, with "platform", "platform_version", "device",
+/// "ok" (whether it compiled, ran, and agreed with the reference), "error" (what went wrong,
+/// if anything), "totals" and "reference_totals" (successful transactions, successful sales,
+/// failed transactions, failed sales), "anomalies" (work items whose counters look wrong next
+/// to the rest, as (work item, why)), and "seconds" (how long the device took). If OpenCL
+/// can't be loaded or won't list its platforms, the list has just one dict, with "ok" False
+/// and the "error". Attach this to bug reports.
+#[cfg(feature = "ocl")]
+#[pyfunction]
+fn doctor(py: Python<'_>) -> PyResult<Vec<PyObject>> {
+    const SEED: u64 = 0x5EED;
+    if let Err(error) = loader::available() {
+        let entry = PyDict::new(py);
+        entry.set_item("ok", false)?;
+        entry.set_item("error", error)?;
+        return Ok(vec![entry.to_object(py)]);
+    }
+    let sim = Simulation::new(20, 3, 10, None, None, Some(SEED));
+    let (starting_quantity, count) = (20, 1000);
+    let seeds = work_item_seeds(&mut StdRng::seed_from_u64(SEED), 1000);
+
+    // Platform::list() panics if the ICD loader fails, and that's just what a report is for
+    let platforms = match ocl::core::get_platform_ids() {
+        Ok(platforms) => ocl::Platform::list_from_core(platforms),
+        Err(error) => {
+            let entry = PyDict::new(py);
+            entry.set_item("ok", false)?;
+            entry.set_item("error", format!("Couldn't list platforms: {}", error))?;
+            return Ok(vec![entry.to_object(py)]);
+        }
+    };
+    let mut report = vec![];
+    for platform in platforms {
+        let devices = match ocl::Device::list_all(platform) {
+            Ok(devices) => devices,
+            Err(error) => {
+                let entry = PyDict::new(py);
+                entry.set_item("platform", platform.name().ok())?;
+                entry.set_item("ok", false)?;
+                entry.set_item("error", format!("Couldn't list devices: {}", error))?;
+                report.push(entry.to_object(py));
+                continue;
+            }
+        };
+        for device in devices {
+            let entry = PyDict::new(py);
+            entry.set_item("platform", platform.name().ok())?;
+            entry.set_item("platform_version", platform.version().ok())?;
+            entry.set_item("device", device.name().ok())?;
+            // A device too small for the whole tables only gets the start of them
+            let table_len = memory::Budget::of(&device).table_len(sim.itemwise_traffic_zipf_precomp.len(), sim.table_entry_bytes(&device));
+            let reference = sim.reference_totals(starting_quantity, &seeds, count / 1000, table_len);
+            entry.set_item("reference_totals", reference.iter().map(|&x| x as u64).collect::<Vec<u64>>())?;
+            let started = std::time::Instant::now();
+            let run = logging::allow_threads(py, || sim.ocl_run_on(Some((platform, device)), Shape::DEFAULT, starting_quantity, count, false, &mut StdRng::seed_from_u64(SEED)));
+            match run {
+                Ok(run) => {
+                    entry.set_item("seconds", started.elapsed().as_secs_f64())?;
+                    entry.set_item("totals", run.totals.iter().map(|&x| x as u64).collect::<Vec<u64>>())?;
+                    entry.set_item("anomalies", &run.anomalies)?;
+                    let ok = run.totals == reference;
+                    entry.set_item("ok", ok)?;
+                    entry.set_item("error", if ok { None } else { Some("The totals don't match the CPU reference") })?;
+                }
+                Err(error) => {
+                    entry.set_item("ok", false)?;
+                    entry.set_item("error", error.to_string())?;
+                }
+            }
+            report.push(entry.to_object(py));
+        }
+    }
+    Ok(report)
+}
+
+/// How much of each zipf table to keep in local memory on `device`, if it's worth it
+///
+/// We need room for two tables of `entry_bytes` entries, and leave half the local memory for the compiler and
+/// whatever else shares the compute unit. The prefix is a power of two, at most the table
+/// length. On a device with less than a few KB, reading straight from global memory is
+/// the better deal, so we don't cache at all.
+///
+/// The tables are read at uniformly random positions, so this saves the fraction of reads
+/// that the prefix is of the whole table. That's a lot for short tables and not so much for
+/// the default 16M entries.
+#[cfg(feature = "ocl")]
+fn local_prefix(device: &ocl::Device, table_len: usize, entry_bytes: usize) -> Option<usize> {
+    const SMALLEST: usize = 256;
+    let local_mem = match device.info(ocl::enums::DeviceInfo::LocalMemSize) {
+        Ok(ocl::enums::DeviceInfoResult::LocalMemSize(bytes)) => bytes as usize,
+        _ => return None,
+    };
+    let entries = (local_mem / 2 / (2 * entry_bytes)).min(table_len);
+    if entries < SMALLEST {
+        return None;
+    }
+    Some(1 << (usize::BITS - 1 - entries.leading_zeros()))
+}
+
+/// The capabilities of one device, as a dict (see Simulation.device_info)
+#[cfg(feature = "ocl")]
+fn describe_device<'p>(py: Python<'p>, platform: &ocl::Platform, device: &ocl::Device) -> PyResult<&'p PyDict> {
+    let dict = PyDict::new(py);
+    dict.set_item("platform", platform.name().ok())?;
+    dict.set_item("name", device.name().ok())?;
+    dict.set_item("vendor", device.vendor().ok())?;
+    dict.set_item("driver_version", device_info!(device, DriverVersion))?;
+    dict.set_item("global_memory", device_info!(device, GlobalMemSize))?;
+    dict.set_item("local_memory", device_info!(device, LocalMemSize))?;
+    dict.set_item("compute_units", device_info!(device, MaxComputeUnits))?;
+    dict.set_item("max_work_group_size", device_info!(device, MaxWorkGroupSize))?;
+    dict.set_item("fp64", device_info!(device, Extensions).map(|_| supports_fp64(device)))?;
+    Ok(dict)
+}
+
+/// Whether `device` does double precision
+#[cfg(feature = "ocl")]
+fn supports_fp64(device: &ocl::Device) -> bool {
+    device_info!(device, Extensions)
+        .map_or(false, |extensions| extensions.split_whitespace().any(|extension| extension == "cl_khr_fp64"))
+}
+
+/// How many repetitions per work item one launch may run on `device`, if it has a watchdog
+///
+/// NVIDIA says whether kernels on a device have a time limit, which is usually the case when
+/// it drives a display too. Nobody else does, so RUSTOCLSIM_WATCHDOG_SLICE can set the slice
+/// for any device, or turn slicing off with 0.
+#[cfg(feature = "ocl")]
+fn watchdog_slice(device: &ocl::Device) -> Option<usize> {
+    const SLICE: usize = 64;
+    const CL_DEVICE_KERNEL_EXEC_TIMEOUT_NV: u32 = 0x4005;
+    if let Ok(value) = std::env::var("RUSTOCLSIM_WATCHDOG_SLICE") {
+        return value.parse().ok().filter(|&slice| slice > 0);
+    }
+    match device.info_raw(CL_DEVICE_KERNEL_EXEC_TIMEOUT_NV) {
+        Ok(bytes) if bytes.iter().any(|&byte| byte != 0) => Some(SLICE),
+        _ => None,
+    }
+}
+
+/// The device OpenCL picks when we don't ask for one in particular
+#[cfg(feature = "ocl")]
+fn default_device() -> Fallible<(ocl::Platform, ocl::Device)> {
+    loader::available().map_err(failure::err_msg)?;
+    // Platform::default() panics when the ICD loader finds no platforms, so ask the fallible way
+    let platform = ocl::Platform::new(ocl::core::default_platform()?);
+    let device = ocl::Device::first(platform)?;
+    debug!("Using {} on {}", device.name().unwrap_or_default(), platform.name().unwrap_or_default());
+    Ok((platform, device))
+}
+
+/// The kernel called `name`, or ValueError
+#[cfg(feature = "ocl")]
+fn parse_kernel(name: &str) -> PyResult<Kernel> {
+    Kernel::parse(name).ok_or_else(|| ValueError::py_err(
+        format!("kernel must be \"lost_sales\", \"backorder\" or \"s_S\", not {:?}", name)))
+}
+
+/// The table_precision called `name`, or ValueError
+#[cfg(feature = "ocl")]
+fn parse_table_precision(name: &str) -> PyResult<TablePrecision> {
+    TablePrecision::parse(name).ok_or_else(|| ValueError::py_err(
+        format!("table_precision must be \"u32\", \"u16\" or \"auto\", not {:?}", name)))
+}
+
+/// Check the constructor's arguments, returning the counts as counts
+///
+/// The counts come from Python as signed, so a negative gets a ValueError naming it rather
+/// than an OverflowError.
+#[cfg(feature = "ocl")]
+fn check_parameters(
+    safety_stock: isize,
+    lead_time: isize,
+    order_quantity: isize,
+    job_lot_zipf: Option<f64>,
+    itemwise_traffic_zipf: Option<f64>,
+) -> PyResult<(usize, usize, usize)> {
+    let at_least = |name: &str, value: isize, least: isize| if value < least {
+        Err(ValueError::py_err(format!("{} must be >= {}, not {}", name, least, value)))
+    } else {
+        Ok(value as usize)
+    };
+    let counts = (
+        at_least("safety_stock", safety_stock, 0)?,
+        at_least("lead_time", lead_time, 1)?,
+        at_least("order_quantity", order_quantity, 1)?,
+    );
+    for &(name, exponent) in &[("job_lot_zipf", job_lot_zipf), ("itemwise_traffic_zipf", itemwise_traffic_zipf)] {
+        match exponent {
+            // Written this way around so NaN fails too
+            Some(exponent) if !(exponent > 0.0 && exponent.is_finite()) => return Err(ValueError::py_err(
+                format!("{} must be > 0, not {}", name, exponent))),
+            _ => {}
+        }
+    }
+    Ok(counts)
+}
+
+// Python threads can share any of these: a Simulation's tables are read-only behind Arcs,
+// and a PendingRun locks its batch
+#[cfg(feature = "ocl")]
+const _: fn() = || {
+    fn shareable<T: Send + Sync>() {}
+    shareable::<Simulation>();
+    shareable::<PendingRun>();
+};
+
+/// Whether this build can run on a GPU at all, and has an OpenCL library to do it with
+///
+/// Without the ocl feature (the default has it), the module was built with no OpenCL, and
+/// only has this: no Simulation, PendingRun, doctor(), validate() or rng_selfcheck(). That's
+/// for ARM servers and slim containers with no OpenCL library to link against, which can
+/// still use rustsim. With it, the library is only loaded when it's first needed (see
+/// loader.rs), so this is False on a machine without one too, and Simulation() raises
+/// RuntimeError saying what to install. A True here doesn't promise a device, only that
+/// doctor() can go looking for one.
+#[cfg(feature = "ocl")]
+#[pyfunction]
+fn has_gpu_support() -> bool {
+    loader::available().is_ok()
+}
+
+/// Whether this build can run on a GPU at all, which without the ocl feature it can't
+#[cfg(not(feature = "ocl"))]
+#[pyfunction]
+fn has_gpu_support() -> bool {
+    false
 }
 
 /// This module is a python module implemented in Rust.
 #[pymodule]
 fn rustoclsim(_py: Python, m: &PyModule) -> PyResult<()> {
-    m.add_class::<Simulation>()?;
+    #[cfg(feature = "ocl")]
+    {
+        m.add_class::<Simulation>()?;
+        m.add_class::<PendingRun>()?;
+        m.add_wrapped(wrap_pyfunction!(doctor))?;
+        m.add_wrapped(wrap_pyfunction!(validate))?;
+        m.add_wrapped(wrap_pyfunction!(rng_selfcheck))?;
+    }
+    m.add_wrapped(wrap_pyfunction!(has_gpu_support))?;
 
     Ok(())
 }
 
+#[cfg(feature = "ocl")]
 #[test]
 fn test_ocl() {
-    let sim = Simulation {
-        safety_stock: 10,
-        lead_time: 10,
-        order_quantity: 7,
-        job_lot_zipf: 2.75,
-        itemwise_traffic_zipf: 4.0,
-    };
+    let sim = Simulation::new(10, 10, 7, None, None, None);
     sim.ocl_repeat_simulate_demand(10, 10000).expect("OCL Failed");
-}
\ No newline at end of file
+}
+
+#[cfg(feature = "ocl")]
+#[test]
+fn test_matches_cpu() {
+    // Years on the device look like years on the CPU, on either side of the old 10-truck limit
+    for &lead_time in &[3, 14] {
+        let sim = Simulation::new(10, lead_time, 7, None, None, Some(1));
+        let device = sim.ocl_run(10, 20000, true, &mut sim.rng()).unwrap().samples;
+        let cpu = rustsim::Simulation::new(10, lead_time, 7, None, None, Some(2));
+        let mut rng = cpu.rng();
+        let reference: Vec<Summary> = (0..device.len()).map(|_| cpu.simulate_summary(10, &mut rng)).collect();
+        for (name, statistic, p) in rustsim::hypothesis::compare_years(&reference, &device) {
+            assert!(p > 0.001, "lead time {}: {} (statistic {}, p {})", lead_time, name, statistic, p);
+        }
+    }
+}
+
+#[cfg(feature = "ocl")]
+#[test]
+fn test_remainder() {
+    // Counts that the work items can't split evenly, even ones smaller than the work items,
+    // still run exactly as many repetitions as asked
+    let sim = Simulation::new(10, 3, 7, None, None, Some(1));
+    for &count in &[10, 1234, 2000] {
+        let run = sim.ocl_run(10, count, true, &mut sim.rng()).unwrap();
+        assert_eq!(run.samples.len(), count);
+        assert_eq!(run.work_items.len(), count.min(1000) + if count > 1000 { count % 1000 } else { 0 });
+    }
+}
+
+#[cfg(feature = "ocl")]
+#[test]
+fn test_table_seed() {
+    // An unseeded simulation's tables can still be drawn again from the seed it records
+    let sim = Simulation::new(10, 3, 7, None, None, None);
+    let again = Simulation::new(10, 3, 7, None, None, Some(sim.table_seed));
+    assert_eq!(sim.itemwise_traffic_zipf_precomp[..], again.itemwise_traffic_zipf_precomp[..]);
+    assert_eq!(sim.job_lot_zipf_precomp[..], again.job_lot_zipf_precomp[..]);
+}
+
+#[cfg(feature = "ocl")]
+#[test]
+fn test_kernel_signature() {
+    check_kernel_signature(include_str!("simulation.cl")).unwrap();
+}
+
+#[cfg(feature = "ocl")]
+#[test]
+fn test_invariants() {
+    let sim = Simulation::new(10, 3, 7, None, None, Some(1));
+    sim.check_invariants(10, 2).unwrap();
+}
+
+#[cfg(feature = "ocl")]
+#[test]
+fn test_sweep() {
+    // Each policy in a sweep sees the same customers it would in a launch of its own
+    let sim = Simulation::new(10, 3, 7, None, None, Some(1));
+    let shape = sim.shape(None).unwrap();
+    let pro_que = sim.program(None, shape.work_items).unwrap();
+    let alone = sim.ocl_launch(&pro_que, pro_que.queue(), shape, 5, 10000, false, &mut sim.rng()).unwrap().finish().unwrap();
+    let swept = sim.ocl_launch_policies(&pro_que, pro_que.queue(), shape, &[(0, 20, 3), (5, 10, 7)], 10000, false, &mut sim.rng())
+        .unwrap().finish_each().unwrap();
+    assert_eq!(swept.len(), 2);
+    assert_eq!(swept[1].totals, alone.totals);
+}
+
+#[cfg(feature = "ocl")]
+#[test]
+fn test_backend() {
+    // The kernel behind rustsim's SimBackend, batched and seeded like any other engine
+    let sim = rustsim::Simulation::new(10, 3, 7, None, None, Some(1));
+    let mut opencl = backend::OpenCl::default();
+    let summary = rustsim::backend::repeat(&mut opencl, &sim, 10, 20000, 10000, &mut StdRng::seed_from_u64(2)).unwrap();
+    assert!(summary.0 + summary.2 > 0);
+}
+
+#[cfg(feature = "ocl")]
+#[test]
+fn test_policy() {
+    // The policy compiled into the kernel orders exactly as the CPU reference interprets it
+    let mut sim = Simulation::new(10, 3, 7, None, None, Some(1));
+    sim.policy = Some(OrderingPolicy::parse("max(0, ceil(2 * forecast * lead_time) + safety_stock - on_hand - pipeline)").unwrap());
+    sim.check_invariants(10, 2).unwrap();
+}
+
+#[cfg(feature = "ocl")]
+#[test]
+fn test_metrics() {
+    // Every day is either below 5 or not, and the kernel counts both
+    let mut sim = Simulation::new(10, 3, 7, None, None, Some(1));
+    sim.metrics = vec![Metric::parse("days_below(5)").unwrap(), Metric::parse("days(stock >= 5)").unwrap()];
+    let run = sim.ocl_repeat_simulate_demand(10, 1000).unwrap();
+    assert_eq!(run.metrics.len(), 2);
+    assert_eq!(run.metrics[0] + run.metrics[1], 365.0 * 1000.0);
+}
+
+#[cfg(feature = "ocl")]
+#[test]
+fn test_rng_quality() {
+    // The kernel's xorshift32 passes, and work items all seeded alike don't
+    let seeds = work_item_seeds(&mut StdRng::seed_from_u64(1), 256);
+    for (name, statistic, p) in rng_quality(&seeds, 4096) {
+        assert!(p > 0.001, "{} (statistic {}, p {})", name, statistic, p);
+    }
+    let alike = rng_quality(&[seeds[0]; 256], 4096);
+    assert!(alike.iter().any(|&(name, _, p)| name == "cross_stream_correlation" && p < 0.001));
+}
+
+#[cfg(feature = "ocl")]
+#[test]
+fn test_replay() {
+    // Given the same draws, the device and the CPU keep the same stock every day
+    let sim = Simulation::new(10, 3, 7, None, None, Some(1));
+    let draws = replay::draws(&mut sim.rng(), 16, 4096);
+    let table_len = sim.itemwise_traffic_zipf_precomp.len();
+    let trace = sim.replay_on_device(10, 16, 2, &draws, table_len).unwrap();
+    let reference = sim.replay_reference(10, 16, 2, &draws, table_len);
+    assert_eq!(trace.first_difference(&reference, 2), None);
+    assert_eq!(trace, reference);
+}
+
+#[cfg(feature = "ocl")]
+#[test]
+fn test_precision() {
+    // fp64 on a device without it is an error to report, and auto quietly settles for fp32
+    let error = Precision::Fp64.double("Test Device", false).unwrap_err();
+    assert!(error.to_string().contains("Test Device has no double precision"));
+    assert!(Precision::Fp64.double("Test Device", true).unwrap());
+    assert!(!Precision::Auto.double("Test Device", false).unwrap());
+    assert!(Precision::Auto.double("Test Device", true).unwrap());
+    assert!(!Precision::Fp32.double("Test Device", true).unwrap());
+}
+
+#[cfg(feature = "ocl")]
+#[test]
+fn test_table_precision() {
+    // Every table entry fits in 16 bits, so u16 tables lose nothing
+    let sim = Simulation::new(10, 3, 7, None, None, Some(1));
+    assert!(sim.job_lot_zipf_precomp.iter().all(|&entry| entry <= u32::from(u16::MAX)));
+    assert_eq!(parse_table_precision("u16").unwrap(), TablePrecision::U16);
+    assert!(parse_table_precision("u8").is_err());
+}
+
+#[cfg(feature = "ocl")]
+#[test]
+fn test_kernels() {
+    // Each model follows its own rules on the device as on the CPU, from the same draws
+    let sim = Simulation::new(10, 3, 7, None, None, Some(1));
+    let draws = replay::draws(&mut sim.rng(), 16, 4096);
+    let table_len = sim.itemwise_traffic_zipf_precomp.len();
+    let mut customers = vec![];
+    for &kernel in &[Kernel::LostSales, Kernel::Backorder, Kernel::SS] {
+        let sim = Simulation { kernel, ..sim.clone() };
+        let reference = sim.replay_reference(10, 16, 2, &draws, table_len);
+        assert_eq!(sim.replay_on_device(10, 16, 2, &draws, table_len).unwrap(), reference);
+        let lowest = reference.years.iter().flatten().copied().min().unwrap();
+        assert_eq!(lowest < 0, kernel == Kernel::Backorder);
+        // Customers come the same whatever happens to them
+        customers.push(reference.totals[0] + reference.totals[2]);
+        assert_eq!(Kernel::parse(kernel.name()), Some(kernel));
+    }
+    assert!(customers.windows(2).all(|pair| pair[0] == pair[1]));
+}
+
+#[cfg(feature = "ocl")]
+#[test]
+fn test_warmup_then_simulate() {
+    // A run started while warmup(background=True) is still compiling waits for it, and
+    // neither gets stuck on the other
+    let sim = Simulation::new(10, 3, 7, None, None, None);
+    let (done, finished) = std::sync::mpsc::channel();
+    let background = sim.clone();
+    let warming = std::thread::spawn(move || background.warm_up());
+    let running = std::thread::spawn(move || done.send(sim.ocl_repeat_simulate_demand(10, 10000).map(|_| ())));
+    finished.recv_timeout(std::time::Duration::from_secs(600)).expect("Stuck behind warmup").unwrap();
+    running.join().unwrap().unwrap();
+    warming.join().unwrap().unwrap();
+}
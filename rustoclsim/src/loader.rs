@@ -0,0 +1,62 @@
+//! Finding the OpenCL library the first time it's needed, rather than when the module loads
+//!
+//! ocl links against libOpenCL, so on a machine without one, the module used to fail to
+//! import at all, with nothing but a loader error about a missing .so. A wheel has to import
+//! everywhere and only complain when someone asks it for a GPU. So on Linux, the build uses
+//! our copy of cl-sys, in cl-sys/, whose OpenCL functions forward to a library loaded at run
+//! time rather than linking against one. This module finds that library, with dlopen(), the
+//! first time anything asks for a device, and hands it to cl-sys.
+//!
+//! The library we load is the ICD loader, which finds the vendors' drivers by itself, under
+//! /etc/OpenCL/vendors. RUSTOCLSIM_OPENCL_LIBRARY can name a different one.
+//!
+//! Elsewhere the library is linked as usual: macOS always has its OpenCL framework, and on
+//! Windows, OpenCL.dll comes with every GPU driver.
+#[cfg(target_os = "linux")]
+use std::sync::OnceLock;
+
+/// What to tell someone who asked for a GPU on a machine with no OpenCL library
+#[cfg(target_os = "linux")]
+const ADVICE: &str = "Install an OpenCL ICD loader and a driver for your device (say, ocl-icd-libopencl1 and \
+    pocl-opencl-icd on Debian), or set RUSTOCLSIM_OPENCL_LIBRARY to the library's path. rustsim runs the same \
+    simulation on the CPU.";
+
+/// Whether cl-sys has the OpenCL library to forward to, or why not
+#[cfg(target_os = "linux")]
+static LIBRARY: OnceLock<Result<(), String>> = OnceLock::new();
+
+/// Load the OpenCL library the first time, and hand it to cl-sys
+#[cfg(target_os = "linux")]
+fn library() -> &'static Result<(), String> {
+    LIBRARY.get_or_init(|| {
+        let names = match std::env::var("RUSTOCLSIM_OPENCL_LIBRARY") {
+            Ok(name) => vec![name],
+            Err(_) => vec!["libOpenCL.so.1".to_string(), "libOpenCL.so".to_string()],
+        };
+        let mut errors = vec![];
+        for name in &names {
+            let path = std::ffi::CString::new(name.as_str()).map_err(|_| format!("{:?} isn't a path", name))?;
+            let handle = unsafe { libc::dlopen(path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+            if !handle.is_null() {
+                debug!("Loaded OpenCL from {}", name);
+                ocl::ffi::loader::set_library(handle);
+                return Ok(());
+            }
+            let error = unsafe { libc::dlerror() };
+            if !error.is_null() {
+                errors.push(unsafe { std::ffi::CStr::from_ptr(error) }.to_string_lossy().into_owned());
+            }
+        }
+        Err(format!("There's no OpenCL library on this machine ({}). {}", errors.join("; "), ADVICE))
+    })
+}
+
+/// Whether there's an OpenCL library to run on, or what to do about it if not
+///
+/// Everything that's about to ask OpenCL for a device asks this first, since ocl panics
+/// rather than returning an error when it can't list platforms.
+pub fn available() -> Result<(), String> {
+    #[cfg(target_os = "linux")]
+    library().as_ref().map_err(String::clone)?;
+    Ok(())
+}
@@ -0,0 +1,108 @@
+//! Log messages, handed to Python's logging module as the "rustoclsim" logger
+//!
+//! That way logging.basicConfig(level=logging.DEBUG), handlers, filters and
+//! logging.getLogger("rustoclsim").setLevel() all work on what happens in here the same as on
+//! Python code. We only need a level and a message, so this is the few lines of the `log`
+//! crate and pyo3-log that we'd use, rather than both of them.
+//!
+//! Logging never takes the GIL. A message is queued, and only handed to Python by a thread
+//! that already holds the GIL: right away if the thread logging it does, or else by flush(),
+//! which allow_threads() calls once the work it ran is done. Taking the GIL to log would
+//! deadlock against a Python thread waiting on a lock the logging thread holds, like the
+//! compiled programs' cache, so a message logged inside holding() waits in the queue until
+//! the lock is dropped, even on a thread with the GIL. Messages below the logger's level are dropped before they're
+//! formatted, going by the level it had at the last flush, so a level set in between only
+//! takes effect from the next one.
+use pyo3::prelude::*;
+use crate::PerProcess;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+extern "C" {
+    /// Whether this thread holds the GIL, which pyo3 doesn't wrap
+    fn PyGILState_Check() -> std::os::raw::c_int;
+}
+
+/// How serious a message is, numbered as Python's logging numbers them
+#[derive(Clone, Copy)]
+pub enum Level {
+    Debug = 10,
+    Info = 20,
+    Warning = 30,
+}
+
+/// The logger's effective level as of the last flush, 0 (everything) before the first
+static LEVEL: AtomicI32 = AtomicI32::new(0);
+
+/// Messages waiting for a thread with the GIL
+static QUEUE: PerProcess<Vec<(Level, String)>> = PerProcess::new(Vec::new);
+
+thread_local! {
+    /// How many holding() calls this thread is inside
+    static HOLDING: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Queue the message `message` makes, if the logger would do anything with it at `level`,
+/// and send everything queued if this thread holds the GIL
+///
+/// The message is only formatted if someone's listening. Nothing here can fail a
+/// simulation: if logging itself raises, the message is dropped.
+pub fn log<F: FnOnce() -> String>(level: Level, message: F) {
+    // Rust tests run without an interpreter, and then there's nowhere to send anything
+    if unsafe { pyo3::ffi::Py_IsInitialized() } == 0 || (level as i32) < LEVEL.load(Ordering::Relaxed) {
+        return;
+    }
+    QUEUE.lock().push((level, message()));
+    flush_if_free();
+}
+
+/// Run `work`, which holds a lock another thread might wait on, and only send what it
+/// logged once it's done
+pub fn holding<T, F: FnOnce() -> T>(work: F) -> T {
+    HOLDING.with(|holding| holding.set(holding.get() + 1));
+    let result = work();
+    HOLDING.with(|holding| holding.set(holding.get() - 1));
+    flush_if_free();
+    result
+}
+
+/// Send everything queued if this thread holds the GIL and no lock from holding()
+fn flush_if_free() {
+    if HOLDING.with(Cell::get) == 0 && unsafe { PyGILState_Check() } == 1 {
+        flush(unsafe { Python::assume_gil_acquired() });
+    }
+}
+
+/// Hand every queued message to the logger, and note its level for the next ones
+pub fn flush(py: Python<'_>) {
+    let queued = std::mem::take(&mut *QUEUE.lock());
+    let _ = (|| -> PyResult<()> {
+        let logger = py.import("logging")?.call1("getLogger", ("rustoclsim",))?;
+        LEVEL.store(logger.call_method0("getEffectiveLevel")?.extract()?, Ordering::Relaxed);
+        for (level, message) in queued {
+            if logger.call_method1("isEnabledFor", (level as i32,))?.is_true()? {
+                logger.call_method1("log", (level as i32, message))?;
+            }
+        }
+        Ok(())
+    })();
+}
+
+/// Run `work` with the GIL released, like py.allow_threads(), then send what it logged
+pub fn allow_threads<T: Send, F: Send + FnOnce() -> T>(py: Python<'_>, work: F) -> T {
+    let result = py.allow_threads(work);
+    flush(py);
+    result
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => { $crate::logging::log($crate::logging::Level::Debug, || format!($($arg)*)) };
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => { $crate::logging::log($crate::logging::Level::Info, || format!($($arg)*)) };
+}
+
+macro_rules! warning {
+    ($($arg:tt)*) => { $crate::logging::log($crate::logging::Level::Warning, || format!($($arg)*)) };
+}
@@ -0,0 +1,112 @@
+//! Fitting a launch into the device's memory
+//!
+//! A launch needs the two zipf tables, 64MB each at full length, a few counters per work item,
+//! and 16 bytes per repetition when every repetition is recorded. A device without that much
+//! used to fail with CL_MEM_OBJECT_ALLOCATION_FAILURE, which says nothing about why. Instead
+//! we ask the device what it has before allocating anything, and cut back, with a warning:
+//!
+//! - Tables that don't fit are cut to a prefix, counting 2 or 4 bytes an entry as
+//!   table_precision has them. Every entry is an independent draw from its
+//!   zipf, so a prefix is still a fair sample, only a shorter one. It's halved each time,
+//!   so the length stays a power of two and a remainder of a 32 bit draw still favours no entry.
+//! - Recorded repetitions that don't fit in one launch are split over several.
+//!
+//! The tables get at most half the device's memory, and every buffer has to fit in the
+//! largest single allocation the device allows, which is often only a quarter of it.
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Bytes of counters and running statistics per work item, rounded up
+const PER_WORK_ITEM: u64 = 64;
+
+/// Bytes recorded per repetition: four u32 counters
+const PER_REPETITION: u64 = 16;
+
+/// The shortest tables we'll cut to before letting the allocation fail after all
+const SHORTEST_TABLE: usize = 1 << 16;
+
+/// Whether we've already warned about cutting the tables, which happens on every launch
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+/// What a device can hold
+#[derive(Clone, Copy, Debug)]
+pub struct Budget {
+    /// Global memory, in bytes
+    global: u64,
+    /// The largest single buffer, in bytes
+    max_alloc: u64,
+}
+
+impl Budget {
+    /// A device with `global` bytes of memory, and at most `max_alloc` in one buffer
+    pub fn new(global: u64, max_alloc: u64) -> Budget {
+        Budget { global, max_alloc }
+    }
+
+    /// What `device` says it can hold, or no limit at all for anything the driver won't say
+    #[cfg(feature = "ocl")]
+    pub fn of(device: &ocl::Device) -> Budget {
+        Budget::new(device_info!(device, GlobalMemSize).unwrap_or(u64::MAX), device_info!(device, MaxMemAllocSize).unwrap_or(u64::MAX))
+    }
+
+    /// The longest prefix of tables `table_len` long, of `entry_bytes` each, that fits,
+    /// warning the first time it's shorter than the tables
+    pub fn table_len(&self, table_len: usize, entry_bytes: usize) -> usize {
+        let fits = |len: usize| {
+            let bytes = (len * entry_bytes) as u64;
+            bytes <= self.max_alloc && 2 * bytes <= self.global / 2
+        };
+        let mut len = table_len;
+        while !fits(len) && len / 2 >= SHORTEST_TABLE {
+            len /= 2;
+        }
+        if len < table_len && !WARNED.swap(true, Ordering::Relaxed) {
+            // Only tests get here without ocl, and they have no Python to log to
+            #[cfg(feature = "ocl")]
+            warning!("The device has {}MB of memory, with at most {}MB in one buffer, so the zipf tables are cut from {} \
+                entries to {}. Results are still fair, but repeat a little sooner.",
+                self.global >> 20, self.max_alloc >> 20, table_len, len);
+        }
+        len
+    }
+
+    /// How many repetitions can be recorded in one launch on `work_items` work items next to
+    /// tables `table_len` long, of `entry_bytes` each, as a multiple of `work_items`, and at
+    /// least one per work item
+    pub fn recorded_repetitions(&self, table_len: usize, entry_bytes: usize, work_items: usize) -> usize {
+        let tables = 2 * (table_len * entry_bytes) as u64;
+        let free = self.global.saturating_sub(tables).saturating_sub(PER_WORK_ITEM * work_items as u64);
+        // Leave a quarter for the driver and whatever else is running
+        let bytes = (free / 4 * 3).min(self.max_alloc);
+        let repetitions = usize::try_from(bytes / PER_REPETITION).unwrap_or(usize::MAX);
+        (repetitions / work_items * work_items).max(work_items)
+    }
+}
+
+/// `table` two entries to a u32, the first in the low half, which is where a little-endian
+/// device's ushort pointer finds it. Entries have to fit in 16 bits.
+pub fn pack_u16(table: &[u32]) -> Vec<u32> {
+    table.chunks(2).map(|pair| pair[0] | pair.get(1).map_or(0, |&high| high << 16)).collect()
+}
+
+#[test]
+fn test_memory_budget() {
+    // A small device gets shorter tables, still a power of two, and recordings split to fit
+    const TABLE_LEN: usize = 16 << 20;
+    let budget = Budget::new(128 << 20, 32 << 20);
+    assert_eq!(budget.table_len(TABLE_LEN, 4), 8 << 20);
+    assert_eq!(budget.table_len(TABLE_LEN, 2), TABLE_LEN);
+    assert_eq!(Budget::new(u64::MAX, u64::MAX).table_len(TABLE_LEN, 4), TABLE_LEN);
+    let repetitions = budget.recorded_repetitions(8 << 20, 4, 1000);
+    assert!(repetitions.is_multiple_of(1000) && repetitions * 16 <= 32 << 20);
+}
+
+#[test]
+fn test_pack_u16() {
+    // Entries unpack back in order, and an odd one out gets a zero high half
+    let table: Vec<u32> = (0..1 << 16).rev().collect();
+    let bytes: Vec<u8> = pack_u16(&table).iter().flat_map(|word| word.to_le_bytes()).collect();
+    let unpacked: Vec<u32> = bytes.chunks_exact(2).map(|half| u32::from(u16::from_le_bytes([half[0], half[1]]))).collect();
+    assert_eq!(unpacked, table);
+    assert_eq!(pack_u16(&[1, 2, 3]), vec![1 | 2 << 16, 3]);
+}
@@ -0,0 +1,109 @@
+//! Running the kernel and the CPU reference on the very same random numbers, for debugging
+//!
+//! doctor() and validate() compare the device with the CPU reference, which runs the same
+//! xorshift32 from the same seeds. When they disagree, though, the generator is as much a
+//! suspect as the simulation: a compiler that gets one shift wrong sends every draw after it
+//! somewhere else, and the two runs have nothing in common from then on. Here the host makes
+//! every draw up front and uploads them, the kernel (built with REPLAY_DRAWS) reads them
+//! instead of running xorshift32, and the reference reads the same ones. Both write down every
+//! day's closing stock, so the first day they part ways is where to look in simulation.cl.
+use crate::Simulation;
+use failure::Fallible;
+use rand::rngs::StdRng;
+use rand::Rng;
+
+/// What a replay did, on the device or on the CPU
+#[derive(Debug, PartialEq)]
+pub struct Trace {
+    /// Successful transactions, successful sales, failed transactions and failed sales
+    pub totals: [u128; 4],
+    /// Each year's closing stock, day by day, for each repetition of each work item in turn
+    pub years: Vec<Vec<i32>>,
+}
+
+impl Trace {
+    /// Where this and `other` first differ, as (work item, repetition, day), if anywhere
+    pub fn first_difference(&self, other: &Trace, repetitions: usize) -> Option<(usize, usize, usize)> {
+        self.years.iter().zip(&other.years).enumerate().find_map(|(year, (ours, theirs))| {
+            ours.iter().zip(theirs).position(|(a, b)| a != b).map(|day| (year / repetitions, year % repetitions, day))
+        })
+    }
+}
+
+/// `per_work_item` draws for each of `work_items` work items, one work item after another
+pub fn draws(rng: &mut StdRng, work_items: usize, per_work_item: usize) -> Vec<u32> {
+    (0..work_items * per_work_item).map(|_| rng.gen()).collect()
+}
+
+impl Simulation {
+    /// `repetitions` years on each of `work_items` work items on the default device, each
+    /// reading its share of `draws` in order, wrapping around if it runs out, and picking
+    /// from the first `table_len` entries of each table
+    pub(crate) fn replay_on_device(&self, starting_quantity: usize, work_items: usize, repetitions: usize, draws: &[u32], table_len: usize) -> Fallible<Trace> {
+        let per_work_item = draws.len() / work_items;
+        let pro_que = self.program_with(None, work_items, &[("REPLAY_DRAWS", per_work_item as i32)])?;
+        let buffer = |len: usize| pro_que.buffer_builder::<u64>().len(len).build();
+        let float_buffer = || pro_que.buffer_builder::<f32>().len(work_items).build();
+        // Each work item's place in its draws, rather than a seed
+        let position = pro_que.buffer_builder::<u32>().len(work_items).fill_val(0).build()?;
+        let (job_lot_zipf_precomp, itemwise_traffic_zipf_precomp) = self.table_buffers(&pro_que, &pro_que.queue().device(), table_len)?;
+        let counters = [buffer(work_items)?, buffer(work_items)?, buffer(work_items)?, buffer(work_items)?];
+        let rate_count = pro_que.buffer_builder::<u32>().len(work_items).build()?;
+        let rates = [float_buffer()?, float_buffer()?, float_buffer()?, float_buffer()?];
+        // Nothing's recorded, but OpenCL has no empty buffers
+        let samples = pro_que.buffer_builder::<u32>().len(1).build()?;
+        let replay = pro_que.buffer_builder::<u32>().len(draws.len()).copy_host_slice(draws).build()?;
+        let trace = pro_que.buffer_builder::<i32>().len(work_items * repetitions * 365).build()?;
+
+        let mut builder = pro_que.kernel_builder("ocl_simulate_demand");
+        builder.global_work_size(work_items).arg(&position).arg(&job_lot_zipf_precomp).arg(&itemwise_traffic_zipf_precomp);
+        for counter in &counters {
+            builder.arg(counter);
+        }
+        builder.arg(&rate_count);
+        for rate in &rates {
+            builder.arg(rate);
+        }
+        builder
+            .arg(&samples)
+            .arg(0u32)
+            .arg(starting_quantity as i32)
+            .arg(self.lead_time as u32)
+            .arg(self.safety_stock as i32)
+            .arg(self.order_quantity as i32)
+            .arg(table_len as u32)
+            .arg(repetitions as u32)
+            .arg(0u32)
+            .arg(repetitions as u32)
+            .arg(&replay)
+            .arg(&trace);
+        let kernel = builder.build()?;
+        unsafe { kernel.enq()?; }
+
+        let mut totals = [0u128; 4];
+        let mut host = vec![0u64; work_items];
+        for (total, counter) in totals.iter_mut().zip(&counters) {
+            counter.read(&mut host).enq()?;
+            *total = host.iter().map(|&x| u128::from(x)).sum();
+        }
+        let mut days = vec![0i32; trace.len()];
+        trace.read(&mut days).enq()?;
+        Ok(Trace { totals, years: days.chunks_exact(365).map(<[i32]>::to_vec).collect() })
+    }
+
+    /// What replay_on_device should come to, worked out on the CPU from the same draws
+    pub(crate) fn replay_reference(&self, starting_quantity: usize, work_items: usize, repetitions: usize, draws: &[u32], table_len: usize) -> Trace {
+        let mut totals = [0u128; 4];
+        let mut days = Vec::with_capacity(work_items * repetitions * 365);
+        for own in draws.chunks_exact(draws.len() / work_items) {
+            let mut position = 0;
+            let mut next = || {
+                let draw = own[position % own.len()];
+                position += 1;
+                draw
+            };
+            self.reference_work_item(starting_quantity, repetitions, table_len, &mut next, &mut totals, Some(&mut days));
+        }
+        Trace { totals, years: days.chunks_exact(365).map(<[i32]>::to_vec).collect() }
+    }
+}
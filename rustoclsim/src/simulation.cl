@@ -1,3 +1,7 @@
+// Contracting a * b + c into an fma rounds differently from the CPU, which matters most to
+// the policy and metric expressions the host pastes in, so it's off for the whole program
+#pragma OPENCL FP_CONTRACT OFF
+
 // Completely by-the-book reference implementation of xorshift
 uint xorshift32(uint* state)
 {
@@ -10,44 +14,234 @@ uint xorshift32(uint* state)
     return x;
 }
 
-// Select an item at random from a buffer
-uint random_select(uint* state, __global uint* precomp, uint len) {
-    return precomp[xorshift32(state) % len];
+// The zipf tables' entries are all between 1 and 1000, so 16 bits hold them exactly. If the
+// host defines TABLE_USHORT, it packs two entries into each uint of the table buffers, and
+// the kernels read them through a ushort pointer, which halves the memory traffic of the
+// reads that are most of what the kernel does. The parameters stay uint, so every kernel
+// source takes the same arguments.
+#ifdef TABLE_USHORT
+typedef ushort table_t;
+#else
+typedef uint table_t;
+#endif
+
+// Select an item from a buffer with a random draw
+//
+// If the host defined LOCAL_PREFIX, the first LOCAL_PREFIX entries have been copied into
+// local memory, which is much closer than global memory, so read those from there instead.
+// Either way we pick exactly the same item.
+#ifdef LOCAL_PREFIX
+uint random_select(uint draw, __global table_t* precomp, __local table_t* cached, uint len) {
+    uint i = draw % len;
+    return i < LOCAL_PREFIX ? cached[i] : precomp[i];
 }
+#else
+uint random_select(uint draw, __global table_t* precomp, uint len) {
+    return precomp[draw % len];
+}
+#endif
 
-__kernel void ocl_simulate_demand(
+// Where each work item's draws come from. For debugging, the host can define REPLAY_DRAWS
+// and hand over REPLAY_DRAWS draws per work item that it made itself, which the work item
+// reads in order, wrapping around, with its "seed" as its place in them. The host reads the
+// same draws, so any difference between the two is the kernel's logic and not its random
+// numbers. The kernels then also take a buffer where each year's closing stock goes, day by
+// day, so the host can find the first day they part ways.
+#ifdef REPLAY_DRAWS
+#define NEXT_DRAW(state) replay[me * REPLAY_DRAWS + (*(state))++ % REPLAY_DRAWS]
+#define REPLAY_ARGS , replay, trace
+#else
+#define NEXT_DRAW(state) xorshift32(state)
+#define REPLAY_ARGS
+#endif
+
+// The host defines these with -D when it builds the program, so the compiler can size the
+// truck ring exactly and turn the modulos into something cheaper. Without them, we fall back
+// to the lead_time argument, for lead times up to 10 days.
+#ifndef HORIZON
+#define HORIZON 365
+#endif
+#ifdef LEAD_TIME
+#define TRUCK_SLOTS LEAD_TIME
+#define CYCLE LEAD_TIME
+#else
+#define TRUCK_SLOTS 10
+#define CYCLE lead_time
+#endif
+
+// The running statistics of the per-sample fill rates are the only floating point in here;
+// the counters are all integers, and exact. They're float, which every device has, unless
+// the host defines STAT_DOUBLE for a device with double precision. Either way they're
+// stored as float, so only the accumulation gets more accurate.
+#ifdef STAT_DOUBLE
+#pragma OPENCL EXTENSION cl_khr_fp64 : enable
+typedef double stat_t;
+#else
+typedef float stat_t;
+#endif
+
+// Which inventory model the kernels simulate. The host picks one by name and defines it when
+// it builds the program, so there's no branch on it in the day loop, every work item runs the
+// same code, and a model only costs anything in the programs that use it. It defines at most
+// one of these:
+//
+// - Neither: lost sales. A customer who wants more than is in stock leaves with nothing, and
+//   whenever a day ends with stock below safety_stock, we order enough multiples of
+//   order_quantity to cover the gap.
+// - MODEL_BACKORDER: the same ordering rule, but a customer who wants more than is in stock
+//   waits, so their request comes out of stock anyway and stock goes negative until trucks
+//   cover it. They still count as failed, since they weren't served on the day.
+// - MODEL_S_S: lost sales, under an (s, S) policy. Whenever what's in stock and on the
+//   trucks ends a day below s = safety_stock, we order up to S = safety_stock + order_quantity.
+#ifdef MODEL_BACKORDER
+#define UNFILLED(request) stock -= (request)
+#else
+#define UNFILLED(request)
+#endif
+
+// Where each table comes from, with or without the local copy
+#ifdef LOCAL_PREFIX
+#define TRAFFIC_TABLE itemwise_traffic_zipf_precomp, local_traffic
+#define JOB_LOT_TABLE job_lot_zipf_precomp, local_job_lot
+#else
+#define TRAFFIC_TABLE itemwise_traffic_zipf_precomp
+#define JOB_LOT_TABLE job_lot_zipf_precomp
+#endif
+
+// The whole work group loads the start of both tables together, each work item taking
+// every local_size'th entry, and then waits until they're all in. Local memory has to be
+// declared in the kernel itself, so this is a macro rather than part of the function.
+#ifdef LOCAL_PREFIX
+#define LOAD_LOCAL_TABLES \
+    __local table_t local_traffic[LOCAL_PREFIX]; \
+    __local table_t local_job_lot[LOCAL_PREFIX]; \
+    for (uint i = get_local_id(0); i < LOCAL_PREFIX && i < precomp_size; i += get_local_size(0)) { \
+        local_traffic[i] = ((__global table_t*) itemwise_traffic_zipf_precomp)[i]; \
+        local_job_lot[i] = ((__global table_t*) job_lot_zipf_precomp)[i]; \
+    } \
+    barrier(CLK_LOCAL_MEM_FENCE);
+#define LOCAL_TABLES local_traffic, local_job_lot,
+#else
+#define LOAD_LOCAL_TABLES
+#define LOCAL_TABLES
+#endif
+
+// With custom metrics, the host defines METRIC_COUNT and the METRIC_START, METRIC_DAY and
+// METRIC_FINISH statements (see rustsim's metric.rs), and the kernels take one more buffer,
+// where each work item keeps a running total of each metric over its repetitions.
+#ifdef METRIC_COUNT
+#define METRIC_ARGS , all_metrics
+#else
+#define METRIC_ARGS
+#endif
+
+// One work item's repetitions, for either kernel below. The scalars after lead_time are the
+// policy, which ocl_simulate_demand_sweep varies by work item.
+void simulate_work_item(
+    int me,
     __global uint* seed,
-    __global uint* job_lot_zipf_precomp,
-    __global uint* itemwise_traffic_zipf_precomp,
+    __global table_t* job_lot_zipf_precomp,
+    __global table_t* itemwise_traffic_zipf_precomp,
+#ifdef LOCAL_PREFIX
+    __local table_t* local_traffic,
+    __local table_t* local_job_lot,
+#endif
     __global ulong* all_successful_transactions,
     __global ulong* all_successful_sales,
     __global ulong* all_failed_transactions,
     __global ulong* all_failed_sales,
-    float starting_quantity,
+    __global uint* all_rate_count,
+    __global float* all_transaction_rate_mean,
+    __global float* all_transaction_rate_m2,
+    __global float* all_unit_rate_mean,
+    __global float* all_unit_rate_m2,
+    __global uint* all_samples,
+    uint record_samples,
     uint lead_time,
+    int starting_quantity,
     int safety_stock,
     int order_quantity,
     uint precomp_size,
-    uint samples
+    uint samples,
+    uint first_sample,
+    uint total_samples
+#ifdef METRIC_COUNT
+    , __global float* all_metrics
+#endif
+#ifdef REPLAY_DRAWS
+    , __global uint* replay
+    , __global int* trace
+#endif
 ) {
-    int me = get_global_id(0);
     ulong successful_transactions = 0;
     ulong successful_sales = 0;
     ulong failed_transactions = 0;
     ulong failed_sales = 0;
-    int stock = starting_quantity;
-    uint trucks[10];
+    int stock;
+    uint trucks[TRUCK_SLOTS];
     uint state = seed[me];
+    // Welford's running mean and variance of the per-sample fill rates
+    uint rate_count = 0;
+    stat_t transaction_rate_mean = 0;
+    stat_t transaction_rate_m2 = 0;
+    stat_t unit_rate_mean = 0;
+    stat_t unit_rate_m2 = 0;
+    // The host may split a long run over several launches, so that none of them runs long
+    // enough to trip a display watchdog. Each one picks up where the last left off.
+    if (first_sample > 0) {
+        successful_transactions = all_successful_transactions[me];
+        successful_sales = all_successful_sales[me];
+        failed_transactions = all_failed_transactions[me];
+        failed_sales = all_failed_sales[me];
+        rate_count = all_rate_count[me];
+        transaction_rate_mean = all_transaction_rate_mean[me];
+        transaction_rate_m2 = all_transaction_rate_m2[me];
+        unit_rate_mean = all_unit_rate_mean[me];
+        unit_rate_m2 = all_unit_rate_m2[me];
+    }
+#ifdef METRIC_COUNT
+    // Each metric's total over this work item's years so far, and its value this year
+    float metric_total[METRIC_COUNT];
+    float metric_year[METRIC_COUNT];
+    for (uint m=0; m<METRIC_COUNT; m++) {
+        metric_total[m] = first_sample > 0 ? all_metrics[me * METRIC_COUNT + m] : 0.0f;
+    }
+#endif
 
     for (uint sample=0; sample<samples; sample++) {
-        for (uint day=0; day<365; day++) {
+        // Remember where the counters were, so we can tell what this sample alone did
+        ulong sample_successful_transactions = successful_transactions;
+        ulong sample_successful_sales = successful_sales;
+        ulong sample_failed_transactions = failed_transactions;
+        ulong sample_failed_sales = failed_sales;
+        // Every year starts from scratch, like on the CPU
+        stock = starting_quantity;
+        for (uint slot=0; slot<TRUCK_SLOTS; slot++) {
+            trucks[slot] = 0;
+        }
+#ifdef METRIC_COUNT
+        METRIC_START
+#endif
+        for (uint day=0; day<HORIZON; day++) {
+#ifdef METRIC_COUNT
+            // Where the counters were this morning, so we can tell what today did
+            ulong day_successful_transactions = successful_transactions;
+            ulong day_successful_sales = successful_sales;
+            ulong day_failed_transactions = failed_transactions;
+            ulong day_failed_sales = failed_sales;
+            uint received = trucks[day % CYCLE];
+#endif
+            uint ordered = 0;
             // A truck arrived
-            stock += trucks[day % lead_time];
+            stock += trucks[day % CYCLE];
+#ifdef METRIC_COUNT
+            int opening_stock = stock;
+#endif
             // This many customers arrive
-            uint customer_count = random_select(&state, itemwise_traffic_zipf_precomp, precomp_size);
+            uint customer_count = random_select(NEXT_DRAW(&state), TRAFFIC_TABLE, precomp_size);
             for (uint _customer=0; _customer < customer_count; _customer++) {
                 // This customer wants this many
-                int request = random_select(&state, job_lot_zipf_precomp, precomp_size);
+                int request = random_select(NEXT_DRAW(&state), JOB_LOT_TABLE, precomp_size);
                 if (stock >= request) {
                     // There are enough.
                     successful_transactions += 1;
@@ -57,18 +251,203 @@ __kernel void ocl_simulate_demand(
                     // There are not enough
                     failed_transactions += 1;
                     failed_sales += request;
+                    UNFILLED(request);
                 }
             }
             // The day is over. Start making orders.
+#ifdef ORDER_POLICY
+            // The host's policy expression instead of the rule below, in rustsim's policy
+            // language. It reads these, as floats, and says how many units to order.
+            uint in_transit = 0;
+            for (uint slot=0; slot<TRUCK_SLOTS; slot++) {
+                in_transit += trucks[slot];
+            }
+            float policy_on_hand = stock;
+            float policy_pipeline = in_transit;
+            float policy_day = day;
+            float policy_forecast = POLICY_FORECAST;
+            float policy_safety_stock = safety_stock;
+            float policy_order_quantity = order_quantity;
+            float policy_lead_time = lead_time;
+            ordered = convert_uint_sat(ORDER_POLICY);
+            if (ordered > 0) {
+                trucks[(day + CYCLE - 1) % CYCLE] = ordered;
+            }
+#elif defined(MODEL_S_S)
+            int position = stock;
+            for (uint slot=0; slot<TRUCK_SLOTS; slot++) {
+                position += trucks[slot];
+            }
+            if (position < safety_stock) {
+                ordered = safety_stock + order_quantity - position;
+                trucks[(day + CYCLE - 1) % CYCLE] = ordered;
+            }
+#else
             if (stock < safety_stock) {
                 int short_by = max(safety_stock - stock, 0);
                 int orders = (short_by + order_quantity - 1) / order_quantity;
-                trucks[(day + lead_time - 1) % lead_time] = orders * order_quantity;
+                ordered = orders * order_quantity;
+                trucks[(day + CYCLE - 1) % CYCLE] = ordered;
             }
+#endif
+#ifdef METRIC_COUNT
+            // The day as the metrics see it, as floats named after rustsim's DayState
+            uint on_trucks = 0;
+            for (uint slot=0; slot<TRUCK_SLOTS; slot++) {
+                on_trucks += trucks[slot];
+            }
+            float metric_stock = stock;
+            float metric_opening_stock = opening_stock;
+            float metric_received = received;
+            float metric_ordered = ordered;
+            float metric_pipeline = on_trucks;
+            float metric_customers = (successful_transactions - day_successful_transactions)
+                + (failed_transactions - day_failed_transactions);
+            float metric_turned_away = failed_transactions - day_failed_transactions;
+            float metric_demand = (successful_sales - day_successful_sales)
+                + (failed_sales - day_failed_sales);
+            float metric_sold = successful_sales - day_successful_sales;
+            float metric_lost = failed_sales - day_failed_sales;
+            float metric_day = day;
+            METRIC_DAY
+#endif
+#ifdef REPLAY_DRAWS
+            trace[(me * total_samples + first_sample + sample) * HORIZON + day] = stock;
+#endif
+        }
+#ifdef METRIC_COUNT
+        METRIC_FINISH
+#endif
+        sample_successful_transactions = successful_transactions - sample_successful_transactions;
+        sample_successful_sales = successful_sales - sample_successful_sales;
+        sample_failed_transactions = failed_transactions - sample_failed_transactions;
+        sample_failed_sales = failed_sales - sample_failed_sales;
+        if (record_samples) {
+            // One year's counters always fit in 32 bits
+            __global uint* out = all_samples + 4 * (me * total_samples + first_sample + sample);
+            out[0] = sample_successful_transactions;
+            out[1] = sample_successful_sales;
+            out[2] = sample_failed_transactions;
+            out[3] = sample_failed_sales;
+        }
+        // A year with no customers at all has no fill rate to speak of
+        if (sample_successful_transactions + sample_failed_transactions > 0) {
+            stat_t transaction_rate = (stat_t) sample_successful_transactions
+                / (stat_t) (sample_successful_transactions + sample_failed_transactions);
+            stat_t unit_rate = (stat_t) sample_successful_sales
+                / (stat_t) (sample_successful_sales + sample_failed_sales);
+            rate_count += 1;
+            stat_t delta = transaction_rate - transaction_rate_mean;
+            transaction_rate_mean += delta / rate_count;
+            transaction_rate_m2 += delta * (transaction_rate - transaction_rate_mean);
+            delta = unit_rate - unit_rate_mean;
+            unit_rate_mean += delta / rate_count;
+            unit_rate_m2 += delta * (unit_rate - unit_rate_mean);
         }
     }
+    // Where the next launch, if there is one, continues the random stream
+    seed[me] = state;
     all_successful_transactions[me] = successful_transactions;
     all_successful_sales[me] = successful_sales;
     all_failed_transactions[me] = failed_transactions;
     all_failed_sales[me] = failed_sales;
-}
\ No newline at end of file
+    all_rate_count[me] = rate_count;
+    all_transaction_rate_mean[me] = transaction_rate_mean;
+    all_transaction_rate_m2[me] = transaction_rate_m2;
+    all_unit_rate_mean[me] = unit_rate_mean;
+    all_unit_rate_m2[me] = unit_rate_m2;
+#ifdef METRIC_COUNT
+    for (uint m=0; m<METRIC_COUNT; m++) {
+        all_metrics[me * METRIC_COUNT + m] = metric_total[m];
+    }
+#endif
+}
+__kernel void ocl_simulate_demand(
+    __global uint* seed,
+    __global uint* job_lot_zipf_precomp,
+    __global uint* itemwise_traffic_zipf_precomp,
+    __global ulong* all_successful_transactions,
+    __global ulong* all_successful_sales,
+    __global ulong* all_failed_transactions,
+    __global ulong* all_failed_sales,
+    __global uint* all_rate_count,
+    __global float* all_transaction_rate_mean,
+    __global float* all_transaction_rate_m2,
+    __global float* all_unit_rate_mean,
+    __global float* all_unit_rate_m2,
+    __global uint* all_samples,
+    uint record_samples,
+    int starting_quantity,
+    uint lead_time,
+    int safety_stock,
+    int order_quantity,
+    uint precomp_size,
+    uint samples,
+    uint first_sample,
+    uint total_samples
+#ifdef METRIC_COUNT
+    , __global float* all_metrics
+#endif
+#ifdef REPLAY_DRAWS
+    , __global uint* replay
+    , __global int* trace
+#endif
+) {
+    LOAD_LOCAL_TABLES
+    simulate_work_item(get_global_id(0), seed,
+        (__global table_t*) job_lot_zipf_precomp, (__global table_t*) itemwise_traffic_zipf_precomp,
+        LOCAL_TABLES
+        all_successful_transactions, all_successful_sales, all_failed_transactions, all_failed_sales,
+        all_rate_count, all_transaction_rate_mean, all_transaction_rate_m2, all_unit_rate_mean,
+        all_unit_rate_m2, all_samples, record_samples, lead_time,
+        starting_quantity, safety_stock, order_quantity,
+        precomp_size, samples, first_sample, total_samples METRIC_ARGS REPLAY_ARGS);
+}
+
+// Like ocl_simulate_demand, for many policies in one launch
+//
+// Policy p is (starting_quantity, safety_stock, order_quantity) at policies[3 * p], and work
+// items p * work_items_per_policy up to (p + 1) * work_items_per_policy run it. The lead time
+// is compiled in, so it's the same for all of them.
+__kernel void ocl_simulate_demand_sweep(
+    __global uint* seed,
+    __global uint* job_lot_zipf_precomp,
+    __global uint* itemwise_traffic_zipf_precomp,
+    __global ulong* all_successful_transactions,
+    __global ulong* all_successful_sales,
+    __global ulong* all_failed_transactions,
+    __global ulong* all_failed_sales,
+    __global uint* all_rate_count,
+    __global float* all_transaction_rate_mean,
+    __global float* all_transaction_rate_m2,
+    __global float* all_unit_rate_mean,
+    __global float* all_unit_rate_m2,
+    __global uint* all_samples,
+    uint record_samples,
+    __global int* policies,
+    uint work_items_per_policy,
+    uint lead_time,
+    uint precomp_size,
+    uint samples,
+    uint first_sample,
+    uint total_samples
+#ifdef METRIC_COUNT
+    , __global float* all_metrics
+#endif
+#ifdef REPLAY_DRAWS
+    , __global uint* replay
+    , __global int* trace
+#endif
+) {
+    int me = get_global_id(0);
+    __global int* policy = policies + 3 * (me / work_items_per_policy);
+    LOAD_LOCAL_TABLES
+    simulate_work_item(me, seed,
+        (__global table_t*) job_lot_zipf_precomp, (__global table_t*) itemwise_traffic_zipf_precomp,
+        LOCAL_TABLES
+        all_successful_transactions, all_successful_sales, all_failed_transactions, all_failed_sales,
+        all_rate_count, all_transaction_rate_mean, all_transaction_rate_m2, all_unit_rate_mean,
+        all_unit_rate_m2, all_samples, record_samples, lead_time,
+        policy[0], policy[1], policy[2],
+        precomp_size, samples, first_sample, total_samples METRIC_ARGS REPLAY_ARGS);
+}
@@ -0,0 +1,126 @@
+//! Shared virtual memory, for devices with OpenCL 2.0 or later
+//!
+//! With fine-grained SVM the device writes straight into memory the host can read, so the
+//! results don't need to be copied back at all once the kernel is done. The ocl crate doesn't
+//! wrap SVM, so this talks to the OpenCL library directly. It's behind the "svm" feature,
+//! since linking against these functions fails on OpenCL 1.2 libraries like macOS's.
+use ocl::ffi;
+use std::marker::PhantomData;
+
+/// Whether `device` can share buffers with the host at a fine grain, which is the only kind
+/// we use: the coarse kind would need mapping and unmapping, which is a copy by another name
+pub fn supported(device: &ocl::Device) -> bool {
+    let capabilities = match device.info_raw(ffi::CL_DEVICE_SVM_CAPABILITIES) {
+        Ok(bytes) if bytes.len() == std::mem::size_of::<ffi::cl_bitfield>() => {
+            let mut raw = [0u8; 8];
+            raw.copy_from_slice(&bytes);
+            ffi::cl_bitfield::from_ne_bytes(raw)
+        }
+        // Older devices don't know the question
+        _ => 0,
+    };
+    capabilities & ffi::CL_DEVICE_SVM_FINE_GRAIN_BUFFER != 0
+}
+
+/// An array both the host and the device can see
+pub struct SvmVec<T: Copy> {
+    context: ocl::Context,
+    ptr: *mut T,
+    len: usize,
+    _type: PhantomData<T>,
+}
+
+impl<T: Copy> SvmVec<T> {
+    /// Allocate `len` elements, or None if the driver won't
+    ///
+    /// The contents start out undefined, like any other kernel output buffer.
+    pub fn new(context: &ocl::Context, len: usize) -> Option<SvmVec<T>> {
+        let flags = ffi::CL_MEM_READ_WRITE | ffi::CL_MEM_SVM_FINE_GRAIN_BUFFER;
+        let size = std::mem::size_of::<T>() * len.max(1);
+        let ptr = unsafe { ffi::clSVMAlloc(context.as_core().as_ptr(), flags, size, 0) } as *mut T;
+        if ptr.is_null() {
+            return None;
+        }
+        Some(SvmVec { context: context.clone(), ptr, len, _type: PhantomData })
+    }
+
+    /// Point argument `index` of `kernel` at this array
+    pub fn set_arg(&self, kernel: &ocl::Kernel, index: u32) -> ocl::Result<()> {
+        let status = unsafe {
+            ffi::clSetKernelArgSVMPointer(kernel.as_core().as_ptr(), index, self.ptr as *const _)
+        };
+        if status == ffi::CL_SUCCESS as ffi::cl_int {
+            Ok(())
+        } else {
+            Err(format!("clSetKernelArgSVMPointer failed with status {}", status).into())
+        }
+    }
+
+    /// The contents, which are only meaningful once every kernel writing to them has finished
+    ///
+    /// # Safety
+    ///
+    /// No kernel may still be writing to this array.
+    pub unsafe fn as_slice(&self) -> &[T] {
+        std::slice::from_raw_parts(self.ptr, self.len)
+    }
+}
+
+// The allocation belongs to the context, not to any thread, and we only hand out shared
+// references to it, so it can move between threads like a Vec can
+unsafe impl<T: Copy + Send> Send for SvmVec<T> {}
+
+impl<T: Copy> Drop for SvmVec<T> {
+    fn drop(&mut self) {
+        unsafe { ffi::clSVMFree(self.context.as_core().as_ptr(), self.ptr as *mut _) }
+    }
+}
+
+/// The kernel's outputs, in shared memory: the same things InFlight reads back otherwise
+pub struct Results {
+    pub counters: Vec<SvmVec<u64>>,
+    pub rate_count: SvmVec<u32>,
+    pub rates: Vec<SvmVec<f32>>,
+    pub samples: SvmVec<u32>,
+}
+
+impl Results {
+    /// Allocate all of them, or None if any one fails
+    pub fn new(context: &ocl::Context, work_items: usize, samples: usize) -> Option<Results> {
+        Some(Results {
+            counters: (0..4).map(|_| SvmVec::new(context, work_items)).collect::<Option<_>>()?,
+            rate_count: SvmVec::new(context, work_items)?,
+            rates: (0..4).map(|_| SvmVec::new(context, work_items)).collect::<Option<_>>()?,
+            samples: SvmVec::new(context, samples)?,
+        })
+    }
+
+    /// Point the kernel's output arguments at these, starting with the counters at `first`
+    /// and in the same order as the kernel's parameters
+    pub fn set_args(&self, kernel: &ocl::Kernel, first: u32) -> ocl::Result<()> {
+        let mut index = first;
+        for counter in &self.counters {
+            counter.set_arg(kernel, index)?;
+            index += 1;
+        }
+        self.rate_count.set_arg(kernel, index)?;
+        index += 1;
+        for rate in &self.rates {
+            rate.set_arg(kernel, index)?;
+            index += 1;
+        }
+        self.samples.set_arg(kernel, index)
+    }
+
+    /// The counters, rate counts, rates and samples, for InFlight to add up where they are
+    ///
+    /// # Safety
+    ///
+    /// The kernel must have finished.
+    #[allow(clippy::type_complexity)]
+    pub unsafe fn slices(&self) -> ([&[u64]; 4], &[u32], [&[f32]; 4], &[u32]) {
+        let counters = [self.counters[0].as_slice(), self.counters[1].as_slice(), self.counters[2].as_slice(), self.counters[3].as_slice()];
+        let rates = [self.rates[0].as_slice(), self.rates[1].as_slice(), self.rates[2].as_slice(), self.rates[3].as_slice()];
+        (counters, self.rate_count.as_slice(), rates, self.samples.as_slice())
+    }
+}
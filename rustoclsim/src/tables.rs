@@ -0,0 +1,133 @@
+//! The zipf tables, built here or mapped from files an earlier process wrote
+//!
+//! Building the two 16M-entry tables is most of the time it takes to make a Simulation, and
+//! every process of a sweep or a CI job used to do it again. With RUSTOCLSIM_ZIPF_CACHE=1, a
+//! seeded simulation writes its tables under the cache directory the first time, and later
+//! ones with the same seed and exponents map those files instead. Unseeded simulations always
+//! draw fresh tables, since sharing one between them would make their runs depend on each
+//! other.
+use std::fs::File;
+use std::ops::Deref;
+use std::path::PathBuf;
+
+/// Entries in each table
+pub use rustsim::gpu::TABLE_LEN;
+
+/// The layout of the files, raised whenever how a table is drawn or stored changes
+const FORMAT: u32 = 1;
+
+/// Whether to keep the tables on disk. Set RUSTOCLSIM_ZIPF_CACHE=1 to turn it on.
+pub fn enabled() -> bool {
+    std::env::var("RUSTOCLSIM_ZIPF_CACHE").is_ok_and(|value| value == "1")
+}
+
+/// One table, in memory or mapped from its file
+pub struct ZipfTable(Storage);
+
+enum Storage {
+    Built(Vec<u32>),
+    /// A read-only private mapping of a whole file, which we unmap on drop
+    #[cfg(unix)]
+    Mapped(*const u32, usize),
+}
+
+// The mapping is read-only and lives as long as the table, so it's as shareable as a Vec
+unsafe impl Send for ZipfTable {}
+unsafe impl Sync for ZipfTable {}
+
+impl From<Vec<u32>> for ZipfTable {
+    fn from(table: Vec<u32>) -> ZipfTable {
+        ZipfTable(Storage::Built(table))
+    }
+}
+
+impl Deref for ZipfTable {
+    type Target = [u32];
+
+    fn deref(&self) -> &[u32] {
+        match &self.0 {
+            Storage::Built(table) => table,
+            #[cfg(unix)]
+            Storage::Mapped(address, len) => unsafe { std::slice::from_raw_parts(*address, *len) },
+        }
+    }
+}
+
+impl Drop for ZipfTable {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Storage::Mapped(address, len) = self.0 {
+            unsafe { libc::munmap(address as *mut libc::c_void, len * 4) };
+        }
+    }
+}
+
+/// The files for a seed and pair of exponents: the job lot table, then the traffic table
+///
+/// They're in this machine's byte order, and only ever replaced whole, by rename, so a
+/// process that has one mapped never sees it change underneath it. The directory is named
+/// for the file format and this crate's version, so a release that draws its tables
+/// differently never maps the ones an older release saved.
+fn paths(seed: u64, job_lot_zipf: f64, itemwise_traffic_zipf: f64) -> Option<(PathBuf, PathBuf)> {
+    let dir = crate::tuning::cache_dir()?.join("zipf").join(format!("v{}-{}", FORMAT, env!("CARGO_PKG_VERSION")));
+    let stem = format!("{:016x}-{:016x}-{:016x}", seed, job_lot_zipf.to_bits(), itemwise_traffic_zipf.to_bits());
+    Some((dir.join(format!("{}-job_lot.u32", stem)), dir.join(format!("{}-traffic.u32", stem))))
+}
+
+/// The saved (job lot, traffic) tables for this seed and these exponents, if both are there
+/// and whole
+pub fn load(seed: u64, job_lot_zipf: f64, itemwise_traffic_zipf: f64) -> Option<(ZipfTable, ZipfTable)> {
+    let (job_lot, traffic) = paths(seed, job_lot_zipf, itemwise_traffic_zipf)?;
+    let tables = (open(&job_lot)?, open(&traffic)?);
+    debug!("Mapped the zipf tables from {}", job_lot.display());
+    Some(tables)
+}
+
+#[cfg(unix)]
+fn open(path: &std::path::Path) -> Option<ZipfTable> {
+    use std::os::unix::io::AsRawFd;
+    let file = File::open(path).ok()?;
+    if file.metadata().ok()?.len() != (TABLE_LEN * 4) as u64 {
+        return None;
+    }
+    let address = unsafe {
+        libc::mmap(std::ptr::null_mut(), TABLE_LEN * 4, libc::PROT_READ, libc::MAP_PRIVATE, file.as_raw_fd(), 0)
+    };
+    if address == libc::MAP_FAILED {
+        return None;
+    }
+    // The mapping outlives the file handle
+    Some(ZipfTable(Storage::Mapped(address as *const u32, TABLE_LEN)))
+}
+
+#[cfg(not(unix))]
+fn open(path: &std::path::Path) -> Option<ZipfTable> {
+    let bytes = std::fs::read(path).ok().filter(|bytes| bytes.len() == TABLE_LEN * 4)?;
+    let table = bytes.chunks_exact(4).map(|entry| u32::from_ne_bytes([entry[0], entry[1], entry[2], entry[3]])).collect::<Vec<u32>>();
+    Some(table.into())
+}
+
+/// Save the tables for next time
+///
+/// Failing to write them isn't worth an error, just a warning; we'll build them again.
+pub fn save(seed: u64, job_lot_zipf: f64, itemwise_traffic_zipf: f64, job_lot_table: &[u32], traffic_table: &[u32]) {
+    let (job_lot, traffic) = match paths(seed, job_lot_zipf, itemwise_traffic_zipf) {
+        Some(paths) => paths,
+        None => return,
+    };
+    for (path, table) in [(job_lot, job_lot_table), (traffic, traffic_table)] {
+        // Written beside the final name first, so no one maps half a table
+        let partial = path.with_extension(format!("{}.partial", std::process::id()));
+        let bytes: Vec<u8> = table.iter().flat_map(|entry| entry.to_ne_bytes()).collect();
+        let written = match path.parent() {
+            Some(dir) => std::fs::create_dir_all(dir),
+            None => Ok(()),
+        }.and_then(|_| std::fs::write(&partial, bytes))
+            .and_then(|_| std::fs::rename(&partial, &path));
+        if let Err(error) = written {
+            let _ = std::fs::remove_file(&partial);
+            warning!("Couldn't save the zipf table to {}: {}", path.display(), error);
+            return;
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! Where the time went in a simulation's last trip to the device, for
+//! Simulation.last_timings(), and what that trip was, for Simulation.last_metadata()
+//!
+//! The phases are the same ones rustsim reports, so a CPU run and a GPU run line up. The
+//! device works asynchronously, so each phase is timed from the host by waiting for it: the
+//! kernel is done when its event completes, and the readback when the reads' events do.
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The phases, in the order a call goes through them
+#[derive(Clone, Copy)]
+pub enum Phase {
+    /// Getting the compiled program ready, which is only slow the first time
+    Precompute,
+    /// Seeding the random number generators
+    Seeds,
+    /// Copying inputs to the device
+    Upload,
+    /// The repetitions themselves
+    Kernel,
+    /// Copying results back from the device
+    Readback,
+    /// Adding the results up into a summary
+    Reduction,
+}
+
+impl Phase {
+    pub const ALL: [(&'static str, Phase); 6] = [
+        ("precompute", Phase::Precompute),
+        ("seed_generation", Phase::Seeds),
+        ("upload", Phase::Upload),
+        ("kernel", Phase::Kernel),
+        ("readback", Phase::Readback),
+        ("reduction", Phase::Reduction),
+    ];
+}
+
+/// Wall-clock time spent in each phase
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Breakdown([Duration; 6]);
+
+impl Breakdown {
+    pub fn add(&mut self, phase: Phase, time: Duration) {
+        self.0[phase as usize] += time;
+    }
+
+    /// Add in the time of another batch, phase by phase
+    pub fn merge(&mut self, other: &Breakdown) {
+        for (time, more) in self.0.iter_mut().zip(&other.0) {
+            *time += *more;
+        }
+    }
+
+    /// Seconds spent in `phase`
+    pub fn seconds(&self, phase: Phase) -> f64 {
+        self.0[phase as usize].as_secs_f64()
+    }
+}
+
+/// Times a call one phase after another
+pub struct Stopwatch {
+    lap: Instant,
+    breakdown: Breakdown,
+}
+
+impl Stopwatch {
+    pub fn start() -> Stopwatch {
+        Stopwatch {
+            lap: Instant::now(),
+            breakdown: Breakdown::default(),
+        }
+    }
+
+    /// Charge everything since the last lap to `phase`
+    pub fn lap(&mut self, phase: Phase) {
+        let now = Instant::now();
+        self.breakdown.add(phase, now - self.lap);
+        self.lap = now;
+    }
+
+    /// The time charged to each phase so far
+    pub fn breakdown(&self) -> Breakdown {
+        self.breakdown
+    }
+}
+
+/// The breakdown of a simulation's last timed call
+///
+/// This isn't part of what the simulation is: copies start without one, and it never makes
+/// two simulations unequal.
+#[derive(Debug, Default)]
+pub struct LastTimings(Mutex<Option<Breakdown>>);
+
+impl LastTimings {
+    pub fn set(&self, breakdown: Breakdown) {
+        *self.0.lock().unwrap() = Some(breakdown);
+    }
+
+    pub fn get(&self) -> Option<Breakdown> {
+        *self.0.lock().unwrap()
+    }
+}
+
+impl Clone for LastTimings {
+    fn clone(&self) -> LastTimings {
+        LastTimings::default()
+    }
+}
+
+impl PartialEq for LastTimings {
+    fn eq(&self, _other: &LastTimings) -> bool {
+        true
+    }
+}
+
+/// What a simulation's last repeat_simulate_demand was asked for: the starting quantity, the
+/// count, and the seed its work items were seeded from
+///
+/// Like LastTimings, copies start without one.
+#[derive(Debug, Default)]
+pub struct LastCall(Mutex<Option<(usize, usize, u64)>>);
+
+impl LastCall {
+    pub fn set(&self, starting_quantity: usize, count: usize, seed: u64) {
+        *self.0.lock().unwrap() = Some((starting_quantity, count, seed));
+    }
+
+    pub fn get(&self) -> Option<(usize, usize, u64)> {
+        *self.0.lock().unwrap()
+    }
+}
+
+impl Clone for LastCall {
+    fn clone(&self) -> LastCall {
+        LastCall::default()
+    }
+}
@@ -0,0 +1,152 @@
+//! How to lay out a launch on a device, and remembering which layout was fastest
+//!
+//! The kernel used to always run on 1000 work items, which leaves most of a big GPU idle and
+//! oversubscribes a small one. Instead, the first time we use a device we try a few layouts,
+//! keep the fastest, and write it down so the next process doesn't have to try again.
+use crate::PerProcess;
+use ocl::enums::{DeviceInfo, DeviceInfoResult};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// One way to split a batch over a device
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Shape {
+    /// The global size: how many work items, each with its own random stream
+    pub work_items: usize,
+    /// The work-group size, or None to let the driver pick
+    pub local_size: Option<usize>,
+    /// How many repetitions each work item runs per batch, where we're free to choose
+    pub chunk_size: usize,
+}
+
+impl Shape {
+    /// What we did before there was any tuning, and still do when it's turned off, when
+    /// the simulation has a seed, or when we have to match the CPU reference seed for seed
+    pub const DEFAULT: Shape = Shape { work_items: 1000, local_size: None, chunk_size: 1000 };
+
+    /// How many repetitions go in one batch
+    pub fn batch_size(&self) -> usize {
+        self.work_items * self.chunk_size
+    }
+}
+
+/// Repetitions per work item to try, once the layout is settled
+pub const CHUNK_SIZES: [usize; 4] = [64, 256, 1024, 4096];
+
+/// Layouts worth trying on `device`: a few work items per compute unit up to a lot of them,
+/// each with the driver's choice of work-group size and with a few fixed ones that fit
+pub fn candidates(device: &ocl::Device) -> ocl::Result<Vec<Shape>> {
+    let compute_units = match device.info(DeviceInfo::MaxComputeUnits)? {
+        DeviceInfoResult::MaxComputeUnits(units) => (units as usize).max(1),
+        _ => 1,
+    };
+    let max_local = device.max_wg_size()?;
+    let mut shapes = vec![Shape::DEFAULT];
+    for &per_unit in &[64, 256, 1024] {
+        for &local_size in &[None, Some(32), Some(64), Some(128), Some(256)] {
+            if local_size.is_some_and(|local| local > max_local) {
+                continue;
+            }
+            // The global size has to be a multiple of the work-group size
+            let granularity = local_size.unwrap_or(1);
+            let work_items = (compute_units * per_unit + granularity - 1) / granularity * granularity;
+            let shape = Shape { work_items, local_size, chunk_size: Shape::DEFAULT.chunk_size };
+            if !shapes.contains(&shape) {
+                shapes.push(shape);
+            }
+        }
+    }
+    Ok(shapes)
+}
+
+/// Whether to tune at all. Set RUSTOCLSIM_AUTOTUNE=0 to always use Shape::DEFAULT.
+pub fn enabled() -> bool {
+    std::env::var("RUSTOCLSIM_AUTOTUNE").map_or(true, |value| value != "0")
+}
+
+/// How long tuning a device may take before it settles for the best layout so far. Set
+/// RUSTOCLSIM_TUNING_BUDGET to a number of seconds to change it from 10.
+pub fn budget() -> Duration {
+    std::env::var("RUSTOCLSIM_TUNING_BUDGET").ok()
+        .and_then(|seconds| seconds.parse::<f64>().ok())
+        .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+        .map_or(Duration::from_secs(10), Duration::from_secs_f64)
+}
+
+/// What we know that could change which layout is best: the device, its driver, and a hash
+/// of the program (its source and defines), since a longer lead time or a policy compiled
+/// in changes how much work each repetition is
+pub fn key(platform: &ocl::Platform, device: &ocl::Device, program: u64) -> String {
+    let driver = match device.info(DeviceInfo::DriverVersion) {
+        Ok(DeviceInfoResult::DriverVersion(version)) => version,
+        _ => String::new(),
+    };
+    format!("{} / {} / {} / {:016x}",
+        platform.name().unwrap_or_default(),
+        device.name().unwrap_or_default(),
+        driver,
+        program).replace('\t', " ")
+}
+
+/// Layouts already chosen in this process
+static SHAPES: PerProcess<HashMap<String, Shape>> = PerProcess::new(HashMap::new);
+
+/// The layout chosen for this device, from this process or an earlier one
+pub fn recall(key: &str) -> Option<Shape> {
+    let mut shapes = SHAPES.lock();
+    if let Some(&shape) = shapes.get(key) {
+        return Some(shape);
+    }
+    let shape = load().remove(key)?;
+    shapes.insert(key.to_string(), shape);
+    Some(shape)
+}
+
+/// Remember the layout for this device, here and on disk
+///
+/// Failing to write the file isn't worth an error, just a warning; we'll tune again next time.
+pub fn remember(key: &str, shape: Shape) {
+    SHAPES.lock().insert(key.to_string(), shape);
+    let mut saved = load();
+    saved.insert(key.to_string(), shape);
+    if let Some(path) = cache_path() {
+        let lines: String = saved.iter().map(|(key, shape)| format!("{}\t{}\t{}\t{}\n",
+            key, shape.work_items, shape.local_size.unwrap_or(0), shape.chunk_size)).collect();
+        let written = match path.parent() {
+            Some(dir) => std::fs::create_dir_all(dir),
+            None => Ok(()),
+        }.and_then(|_| std::fs::write(&path, lines));
+        if let Err(error) = written {
+            warning!("Couldn't save the tuned layout to {}: {}", path.display(), error);
+        }
+    }
+}
+
+/// Where the chosen layouts are kept
+fn cache_path() -> Option<PathBuf> {
+    Some(cache_dir()?.join("tuning.tsv"))
+}
+
+/// Where anything worth keeping between processes goes: $RUSTOCLSIM_CACHE_DIR, or the usual
+/// per-user cache
+pub fn cache_dir() -> Option<PathBuf> {
+    std::env::var_os("RUSTOCLSIM_CACHE_DIR").map(PathBuf::from)
+        .or_else(|| std::env::var_os("XDG_CACHE_HOME").map(|dir| PathBuf::from(dir).join("rustoclsim")))
+        .or_else(|| std::env::var_os("HOME").map(|dir| PathBuf::from(dir).join(".cache").join("rustoclsim")))
+}
+
+/// Read the saved layouts, one device per line. Anything we can't make sense of is skipped.
+fn load() -> HashMap<String, Shape> {
+    let text = cache_path().and_then(|path| std::fs::read_to_string(path).ok()).unwrap_or_default();
+    text.lines().filter_map(|line| {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 4 {
+            return None;
+        }
+        let work_items = fields[1].parse().ok().filter(|&n| n > 0)?;
+        let local_size = fields[2].parse().ok().filter(|&n| n > 0);
+        let chunk_size = fields[3].parse().ok().filter(|&n| n > 0)?;
+        Some((fields[0].to_string(), Shape { work_items, local_size, chunk_size }))
+    }).collect()
+}
@@ -0,0 +1,181 @@
+use extendr_api::prelude::*;
+use rayon::prelude::*;
+
+/// R has no integers wider than 32 bits, so counts arrive as doubles; take them if they're
+/// whole and not negative
+fn whole(name: &str, value: f64) -> Result<usize> {
+    if value < 0.0 || value.fract() != 0.0 || !value.is_finite() {
+        Err(Error::Other(format!("{} must be a non-negative whole number, not {}", name, value)))
+    } else {
+        Ok(value as usize)
+    }
+}
+
+/// Counters and rates, as a named list, in the same order as rustsim's Summary tuple
+fn summary_list((st, ss, ft, fs, tr, ur): rustsim::Summary) -> List {
+    list!(
+        successful_transactions = st as f64,
+        successful_sales = ss as f64,
+        failed_transactions = ft as f64,
+        failed_sales = fs as f64,
+        transaction_fill_rate = tr,
+        unit_fill_rate = ur
+    )
+}
+
+/// The same checks as Python's optimize functions, so R gets an error rather than a crash
+fn simulation(
+    safety_stock: f64,
+    lead_time: f64,
+    order_quantity: f64,
+    job_lot_zipf: Option<f64>,
+    itemwise_traffic_zipf: Option<f64>,
+    seed: Option<f64>,
+) -> Result<rustsim::Simulation> {
+    let lead_time = whole("lead_time", lead_time)?;
+    let order_quantity = whole("order_quantity", order_quantity)?;
+    if lead_time < 1 || order_quantity < 1 {
+        return Err(Error::Other("lead_time and order_quantity must be at least 1".into()));
+    }
+    if job_lot_zipf.unwrap_or(1.0) <= 0.0 || itemwise_traffic_zipf.unwrap_or(1.0) <= 0.0 {
+        return Err(Error::Other("zipf exponents must be positive".into()));
+    }
+    Ok(rustsim::Simulation::new(
+        whole("safety_stock", safety_stock)?,
+        lead_time,
+        order_quantity,
+        job_lot_zipf,
+        itemwise_traffic_zipf,
+        seed.map(|seed| whole("seed", seed)).transpose()?.map(|seed| seed as u64),
+    ))
+}
+
+/// Simulation parameters
+///
+/// The R twin of rustsim.Simulation: the same engine, so a seeded run gives the same results
+/// as it would in Python. Seeds are doubles, like every number in R, so they're exact up to
+/// 2^53.
+/// @export
+struct Simulation {
+    sim: rustsim::Simulation,
+}
+
+#[extendr]
+impl Simulation {
+    /// Create a simulation; leave the zipf exponents and seed NULL for the defaults
+    fn new(
+        safety_stock: f64,
+        lead_time: f64,
+        order_quantity: f64,
+        job_lot_zipf: Nullable<f64>,
+        itemwise_traffic_zipf: Nullable<f64>,
+        seed: Nullable<f64>,
+    ) -> Result<Self> {
+        Ok(Simulation {
+            sim: simulation(
+                safety_stock,
+                lead_time,
+                order_quantity,
+                job_lot_zipf.into_option(),
+                itemwise_traffic_zipf.into_option(),
+                seed.into_option(),
+            )?,
+        })
+    }
+
+    /// Simulate one year, returning a named list of counters and fill rates
+    fn simulate_demand(&self, starting_quantity: f64) -> Result<List> {
+        let starting_quantity = whole("starting_quantity", starting_quantity)?;
+        Ok(summary_list(self.sim.simulate_summary(starting_quantity, &mut self.sim.rng())))
+    }
+
+    /// Repeat the simulation `count` times and add it all up
+    fn repeat_simulate_demand(&self, starting_quantity: f64, count: f64) -> Result<List> {
+        let starting_quantity = whole("starting_quantity", starting_quantity)?;
+        Ok(summary_list(self.sim.repeat_summary(starting_quantity, whole("count", count)?)))
+    }
+
+    /// Find the smallest starting quantity that reaches `target_fill_rate` (unit fill rate)
+    ///
+    /// Just like Python's Simulation.solve_starting_quantity(): every trial sees the same
+    /// `count` years of customers, so noise can't send the search the wrong way. Returns a
+    /// list of starting_quantity and unit_fill_rate, or NULL if even `max_quantity` isn't
+    /// enough.
+    fn solve_starting_quantity(&self, target_fill_rate: f64, count: f64, max_quantity: f64) -> Result<Robj> {
+        let count = whole("count", count)?;
+        let max_quantity = whole("max_quantity", max_quantity)?;
+        Ok(match self.sim.smallest_starting_quantity(target_fill_rate, count, max_quantity) {
+            Some((starting_quantity, rate)) => {
+                list!(starting_quantity = starting_quantity as f64, unit_fill_rate = rate).into()
+            }
+            None => NULL.into(),
+        })
+    }
+}
+
+/// Simulate every row of a parameter grid, like rustsim.optimize.grid_search without the search
+///
+/// Pass equal length columns, usually from expand.grid(); starting_quantity may be NULL to
+/// start each row at its safety stock. Every row sees the same customers (common random
+/// numbers), so differences between rows are down to the policy rather than luck. The rows
+/// run in parallel on all your cores. Returns a data.frame of the parameters with
+/// transaction_fill_rate and unit_fill_rate added.
+/// @export
+#[extendr]
+#[allow(clippy::too_many_arguments)]
+fn sweep(
+    safety_stock: Vec<f64>,
+    lead_time: Vec<f64>,
+    order_quantity: Vec<f64>,
+    starting_quantity: Nullable<Vec<f64>>,
+    count: f64,
+    job_lot_zipf: Nullable<f64>,
+    itemwise_traffic_zipf: Nullable<f64>,
+    seed: Nullable<f64>,
+) -> Result<Robj> {
+    let rows = safety_stock.len();
+    let starting_quantity = starting_quantity.into_option().unwrap_or_else(|| safety_stock.clone());
+    if lead_time.len() != rows || order_quantity.len() != rows || starting_quantity.len() != rows {
+        return Err(Error::Other("Every column of the grid must be the same length".into()));
+    }
+    let count = whole("count", count)?;
+    let (job_lot_zipf, itemwise_traffic_zipf) =
+        (job_lot_zipf.into_option(), itemwise_traffic_zipf.into_option());
+    // One seed for every row, drawn once if the caller didn't pick one
+    let seed = seed.into_option().unwrap_or_else(|| rand::random::<u32>() as f64);
+    let mut candidates = Vec::with_capacity(rows);
+    for row in 0..rows {
+        candidates.push((
+            simulation(
+                safety_stock[row],
+                lead_time[row],
+                order_quantity[row],
+                job_lot_zipf,
+                itemwise_traffic_zipf,
+                Some(seed),
+            )?,
+            whole("starting_quantity", starting_quantity[row])?,
+        ));
+    }
+    let summaries: Vec<rustsim::Summary> = candidates
+        .par_iter()
+        .map(|(sim, starting_quantity)| sim.repeat_summary(*starting_quantity, count))
+        .collect();
+    Ok(data_frame!(
+        safety_stock = safety_stock,
+        lead_time = lead_time,
+        order_quantity = order_quantity,
+        starting_quantity = starting_quantity,
+        transaction_fill_rate = summaries.iter().map(|summary| summary.4).collect::<Vec<f64>>(),
+        unit_fill_rate = summaries.iter().map(|summary| summary.5).collect::<Vec<f64>>()
+    ))
+}
+
+// Macro to generate exports.
+// This ensures exported functions are registered with R.
+// See corresponding C code in `entrypoint.c`.
+extendr_module! {
+    mod rustrsim;
+    impl Simulation;
+    fn sweep;
+}
@@ -0,0 +1,430 @@
+# Type stubs for the rustsim extension module, for IDEs and mypy.
+#
+# maturin ships this next to the compiled module. It's kept by hand, so when a #[pymethods]
+# or #[pyfunction] signature changes in src/, change it here too.
+from typing import (
+    Any,
+    Callable,
+    Dict,
+    Iterable,
+    Iterator,
+    List,
+    Literal,
+    Mapping,
+    Optional,
+    Protocol,
+    Sequence,
+    Tuple,
+    Type,
+    Union,
+)
+
+# (successful transactions, successful sales, failed transactions, failed sales,
+#  transaction fill rate, unit fill rate)
+Summary = Tuple[int, int, int, int, float, float]
+# (mean, variance)
+Moments = Tuple[float, float]
+# Days of demand for repeat_simulate_demand_python, each a list of request sizes
+DemandBatch = Sequence[Sequence[int]]
+
+class RunCallback(Protocol):
+    """What Simulation.repeat_simulate_demand(callbacks=...) calls; either method may be left out"""
+    def on_run_start(self, config: Dict[str, Any]) -> None: ...
+    def on_run_end(self, config: Dict[str, Any], metrics: Dict[str, float]) -> None: ...
+
+class Simulation:
+    def __init__(
+        self,
+        *,
+        safety_stock: int = 10,
+        lead_time: int = 3,
+        order_quantity: int = 7,
+        job_lot_zipf: Optional[float] = None,
+        itemwise_traffic_zipf: Optional[float] = None,
+        seed: Optional[int] = None,
+        policy: Optional[str] = None,
+        job_lot_mixture: Optional[Sequence[Tuple[float, float]]] = None,
+        itemwise_traffic_mixture: Optional[Sequence[Tuple[float, float]]] = None,
+        max_request: Optional[int] = None,
+        max_request_mode: Literal["clip", "resample"] = "clip",
+        forecast_bias: float = 0.0,
+        forecast_mape: float = 0.0,
+    ) -> None: ...
+    def with_params(
+        self,
+        *,
+        safety_stock: int = ...,
+        lead_time: int = ...,
+        order_quantity: int = ...,
+        job_lot_zipf: float = ...,
+        itemwise_traffic_zipf: float = ...,
+        seed: Optional[int] = ...,
+        policy: Optional[str] = ...,
+        job_lot_mixture: Optional[Sequence[Tuple[float, float]]] = ...,
+        itemwise_traffic_mixture: Optional[Sequence[Tuple[float, float]]] = ...,
+        max_request: Optional[int] = ...,
+        max_request_mode: Literal["clip", "resample"] = ...,
+        forecast_bias: float = ...,
+        forecast_mape: float = ...,
+    ) -> Simulation: ...
+    @property
+    def seed(self) -> Optional[int]: ...
+    @property
+    def policy(self) -> Optional[str]: ...
+    @property
+    def job_lot_mixture(self) -> Optional[List[Tuple[float, float]]]: ...
+    @property
+    def itemwise_traffic_mixture(self) -> Optional[List[Tuple[float, float]]]: ...
+    @property
+    def max_request(self) -> Optional[int]: ...
+    @property
+    def max_request_mode(self) -> Literal["clip", "resample"]: ...
+    @property
+    def forecast_bias(self) -> float: ...
+    @property
+    def forecast_mape(self) -> float: ...
+    @property
+    def compound_poisson(self) -> Optional[Dict[str, Any]]: ...
+    @property
+    def deterministic(self) -> bool: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __ne__(self, other: object) -> bool: ...
+    def __hash__(self) -> int: ...
+    def to_json(self) -> str: ...
+    @staticmethod
+    def from_json(json: str) -> Simulation: ...
+    @staticmethod
+    def from_config(path: str, scenario: Optional[str] = None) -> Simulation: ...
+    @staticmethod
+    def from_compound_poisson(
+        mean_daily_units: float,
+        cv: float,
+        basket: Literal["geometric", "zipf"] = "geometric",
+        **params: Any,
+    ) -> Simulation: ...
+    @staticmethod
+    def scenarios(path: str) -> List[str]: ...
+    def metadata(self) -> Dict[str, Any]: ...
+    def last_timings(self) -> Optional[Dict[str, float]]: ...
+    def simulate_demand_inner(self, starting_quantity: int) -> Summary: ...
+    def simulate_demand(self, starting_quantity: int) -> Summary: ...
+    def repeat_simulate_demand(
+        self,
+        starting_quantity: int,
+        count: int,
+        sampling: Literal["random", "sobol"] = "random",
+        store: Optional[ResultStore] = None,
+        callbacks: Sequence[RunCallback] = [],
+        backend: Literal["cpu", "opencl", "auto"] = "cpu",
+    ) -> Summary: ...
+    def repeat_simulate_demand_controlled(
+        self,
+        starting_quantity: int,
+        count: int,
+        sampling: Literal["random", "sobol"] = "random",
+        store: Optional[ResultStore] = None,
+        callbacks: Sequence[RunCallback] = [],
+    ) -> Tuple[Summary, Tuple[float, float, float]]: ...
+    def repeat_simulate_demand_scheduled(
+        self, starting_quantity: int, count: int, schedule: Sequence[Tuple[int, str, float]]
+    ) -> Summary: ...
+    def repeat_simulate_demand_events(
+        self, starting_quantity: int, count: int, lead_time_spread: float = 0.0
+    ) -> Summary: ...
+    def repeat_simulate_demand_python(
+        self,
+        starting_quantity: int,
+        count: int,
+        demand: Union[Callable[[int], DemandBatch], Iterable[DemandBatch]],
+        batch: int = 1,
+    ) -> Summary: ...
+    def repeat_simulate_demand_metrics(
+        self, starting_quantity: int, count: int, metrics: List[str]
+    ) -> Tuple[Summary, Dict[str, float]]: ...
+    def repeat_simulate_demand_vectorized(self, starting_quantity: int, count: int) -> Summary: ...
+    def repeat_simulate_demand_batch(
+        self, starting_quantities: Sequence[int], count: int
+    ) -> List[Summary]: ...
+    def repeat_simulate_demand_stats(
+        self, starting_quantity: int, count: int
+    ) -> Tuple[Summary, Moments, Moments]: ...
+    def repeat_until(
+        self, starting_quantity: int, target_ci_width: float, max_count: int = 1_000_000
+    ) -> Tuple[Summary, int, float]: ...
+    def compare_sequential(
+        self,
+        other: Simulation,
+        starting_quantity: int,
+        alpha: float = 0.05,
+        indifference: float = 0.05,
+        max_count: int = 1_000_000,
+    ) -> Tuple[Optional[Literal["self", "other"]], int, int, int]: ...
+    def stockout_probability(
+        self, starting_quantity: int, count: int, tilt: float = 0.5
+    ) -> Tuple[float, float, float]: ...
+    def repeat_simulate_demand_stratified(
+        self, starting_quantity: int, count: int, strata: int = 10, pilot: int = 1000
+    ) -> Tuple[Summary, float, float]: ...
+    def evaluate(
+        self,
+        param_matrix: Any,
+        params: Optional[Sequence[str]] = None,
+        count: int = 1000,
+        holding_cost: float = 0.01,
+        order_cost: float = 1.0,
+        shortage_cost: float = 1.0,
+    ) -> List[List[float]]: ...
+    def solve_starting_quantity(
+        self, target_fill_rate: float, count: int = 1000, max_quantity: int = 1 << 32
+    ) -> Optional[Tuple[int, float]]: ...
+    def forecast_error_safety_stock(
+        self, target_fill_rate: float, count: int = 1000, max_quantity: int = 1 << 32
+    ) -> Dict[str, Optional[int]]: ...
+    def sensitivity(
+        self,
+        starting_quantity: int,
+        params: Sequence[str],
+        epsilons: Sequence[float],
+        count: int,
+        holding_cost: float = 0.01,
+        order_cost: float = 1.0,
+        shortage_cost: float = 1.0,
+    ) -> Dict[str, Dict[str, float]]: ...
+    def repeat_simulate_demand_stored(
+        self, starting_quantity: int, count: int, callbacks: Sequence[RunCallback] = []
+    ) -> Repetitions: ...
+    def repeat_simulate_demand_quantiles(
+        self, starting_quantity: int, count: int, quantiles: Sequence[float] = [0.01, 0.5, 0.99]
+    ) -> Tuple[Summary, List[float], List[float], List[float]]: ...
+    def export_trace_hdf5(
+        self, path: str, starting_quantity: int, count: int, chunk: int = 1024
+    ) -> Summary: ...
+    def repeat_simulate_demand_sampled(
+        self, starting_quantity: int, count: int, sample_size: int
+    ) -> Tuple[Summary, List[Tuple[int, int, int, bool]]]: ...
+    def lead_time_demand(self, count: int) -> Dict[str, Any]: ...
+    def iter_simulations(self, starting_quantity: int, count: int) -> SimulationIter: ...
+
+class SimulationIter(Iterator[Summary]):
+    def __iter__(self) -> SimulationIter: ...
+    def __next__(self) -> Summary: ...
+
+class SimulationState:
+    def __init__(self, sim: Simulation, starting_quantity: int, seed: Optional[int] = None) -> None: ...
+    def reset(self, starting_quantity: int, seed: Optional[int] = None) -> None: ...
+    def step_day(self) -> DayResult: ...
+    @property
+    def day(self) -> int: ...
+    @property
+    def stock(self) -> int: ...
+    @stock.setter
+    def stock(self, stock: int) -> None: ...
+    def pipeline(self) -> List[int]: ...
+    @property
+    def simulation(self) -> Simulation: ...
+    @simulation.setter
+    def simulation(self, sim: Simulation) -> None: ...
+    @property
+    def successful_transactions(self) -> int: ...
+    @property
+    def successful_sales(self) -> int: ...
+    @property
+    def failed_transactions(self) -> int: ...
+    @property
+    def failed_sales(self) -> int: ...
+    def summary(self) -> Summary: ...
+    def run_until(self, day: int = 365) -> Summary: ...
+    def branch(self, n: int, seeds: Optional[Sequence[int]] = None) -> List[SimulationState]: ...
+    def to_bytes(self) -> bytes: ...
+    @staticmethod
+    def from_bytes(data: bytes) -> SimulationState: ...
+
+class DayResult:
+    day: int
+    received: int
+    opening_stock: int
+    successful_transactions: int
+    successful_sales: int
+    failed_transactions: int
+    failed_sales: int
+    closing_stock: int
+    ordered: int
+
+MetricName = Literal[
+    "successful_transactions",
+    "successful_sales",
+    "failed_transactions",
+    "failed_sales",
+    "transaction_fill_rate",
+    "unit_fill_rate",
+]
+
+class Repetitions:
+    def metadata(self) -> Dict[str, Any]: ...
+    def results(self) -> List[Summary]: ...
+    def daily_stock(self) -> List[float]: ...
+    def _repr_html_(self) -> str: ...
+    def to_pandas(self) -> Any: ...
+    def bootstrap_ci(
+        self, metric: MetricName, n_boot: int = 1000, confidence: float = 0.95
+    ) -> Tuple[float, float, float]: ...
+
+class ResultStore:
+    def __init__(self, path: str) -> None: ...
+    def record(
+        self,
+        sim: Simulation,
+        starting_quantity: int,
+        count: int,
+        summary: Summary,
+        method: str = "repeat_simulate_demand",
+    ) -> int: ...
+    def runs(self, **filters: Any) -> List[Dict[str, Any]]: ...
+    def to_pandas(self, **filters: Any) -> Any: ...
+    def _repr_html_(self) -> str: ...
+    def close(self) -> None: ...
+
+def simulate(
+    safety_stock: int = 10,
+    lead_time: int = 3,
+    order_quantity: int = 7,
+    starting_quantity: Optional[int] = None,
+    count: int = 1000,
+    job_lot_zipf: Optional[float] = None,
+    itemwise_traffic_zipf: Optional[float] = None,
+    seed: Optional[int] = None,
+) -> Repetitions: ...
+def crosscheck(
+    cpu_sim: Simulation, ocl_sim: Any, starting_quantity: int, count: int = 100_000, alpha: float = 0.001
+) -> Dict[str, Tuple[float, float]]: ...
+def fit_report(
+    samples: Sequence[int],
+    distribution: Optional[Literal["zipf", "poisson", "negative_binomial"]] = None,
+) -> Dict[str, Any]: ...
+def benchmark(
+    counts: Sequence[int] = [1_000, 10_000, 100_000, 1_000_000],
+    sim: Optional[Simulation] = None,
+    starting_quantity: Optional[int] = None,
+) -> Dict[str, Any]: ...
+def validate(count: int = 100, seed: Optional[int] = None) -> int: ...
+
+# Grids map parameter names (see optimize.PARAMETERS) to the values to try
+ParamGrid = Mapping[str, Sequence[float]]
+
+class ResponseSurface:
+    def params(self) -> List[str]: ...
+    def predict(self, point: Sequence[float]) -> float: ...
+    def curvature(self) -> List[List[float]]: ...
+    def optimum(self) -> Optional[Tuple[List[float], float, str]]: ...
+
+class _Optimize:
+    """rustsim.optimize, which is an attribute of the module rather than a package of its own"""
+    PARAMETERS: List[str]
+    METRICS: List[str]
+    ResponseSurface: Type[ResponseSurface]
+    @staticmethod
+    def grid_search(
+        param_grid: ParamGrid,
+        objective: str = "fill_rate",
+        count: int = 1000,
+        base: Optional[Simulation] = None,
+        max_cost: Optional[float] = None,
+        constraints: Optional[Mapping[str, Any]] = None,
+        holding_cost: float = 0.01,
+        order_cost: float = 1.0,
+        shortage_cost: float = 1.0,
+        backend: Literal["cpu", "opencl"] = "cpu",
+    ) -> Tuple[Optional[Dict[str, Any]], List[Dict[str, Any]]]: ...
+    @staticmethod
+    def sweep_to_parquet(
+        param_grid: ParamGrid,
+        path: str,
+        count: int = 1000,
+        base: Optional[Simulation] = None,
+        holding_cost: float = 0.01,
+        order_cost: float = 1.0,
+        shortage_cost: float = 1.0,
+    ) -> int: ...
+    @staticmethod
+    def sweep_to_pandas(
+        param_grid: ParamGrid,
+        count: int = 1000,
+        base: Optional[Simulation] = None,
+        holding_cost: float = 0.01,
+        order_cost: float = 1.0,
+        shortage_cost: float = 1.0,
+    ) -> Any: ...
+    @staticmethod
+    def pareto(
+        param_grid: ParamGrid,
+        objectives: Sequence[str] = ["fill_rate", "total_cost"],
+        count: int = 1000,
+        base: Optional[Simulation] = None,
+        constraints: Optional[Mapping[str, Any]] = None,
+        holding_cost: float = 0.01,
+        order_cost: float = 1.0,
+        shortage_cost: float = 1.0,
+    ) -> List[Dict[str, Any]]: ...
+    @staticmethod
+    def anneal(
+        bounds: Mapping[str, Tuple[float, float]],
+        objective: Union[str, Callable[[Dict[str, Any]], float], None] = None,
+        count: int = 1000,
+        base: Optional[Simulation] = None,
+        steps: int = 1000,
+        initial_temperature: float = 0.1,
+        cooling: float = 0.995,
+        holding_cost: float = 0.01,
+        order_cost: float = 1.0,
+        shortage_cost: float = 1.0,
+    ) -> Dict[str, Any]: ...
+    @staticmethod
+    def robust_search(
+        param_grid: ParamGrid,
+        scenarios: Sequence[Mapping[str, float]],
+        objective: str = "fill_rate",
+        criterion: Literal["worst_case", "cvar"] = "worst_case",
+        alpha: float = 0.2,
+        count: int = 1000,
+        base: Optional[Simulation] = None,
+        holding_cost: float = 0.01,
+        order_cost: float = 1.0,
+        shortage_cost: float = 1.0,
+    ) -> Tuple[Optional[Dict[str, Any]], List[Dict[str, Any]]]: ...
+    @staticmethod
+    def fit_surface(
+        points: Sequence[Mapping[str, Any]], params: Sequence[str], metric: str = "unit_fill_rate"
+    ) -> ResponseSurface: ...
+
+optimize: _Optimize
+
+class _Portfolio:
+    """rustsim.portfolio, which is an attribute of the module rather than a package of its own"""
+    @staticmethod
+    def classify_abc(
+        items: Sequence[Simulation],
+        starting_quantities: Sequence[int],
+        count: int = 1000,
+        unit_values: Optional[Sequence[float]] = None,
+        a_share: float = 0.8,
+        b_share: float = 0.95,
+    ) -> Dict[str, Any]: ...
+    @staticmethod
+    def risk_pooling(
+        sim: Simulation,
+        locations: int,
+        target_fill_rate: float,
+        count: int = 1000,
+        max_quantity: int = 1 << 32,
+    ) -> Dict[str, Any]: ...
+    @staticmethod
+    def portfolio_risk(
+        items: Sequence[Simulation],
+        starting_quantities: Sequence[int],
+        correlation: Optional[Sequence[Sequence[float]]] = None,
+        count: int = 1000,
+        unit_values: Optional[Sequence[float]] = None,
+        quantiles: Sequence[float] = [0.5, 0.95, 0.99],
+    ) -> Dict[str, Any]: ...
+
+portfolio: _Portfolio
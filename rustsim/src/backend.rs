@@ -0,0 +1,312 @@
+//! One interface to every engine that repeats the simulation
+//!
+//! The scalar loop, the rayon pool, the SIMD lanes and rustoclsim's kernel all do the same
+//! three things: get ready for a simulation (build zipf tables, compile a program), run
+//! batches of years, and add the batches up. SimBackend is those three things, so code that
+//! drives a run (batching, seeding, stopping early, comparing engines) is written once
+//! against it rather than once per engine.
+#[cfg(feature = "simd")]
+use crate::simd;
+#[cfg(feature = "parallel")]
+use crate::streams;
+use crate::{summarize, Simulation, Summary};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// What a batch adds up to:
+/// (successful transactions, successful sales, failed transactions, failed sales)
+pub type Counts = (usize, usize, usize, usize);
+
+/// An engine that repeats the simulation
+pub trait SimBackend {
+    /// A short name for reports, like "cpu" or "opencl"
+    fn name(&self) -> &'static str;
+
+    /// Get ready to simulate `sim`. Anything built for it, like zipf tables or a compiled
+    /// program, is built here once rather than for every batch.
+    fn configure(&mut self, sim: &Simulation) -> Result<(), String>;
+
+    /// Simulate `count` years from `starting_quantity`, with random numbers seeded from `seed`
+    fn run_batch(
+        &mut self,
+        starting_quantity: usize,
+        count: usize,
+        seed: u64,
+    ) -> Result<Counts, String>;
+
+    /// Add up batches into a summary
+    fn reduce(&self, batches: &[Counts]) -> Summary {
+        let (mut st, mut ss, mut ft, mut fs) = (0, 0, 0, 0);
+        for &(xst, xss, xft, xfs) in batches {
+            st += xst;
+            ss += xss;
+            ft += xft;
+            fs += xfs;
+        }
+        summarize(st, ss, ft, fs)
+    }
+}
+
+/// Simulate `count` years of `sim` on `backend`, `batch_size` at a time, seeding each batch
+/// from `rng`
+pub fn repeat<B: SimBackend + ?Sized, R: Rng>(
+    backend: &mut B,
+    sim: &Simulation,
+    starting_quantity: usize,
+    count: usize,
+    batch_size: usize,
+    rng: &mut R,
+) -> Result<Summary, String> {
+    backend.configure(sim)?;
+    let batch_size = batch_size.max(1);
+    let mut batches = vec![];
+    let mut done = 0;
+    while done < count {
+        let size = batch_size.min(count - done);
+        batches.push(backend.run_batch(starting_quantity, size, rng.gen())?);
+        done += size;
+    }
+    Ok(backend.reduce(&batches))
+}
+
+/// The simulation a backend was configured with, or the error for running before that
+fn configured<'s>(sim: &'s Option<Simulation>, backend: &str) -> Result<&'s Simulation, String> {
+    sim.as_ref()
+        .ok_or_else(|| format!("The {} backend has to be configured first", backend))
+}
+
+/// One year after another on this thread, as repeat_summary does
+///
+/// Like repeat_summary, it runs whatever simulation it's given; checking the parameters is
+/// up to whoever made it.
+#[derive(Default)]
+pub struct Scalar {
+    sim: Option<Simulation>,
+}
+
+impl SimBackend for Scalar {
+    fn name(&self) -> &'static str {
+        "cpu"
+    }
+
+    fn configure(&mut self, sim: &Simulation) -> Result<(), String> {
+        self.sim = Some(sim.clone());
+        Ok(())
+    }
+
+    fn run_batch(
+        &mut self,
+        starting_quantity: usize,
+        count: usize,
+        seed: u64,
+    ) -> Result<Counts, String> {
+        let sim = configured(&self.sim, self.name())?;
+        let mut rng = StdRng::seed_from_u64(seed);
+        let (mut st, mut ss, mut ft, mut fs) = (0, 0, 0, 0);
+        for _ in 0..count {
+            let (xst, xss, xft, xfs, _, _) = sim.simulate_summary(starting_quantity, &mut rng);
+            st += xst;
+            ss += xss;
+            ft += xft;
+            fs += xfs;
+        }
+        Ok((st, ss, ft, fs))
+    }
+}
+
+/// The scalar loop, split over all your cores by rayon
+///
+/// Each thread's share of a batch gets its own generator, seeded from the batch's seed, so a
+/// batch comes out the same however many threads there are. Only there with the parallel
+/// feature.
+#[cfg(feature = "parallel")]
+#[derive(Default)]
+pub struct Parallel {
+    sim: Option<Simulation>,
+}
+
+/// Years per share of a batch in Parallel
+#[cfg(feature = "parallel")]
+const SHARE: usize = 1024;
+
+#[cfg(feature = "parallel")]
+impl SimBackend for Parallel {
+    fn name(&self) -> &'static str {
+        "rayon"
+    }
+
+    fn configure(&mut self, sim: &Simulation) -> Result<(), String> {
+        sim.check_parameters()?;
+        self.sim = Some(sim.clone());
+        Ok(())
+    }
+
+    fn run_batch(
+        &mut self,
+        starting_quantity: usize,
+        count: usize,
+        seed: u64,
+    ) -> Result<Counts, String> {
+        let sim = configured(&self.sim, self.name())?;
+        let shares: Vec<(usize, u64)> = (0..count)
+            .step_by(SHARE)
+            .enumerate()
+            .map(|(index, first)| (SHARE.min(count - first), index as u64))
+            .collect();
+        Ok(shares
+            .par_iter()
+            .map(|&(size, index)| {
+                let mut rng = streams::stream(seed, index);
+                let mut counts = (0, 0, 0, 0);
+                for _ in 0..size {
+                    let (xst, xss, xft, xfs, _, _) =
+                        sim.simulate_summary(starting_quantity, &mut rng);
+                    counts = (
+                        counts.0 + xst,
+                        counts.1 + xss,
+                        counts.2 + xft,
+                        counts.3 + xfs,
+                    );
+                }
+                counts
+            })
+            .reduce(
+                || (0, 0, 0, 0),
+                |a, b| (a.0 + b.0, a.1 + b.1, a.2 + b.2, a.3 + b.3),
+            ))
+    }
+}
+
+/// Eight years at a time in SIMD lanes, over all your cores, as
+/// repeat_simulate_demand_vectorized does
+///
+/// The zipf tables are built by configure(), from the simulation's own generator, so they
+/// change only when the simulation does. Only there with the simd feature, and only over all
+/// your cores with the parallel feature too; without it the lanes run on this thread.
+#[cfg(feature = "simd")]
+#[derive(Default)]
+pub struct Vectorized {
+    sim: Option<Simulation>,
+    tables: Option<simd::Tables>,
+}
+
+#[cfg(feature = "simd")]
+impl SimBackend for Vectorized {
+    fn name(&self) -> &'static str {
+        "simd"
+    }
+
+    fn configure(&mut self, sim: &Simulation) -> Result<(), String> {
+        sim.check_parameters()?;
+        if sim.policy.is_some() {
+            return Err("The simd backend only has the built-in ordering rule".to_string());
+        }
+        if sim.custom_demand() {
+            return Err("The simd backend only draws from plain zipfs".to_string());
+        }
+        if self.sim.as_ref() != Some(sim) {
+            self.tables = Some(simd::Tables::new(sim, &mut sim.rng()));
+            self.sim = Some(sim.clone());
+        }
+        Ok(())
+    }
+
+    fn run_batch(
+        &mut self,
+        starting_quantity: usize,
+        count: usize,
+        seed: u64,
+    ) -> Result<Counts, String> {
+        let sim = configured(&self.sim, self.name())?;
+        let tables = self.tables.as_ref().unwrap();
+        let batches: Vec<[u32; simd::LANES]> = (0..count.div_ceil(simd::LANES))
+            .map(|batch| simd::seeds(seed, batch as u64))
+            .collect();
+        #[cfg(feature = "parallel")]
+        let batches = batches.par_iter();
+        #[cfg(not(feature = "parallel"))]
+        let batches = batches.iter();
+        let years: Vec<simd::Counters> = batches
+            .map(|&seeds| simd::simulate_lanes(sim, starting_quantity, tables, seeds))
+            .collect();
+        // The last group of lanes may have more years than we asked for
+        let mut counts = (0, 0, 0, 0);
+        for &(xst, xss, xft, xfs) in years.iter().flatten().take(count) {
+            counts.0 += xst as usize;
+            counts.1 += xss as usize;
+            counts.2 += xft as usize;
+            counts.3 += xfs as usize;
+        }
+        Ok(counts)
+    }
+}
+
+#[test]
+fn test_scalar() {
+    // repeat_summary is the scalar backend, and a batch only counts once it's configured
+    let sim = Simulation::new(10, 3, 7, None, None, Some(3));
+    let mut scalar = Scalar::default();
+    assert!(scalar
+        .run_batch(10, 100, 1)
+        .unwrap_err()
+        .contains("configured"));
+    let summary = repeat(&mut scalar, &sim, 10, 500, 500, &mut sim.rng()).unwrap();
+    assert_eq!(summary, sim.repeat_summary(10, 500));
+    // Batches are seeded one after another from the generator, and added up by reduce
+    let mut rng = sim.rng();
+    let batches = [
+        scalar.run_batch(10, 200, rng.gen()).unwrap(),
+        scalar.run_batch(10, 200, rng.gen()).unwrap(),
+        scalar.run_batch(10, 100, rng.gen()).unwrap(),
+    ];
+    assert_eq!(
+        repeat(&mut scalar, &sim, 10, 500, 200, &mut sim.rng()).unwrap(),
+        scalar.reduce(&batches)
+    );
+    assert_eq!(scalar.reduce(&[]), summarize(0, 0, 0, 0));
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn test_parallel() {
+    // A batch comes out the same however many threads run it, and bad parameters are refused
+    let sim = Simulation::new(10, 3, 7, None, None, Some(3));
+    let mut parallel = Parallel::default();
+    parallel.configure(&sim).unwrap();
+    let everywhere = parallel.run_batch(10, 3000, 5).unwrap();
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .unwrap();
+    assert_eq!(
+        pool.install(|| parallel.run_batch(10, 3000, 5)).unwrap(),
+        everywhere
+    );
+    assert!(everywhere.0 + everywhere.2 > 0);
+    assert!(parallel
+        .configure(&Simulation::new(10, 0, 7, None, None, None))
+        .unwrap_err()
+        .contains("lead_time"));
+}
+
+#[cfg(feature = "simd")]
+#[test]
+fn test_vectorized() {
+    // Lanes cover exactly the years asked for, the same ones for the same seed, and only
+    // for the built-in rule
+    let sim = Simulation::new(10, 3, 7, None, None, Some(3));
+    let mut lanes = Vectorized::default();
+    lanes.configure(&sim).unwrap();
+    let eleven = lanes.run_batch(10, 11, 5).unwrap();
+    assert_eq!(lanes.run_batch(10, 11, 5).unwrap(), eleven);
+    let sixteen = lanes.run_batch(10, 16, 5).unwrap();
+    assert!(eleven.0 + eleven.2 > 0 && eleven.0 <= sixteen.0 && eleven.2 <= sixteen.2);
+    let mut with_policy = sim.clone();
+    with_policy.policy = Some(crate::policy::Policy::parse("order_quantity").unwrap());
+    assert!(lanes
+        .configure(&with_policy)
+        .unwrap_err()
+        .contains("built-in"));
+}
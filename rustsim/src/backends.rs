@@ -0,0 +1,321 @@
+//! Comparing this crate with rustoclsim on the machine at hand, and picking between them
+//!
+//! The GPU has to upload its zipf tables and launch a kernel every call, so it loses to the
+//! CPU on small counts however fast it is on big ones. Where it starts winning depends on the
+//! machine, so we measure it, once, and keep the answer on disk for backend="auto".
+use crate::timings::{Breakdown, Phase};
+use crate::{Simulation, Summary};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The counts the first-use probe times, small enough to take a second or so on either side
+const PROBE_COUNTS: [usize; 3] = [1_000, 10_000, 100_000];
+
+/// How many GPU copies of simulations to keep around, most recently used last
+const KEPT_SIMULATIONS: usize = 16;
+
+/// How many different sets of zipf tables those copies may hold between them. Each set is
+/// two tables of 16M entries, 128MB, so this keeps the cache to half a gigabyte; copies that
+/// only differ in their policy share one set.
+const KEPT_TABLES: usize = 4;
+
+/// The default device's name, or None if there's no usable one; unset until we ask
+static DEVICE: Mutex<Option<Option<String>>> = Mutex::new(None);
+
+/// The crossover on this machine, as in crossover(); unset until it's loaded or probed
+static CROSSOVER: Mutex<Option<Option<usize>>> = Mutex::new(None);
+
+/// rustoclsim copies of recently used simulations, so their tables are only built once
+static SIMULATIONS: Mutex<Vec<(Simulation, PyObject)>> = Mutex::new(Vec::new());
+
+/// The name of rustoclsim's default device, or None if rustoclsim isn't installed or can't
+/// run on it
+///
+/// rustoclsim aborts the process rather than raising when it has no device, so we ask its
+/// doctor() first, which checks without crashing. A rustoclsim built without OpenCL has no
+/// doctor(), and says so in has_gpu_support(). The answer holds for the whole process.
+pub fn device(py: Python<'_>) -> PyResult<Option<String>> {
+    if let Some(device) = DEVICE.lock().unwrap().clone() {
+        return Ok(device);
+    }
+    let device = match py.import("rustoclsim") {
+        Ok(module) if !module.call0("has_gpu_support")?.is_true()? => None,
+        Ok(module) => {
+            let report: Vec<&PyDict> = module.call0("doctor")?.extract()?;
+            match report.first() {
+                Some(entry) if entry.get_item("ok").map_or(Ok(false), |ok| ok.is_true())? => Some(
+                    entry
+                        .get_item("device")
+                        .map_or(Ok(String::new()), |name| name.extract())?,
+                ),
+                _ => None,
+            }
+        }
+        Err(_) => None,
+    };
+    *DEVICE.lock().unwrap() = Some(device.clone());
+    Ok(device)
+}
+
+/// What rustoclsim draws a simulation's zipf tables from: the exponents and the seed
+fn table_key(sim: &Simulation) -> (u64, u64, Option<u64>) {
+    (
+        sim.job_lot_zipf.to_bits(),
+        sim.itemwise_traffic_zipf.to_bits(),
+        sim.seed,
+    )
+}
+
+/// rustoclsim's copy of `sim`, or None if there's no GPU to run it on
+///
+/// Copies are kept, so calling this again with the same simulation doesn't rebuild its
+/// tables, and a new copy is made with with_params() from a kept one with the same tables
+/// when there is one, so it shares them. The oldest copies go once there are more than
+/// KEPT_SIMULATIONS, or more than KEPT_TABLES sets of tables among them. The lock is never
+/// held while Python runs, since that may let another thread in.
+pub fn gpu_simulation(py: Python<'_>, sim: &Simulation) -> PyResult<Option<PyObject>> {
+    // rustoclsim's tables are plain zipfs, and its policies see the true forecast
+    if sim.custom_demand() || sim.noisy_forecast() || device(py)?.is_none() {
+        return Ok(None);
+    }
+    let same_tables = {
+        let mut kept = SIMULATIONS.lock().unwrap();
+        if let Some(at) = kept.iter().position(|(kept_sim, _)| kept_sim == sim) {
+            let entry = kept.remove(at);
+            let gpu_sim = entry.1.clone_ref(py);
+            kept.push(entry);
+            return Ok(Some(gpu_sim));
+        }
+        kept.iter()
+            .rev()
+            .find(|(kept_sim, _)| table_key(kept_sim) == table_key(sim))
+            .map(|(_, gpu_sim)| gpu_sim.clone_ref(py))
+    };
+    let params = PyDict::new(py);
+    params.set_item("safety_stock", sim.safety_stock)?;
+    params.set_item("lead_time", sim.lead_time)?;
+    params.set_item("order_quantity", sim.order_quantity)?;
+    params.set_item("policy", sim.policy.as_ref().map(|policy| policy.source()))?;
+    let gpu_sim: PyObject = match same_tables {
+        Some(same_tables) => same_tables.call_method(py, "with_params", (), Some(params))?,
+        None => {
+            params.set_item("job_lot_zipf", sim.job_lot_zipf)?;
+            params.set_item("itemwise_traffic_zipf", sim.itemwise_traffic_zipf)?;
+            params.set_item("seed", sim.seed)?;
+            py.import("rustoclsim")?
+                .getattr("Simulation")?
+                .call((), Some(params))?
+                .into()
+        }
+    };
+    let mut kept = SIMULATIONS.lock().unwrap();
+    kept.push((sim.clone(), gpu_sim.clone_ref(py)));
+    loop {
+        let mut tables: Vec<_> = kept
+            .iter()
+            .map(|(kept_sim, _)| table_key(kept_sim))
+            .collect();
+        tables.sort();
+        tables.dedup();
+        if kept.len() <= KEPT_SIMULATIONS && tables.len() <= KEPT_TABLES {
+            break;
+        }
+        kept.remove(0);
+    }
+    Ok(Some(gpu_sim))
+}
+
+/// Run repeat_simulate_demand on the GPU copy, with its breakdown of where the time went
+pub fn repeat_on_gpu(
+    py: Python<'_>,
+    gpu_sim: &PyObject,
+    starting_quantity: usize,
+    count: usize,
+) -> PyResult<(Summary, Option<Breakdown>)> {
+    let summary = gpu_sim
+        .call_method1(py, "repeat_simulate_demand", (starting_quantity, count))?
+        .extract(py)?;
+    let timings = gpu_sim.call_method0(py, "last_timings")?;
+    let timings: Option<HashMap<String, f64>> = timings.extract(py)?;
+    let breakdown = timings.map(|timings| {
+        let mut breakdown = Breakdown::default();
+        for &(name, phase) in &Phase::ALL {
+            let seconds = timings.get(name).copied().unwrap_or(0.0);
+            breakdown.add(phase, Duration::from_secs_f64(seconds.max(0.0)));
+        }
+        breakdown
+    });
+    Ok((summary, breakdown))
+}
+
+/// Seconds the CPU takes for repeat_summary at each of `counts`
+pub fn time_cpu(
+    py: Python<'_>,
+    sim: &Simulation,
+    starting_quantity: usize,
+    counts: &[usize],
+) -> Vec<f64> {
+    py.allow_threads(|| {
+        counts
+            .iter()
+            .map(|&count| {
+                let started = Instant::now();
+                sim.repeat_summary(starting_quantity, count);
+                started.elapsed().as_secs_f64()
+            })
+            .collect()
+    })
+}
+
+/// Seconds the GPU copy takes for repeat_simulate_demand at each of `counts`
+///
+/// The first call compiles the kernel, which only ever happens once, so it isn't counted.
+pub fn time_gpu(
+    py: Python<'_>,
+    gpu_sim: &PyObject,
+    starting_quantity: usize,
+    counts: &[usize],
+) -> PyResult<Vec<f64>> {
+    gpu_sim.call_method1(py, "repeat_simulate_demand", (starting_quantity, counts[0]))?;
+    let mut seconds = vec![];
+    for &count in counts {
+        let started = Instant::now();
+        gpu_sim.call_method1(py, "repeat_simulate_demand", (starting_quantity, count))?;
+        seconds.push(started.elapsed().as_secs_f64());
+    }
+    Ok(seconds)
+}
+
+/// Whether backend="auto" should run `count` repetitions of `sim` on the GPU
+///
+/// That's when there is one, and `count` is past the crossover. The crossover comes from
+/// the last benchmark() on this device, or failing that a short probe the first time it's
+/// needed, which is saved the same way.
+pub fn prefer_gpu(py: Python<'_>, count: usize) -> PyResult<bool> {
+    let cached = *CROSSOVER.lock().unwrap();
+    let crossover = match cached {
+        Some(crossover) => crossover,
+        None => {
+            let device = match device(py)? {
+                Some(device) => device,
+                None => return Ok(false),
+            };
+            match load().remove(&device) {
+                Some(crossover) => {
+                    *CROSSOVER.lock().unwrap() = Some(crossover);
+                    crossover
+                }
+                None => probe(py, &device)?,
+            }
+        }
+    };
+    Ok(crossover.is_some_and(|crossover| count >= crossover))
+}
+
+/// Time both backends on the default simulation, and remember where they cross
+fn probe(py: Python<'_>, device: &str) -> PyResult<Option<usize>> {
+    let sim = Simulation::new(10, 3, 7, None, None, None);
+    let gpu_sim = match gpu_simulation(py, &sim)? {
+        Some(gpu_sim) => gpu_sim,
+        None => return Ok(None),
+    };
+    let gpu_seconds = time_gpu(py, &gpu_sim, sim.safety_stock, &PROBE_COUNTS)?;
+    let cpu_seconds = time_cpu(py, &sim, sim.safety_stock, &PROBE_COUNTS);
+    let crossover = crossover(&PROBE_COUNTS, &cpu_seconds, &gpu_seconds);
+    remember(device, crossover);
+    Ok(crossover)
+}
+
+/// Remember the crossover for this device, here and on disk
+///
+/// Failing to write the file isn't worth an error; we'll probe again next time.
+pub fn remember(device: &str, crossover: Option<usize>) {
+    *CROSSOVER.lock().unwrap() = Some(crossover);
+    let mut saved = load();
+    saved.insert(device.to_string(), crossover);
+    if let Some(path) = cache_path() {
+        let lines: String = saved
+            .iter()
+            .map(|(device, crossover)| match crossover {
+                Some(crossover) => format!("{}\t{}\n", device, crossover),
+                None => format!("{}\tnever\n", device),
+            })
+            .collect();
+        let _ = match path.parent() {
+            Some(dir) => std::fs::create_dir_all(dir),
+            None => Ok(()),
+        }
+        .and_then(|_| std::fs::write(&path, lines));
+    }
+}
+
+/// The saved crossovers, one device per line. Anything we can't make sense of is skipped.
+fn load() -> HashMap<String, Option<usize>> {
+    let text = cache_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .unwrap_or_default();
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let device = fields.next()?;
+            let crossover = match fields.next()? {
+                "never" => None,
+                count => Some(count.parse().ok()?),
+            };
+            Some((device.to_string(), crossover))
+        })
+        .collect()
+}
+
+/// Where the crossovers are kept: $RUSTSIM_CACHE_DIR, or the usual per-user cache
+fn cache_path() -> Option<PathBuf> {
+    let dir = std::env::var_os("RUSTSIM_CACHE_DIR")
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("XDG_CACHE_HOME").map(|dir| PathBuf::from(dir).join("rustsim"))
+        })
+        .or_else(|| {
+            std::env::var_os("HOME").map(|dir| PathBuf::from(dir).join(".cache").join("rustsim"))
+        })?;
+    Some(dir.join("crossover.tsv"))
+}
+
+/// Where the GPU starts beating the CPU, from how long each took at each count
+///
+/// Each backend's time is fit with a straight line, seconds = overhead + count / throughput,
+/// and the crossover is where the lines meet. None means the GPU never catches up.
+pub fn crossover(counts: &[usize], cpu_seconds: &[f64], gpu_seconds: &[f64]) -> Option<usize> {
+    let xs: Vec<f64> = counts.iter().map(|&count| count as f64).collect();
+    let (cpu_overhead, cpu_slope) = fit_line(&xs, cpu_seconds);
+    let (gpu_overhead, gpu_slope) = fit_line(&xs, gpu_seconds);
+    if gpu_slope >= cpu_slope {
+        return None;
+    }
+    Some(
+        ((gpu_overhead - cpu_overhead) / (cpu_slope - gpu_slope))
+            .max(0.0)
+            .ceil() as usize,
+    )
+}
+
+/// Least squares (intercept, slope) of `ys` against `xs`
+fn fit_line(xs: &[f64], ys: &[f64]) -> (f64, f64) {
+    let n = xs.len() as f64;
+    let (mean_x, mean_y) = (xs.iter().sum::<f64>() / n, ys.iter().sum::<f64>() / n);
+    let covariance: f64 = xs
+        .iter()
+        .zip(ys)
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let variance: f64 = xs.iter().map(|x| (x - mean_x).powi(2)).sum();
+    let slope = if variance > 0.0 {
+        covariance / variance
+    } else {
+        0.0
+    };
+    (mean_y - slope * mean_x, slope)
+}
@@ -0,0 +1,134 @@
+use crate::normal;
+use crate::{fill_rate, Summary};
+use rand::Rng;
+
+/// Which number to compute from a set of repetitions
+#[derive(Clone, Copy)]
+pub enum Metric {
+    SuccessfulTransactions,
+    SuccessfulSales,
+    FailedTransactions,
+    FailedSales,
+    TransactionFillRate,
+    UnitFillRate,
+}
+
+impl Metric {
+    /// Every metric, by name
+    pub const ALL: [(&'static str, Metric); 6] = [
+        ("successful_transactions", Metric::SuccessfulTransactions),
+        ("successful_sales", Metric::SuccessfulSales),
+        ("failed_transactions", Metric::FailedTransactions),
+        ("failed_sales", Metric::FailedSales),
+        ("transaction_fill_rate", Metric::TransactionFillRate),
+        ("unit_fill_rate", Metric::UnitFillRate),
+    ];
+
+    pub fn parse(name: &str) -> Option<Metric> {
+        Metric::ALL
+            .iter()
+            .find(|&&(known, _)| known == name)
+            .map(|&(_, metric)| metric)
+    }
+
+    /// Compute the metric over the repetitions picked by `indices`
+    ///
+    /// Counters are averaged per repetition. Rates are pooled the same way
+    /// repeat_simulate_demand does it: total successes over total attempts.
+    fn compute<I: Iterator<Item = usize>>(self, years: &[Summary], indices: I) -> f64 {
+        let (mut st, mut ss, mut ft, mut fs, mut n) = (0, 0, 0, 0, 0);
+        for i in indices {
+            let (xst, xss, xft, xfs, _, _) = years[i];
+            st += xst;
+            ss += xss;
+            ft += xft;
+            fs += xfs;
+            n += 1;
+        }
+        let n = n as f64;
+        match self {
+            Metric::SuccessfulTransactions => st as f64 / n,
+            Metric::SuccessfulSales => ss as f64 / n,
+            Metric::FailedTransactions => ft as f64 / n,
+            Metric::FailedSales => fs as f64 / n,
+            Metric::TransactionFillRate => fill_rate(st, ft),
+            Metric::UnitFillRate => fill_rate(ss, fs),
+        }
+    }
+
+    /// Compute the metric over all the repetitions
+    pub fn estimate(self, years: &[Summary]) -> f64 {
+        self.compute(years, 0..years.len())
+    }
+}
+
+/// Bias-corrected percentile bootstrap confidence interval
+///
+/// Resamples the repetitions with replacement `n_boot` times, and then shifts the percentiles
+/// we read off according to how lopsided the resampled estimates are around the original one.
+/// Returns (estimate, low, high).
+pub fn bootstrap_ci<R: Rng>(
+    years: &[Summary],
+    metric: Metric,
+    n_boot: usize,
+    confidence: f64,
+    rng: &mut R,
+) -> (f64, f64, f64) {
+    let n = years.len();
+    let estimate = metric.compute(years, 0..n);
+    if n == 0 || n_boot == 0 {
+        return (estimate, f64::NAN, f64::NAN);
+    }
+    let mut resampled: Vec<f64> = (0..n_boot)
+        .map(|_| metric.compute(years, (0..n).map(|_| rng.gen_range(0, n))))
+        .collect();
+    resampled.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+    // How far off-center the original estimate is, in standard normal units
+    let below = resampled.iter().filter(|&&x| x < estimate).count();
+    let bias = normal::ppf(below as f64 / n_boot as f64);
+    let z = normal::ppf(0.5 + confidence / 2.0);
+    let percentile = |p: f64| {
+        let index = (p * (n_boot - 1) as f64).round() as usize;
+        resampled[index.min(n_boot - 1)]
+    };
+    (
+        estimate,
+        percentile(normal::cdf(2.0 * bias - z)),
+        percentile(normal::cdf(2.0 * bias + z)),
+    )
+}
+
+#[test]
+fn test_bootstrap_ci() {
+    // The interval around the mean of 500 draws from 0..=20 brackets both the estimate and the
+    // true mean of 10, and narrows as it gets less confident
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    let mut rng = StdRng::seed_from_u64(5);
+    let years: Vec<Summary> = (0..500)
+        .map(|_| (rng.gen_range(0, 21), 0, 0, 0, 0.0, 0.0))
+        .collect();
+    let (estimate, low, high) =
+        bootstrap_ci(&years, Metric::SuccessfulTransactions, 2000, 0.95, &mut rng);
+    assert_eq!(estimate, Metric::SuccessfulTransactions.estimate(&years));
+    assert!(
+        low < estimate && estimate < high,
+        "{} {} {}",
+        low,
+        estimate,
+        high
+    );
+    assert!(low < 10.0 && 10.0 < high, "{} {}", low, high);
+    assert!(high - low < 2.0, "{} {}", low, high);
+    let (_, narrow_low, narrow_high) =
+        bootstrap_ci(&years, Metric::SuccessfulTransactions, 2000, 0.5, &mut rng);
+    assert!(narrow_high - narrow_low < high - low);
+    let (_, low, high) = bootstrap_ci(&[], Metric::UnitFillRate, 2000, 0.95, &mut rng);
+    assert!(low.is_nan() && high.is_nan());
+    for &(name, metric) in Metric::ALL.iter() {
+        let parsed = Metric::parse(name).unwrap();
+        assert_eq!(parsed.estimate(&years), metric.estimate(&years), "{}", name);
+    }
+    assert!(Metric::parse("fill_rate").is_none());
+}
@@ -0,0 +1,53 @@
+//! Hooks that tell Python code about each run, so it can forward them to MLflow, Weights &
+//! Biases or whatever else keeps track of experiments
+//!
+//! A callback is any Python object with an on_run_start(config) method, an
+//! on_run_end(config, metrics) method, or both; whichever it lacks is skipped. `config` is a
+//! dict of everything needed to make the run again, as in Simulation.metadata() plus the
+//! method, starting quantity and count, and `metrics` is a dict of the run's totals. If a
+//! callback raises, so does the run.
+use crate::Summary;
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyDict};
+
+/// The callbacks for one run, and the config they're all told about
+pub struct Callbacks<'p> {
+    callbacks: Vec<&'p PyAny>,
+    config: &'p PyDict,
+}
+
+impl<'p> Callbacks<'p> {
+    /// Call every on_run_start, before the run begins
+    pub fn start(callbacks: Vec<&'p PyAny>, config: &'p PyDict) -> PyResult<Callbacks<'p>> {
+        for callback in &callbacks {
+            if callback.hasattr("on_run_start")? {
+                callback.call_method1("on_run_start", (config,))?;
+            }
+        }
+        Ok(Callbacks { callbacks, config })
+    }
+
+    /// Call every on_run_end with the totals, and any `extra` metrics, once the run is over
+    pub fn end(&self, py: Python<'p>, summary: Summary, extra: &[(&str, f64)]) -> PyResult<()> {
+        if self.callbacks.is_empty() {
+            return Ok(());
+        }
+        let (st, ss, ft, fs, tr, ur) = summary;
+        let metrics = PyDict::new(py);
+        metrics.set_item("successful_transactions", st)?;
+        metrics.set_item("successful_sales", ss)?;
+        metrics.set_item("failed_transactions", ft)?;
+        metrics.set_item("failed_sales", fs)?;
+        metrics.set_item("transaction_fill_rate", tr)?;
+        metrics.set_item("unit_fill_rate", ur)?;
+        for (name, value) in extra {
+            metrics.set_item(name, value)?;
+        }
+        for callback in &self.callbacks {
+            if callback.hasattr("on_run_end")? {
+                callback.call_method1("on_run_end", (self.config, metrics))?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+//! Compound Poisson demand, described the way a planner would: the units sold on an average
+//! day, and how much that varies from day to day
+//!
+//! Customers come as a Poisson process, so each day's count is Poisson, and each one buys a
+//! basket drawn from a geometric or a zipf. With a rate of λ customers a day and baskets X,
+//! a day's units have
+//!
+//! ```text
+//! mean = λ E[X]        variance = λ E[X²]
+//! ```
+//!
+//! so for mean daily units μ and coefficient of variation c, the basket has to have
+//! E[X²] / E[X] = μ c², which picks its shape, and then λ = μ / E[X]. The least variation
+//! there can be is with every basket a single unit, where c² = 1 / μ.
+use crate::importance::harmonic;
+use crate::mixture::{Demand, Truncation};
+use serde::{Deserialize, Serialize};
+
+/// What each customer's basket is drawn from
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Basket {
+    /// A geometric over 1, 2, 3 and so on, which fits any variation
+    Geometric,
+    /// A zipf over 1..=1000, like the simulation's own job lots, for a longer tail
+    Zipf,
+}
+
+impl Basket {
+    /// The basket called `name`, "geometric" or "zipf"
+    pub fn parse(name: &str) -> Result<Basket, String> {
+        match name {
+            "geometric" => Ok(Basket::Geometric),
+            "zipf" => Ok(Basket::Zipf),
+            _ => Err(format!(
+                "basket must be \"geometric\" or \"zipf\", not {:?}",
+                name
+            )),
+        }
+    }
+
+    /// The name parse() takes
+    pub fn name(self) -> &'static str {
+        match self {
+            Basket::Geometric => "geometric",
+            Basket::Zipf => "zipf",
+        }
+    }
+}
+
+/// Compound Poisson demand, and the customer rate and basket it comes to
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct CompoundPoisson {
+    pub mean_daily_units: f64,
+    pub cv: f64,
+    pub basket: Basket,
+    /// Customers a day, on average
+    #[serde(skip)]
+    customers: f64,
+    /// The geometric's mean or the zipf's exponent
+    #[serde(skip)]
+    basket_parameter: f64,
+}
+
+impl CompoundPoisson {
+    /// Demand averaging `mean_daily_units` a day with coefficient of variation `cv`, or why
+    /// there's no such demand with this basket
+    pub fn new(mean_daily_units: f64, cv: f64, basket: Basket) -> Result<CompoundPoisson, String> {
+        // Written this way around so NaN fails too
+        if !(mean_daily_units > 0.0 && mean_daily_units.is_finite()) {
+            return Err(format!(
+                "mean_daily_units must be > 0, not {}",
+                mean_daily_units
+            ));
+        }
+        let least = 1.0 / mean_daily_units.sqrt();
+        if !(cv >= least && cv.is_finite()) {
+            return Err(format!(
+                "cv must be at least {} (1 / sqrt(mean_daily_units), one unit per customer), \
+                 not {}",
+                least, cv
+            ));
+        }
+        // E[X²] / E[X] for the basket
+        let ratio = mean_daily_units * cv * cv;
+        let (basket_mean, basket_parameter) = match basket {
+            Basket::Geometric => {
+                // E[X²] = 2m² - m for a geometric with mean m
+                let mean = (ratio + 1.0) / 2.0;
+                (mean, mean)
+            }
+            Basket::Zipf => {
+                let exponent = zipf_exponent(ratio)?;
+                (zipf_moments(exponent).0, exponent)
+            }
+        };
+        Ok(CompoundPoisson {
+            mean_daily_units,
+            cv,
+            basket,
+            customers: mean_daily_units / basket_mean,
+            basket_parameter,
+        })
+    }
+
+    /// Where each day's customer count comes from
+    pub fn customers(&self) -> Demand {
+        Demand::poisson(self.customers)
+    }
+
+    /// Where each customer's basket comes from, capped by `max_request` if it's set
+    pub fn basket(&self, max_request: Option<(usize, Truncation)>) -> Demand {
+        match self.basket {
+            Basket::Geometric => Demand::geometric(self.basket_parameter, max_request),
+            Basket::Zipf => Demand::new(self.basket_parameter, None, max_request),
+        }
+    }
+}
+
+/// E[X] and E[X²] / E[X] for a zipf over 1..=1000
+fn zipf_moments(exponent: f64) -> (f64, f64) {
+    let (total, first, second) = (
+        harmonic(1000, exponent),
+        harmonic(1000, exponent - 1.0),
+        harmonic(1000, exponent - 2.0),
+    );
+    (first / total, second / first)
+}
+
+/// The zipf exponent whose E[X²] / E[X] is `ratio`, by bisection
+///
+/// The ratio falls from about 667 towards 1 as the exponent grows, so anything outside that
+/// needs a geometric basket.
+fn zipf_exponent(ratio: f64) -> Result<f64, String> {
+    let (mut low, mut high) = (0.01, 50.0);
+    if !(zipf_moments(high).1 <= ratio && ratio <= zipf_moments(low).1) {
+        return Err(format!(
+            "A zipf basket can't vary that much or that little (mean_daily_units * cv² = {}); \
+             try basket=\"geometric\"",
+            ratio
+        ));
+    }
+    for _ in 0..100 {
+        let middle = (low + high) / 2.0;
+        if zipf_moments(middle).1 > ratio {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    Ok((low + high) / 2.0)
+}
+
+#[test]
+fn test_compound_poisson() {
+    // Simulated days come out with the mean and variation they were asked for
+    use rand::distributions::Distribution;
+    use rand::SeedableRng;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(5);
+    for &basket in &[Basket::Geometric, Basket::Zipf] {
+        let demand = CompoundPoisson::new(40.0, 0.5, basket).unwrap();
+        let (customers, baskets) = (demand.customers(), demand.basket(None));
+        assert!((customers.mean() * baskets.mean() - 40.0).abs() < 1e-6);
+    }
+    // The zipf basket is fitted to the textbook zipf, like zipf_mean() and the forecasts, and
+    // the zipf crate's sampler runs a little light of that, so only the geometric is exact
+    let demand = CompoundPoisson::new(40.0, 0.5, Basket::Geometric).unwrap();
+    let (customers, baskets) = (demand.customers(), demand.basket(None));
+    let days: Vec<f64> = (0..20_000)
+        .map(|_| {
+            (0..customers.sample(&mut rng))
+                .map(|_| baskets.sample(&mut rng))
+                .sum::<usize>() as f64
+        })
+        .collect();
+    let mean = days.iter().sum::<f64>() / days.len() as f64;
+    let variance = days.iter().map(|d| (d - mean).powi(2)).sum::<f64>() / days.len() as f64;
+    assert!((mean - 40.0).abs() < 1.0, "mean {}", mean);
+    assert!(
+        (variance.sqrt() / mean - 0.5).abs() < 0.05,
+        "cv {}",
+        variance.sqrt() / mean
+    );
+    assert!(CompoundPoisson::new(40.0, 0.1, Basket::Geometric).is_err());
+    assert!(CompoundPoisson::new(40.0, 100.0, Basket::Zipf).is_err());
+    assert!(CompoundPoisson::new(-1.0, 0.5, Basket::Geometric).is_err());
+}
@@ -0,0 +1,87 @@
+use crate::importance::harmonic;
+
+/// Mean of a zipf distribution over 1..=n
+pub fn zipf_mean(n: usize, exponent: f64) -> f64 {
+    harmonic(n, exponent - 1.0) / harmonic(n, exponent)
+}
+
+#[cfg(any(feature = "python", test))]
+/// Estimates the mean of Y using a second variable C whose true mean we know
+///
+/// If C came out above its mean in our repetitions, and Y tends to move with C, then Y
+/// probably came out high too. So we correct the mean of Y by beta * (mean of C - true mean),
+/// where beta = Cov(Y, C) / Var(C) is estimated from the same repetitions.
+/// Everything is accumulated online, so there's nothing to store per repetition.
+#[derive(Default)]
+pub struct ControlVariate {
+    count: f64,
+    mean_y: f64,
+    mean_c: f64,
+    /// Running sum of (c - mean_c)^2
+    m2_c: f64,
+    /// Running sum of (y - mean_y) * (c - mean_c)
+    co_moment: f64,
+}
+
+#[cfg(any(feature = "python", test))]
+impl ControlVariate {
+    pub fn new() -> ControlVariate {
+        ControlVariate::default()
+    }
+
+    pub fn push(&mut self, y: f64, c: f64) {
+        self.count += 1.0;
+        let delta_c = c - self.mean_c;
+        self.mean_c += delta_c / self.count;
+        self.mean_y += (y - self.mean_y) / self.count;
+        self.m2_c += delta_c * (c - self.mean_c);
+        self.co_moment += delta_c * (y - self.mean_y);
+    }
+
+    /// Corrected estimate of the mean of Y, given the true mean of C
+    pub fn estimate(&self, true_mean_c: f64) -> f64 {
+        if self.m2_c == 0.0 {
+            return self.mean_y;
+        }
+        let beta = self.co_moment / self.m2_c;
+        self.mean_y - beta * (self.mean_c - true_mean_c)
+    }
+}
+
+#[test]
+fn test_control_variate() {
+    // When Y follows C closely, correcting by C's known mean lands much nearer the true mean
+    // of Y than the plain average does, across many seeded trials
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    let mut rng = StdRng::seed_from_u64(17);
+    let (mut plain_error, mut corrected_error) = (0.0, 0.0);
+    for _ in 0..200 {
+        let mut control = ControlVariate::new();
+        let mut total = 0.0;
+        for _ in 0..100 {
+            let c: f64 = rng.gen_range(0.0, 10.0);
+            let y = 3.0 + 2.0 * c + rng.gen_range(-1.0, 1.0);
+            control.push(y, c);
+            total += y;
+        }
+        // C averages 5, so Y averages 13
+        plain_error += (total / 100.0 - 13.0).powi(2);
+        corrected_error += (control.estimate(5.0) - 13.0).powi(2);
+    }
+    assert!(
+        corrected_error * 50.0 < plain_error,
+        "{} {}",
+        corrected_error,
+        plain_error
+    );
+    // Without any spread in C there's nothing to correct by
+    let mut flat = ControlVariate::new();
+    [1.0, 2.0, 6.0].iter().for_each(|&y| flat.push(y, 4.0));
+    assert_eq!(flat.estimate(0.0), 3.0);
+    let direct: f64 = (1..=30)
+        .map(|k| k as f64 * (k as f64).powf(-1.5))
+        .sum::<f64>()
+        / harmonic(30, 1.5);
+    assert!((zipf_mean(30, 1.5) - direct).abs() < 1e-12);
+}
@@ -0,0 +1,115 @@
+//! Daily customer counts for several items at once, linked by a Gaussian copula
+//!
+//! Related items tend to get busy together: a heatwave sells fans and cold drinks on the same
+//! days. Simulated on their own, each item's bad days land at random, so the portfolio never
+//! has them all at once. A copula keeps each item's own traffic zipf but ties their draws
+//! together. Each day we draw standard normals z = L ε, where L Lᵀ is the correlation matrix,
+//! and each item's count is its zipf's quantile at Φ(z), so items with a high correlation are
+//! in the tails of their zipfs on the same days.
+use crate::linalg;
+use crate::normal;
+use crate::quasi::InverseZipf;
+#[allow(deprecated)]
+use rand::distributions::{Distribution, StandardNormal};
+use rand::Rng;
+
+/// Customer counts for several items, linked by a correlation matrix
+pub struct Copula {
+    /// The Cholesky factor of the correlation matrix
+    factor: Vec<Vec<f64>>,
+    /// Each item's traffic zipf over 1..=1000
+    traffic: Vec<InverseZipf>,
+}
+
+impl Copula {
+    /// Counts with each item's traffic zipf exponent in `exponents`, correlated by
+    /// `correlation`, or why that isn't a correlation matrix
+    pub fn new(correlation: &[Vec<f64>], exponents: &[f64]) -> Result<Copula, String> {
+        let n = exponents.len();
+        if correlation.len() != n || correlation.iter().any(|row| row.len() != n) {
+            return Err(format!(
+                "correlation must be {0} by {0}, one row and column per item",
+                n
+            ));
+        }
+        for (i, row) in correlation.iter().enumerate() {
+            if row[i] != 1.0 {
+                return Err("correlation must have 1s on the diagonal".to_string());
+            }
+            for (j, &rho) in row.iter().enumerate().take(i) {
+                // Written this way around so NaN fails too
+                if !((-1.0..=1.0).contains(&rho) && rho == correlation[j][i]) {
+                    return Err(format!(
+                        "correlation must be symmetric with entries between -1 and 1, but \
+                         [{}][{}] is {} and [{}][{}] is {}",
+                        i, j, rho, j, i, correlation[j][i]
+                    ));
+                }
+            }
+        }
+        let factor = linalg::cholesky(correlation).ok_or_else(|| {
+            "correlation isn't positive semidefinite, so no demand could have it; check that \
+             the correlations agree with each other"
+                .to_string()
+        })?;
+        Ok(Copula {
+            factor,
+            traffic: exponents
+                .iter()
+                .map(|&exponent| InverseZipf::new(1000, exponent))
+                .collect(),
+        })
+    }
+
+    /// One day's customer count for each item, into `counts`
+    #[allow(deprecated)]
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R, counts: &mut [usize]) {
+        let independent: Vec<f64> = (0..self.factor.len())
+            .map(|_| StandardNormal.sample(rng))
+            .collect();
+        for ((count, row), traffic) in counts.iter_mut().zip(&self.factor).zip(&self.traffic) {
+            let z: f64 = row.iter().zip(&independent).map(|(l, e)| l * e).sum();
+            *count = traffic.quantile(normal::cdf(z));
+        }
+    }
+}
+
+#[test]
+fn test_copula() {
+    // Perfectly correlated items with the same zipf get the same customers every day, and
+    // independent ones don't
+    use rand::SeedableRng;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(9);
+    let mut counts = [0; 3];
+    let together = Copula::new(
+        &[
+            vec![1.0, 1.0, 0.0],
+            vec![1.0, 1.0, 0.0],
+            vec![0.0, 0.0, 1.0],
+        ],
+        &[2.0, 2.0, 2.0],
+    )
+    .unwrap();
+    let mut apart = 0;
+    for _ in 0..1000 {
+        together.sample(&mut rng, &mut counts);
+        assert_eq!(counts[0], counts[1]);
+        apart += (counts[0] != counts[2]) as usize;
+    }
+    assert!(apart > 200, "{} days apart", apart);
+    for broken in &[
+        vec![vec![1.0, 0.5], vec![0.4, 1.0]],
+        vec![vec![2.0, 0.0], vec![0.0, 1.0]],
+        vec![vec![1.0, f64::NAN], vec![f64::NAN, 1.0]],
+        vec![vec![1.0, 0.0]],
+    ] {
+        assert!(Copula::new(broken, &[2.0, 2.0]).is_err());
+    }
+    // Each pair is fine, but they can't all hold at once
+    let inconsistent = [
+        vec![1.0, 0.9, 0.9],
+        vec![0.9, 1.0, -0.9],
+        vec![0.9, -0.9, 1.0],
+    ];
+    assert!(Copula::new(&inconsistent, &[2.0, 2.0, 2.0]).is_err());
+}
@@ -0,0 +1,78 @@
+//! Demand from Python, for trying out a model of customers before it's a built-in distribution
+//!
+//! The demand is either a callable or an iterable. A callable is asked for `days` days at a
+//! time, as demand(days), and an iterable hands back as many days as it likes with each
+//! next(). Either way, a batch of days is a list with a list of request sizes for each day,
+//! one per customer, so [[3, 1], [], [12]] is two customers on the first day, none on the
+//! second and one big one on the third. Calling into Python a year or more at a time, rather
+//! than for every customer, keeps the interpreter out of the simulation's way.
+use crate::stratified::DemandYear;
+use pyo3::exceptions::ValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyIterator};
+use std::collections::VecDeque;
+
+/// Where the days come from
+enum Source<'p> {
+    Callable(&'p PyAny),
+    Iterator(PyIterator<'p>),
+}
+
+/// Days of demand from Python, handed out a year at a time
+pub struct PythonDemand<'p> {
+    source: Source<'p>,
+    /// Days to ask a callable for at once
+    batch_days: usize,
+    /// Days we have but haven't simulated yet
+    days: VecDeque<Vec<usize>>,
+    /// Days handed out so far, for error messages
+    used: usize,
+}
+
+impl<'p> PythonDemand<'p> {
+    /// Demand from `demand`, a callable asked for `batch_days` days at a time or an iterable
+    /// of batches
+    pub fn new(demand: &'p PyAny, batch_days: usize) -> PyResult<PythonDemand<'p>> {
+        let source = if demand.is_callable() {
+            Source::Callable(demand)
+        } else {
+            Source::Iterator(demand.iter().map_err(|_| {
+                ValueError::py_err("demand must be a callable or an iterable of batches of days")
+            })?)
+        };
+        Ok(PythonDemand {
+            source,
+            batch_days,
+            days: VecDeque::new(),
+            used: 0,
+        })
+    }
+
+    /// The next 365 days, as a year the simulation can replay
+    pub fn year(&mut self) -> PyResult<DemandYear> {
+        while self.days.len() < 365 {
+            let batch = match &mut self.source {
+                Source::Callable(demand) => demand.call1((self.batch_days,))?,
+                Source::Iterator(batches) => match batches.next() {
+                    Some(batch) => batch?,
+                    None => {
+                        return Err(ValueError::py_err(format!(
+                            "demand ran out after {} days, partway through a year",
+                            self.used + self.days.len()
+                        )))
+                    }
+                },
+            };
+            let batch: Vec<Vec<usize>> = batch.extract()?;
+            if batch.is_empty() {
+                return Err(ValueError::py_err(format!(
+                    "demand gave no days after {} days",
+                    self.used + self.days.len()
+                )));
+            }
+            self.days.extend(batch);
+        }
+        self.used += 365;
+        Ok(DemandYear::from_days(self.days.drain(..365)))
+    }
+}
@@ -0,0 +1,250 @@
+//! A discrete-event core: everything that happens to the shelf is an event at a point in
+//! time, handled in time order
+//!
+//! The daily loop can only say what happens on a day, and in a fixed order: the truck, then
+//! the customers, then the order. Here a day is just the stretch of time between two
+//! reviews, so customers can come at any time of day, a truck can arrive in the afternoon,
+//! and lead times can vary from order to order, which lets a later order overtake an earlier
+//! one. Something new that happens to the shelf is a new kind of Event.
+//!
+//! The daily engine (Simulation::simulate_day_with) keeps its fixed loop, which is the same
+//! rules in the one order they can happen in a day, without a heap to go through. It keeps
+//! its truck ring rather than scheduling each order, too, since the GPU kernel and the SIMD
+//! lanes deliver from that ring, and the three have to keep giving the same years.
+use crate::{serve, summarize, Simulation, Summary};
+use rand::distributions::Distribution;
+use rand::Rng;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, VecDeque};
+
+/// Something that happens to the shelf
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    /// A truck arrives with this many units
+    Receipt(usize),
+    /// A customer asks for this many units
+    Demand(usize),
+    /// The day is over, and it's time to decide whether to order
+    Review,
+}
+
+/// An event waiting to happen
+///
+/// Events at the same time happen in the order they were scheduled.
+#[derive(Clone, Debug)]
+struct Pending {
+    time: f64,
+    sequence: u64,
+    event: Event,
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Pending) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Pending) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Pending) -> Ordering {
+        self.time
+            .total_cmp(&other.time)
+            .then(self.sequence.cmp(&other.sequence))
+    }
+}
+
+/// The events still to come, earliest first
+///
+/// Events scheduled in the order they happen queue up in a plain list and skip the heap.
+/// Anything scheduled earlier than the last of them goes in the heap.
+#[derive(Clone, Debug, Default)]
+pub struct Scheduler {
+    in_order: VecDeque<Pending>,
+    out_of_order: BinaryHeap<Reverse<Pending>>,
+    scheduled: u64,
+}
+
+impl Scheduler {
+    /// Have `event` happen at `time`, in days from the start of the year
+    pub fn schedule(&mut self, time: f64, event: Event) {
+        let pending = Pending {
+            time,
+            sequence: self.scheduled,
+            event,
+        };
+        self.scheduled += 1;
+        match self.in_order.back() {
+            Some(last) if last.time > time => self.out_of_order.push(Reverse(pending)),
+            _ => self.in_order.push_back(pending),
+        }
+    }
+
+    /// The next event and when it happens, if anything's left
+    pub fn next(&mut self) -> Option<(f64, Event)> {
+        let pending = match (self.in_order.front(), self.out_of_order.peek()) {
+            (Some(first), Some(Reverse(earliest))) if earliest < first => {
+                self.out_of_order.pop().map(|Reverse(pending)| pending)
+            }
+            (Some(_), _) => self.in_order.pop_front(),
+            (None, _) => self.out_of_order.pop().map(|Reverse(pending)| pending),
+        };
+        pending.map(|pending| (pending.time, pending.event))
+    }
+}
+
+/// Simulate one year entirely in events, and summarize it
+///
+/// Customers come at random times through each day, and each is served as they come. At the
+/// end of each day we order as the daily engine does, policy and all, but each order is its
+/// own truck, due
+/// a lead time drawn from `lead_times` after it's placed (in days, fractions and all). So
+/// trucks arrive once each, at any time of day, and not always in the order they were sent.
+pub fn simulate_year<R, LT, IT, JL>(
+    sim: &Simulation,
+    starting_quantity: usize,
+    lead_times: &LT,
+    it_dist: &IT,
+    jl_dist: &JL,
+    rng: &mut R,
+) -> Summary
+where
+    R: Rng,
+    LT: Distribution<f64>,
+    IT: Distribution<usize>,
+    JL: Distribution<usize>,
+{
+    let mut events = Scheduler::default();
+    let mut stock = starting_quantity;
+    // Units ordered that haven't arrived yet
+    let mut pipeline = 0;
+    let (mut st, mut ss, mut ft, mut fs) = (0, 0, 0, 0);
+    for day in 0..365 {
+        let start = day as f64;
+        for _customer in 0..it_dist.sample(rng) {
+            let time = start + rng.gen::<f64>();
+            events.schedule(time, Event::Demand(jl_dist.sample(rng)));
+        }
+        events.schedule(start + 1.0, Event::Review);
+        while let Some((time, event)) = events.next() {
+            match event {
+                Event::Receipt(units) => {
+                    stock += units;
+                    pipeline -= units;
+                }
+                Event::Demand(request) => {
+                    if serve(&mut stock, day, request, &mut |_, _, _| {}) {
+                        st += 1;
+                        ss += request;
+                    } else {
+                        ft += 1;
+                        fs += request;
+                    }
+                }
+                Event::Review => {
+                    let ordered = sim.order(stock, pipeline, day, rng);
+                    if ordered > 0 {
+                        let lead_time = lead_times.sample(rng).max(0.0);
+                        events.schedule(time + lead_time, Event::Receipt(ordered));
+                        pipeline += ordered;
+                    }
+                    break;
+                }
+            }
+        }
+    }
+    summarize(st, ss, ft, fs)
+}
+
+#[test]
+fn test_daily_loop() {
+    // The daily engine's fixed loop gives the same days as its rules played out on the
+    // Scheduler: the morning's truck, then the customers through the day, then the review
+    use crate::Shelf;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    let sim = Simulation::new(10, 3, 7, None, None, Some(5));
+    let (it_zipf, jl_zipf) = (sim.traffic(), sim.job_lots());
+    let (mut fast, mut slow) = (StdRng::seed_from_u64(5), StdRng::seed_from_u64(5));
+    let mut shelf = Shelf::new(20, sim.lead_time);
+    let (mut stock, mut trucks) = (20, vec![0; sim.lead_time]);
+    let mut events = Scheduler::default();
+    for day in 0..365 {
+        let expected =
+            sim.simulate_day_with(&mut shelf, &it_zipf, &jl_zipf, &mut fast, &mut |_, _, _| {});
+        let start = day as f64;
+        events.schedule(start, Event::Receipt(trucks[day % sim.lead_time]));
+        let customers = it_zipf.sample(&mut slow);
+        for customer in 0..customers {
+            let time = start + (customer + 1) as f64 / (customers + 1) as f64;
+            events.schedule(time, Event::Demand(jl_zipf.sample(&mut slow)));
+        }
+        events.schedule(start + 1.0, Event::Review);
+        let (mut st, mut ss, mut ft, mut fs) = (0, 0, 0, 0);
+        while let Some((_, event)) = events.next() {
+            match event {
+                Event::Receipt(units) => stock += units,
+                Event::Demand(request) => {
+                    if serve(&mut stock, day, request, &mut |_, _, _| {}) {
+                        st += 1;
+                        ss += request;
+                    } else {
+                        ft += 1;
+                        fs += request;
+                    }
+                }
+                Event::Review => {
+                    let ordered = sim.order(stock, trucks.iter().sum(), day, &mut slow);
+                    if ordered > 0 {
+                        trucks[(day + sim.lead_time - 1) % sim.lead_time] = ordered;
+                    }
+                    break;
+                }
+            }
+        }
+        let counts = (
+            expected.successful_transactions,
+            expected.successful_sales,
+            expected.failed_transactions,
+            expected.failed_sales,
+        );
+        assert_eq!((st, ss, ft, fs), counts, "day {}", day);
+        assert_eq!(stock, expected.closing_stock, "day {}", day);
+    }
+}
+
+#[test]
+fn test_simulate_year() {
+    // With more stock than a year could ask for, every customer is served however late the
+    // trucks come, and the same seed gives the same year
+    use rand::distributions::Uniform;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    let sim = Simulation::new(10, 3, 7, None, None, Some(5));
+    let (it_zipf, jl_zipf) = (sim.traffic(), sim.job_lots());
+    let lead_times = Uniform::new(1.0, 10.0);
+    let year = |starting_quantity, seed| {
+        let mut rng = StdRng::seed_from_u64(seed);
+        simulate_year(
+            &sim,
+            starting_quantity,
+            &lead_times,
+            &it_zipf,
+            &jl_zipf,
+            &mut rng,
+        )
+    };
+    let (st, ss, ft, fs, transaction_rate, unit_rate) = year(1 << 30, 6);
+    assert!(st > 0 && ss >= st);
+    assert_eq!((ft, fs), (0, 0));
+    assert_eq!((transaction_rate, unit_rate), (1.0, 1.0));
+    assert_eq!(year(10, 6), year(10, 6));
+    let (_, _, ft, _, _, unit_rate) = year(0, 7);
+    assert!(ft > 0 && unit_rate < 1.0);
+}
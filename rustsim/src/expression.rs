@@ -0,0 +1,459 @@
+//! The arithmetic shared by ordering policies and custom metrics
+//!
+//! Numbers, + - * /, comparisons (< <= > >= == !=, which are 1 or 0), parentheses, min(a, b),
+//! max(a, b), ceil(x), floor(x) and if(condition, then, else), which takes `then` unless
+//! `condition` is 0, over names that whoever parses it provides. Everything is single
+//! precision, so the CPU's interpreter and the OpenCL C from write_opencl() round alike, as
+//! long as the kernel is built without fma contraction and with correctly rounded division.
+use std::fmt::Write;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Op {
+    fn symbol(self) -> &'static str {
+        match self {
+            Op::Add => "+",
+            Op::Subtract => "-",
+            Op::Multiply => "*",
+            Op::Divide => "/",
+            Op::Less => "<",
+            Op::LessOrEqual => "<=",
+            Op::Greater => ">",
+            Op::GreaterOrEqual => ">=",
+            Op::Equal => "==",
+            Op::NotEqual => "!=",
+        }
+    }
+
+    fn is_comparison(self) -> bool {
+        !matches!(self, Op::Add | Op::Subtract | Op::Multiply | Op::Divide)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Function {
+    Min,
+    Max,
+    Ceil,
+    Floor,
+    If,
+}
+
+impl Function {
+    fn parse(name: &str) -> Option<Function> {
+        match name {
+            "min" => Some(Function::Min),
+            "max" => Some(Function::Max),
+            "ceil" => Some(Function::Ceil),
+            "floor" => Some(Function::Floor),
+            "if" => Some(Function::If),
+            _ => None,
+        }
+    }
+
+    fn arguments(self) -> usize {
+        match self {
+            Function::Ceil | Function::Floor => 1,
+            Function::Min | Function::Max => 2,
+            Function::If => 3,
+        }
+    }
+}
+
+/// An expression, with names as indices into the list it was parsed with
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Number(f32),
+    Input(usize),
+    Negate(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+}
+
+impl Expr {
+    /// What the expression comes to, with each name standing for its entry in `values`
+    pub fn evaluate(&self, values: &[f32]) -> f32 {
+        match self {
+            Expr::Number(x) => *x,
+            Expr::Input(input) => values[*input],
+            Expr::Negate(x) => -x.evaluate(values),
+            Expr::Binary(op, a, b) => {
+                let (a, b) = (a.evaluate(values), b.evaluate(values));
+                let truth = |holds: bool| if holds { 1.0 } else { 0.0 };
+                match op {
+                    Op::Add => a + b,
+                    Op::Subtract => a - b,
+                    Op::Multiply => a * b,
+                    Op::Divide => a / b,
+                    Op::Less => truth(a < b),
+                    Op::LessOrEqual => truth(a <= b),
+                    Op::Greater => truth(a > b),
+                    Op::GreaterOrEqual => truth(a >= b),
+                    Op::Equal => truth(a == b),
+                    Op::NotEqual => truth(a != b),
+                }
+            }
+            Expr::Call(function, args) => match function {
+                Function::Min => args[0].evaluate(values).min(args[1].evaluate(values)),
+                Function::Max => args[0].evaluate(values).max(args[1].evaluate(values)),
+                Function::Ceil => args[0].evaluate(values).ceil(),
+                Function::Floor => args[0].evaluate(values).floor(),
+                Function::If if args[0].evaluate(values) != 0.0 => args[1].evaluate(values),
+                Function::If => args[2].evaluate(values),
+            },
+        }
+    }
+
+    /// OpenCL C for this expression, fully parenthesized, reading each name from a float
+    /// variable called `prefix` followed by the name
+    pub fn write_opencl(&self, out: &mut String, prefix: &str, names: &[&str]) {
+        match self {
+            // Debug prints the shortest decimal that reads back as the same float, and
+            // parse() only makes finite ones
+            Expr::Number(x) => {
+                debug_assert!(x.is_finite());
+                write!(out, "{:?}f", x).unwrap()
+            }
+            Expr::Input(input) => write!(out, "{}{}", prefix, names[*input]).unwrap(),
+            Expr::Negate(x) => {
+                out.push_str("(-");
+                x.write_opencl(out, prefix, names);
+                out.push(')');
+            }
+            Expr::Binary(op, a, b) => {
+                // Comparisons are ints in OpenCL C
+                out.push_str(if op.is_comparison() { "(float)(" } else { "(" });
+                a.write_opencl(out, prefix, names);
+                write!(out, " {} ", op.symbol()).unwrap();
+                b.write_opencl(out, prefix, names);
+                out.push(')');
+            }
+            Expr::Call(Function::If, args) => {
+                out.push('(');
+                args[0].write_opencl(out, prefix, names);
+                out.push_str(" != 0.0f ? ");
+                args[1].write_opencl(out, prefix, names);
+                out.push_str(" : ");
+                args[2].write_opencl(out, prefix, names);
+                out.push(')');
+            }
+            Expr::Call(function, args) => {
+                out.push_str(match function {
+                    Function::Min => "fmin(",
+                    Function::Max => "fmax(",
+                    Function::Ceil => "ceil(",
+                    Function::Floor => "floor(",
+                    Function::If => unreachable!(),
+                });
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    arg.write_opencl(out, prefix, names);
+                }
+                out.push(')');
+            }
+        }
+    }
+}
+
+/// The expression in `source`, which can use `names`, or what's wrong with it and where
+pub fn parse(source: &str, names: &[&str]) -> Result<Expr, String> {
+    let tokens = tokenize(source)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        at: 0,
+        names,
+    };
+    let expr = parser.comparison()?;
+    parser.end()?;
+    Ok(expr)
+}
+
+/// The aggregate and the expression in `source`, when it's written as one of `aggregates`
+/// around an expression, like "max(stock)"
+///
+/// Returns None if it isn't, leaving the caller to decide what else it might be.
+pub fn parse_call(
+    source: &str,
+    aggregates: &[&str],
+    names: &[&str],
+) -> Result<Option<(usize, Expr)>, String> {
+    let tokens = tokenize(source)?;
+    let aggregate = match tokens.as_slice() {
+        [(_, Token::Name(name)), (_, Token::Open), ..] => {
+            match aggregates.iter().position(|known| known == name) {
+                Some(aggregate) => aggregate,
+                None => return Ok(None),
+            }
+        }
+        _ => return Ok(None),
+    };
+    let mut parser = Parser {
+        tokens: &tokens,
+        at: 2,
+        names,
+    };
+    let expr = parser.comparison()?;
+    parser.expect(Token::Close)?;
+    parser.end()?;
+    Ok(Some((aggregate, expr)))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f32),
+    Name(String),
+    Op(Op),
+    Open,
+    Close,
+    Comma,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Number(x) => format!("the number {}", x),
+            Token::Name(name) => format!("{:?}", name),
+            Token::Op(op) => format!("{:?}", op.symbol()),
+            Token::Open => "\"(\"".to_string(),
+            Token::Close => "\")\"".to_string(),
+            Token::Comma => "\",\"".to_string(),
+        }
+    }
+}
+
+/// The tokens in `source`, each with the position it starts at
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = vec![];
+    let mut at = 0;
+    while at < chars.len() {
+        let c = chars[at];
+        let start = at;
+        if c.is_whitespace() {
+            at += 1;
+            continue;
+        }
+        let token = if c.is_ascii_digit() || c == '.' {
+            while at < chars.len()
+                && (chars[at].is_ascii_digit()
+                    || chars[at] == '.'
+                    || chars[at] == 'e'
+                    || chars[at] == 'E'
+                    || ((chars[at] == '-' || chars[at] == '+')
+                        && matches!(chars[at - 1], 'e' | 'E')))
+            {
+                at += 1;
+            }
+            let text: String = chars[start..at].iter().collect();
+            let x: f32 = text
+                .parse()
+                .map_err(|_| format!("{:?} at position {} isn't a number", text, start))?;
+            // Past f32's range it would be inf, which OpenCL C has no literal for, and below
+            // its normal range a device may flush it to 0 where the CPU doesn't
+            if !(x.is_normal() || (x == 0.0 && text.parse::<f64>() == Ok(0.0))) {
+                return Err(format!(
+                    "{} at position {} is out of range for single precision",
+                    text, start
+                ));
+            }
+            Token::Number(x)
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            while at < chars.len() && (chars[at].is_ascii_alphanumeric() || chars[at] == '_') {
+                at += 1;
+            }
+            Token::Name(chars[start..at].iter().collect())
+        } else {
+            let next = chars.get(at + 1).copied();
+            let (token, len) = match (c, next) {
+                ('<', Some('=')) => (Token::Op(Op::LessOrEqual), 2),
+                ('>', Some('=')) => (Token::Op(Op::GreaterOrEqual), 2),
+                ('=', Some('=')) => (Token::Op(Op::Equal), 2),
+                ('!', Some('=')) => (Token::Op(Op::NotEqual), 2),
+                ('<', _) => (Token::Op(Op::Less), 1),
+                ('>', _) => (Token::Op(Op::Greater), 1),
+                ('+', _) => (Token::Op(Op::Add), 1),
+                ('-', _) => (Token::Op(Op::Subtract), 1),
+                ('*', _) => (Token::Op(Op::Multiply), 1),
+                ('/', _) => (Token::Op(Op::Divide), 1),
+                ('(', _) => (Token::Open, 1),
+                (')', _) => (Token::Close, 1),
+                (',', _) => (Token::Comma, 1),
+                _ => return Err(format!("Unexpected {:?} at position {}", c, start)),
+            };
+            at += len;
+            token
+        };
+        tokens.push((start, token));
+    }
+    Ok(tokens)
+}
+
+/// A recursive descent parser, one method per level of precedence
+struct Parser<'t> {
+    tokens: &'t [(usize, Token)],
+    at: usize,
+    names: &'t [&'t str],
+}
+
+impl<'t> Parser<'t> {
+    fn peek(&self) -> Option<&'t (usize, Token)> {
+        self.tokens.get(self.at)
+    }
+
+    /// The position of the next token, or the end, for errors
+    fn position(&self) -> String {
+        match self.peek() {
+            Some((at, token)) => format!("{} at position {}", token.describe(), at),
+            None => "the end".to_string(),
+        }
+    }
+
+    /// Nothing, since we've parsed everything
+    fn end(&self) -> Result<(), String> {
+        match self.peek() {
+            None => Ok(()),
+            Some((at, token)) => Err(format!(
+                "Expected the end at position {}, not {}",
+                at,
+                token.describe()
+            )),
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.peek() {
+            Some((_, token)) if *token == expected => {
+                self.at += 1;
+                Ok(())
+            }
+            _ => Err(format!(
+                "Expected {}, not {}",
+                expected.describe(),
+                self.position()
+            )),
+        }
+    }
+
+    /// The next token if it's one of `ops`
+    fn op(&mut self, ops: &[Op]) -> Option<Op> {
+        match self.peek() {
+            Some((_, Token::Op(op))) if ops.contains(op) => {
+                self.at += 1;
+                Some(*op)
+            }
+            _ => None,
+        }
+    }
+
+    /// Comparisons don't chain, so a < b < c is an error rather than a surprise
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let a = self.sum()?;
+        let comparisons = [
+            Op::Less,
+            Op::LessOrEqual,
+            Op::Greater,
+            Op::GreaterOrEqual,
+            Op::Equal,
+            Op::NotEqual,
+        ];
+        match self.op(&comparisons) {
+            Some(op) => Ok(Expr::Binary(op, Box::new(a), Box::new(self.sum()?))),
+            None => Ok(a),
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut a = self.product()?;
+        while let Some(op) = self.op(&[Op::Add, Op::Subtract]) {
+            a = Expr::Binary(op, Box::new(a), Box::new(self.product()?));
+        }
+        Ok(a)
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut a = self.unary()?;
+        while let Some(op) = self.op(&[Op::Multiply, Op::Divide]) {
+            a = Expr::Binary(op, Box::new(a), Box::new(self.unary()?));
+        }
+        Ok(a)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.op(&[Op::Subtract]).is_some() {
+            Ok(Expr::Negate(Box::new(self.unary()?)))
+        } else {
+            self.atom()
+        }
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        let (at, token) = match self.peek() {
+            Some(next) => next.clone(),
+            None => return Err("Expected a value, not the end".to_string()),
+        };
+        self.at += 1;
+        match token {
+            Token::Number(x) => Ok(Expr::Number(x)),
+            Token::Open => {
+                let inner = self.comparison()?;
+                self.expect(Token::Close)?;
+                Ok(inner)
+            }
+            Token::Name(name) => {
+                if let Some(input) = self.names.iter().position(|&known| known == name) {
+                    return Ok(Expr::Input(input));
+                }
+                let function = Function::parse(&name).ok_or_else(|| {
+                    format!(
+                        "Unknown name {:?} at position {}; this can use {} and the functions \
+                         min, max, ceil, floor and if",
+                        name,
+                        at,
+                        self.names.join(", ")
+                    )
+                })?;
+                self.expect(Token::Open)?;
+                let mut args = vec![self.comparison()?];
+                while self.peek().is_some_and(|(_, token)| *token == Token::Comma) {
+                    self.at += 1;
+                    args.push(self.comparison()?);
+                }
+                self.expect(Token::Close)?;
+                if args.len() != function.arguments() {
+                    return Err(format!(
+                        "{}() at position {} takes {} {}, not {}",
+                        name,
+                        at,
+                        function.arguments(),
+                        if function.arguments() == 1 {
+                            "argument"
+                        } else {
+                            "arguments"
+                        },
+                        args.len()
+                    ));
+                }
+                Ok(Expr::Call(function, args))
+            }
+            _ => Err(format!(
+                "Expected a value at position {}, not {}",
+                at,
+                token.describe()
+            )),
+        }
+    }
+}
@@ -0,0 +1,312 @@
+//! Fitting demand models to observed counts, to pick one before simulating
+//!
+//! Each family is fitted by maximum likelihood and then scored three ways:
+//!
+//! - AIC, 2 (parameters) - 2 ln L, for comparing families; lower is better
+//! - the Kolmogorov-Smirnov distance between the sample's CDF and the model's
+//! - Pearson's chi-squared, over runs of values that each expect at least 5 samples
+//!
+//! The p-values treat the fitted parameters as if they were known beforehand, which flatters
+//! every model a little, so they're for ruling a family out rather than proving one right.
+use crate::hypothesis::{gamma_q, kolmogorov_q, ln_gamma};
+use crate::importance::harmonic;
+
+/// A family of demand distributions
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Family {
+    /// A zipf over 1..=1000, as the simulation draws job lots and traffic
+    Zipf,
+    Poisson,
+    /// A Poisson whose mean varies, for counts more spread out than a Poisson's
+    NegativeBinomial,
+}
+
+/// Every family, in the order fit_report lists them
+pub const FAMILIES: [Family; 3] = [Family::Zipf, Family::Poisson, Family::NegativeBinomial];
+
+impl Family {
+    /// The family called `name`, "zipf", "poisson" or "negative_binomial"
+    pub fn parse(name: &str) -> Result<Family, String> {
+        FAMILIES
+            .iter()
+            .copied()
+            .find(|family| family.name() == name)
+            .ok_or_else(|| {
+                format!(
+                    "distribution must be \"zipf\", \"poisson\" or \"negative_binomial\", not {:?}",
+                    name
+                )
+            })
+    }
+
+    /// The name parse() takes
+    pub fn name(self) -> &'static str {
+        match self {
+            Family::Zipf => "zipf",
+            Family::Poisson => "poisson",
+            Family::NegativeBinomial => "negative_binomial",
+        }
+    }
+}
+
+/// A fitted distribution
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Model {
+    /// A zipf over 1..=1000, with ln of its normalizing sum kept to save working it out again
+    Zipf {
+        exponent: f64,
+        ln_total: f64,
+    },
+    Poisson {
+        mean: f64,
+    },
+    /// Failures before the r-th success, with success probability p
+    NegativeBinomial {
+        r: f64,
+        p: f64,
+    },
+}
+
+impl Model {
+    /// The parameters, by name
+    pub fn parameters(&self) -> Vec<(&'static str, f64)> {
+        match *self {
+            Model::Zipf { exponent, .. } => vec![("exponent", exponent)],
+            Model::Poisson { mean } => vec![("mean", mean)],
+            Model::NegativeBinomial { r, p } => vec![("r", r), ("p", p)],
+        }
+    }
+
+    /// ln P(X = k)
+    fn ln_pmf(&self, k: usize) -> f64 {
+        match *self {
+            Model::Zipf { .. } if k == 0 || k > 1000 => f64::NEG_INFINITY,
+            Model::Zipf { exponent, ln_total } => -exponent * (k as f64).ln() - ln_total,
+            Model::Poisson { mean } => k as f64 * mean.ln() - mean - ln_gamma(k as f64 + 1.0),
+            Model::NegativeBinomial { r, p } => {
+                ln_rising(r, k) - ln_gamma(k as f64 + 1.0) + r * p.ln() + k as f64 * (1.0 - p).ln()
+            }
+        }
+    }
+}
+
+/// How well a model fits a sample
+#[derive(Clone, Debug)]
+pub struct Fit {
+    pub model: Model,
+    pub log_likelihood: f64,
+    /// Akaike's information criterion, lower being better
+    pub aic: f64,
+    /// (distance, p-value)
+    pub ks: (f64, f64),
+    /// (statistic, degrees of freedom, p-value)
+    pub chi_squared: (f64, usize, f64),
+}
+
+/// Fit `family` to `samples` and score it, or say why it can't fit them
+pub fn fit(samples: &[usize], family: Family) -> Result<Fit, String> {
+    if samples.is_empty() {
+        return Err("There are no samples to fit".to_string());
+    }
+    // (value, how many times it came up), in order
+    let mut counts: Vec<(usize, usize)> = vec![];
+    let mut sorted = samples.to_vec();
+    sorted.sort_unstable();
+    for &x in &sorted {
+        match counts.last_mut() {
+            Some((value, count)) if *value == x => *count += 1,
+            _ => counts.push((x, 1)),
+        }
+    }
+    let n = samples.len() as f64;
+    let mean = samples.iter().sum::<usize>() as f64 / n;
+    if mean == 0.0 {
+        return Err("Every sample is 0, so there's nothing to fit".to_string());
+    }
+    let model = match family {
+        Family::Zipf => {
+            if sorted[0] == 0 || sorted[sorted.len() - 1] > 1000 {
+                return Err(
+                    "A zipf only covers 1..=1000, and the samples don't fit in that".into(),
+                );
+            }
+            let total_ln: f64 = counts
+                .iter()
+                .map(|&(x, count)| count as f64 * (x as f64).ln())
+                .sum();
+            let ln_likelihood =
+                |exponent: f64| -exponent * total_ln - n * harmonic(1000, exponent).ln();
+            let exponent = golden_section(0.01, 50.0, ln_likelihood);
+            Model::Zipf {
+                exponent,
+                ln_total: harmonic(1000, exponent).ln(),
+            }
+        }
+        Family::Poisson => Model::Poisson { mean },
+        Family::NegativeBinomial => {
+            // For a given r the best p matches the mean, which leaves a search over r alone
+            let at = |ln_r: f64| {
+                let r = ln_r.exp();
+                Model::NegativeBinomial {
+                    r,
+                    p: r / (r + mean),
+                }
+            };
+            let ln_r = golden_section(-7.0, 12.0, |ln_r| log_likelihood(&at(ln_r), &counts));
+            at(ln_r)
+        }
+    };
+    let log_likelihood = log_likelihood(&model, &counts);
+    let parameters = model.parameters().len();
+    Ok(Fit {
+        model,
+        log_likelihood,
+        aic: 2.0 * parameters as f64 - 2.0 * log_likelihood,
+        ks: ks(&model, &counts, n),
+        chi_squared: chi_squared(&model, &counts, n, parameters),
+    })
+}
+
+/// ln of r (r + 1) ... (r + k - 1), which is ln Γ(r + k) - ln Γ(r)
+///
+/// Subtracting the gammas loses most of the digits when r is large, so small k multiply it
+/// out instead.
+fn ln_rising(r: f64, k: usize) -> f64 {
+    if k < 1000 {
+        (0..k).map(|j| (r + j as f64).ln()).sum()
+    } else {
+        ln_gamma(r + k as f64) - ln_gamma(r)
+    }
+}
+
+/// ln L of `model` for a sample of (value, count)
+fn log_likelihood(model: &Model, counts: &[(usize, usize)]) -> f64 {
+    counts
+        .iter()
+        .map(|&(x, count)| count as f64 * model.ln_pmf(x))
+        .sum()
+}
+
+/// Where `f`, with one peak in [low, high], is highest
+fn golden_section<F: Fn(f64) -> f64>(mut low: f64, mut high: f64, f: F) -> f64 {
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    let mut a = high - ratio * (high - low);
+    let mut b = low + ratio * (high - low);
+    let (mut fa, mut fb) = (f(a), f(b));
+    for _ in 0..100 {
+        if fa < fb {
+            low = a;
+            a = b;
+            fa = fb;
+            b = low + ratio * (high - low);
+            fb = f(b);
+        } else {
+            high = b;
+            b = a;
+            fb = fa;
+            a = high - ratio * (high - low);
+            fa = f(a);
+        }
+    }
+    (low + high) / 2.0
+}
+
+/// The largest gap between the sample's CDF and the model's, and its p-value
+fn ks(model: &Model, counts: &[(usize, usize)], n: f64) -> (f64, f64) {
+    let (mut model_cdf, mut sample_cdf, mut d) = (0.0, 0.0, 0.0_f64);
+    let mut next = counts.iter().peekable();
+    for k in 0..=counts[counts.len() - 1].0 {
+        model_cdf += model.ln_pmf(k).exp();
+        if let Some(&(_, count)) = next.next_if(|&&(x, _)| x == k) {
+            sample_cdf += count as f64 / n;
+        }
+        d = d.max((sample_cdf - model_cdf).abs());
+    }
+    let en = n.sqrt();
+    (d, kolmogorov_q((en + 0.12 + 0.11 / en) * d))
+}
+
+/// Pearson's chi-squared over runs of values expecting at least 5 samples each, with the last
+/// run taking the whole tail, and its degrees of freedom and p-value
+fn chi_squared(
+    model: &Model,
+    counts: &[(usize, usize)],
+    n: f64,
+    parameters: usize,
+) -> (f64, usize, f64) {
+    // (observed, expected) for each run
+    let mut bins: Vec<(f64, f64)> = vec![];
+    let (mut observed, mut expected, mut cdf) = (0.0, 0.0, 0.0);
+    let mut next = counts.iter().peekable();
+    for k in 0..=counts[counts.len() - 1].0 {
+        let probability = model.ln_pmf(k).exp();
+        cdf += probability;
+        expected += n * probability;
+        if let Some(&(_, count)) = next.next_if(|&&(x, _)| x == k) {
+            observed += count as f64;
+        }
+        if expected >= 5.0 {
+            bins.push((observed, expected));
+            observed = 0.0;
+            expected = 0.0;
+        }
+    }
+    // Whatever the model expects past the largest sample goes in the last run
+    expected += n * (1.0 - cdf).max(0.0);
+    match bins.last_mut() {
+        Some(last) if expected < 5.0 => {
+            last.0 += observed;
+            last.1 += expected;
+        }
+        _ => bins.push((observed, expected)),
+    }
+    let statistic: f64 = bins
+        .iter()
+        .map(|&(observed, expected)| (observed - expected).powi(2) / expected)
+        .sum();
+    let df = bins.len().saturating_sub(1 + parameters).max(1);
+    (statistic, df, gamma_q(df as f64 / 2.0, statistic / 2.0))
+}
+
+#[test]
+fn test_fit() {
+    // Each family wins on its own kind of sample, and finds roughly its parameters
+    use crate::mixture::Demand;
+    use rand::distributions::Distribution;
+    use rand::SeedableRng;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(11);
+    let best = |samples: &[usize]| {
+        FAMILIES
+            .iter()
+            .filter_map(|&family| fit(samples, family).ok())
+            .min_by(|a, b| a.aic.partial_cmp(&b.aic).unwrap())
+            .unwrap()
+    };
+    let poisson = Demand::poisson(6.0);
+    let samples: Vec<usize> = (0..5000).map(|_| poisson.sample(&mut rng)).collect();
+    let fitted = best(&samples);
+    assert!(matches!(fitted.model, Model::Poisson { mean } if (mean - 6.0).abs() < 0.2));
+    assert!((fitted.aic - (2.0 - 2.0 * fitted.log_likelihood)).abs() < 1e-9);
+    assert!(
+        fitted.chi_squared.2 > 0.001 && fitted.ks.1 > 0.001,
+        "{:?}",
+        fitted
+    );
+    let zipf = Demand::new(2.0, None, None);
+    let samples: Vec<usize> = (0..5000).map(|_| zipf.sample(&mut rng)).collect();
+    assert!(matches!(best(&samples).model, Model::Zipf { .. }));
+    // Poisson counts with a gamma mixed into the mean are overdispersed
+    let samples: Vec<usize> = (0..5000)
+        .map(|_| {
+            Demand::poisson(1.0 + 10.0 * -(1.0 - rand::Rng::gen::<f64>(&mut rng)).ln())
+                .sample(&mut rng)
+        })
+        .collect();
+    assert!(matches!(
+        best(&samples).model,
+        Model::NegativeBinomial { .. }
+    ));
+    assert!(fit(&[0, 0], Family::Poisson).is_err());
+    assert!(fit(&[0, 3], Family::Zipf).is_err());
+    assert!(Family::parse("normal").is_err());
+}
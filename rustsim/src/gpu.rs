@@ -0,0 +1,61 @@
+//! What the GPU backends hand their kernels, so rustoclsim, rustmetalsim and rustvulkansim
+//! all draw the same tables and seeds and a seeded run agrees across them
+//!
+//! A kernel can't run a zipf sampler of its own, so it looks its draws up in a long table of
+//! samples made here instead. Each thread gets a xorshift32 seed from its own stream under one
+//! master seed (see streams.rs), and the threads' u64 counters come back to be added up here.
+use crate::{streams, summarize, Summary};
+use rand::distributions::Distribution;
+use rand::Rng;
+use std::convert::TryInto;
+
+/// Entries in each zipf table
+pub const TABLE_LEN: usize = 16 << 20;
+
+/// The most items a zipf table draws from. Nobody asks for more than this in one go.
+const ZIPF_ELEMENTS: usize = 1000;
+
+/// TABLE_LEN samples of a zipf distribution with `exponent`, which must be positive
+pub fn zipf_table<R: Rng>(exponent: f64, rng: &mut R) -> Vec<u32> {
+    let zipf = zipf::ZipfDistribution::new(ZIPF_ELEMENTS, exponent).unwrap();
+    (0..TABLE_LEN).map(|_| zipf.sample(rng) as u32).collect()
+}
+
+/// Seeds for `count` threads' xorshift generators, one stream each under a master seed from
+/// `rng`
+pub fn work_item_seeds<R: Rng>(rng: &mut R, count: usize) -> Vec<u32> {
+    let master = rng.gen();
+    (0..count as u64)
+        .map(|item| streams::xorshift_seed(master, item))
+        .collect()
+}
+
+/// The summary of the threads' counters (successful transactions, successful sales, failed
+/// transactions and failed sales), added up in u128, or None if they don't fit in a usize
+///
+/// A thousand u64s added up might not fit in one, so the backends keep them wider until here.
+pub fn summarize_totals(totals: [u128; 4]) -> Option<Summary> {
+    let [st, ss, ft, fs] = totals;
+    Some(summarize(
+        st.try_into().ok()?,
+        ss.try_into().ok()?,
+        ft.try_into().ok()?,
+        fs.try_into().ok()?,
+    ))
+}
+
+#[test]
+fn test_gpu() {
+    // Seeds only depend on the generator they're drawn from, and are never zero
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    let seeds = work_item_seeds(&mut StdRng::seed_from_u64(3), 1000);
+    assert_eq!(seeds, work_item_seeds(&mut StdRng::seed_from_u64(3), 1000));
+    assert_ne!(seeds, work_item_seeds(&mut StdRng::seed_from_u64(4), 1000));
+    assert!(seeds.iter().all(|&seed| seed != 0));
+    assert_eq!(
+        summarize_totals([3, 6, 1, 2]),
+        Some((3, 6, 1, 2, 0.75, 0.75))
+    );
+    assert_eq!(summarize_totals([u128::from(u64::MAX) * 4, 0, 0, 0]), None);
+}
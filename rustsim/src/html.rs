@@ -0,0 +1,65 @@
+//! Small HTML tables for Jupyter, which shows an object's _repr_html_() in place of its repr()
+//!
+//! Notebooks style bare tables well enough on their own, so these carry no CSS beyond
+//! right-aligning the numbers.
+
+/// `text` with everything HTML treats specially replaced by entities
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// A line of block characters, one per `width` equal slices of `values`, each as tall as its
+/// slice's average is between the smallest and largest
+pub fn sparkline(values: &[f64], width: usize) -> String {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let width = width.min(values.len());
+    let averages: Vec<f64> = (0..width)
+        .map(|i| {
+            let slice = &values[i * values.len() / width..(i + 1) * values.len() / width];
+            slice.iter().sum::<f64>() / slice.len() as f64
+        })
+        .collect();
+    let low = averages.iter().cloned().fold(f64::INFINITY, f64::min);
+    let high = averages.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    averages
+        .iter()
+        .map(|&average| {
+            // A flat line sits in the middle rather than on the floor
+            let height = if high > low {
+                (average - low) / (high - low)
+            } else {
+                0.5
+            };
+            BLOCKS[((height * 7.0).round() as usize).min(7)]
+        })
+        .collect()
+}
+
+/// A table with `caption` over it, a header row of `columns`, then `rows`
+///
+/// Cells are written as they are, so escape anything that isn't already HTML.
+pub fn table(caption: &str, columns: &[&str], rows: &[Vec<String>]) -> String {
+    let mut out = format!("<table>\n<caption>{}</caption>\n<thead><tr>", caption);
+    for column in columns {
+        out += &format!("<th>{}</th>", column);
+    }
+    out += "</tr></thead>\n<tbody>\n";
+    for row in rows {
+        out += "<tr>";
+        for cell in row {
+            out += &format!("<td style=\"text-align: right\">{}</td>", cell);
+        }
+        out += "</tr>\n";
+    }
+    out + "</tbody>\n</table>"
+}
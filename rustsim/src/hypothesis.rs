@@ -0,0 +1,227 @@
+//! Two-sample hypothesis tests, for checking that two implementations agree
+
+use crate::Summary;
+
+/// Compare two batches of simulated years, like the CPU engine's against another backend's
+///
+/// Runs a KS test on both fill rates, skipping years without any customers since they have
+/// no fill rate to speak of, and a chi-squared test on total demand. Returns (name,
+/// statistic, p-value) for each.
+pub fn compare_years(a: &[Summary], b: &[Summary]) -> Vec<(&'static str, f64, f64)> {
+    let rates = |years: &[Summary], rate: fn(&Summary) -> f64| -> Vec<f64> {
+        years.iter().filter(|y| y.0 + y.2 > 0).map(rate).collect()
+    };
+    let demand =
+        |years: &[Summary]| -> Vec<f64> { years.iter().map(|y| (y.1 + y.3) as f64).collect() };
+    let mut results = vec![];
+    let (d, p) = ks_two_sample(&rates(a, |y| y.4), &rates(b, |y| y.4));
+    results.push(("transaction_fill_rate_ks", d, p));
+    let (d, p) = ks_two_sample(&rates(a, |y| y.5), &rates(b, |y| y.5));
+    results.push(("unit_fill_rate_ks", d, p));
+    let (chi2, _, p) = chi_squared_two_sample(&demand(a), &demand(b), 20);
+    results.push(("demand_chi_squared", chi2, p));
+    results
+}
+
+/// Kolmogorov-Smirnov test that `a` and `b` come from the same distribution
+///
+/// Returns (D, p-value), where D is the largest gap between the empirical CDFs. The p-value
+/// uses the asymptotic Kolmogorov distribution with Stephens' small-sample correction, and is
+/// conservative when there are ties.
+pub fn ks_two_sample(a: &[f64], b: &[f64]) -> (f64, f64) {
+    let mut a = a.to_vec();
+    let mut b = b.to_vec();
+    a.sort_by(f64::total_cmp);
+    b.sort_by(f64::total_cmp);
+    let (n, m) = (a.len() as f64, b.len() as f64);
+    let (mut i, mut j) = (0, 0);
+    let mut d: f64 = 0.0;
+    while i < a.len() && j < b.len() {
+        // Step past every copy of the next value in either sample, so ties move together
+        let x = a[i].min(b[j]);
+        if x.is_nan() {
+            // total_cmp sorts NaNs last, so only NaNs are left on both sides
+            break;
+        }
+        while i < a.len() && a[i] <= x {
+            i += 1;
+        }
+        while j < b.len() && b[j] <= x {
+            j += 1;
+        }
+        d = d.max((i as f64 / n - j as f64 / m).abs());
+    }
+    let en = (n * m / (n + m)).sqrt();
+    (d, kolmogorov_q((en + 0.12 + 0.11 / en) * d))
+}
+
+/// P(K > lambda) for the Kolmogorov distribution
+pub fn kolmogorov_q(lambda: f64) -> f64 {
+    if lambda < 0.2 {
+        return 1.0;
+    }
+    let mut sum = 0.0;
+    let mut sign = 1.0;
+    for j in 1..=100 {
+        let term = (-2.0 * (j * j) as f64 * lambda * lambda).exp();
+        sum += sign * term;
+        if term < 1e-12 {
+            break;
+        }
+        sign = -sign;
+    }
+    (2.0 * sum).clamp(0.0, 1.0)
+}
+
+/// Chi-squared test of homogeneity between `a` and `b`
+///
+/// Both samples are binned on (roughly) `bins` quantiles of the two pooled together, so each
+/// bin expects a fair share of both. Returns (statistic, degrees of freedom, p-value).
+pub fn chi_squared_two_sample(a: &[f64], b: &[f64], bins: usize) -> (f64, usize, f64) {
+    let mut pooled: Vec<f64> = a.iter().chain(b).copied().collect();
+    pooled.sort_by(f64::total_cmp);
+    // Upper edges of every bin but the last; repeated values would make empty bins, so dedup
+    let mut edges: Vec<f64> = (1..bins).map(|k| pooled[k * pooled.len() / bins]).collect();
+    edges.dedup();
+    let histogram = |sample: &[f64]| {
+        let mut counts = vec![0.0; edges.len() + 1];
+        for x in sample {
+            counts[edges.partition_point(|edge| edge < x)] += 1.0;
+        }
+        counts
+    };
+    let (counts_a, counts_b) = (histogram(a), histogram(b));
+    let (n, m) = (a.len() as f64, b.len() as f64);
+    let mut statistic = 0.0;
+    let mut used = 0;
+    for (&ca, &cb) in counts_a.iter().zip(&counts_b) {
+        let total = ca + cb;
+        if total == 0.0 {
+            continue;
+        }
+        used += 1;
+        let expected_a = total * n / (n + m);
+        let expected_b = total * m / (n + m);
+        statistic +=
+            (ca - expected_a).powi(2) / expected_a + (cb - expected_b).powi(2) / expected_b;
+    }
+    let df = used.max(2) - 1;
+    (statistic, df, gamma_q(df as f64 / 2.0, statistic / 2.0))
+}
+
+/// Regularized upper incomplete gamma function Q(a, x), which gives chi-squared p-values
+///
+/// Series for small x and a continued fraction otherwise, as in Numerical Recipes.
+pub fn gamma_q(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let log_prefix = a * x.ln() - x - ln_gamma(a);
+    if x < a + 1.0 {
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        for n in 1..1000 {
+            term *= x / (a + n as f64);
+            sum += term;
+            if term.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+        1.0 - sum * log_prefix.exp()
+    } else {
+        // Lentz's method
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..1000 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < 1e-15 {
+                break;
+            }
+        }
+        log_prefix.exp() * h
+    }
+}
+
+/// ln Γ(x), by the Lanczos approximation
+pub fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.180_091_729_471_46,
+        -86.505_320_329_416_77,
+        24.014_098_240_830_91,
+        -1.231_739_572_450_155,
+        0.001_208_650_973_866_179,
+        -0.000_005_395_239_384_953,
+    ];
+    let tmp = x + 5.5 - (x + 0.5) * (x + 5.5).ln();
+    let mut series = 1.000_000_000_190_015;
+    for (i, c) in COEFFICIENTS.iter().enumerate() {
+        series += c / (x + 1.0 + i as f64);
+    }
+    -tmp + (2.506_628_274_631_000_5 * series / x).ln()
+}
+
+#[test]
+fn test_compare_sequential() {
+    // Far more safety stock wins nearly every year, so the test decides quickly, and
+    // thresholds that mean nothing are refused
+    use crate::Simulation;
+    let generous = Simulation::new(40, 3, 15, None, None, Some(6));
+    let stingy = Simulation::new(2, 3, 15, None, None, Some(6));
+    let (winner, repetitions, wins, losses) = generous
+        .sequential_comparison(&stingy, 10, 0.05, 0.05, 10_000)
+        .unwrap();
+    assert_eq!(winner.as_deref(), Some("self"));
+    assert!(repetitions < 1000 && wins > losses);
+    let (winner, _, _, _) = stingy
+        .sequential_comparison(&generous, 10, 0.05, 0.05, 10_000)
+        .unwrap();
+    assert_eq!(winner.as_deref(), Some("other"));
+    for &(alpha, indifference) in &[
+        (0.0, 0.05),
+        (1.0, 0.05),
+        (f64::NAN, 0.05),
+        (0.05, 0.0),
+        (0.05, 0.5),
+    ] {
+        assert!(generous
+            .sequential_comparison(&stingy, 10, alpha, indifference, 10)
+            .is_err());
+    }
+}
+
+#[test]
+fn test_compare_years() {
+    // Two seeds of the same simulation agree, a much stingier one doesn't, and NaNs sort
+    // instead of panicking or hanging
+    use crate::Simulation;
+    let years = |sim: &Simulation| -> Vec<Summary> {
+        let mut rng = sim.rng();
+        (0..2000)
+            .map(|_| sim.simulate_summary(10, &mut rng))
+            .collect()
+    };
+    let a = years(&Simulation::new(10, 3, 7, None, None, Some(1)));
+    let b = years(&Simulation::new(10, 3, 7, None, None, Some(2)));
+    let stingy = years(&Simulation::new(0, 3, 7, None, None, Some(3)));
+    assert!(compare_years(&a, &b).iter().all(|&(_, _, p)| p > 0.001));
+    let results = compare_years(&a, &stingy);
+    assert!(results[..2].iter().all(|&(_, _, p)| p < 0.001));
+    assert!(results[2].2 > 0.001);
+    let (_, p) = ks_two_sample(&[f64::NAN, 1.0, 2.0], &[0.5, f64::NAN]);
+    assert!((0.0..=1.0).contains(&p));
+}
@@ -0,0 +1,92 @@
+#[cfg(any(feature = "python", test))]
+use {rand::distributions::Distribution, rand::Rng, std::cell::Cell, zipf::ZipfDistribution};
+
+#[cfg(any(feature = "python", test))]
+/// A zipf distribution sampled with a lighter exponent, keeping score of the difference
+///
+/// Every draw of k from the tilted distribution adds log(p(k) / q(k)) to a running total,
+/// where p is the zipf we really mean and q the one we actually sampled. The exponential
+/// of that total is the importance weight of everything drawn since the last reset.
+pub struct Tilted {
+    dist: ZipfDistribution,
+    /// Each draw of k adds `exponent_change * ln(k) + log_normalizer`
+    exponent_change: f64,
+    log_normalizer: f64,
+    log_weight: Cell<f64>,
+}
+
+#[cfg(any(feature = "python", test))]
+impl Tilted {
+    pub fn new(num_elements: usize, exponent: f64, tilt: f64) -> Tilted {
+        let tilted_exponent = exponent - tilt;
+        Tilted {
+            dist: ZipfDistribution::new(num_elements, tilted_exponent).unwrap(),
+            exponent_change: -tilt,
+            log_normalizer: (harmonic(num_elements, tilted_exponent)
+                / harmonic(num_elements, exponent))
+            .ln(),
+            log_weight: Cell::new(0.0),
+        }
+    }
+
+    /// Start keeping score from scratch
+    pub fn reset(&self) {
+        self.log_weight.set(0.0);
+    }
+
+    /// Log of the likelihood ratio of everything drawn since the last reset
+    pub fn log_weight(&self) -> f64 {
+        self.log_weight.get()
+    }
+}
+
+#[cfg(any(feature = "python", test))]
+impl Distribution<usize> for Tilted {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        let k = self.dist.sample(rng);
+        self.log_weight.set(
+            self.log_weight.get() + self.exponent_change * (k as f64).ln() + self.log_normalizer,
+        );
+        k
+    }
+}
+
+/// Generalized harmonic number: the sum of k^-exponent for k in 1..=n
+pub fn harmonic(n: usize, exponent: f64) -> f64 {
+    (1..=n).map(|k| (k as f64).powf(-exponent)).sum()
+}
+
+#[test]
+fn test_tilted_tail() {
+    // The chance of a draw over 100 from zipf(1000, 1.5), weighted from the tilted draws,
+    // agrees with plain Monte Carlo and the exact sum
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    let mut rng = StdRng::seed_from_u64(13);
+    let exact = (101..=1000).map(|k| (k as f64).powf(-1.5)).sum::<f64>() / harmonic(1000, 1.5);
+    let plain = ZipfDistribution::new(1000, 1.5).unwrap();
+    let draws = 100_000;
+    let hits = (0..draws).filter(|_| plain.sample(&mut rng) > 100).count();
+    let plain_estimate = hits as f64 / draws as f64;
+    let tilted = Tilted::new(1000, 1.5, 0.5);
+    let mut weighted = 0.0;
+    for _ in 0..draws {
+        tilted.reset();
+        if tilted.sample(&mut rng) > 100 {
+            weighted += tilted.log_weight().exp();
+        }
+    }
+    let weighted = weighted / draws as f64;
+    assert!(
+        (weighted - exact).abs() < 0.05 * exact,
+        "{} vs {}",
+        weighted,
+        exact
+    );
+    assert!(
+        (weighted - plain_estimate).abs() < 0.1 * exact,
+        "{} vs {}",
+        weighted,
+        plain_estimate
+    );
+}
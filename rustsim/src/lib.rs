@@ -1,124 +1,3130 @@
-use pyo3::prelude::*;
+// The code generated by #[pyclass] in pyo3 0.8 rounds up sizes by hand
+#![allow(clippy::manual_div_ceil)]
+// The SIMD engine is on std::simd, which is nightly only
+#![cfg_attr(feature = "simd", feature(portable_simd))]
+
+use compound::CompoundPoisson;
+use mixture::{Demand, Mixture, Truncation};
+use policy::{Inputs, Policy};
 use rand::distributions::Distribution;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
 use std::cmp::max;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use timings::LastTimings;
+use welford::Welford;
+
+pub mod backend;
+#[cfg(feature = "python")]
+mod backends;
+#[cfg(any(feature = "python", test))]
+mod bootstrap;
+#[cfg(feature = "python")]
+mod callbacks;
+pub mod compound;
+mod controls;
+#[cfg(any(feature = "python", test))]
+mod copula;
+#[cfg(feature = "python")]
+mod demand;
+#[cfg(any(feature = "python", test))]
+mod events;
+mod expression;
+#[cfg(any(feature = "python", test))]
+mod fit;
+pub mod gpu;
+#[cfg(feature = "python")]
+mod html;
+pub mod hypothesis;
+mod importance;
+#[cfg(any(feature = "python", test))]
+mod linalg;
+pub mod metric;
+pub mod mixture;
+pub mod normal;
+#[cfg(feature = "python")]
+mod optimize;
+pub mod parquet;
+pub mod policy;
+#[cfg(feature = "python")]
+mod portfolio;
+#[cfg(any(feature = "python", test))]
+mod quasi;
+#[cfg(any(feature = "python", test))]
+mod reservoir;
+pub mod scenario;
+pub mod schema;
+pub mod search;
+#[cfg(feature = "simd")]
+mod simd;
+#[cfg(any(feature = "python", test))]
+mod sketch;
+#[cfg(feature = "python")]
+mod state;
+#[cfg(feature = "python")]
+mod store;
+#[cfg(any(feature = "python", test))]
+mod stratified;
+pub mod streams;
+#[cfg(feature = "python")]
+mod surface;
+mod timings;
+#[cfg(feature = "python")]
+mod trace;
+pub mod welford;
+
+// Everything Python sees, which the "python" feature (on by default) turns on
+#[cfg(feature = "python")]
+use {
+    bootstrap::Metric,
+    callbacks::Callbacks,
+    controls::ControlVariate,
+    demand::PythonDemand,
+    importance::Tilted,
+    optimize::{Candidate, Costs, PyInit_optimize},
+    portfolio::PyInit_portfolio,
+    pyo3::basic::CompareOp,
+    pyo3::exceptions::{AssertionError, RuntimeError, ValueError},
+    pyo3::prelude::*,
+    pyo3::types::{PyAny, PyDict},
+    pyo3::{wrap_pyfunction, wrap_pymodule, PyIterProtocol, PyNativeType, PyObjectProtocol},
+    quasi::{InverseZipf, QuasiDemand},
+    rayon::prelude::*,
+    reservoir::Reservoir,
+    scenario::Scenarios,
+    sketch::Sketch,
+    state::{DayResult, SimulationState},
+    std::collections::hash_map::DefaultHasher,
+    std::convert::TryFrom,
+    std::path::Path,
+    std::sync::mpsc::{sync_channel, Receiver},
+    std::sync::Mutex,
+    std::thread,
+    store::ResultStore,
+    stratified::DemandYear,
+    timings::{Phase, Stopwatch},
+};
+
+/// Counters and rates from one or many simulated years:
+/// (successful transactions, successful sales, failed transactions, failed sales,
+///  transaction fill rate, unit fill rate)
+///
+/// With no customers at all, the rates are 1.0, since nobody was turned away. The counters
+/// (all zero) are the flag for that case.
+pub type Summary = (usize, usize, usize, usize, f64, f64);
+
+/// Fraction of the demand we met, or 1.0 if there wasn't any
+fn fill_rate(filled: usize, missed: usize) -> f64 {
+    if filled + missed == 0 {
+        1.0
+    } else {
+        // Rust enforces that floats and integers stay separate
+        filled as f64 / (filled + missed) as f64
+    }
+}
+
+/// A Summary, with rates computed from the counters
+pub fn summarize(st: usize, ss: usize, ft: usize, fs: usize) -> Summary {
+    (st, ss, ft, fs, fill_rate(st, ft), fill_rate(ss, fs))
+}
+
+#[cfg_attr(feature = "python", pyclass(module = "rustsim"))]
+#[derive(Clone, PartialEq, Deserialize, Serialize)]
+#[serde(into = "schema::Config", try_from = "schema::Config")]
+pub struct Simulation {
+    safety_stock: usize,
+    lead_time: usize,
+    order_quantity: usize,
+    job_lot_zipf: f64,
+    itemwise_traffic_zipf: f64,
+    /// If set, every method draws its random numbers from a generator seeded with this, so
+    /// the same call on the CPU engine gives the same results every time. GPU backends only
+    /// promise that for the same device and driver; see rng()
+    seed: Option<u64>,
+    /// What to order at the end of each day, if not the built-in rule
+    policy: Option<Policy>,
+    /// Mixtures to draw job lots and traffic from in place of the single exponents
+    job_lot_mixture: Option<Mixture>,
+    itemwise_traffic_mixture: Option<Mixture>,
+    /// The most one customer asks for, and what happens to requests over it
+    max_request: Option<usize>,
+    max_request_mode: Truncation,
+    /// Compound Poisson demand in place of both zipfs, if it's set
+    compound_poisson: Option<CompoundPoisson>,
+    /// How far off the forecast a policy sees is: its average relative error, and the mean
+    /// absolute percentage error of the noise around that, both 0 for a perfect forecast
+    forecast_bias: f64,
+    forecast_mape: f64,
+    /// Where the time went in the last timed call
+    timings: LastTimings,
+}
+
+/// Hashes the parameters and seed, consistently with ==
+///
+/// The exponents are hashed by their bits. That's consistent with == for every exponent a
+/// Simulation can have, since NaN and -0.0 aren't positive.
+impl Hash for Simulation {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.safety_stock.hash(state);
+        self.lead_time.hash(state);
+        self.order_quantity.hash(state);
+        self.job_lot_zipf.to_bits().hash(state);
+        self.itemwise_traffic_zipf.to_bits().hash(state);
+        self.seed.hash(state);
+        self.policy.as_ref().map(Policy::source).hash(state);
+        for mixture in &[&self.job_lot_mixture, &self.itemwise_traffic_mixture] {
+            for &(weight, exponent) in mixture.iter().flat_map(|mixture| mixture.components()) {
+                weight.to_bits().hash(state);
+                exponent.to_bits().hash(state);
+            }
+            mixture.is_some().hash(state);
+        }
+        self.max_request.hash(state);
+        self.max_request_mode.hash(state);
+        if let Some(compound) = &self.compound_poisson {
+            compound.mean_daily_units.to_bits().hash(state);
+            compound.cv.to_bits().hash(state);
+            compound.basket.name().hash(state);
+        }
+        // Adding 0.0 turns -0.0 into 0.0, which == already treats the same
+        (self.forecast_bias + 0.0).to_bits().hash(state);
+        (self.forecast_mape + 0.0).to_bits().hash(state);
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl Simulation {
+    /// Implementation of python Simulation.__init__() (wraps rust Simulation::new())
+    ///
+    /// Every argument is a keyword with a default, like Simulation(safety_stock=20), and
+    /// ValueError says which one is out of range. The zipf exponents default to 2.75 and 4.0.
+    ///
+    /// `policy` replaces the built-in ordering rule with an expression for how many units to
+    /// order at the end of each day, like
+    /// "max(0, 2 * forecast * lead_time + safety_stock - on_hand - pipeline)". See policy.rs
+    /// for what it can say. rustoclsim compiles the same expression into its kernel.
+    ///
+    /// `job_lot_mixture` and `itemwise_traffic_mixture` replace their exponent with a mixture
+    /// of zipfs, given as (weight, exponent) pairs like [(0.9, 4.0), (0.1, 2.0)], for demand
+    /// with more than one mode. See mixture.rs. Only the CPU engines draw from mixtures.
+    ///
+    /// `max_request` caps what one customer asks for. `max_request_mode` is "clip" to cut
+    /// bigger requests down to it, or "resample" to draw them again until they fit. Like
+    /// mixtures, only the CPU engines do it.
+    ///
+    /// `forecast_bias` and `forecast_mape` make the forecast a policy sees noisy: at each
+    /// review it's off by forecast_bias on average (0.1 is 10% high), plus normal noise with
+    /// that mean absolute percentage error. They need a policy, since the built-in rule
+    /// doesn't forecast, and only the CPU engines do them.
+    #[new]
+    #[args(
+        "*",
+        safety_stock = "10",
+        lead_time = "3",
+        order_quantity = "7",
+        job_lot_zipf = "None",
+        itemwise_traffic_zipf = "None",
+        seed = "None",
+        policy = "None",
+        job_lot_mixture = "None",
+        itemwise_traffic_mixture = "None",
+        max_request = "None",
+        max_request_mode = "\"clip\"",
+        forecast_bias = "0.0",
+        forecast_mape = "0.0"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn init(
+        obj: &PyRawObject,
+        safety_stock: isize,
+        lead_time: isize,
+        order_quantity: isize,
+        job_lot_zipf: Option<f64>,
+        itemwise_traffic_zipf: Option<f64>,
+        seed: Option<u64>,
+        policy: Option<&str>,
+        job_lot_mixture: Option<Vec<(f64, f64)>>,
+        itemwise_traffic_mixture: Option<Vec<(f64, f64)>>,
+        max_request: Option<isize>,
+        max_request_mode: &str,
+        forecast_bias: f64,
+        forecast_mape: f64,
+    ) -> PyResult<()> {
+        let mut sim = Simulation::new(
+            at_least("safety_stock", safety_stock, 0)?,
+            at_least("lead_time", lead_time, 1)?,
+            at_least("order_quantity", order_quantity, 1)?,
+            job_lot_zipf,
+            itemwise_traffic_zipf,
+            seed,
+        );
+        sim.policy = policy
+            .map(Policy::parse)
+            .transpose()
+            .map_err(ValueError::py_err)?;
+        sim.job_lot_mixture = mixture(job_lot_mixture)?;
+        sim.itemwise_traffic_mixture = mixture(itemwise_traffic_mixture)?;
+        sim.max_request = max_request
+            .map(|limit| at_least("max_request", limit, 1))
+            .transpose()?;
+        sim.max_request_mode = Truncation::parse(max_request_mode).map_err(ValueError::py_err)?;
+        sim.forecast_bias = forecast_bias;
+        sim.forecast_mape = forecast_mape;
+        sim.check_parameters().map_err(ValueError::py_err)?;
+        obj.init(sim);
+        Ok(())
+    }
+
+    /// A copy of this simulation with some parameters changed, like
+    /// sim.with_params(safety_stock=20)
+    ///
+    /// Takes the same keywords as the constructor, and checks them the same way. Nothing is
+    /// precomputed on the CPU, so this is as cheap as it looks, and sweeps written as Python
+    /// loops can make one per point.
+    #[args(params = "**")]
+    fn with_params(&self, params: Option<&PyDict>) -> PyResult<Simulation> {
+        let mut sim = self.clone();
+        for (name, value) in params.into_iter().flatten() {
+            match name.extract()? {
+                "safety_stock" => sim.safety_stock = at_least("safety_stock", value.extract()?, 0)?,
+                "lead_time" => sim.lead_time = at_least("lead_time", value.extract()?, 1)?,
+                "order_quantity" => {
+                    sim.order_quantity = at_least("order_quantity", value.extract()?, 1)?
+                }
+                "job_lot_zipf" => sim.job_lot_zipf = value.extract()?,
+                "itemwise_traffic_zipf" => sim.itemwise_traffic_zipf = value.extract()?,
+                "seed" => sim.seed = value.extract()?,
+                "policy" => {
+                    let policy: Option<&str> = value.extract()?;
+                    sim.policy = policy
+                        .map(Policy::parse)
+                        .transpose()
+                        .map_err(ValueError::py_err)?;
+                }
+                "job_lot_mixture" => sim.job_lot_mixture = mixture(value.extract()?)?,
+                "itemwise_traffic_mixture" => {
+                    sim.itemwise_traffic_mixture = mixture(value.extract()?)?
+                }
+                "max_request" => {
+                    let limit: Option<isize> = value.extract()?;
+                    sim.max_request = limit
+                        .map(|limit| at_least("max_request", limit, 1))
+                        .transpose()?;
+                }
+                "max_request_mode" => {
+                    sim.max_request_mode =
+                        Truncation::parse(value.extract()?).map_err(ValueError::py_err)?
+                }
+                "forecast_bias" => sim.forecast_bias = value.extract()?,
+                "forecast_mape" => sim.forecast_mape = value.extract()?,
+                name => {
+                    return Err(ValueError::py_err(format!(
+                        "with_params() got an unexpected keyword argument {:?}",
+                        name
+                    )))
+                }
+            }
+        }
+        sim.check_parameters().map_err(ValueError::py_err)?;
+        Ok(sim)
+    }
+
+    /// The seed this simulation was given, if any
+    #[getter]
+    fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// The ordering policy's expression, or None for the built-in rule
+    #[getter]
+    fn policy(&self) -> Option<&str> {
+        self.policy.as_ref().map(Policy::source)
+    }
+
+    /// The job lot mixture as (weight, exponent) pairs, weights scaled to add up to 1, or None
+    /// for the single exponent
+    #[getter]
+    fn job_lot_mixture(&self) -> Option<Vec<(f64, f64)>> {
+        self.job_lot_mixture
+            .as_ref()
+            .map(|mixture| mixture.components().to_vec())
+    }
+
+    /// The traffic mixture, like job_lot_mixture
+    #[getter]
+    fn itemwise_traffic_mixture(&self) -> Option<Vec<(f64, f64)>> {
+        self.itemwise_traffic_mixture
+            .as_ref()
+            .map(|mixture| mixture.components().to_vec())
+    }
+
+    /// The most one customer asks for, or None for no cap
+    #[getter]
+    fn max_request(&self) -> Option<usize> {
+        self.max_request
+    }
+
+    /// "clip" or "resample", for requests over max_request
+    #[getter]
+    fn max_request_mode(&self) -> &'static str {
+        self.max_request_mode.name()
+    }
+
+    /// The forecast's average relative error, 0 for none
+    #[getter]
+    fn forecast_bias(&self) -> f64 {
+        self.forecast_bias
+    }
+
+    /// The mean absolute percentage error of the forecast's noise, 0 for none
+    #[getter]
+    fn forecast_mape(&self) -> f64 {
+        self.forecast_mape
+    }
+
+    /// The compound Poisson demand as a dict of mean_daily_units, cv and basket, or None for
+    /// the zipfs
+    #[getter]
+    fn compound_poisson(&self, py: Python<'_>) -> PyResult<PyObject> {
+        match &self.compound_poisson {
+            None => Ok(py.None()),
+            Some(compound) => {
+                let dict = PyDict::new(py);
+                dict.set_item("mean_daily_units", compound.mean_daily_units)?;
+                dict.set_item("cv", compound.cv)?;
+                dict.set_item("basket", compound.basket.name())?;
+                Ok(dict.to_object(py))
+            }
+        }
+    }
+
+    /// The parameters as JSON, in the same versioned format simserver and the other
+    /// bindings read
+    fn to_json(&self) -> PyResult<String> {
+        serde_json::to_string(self).map_err(|error| ValueError::py_err(error.to_string()))
+    }
+
+    /// A simulation from JSON written by to_json() (or by hand, leaving out the zipf
+    /// exponents and seed for the defaults)
+    #[staticmethod]
+    fn from_json(json: &str) -> PyResult<Simulation> {
+        serde_json::from_str(json).map_err(|error| ValueError::py_err(error.to_string()))
+    }
+
+    /// A simulation from a scenario file (TOML, YAML or JSON), or from its base parameters
+    /// if `scenario` is None
+    ///
+    /// See scenario.rs for the format. TOML needs Python 3.11 or tomli, and YAML needs PyYAML.
+    #[staticmethod]
+    #[args(scenario = "None")]
+    fn from_config(py: Python<'_>, path: &str, scenario: Option<&str>) -> PyResult<Simulation> {
+        let parse = |path: &Path| scenario::parse_with_python(py, path);
+        Scenarios::load(Path::new(path), &parse)
+            .and_then(|scenarios| scenarios.simulation(scenario))
+            .map_err(ValueError::py_err)
+    }
+
+    /// A simulation whose demand is compound Poisson, averaging `mean_daily_units` a day with
+    /// coefficient of variation `cv`, like Simulation.from_compound_poisson(40, 0.5)
+    ///
+    /// Customers come Poisson, and each buys a basket from `basket`, "geometric" or "zipf";
+    /// the customer rate and the basket's shape are worked out to match. That's much easier
+    /// to get from a sales history than zipf exponents. Any other keywords are parameters as
+    /// in the constructor, though not the zipfs or mixtures, which this replaces. A
+    /// max_request caps the baskets, which brings the mean down. See compound.rs. Only the
+    /// CPU engines draw it.
+    #[staticmethod]
+    #[args(basket = "\"geometric\"", params = "**")]
+    fn from_compound_poisson(
+        mean_daily_units: f64,
+        cv: f64,
+        basket: &str,
+        params: Option<&PyDict>,
+    ) -> PyResult<Simulation> {
+        let basket = compound::Basket::parse(basket).map_err(ValueError::py_err)?;
+        let mut sim = Simulation::new(10, 3, 7, None, None, None).with_params(params)?;
+        sim.compound_poisson =
+            Some(CompoundPoisson::new(mean_daily_units, cv, basket).map_err(ValueError::py_err)?);
+        sim.check_parameters().map_err(ValueError::py_err)?;
+        Ok(sim)
+    }
+
+    /// The names of the scenarios in a scenario file
+    #[staticmethod]
+    fn scenarios(py: Python<'_>, path: &str) -> PyResult<Vec<String>> {
+        let parse = |path: &Path| scenario::parse_with_python(py, path);
+        let scenarios = Scenarios::load(Path::new(path), &parse).map_err(ValueError::py_err)?;
+        Ok(scenarios.names().into_iter().map(String::from).collect())
+    }
+
+    /// Whether this simulation was given a seed, and so gives the same results every time
+    #[getter]
+    fn deterministic(&self) -> bool {
+        self.seed.is_some()
+    }
+
+    /// Everything needed to re-run this simulation: the backend, device, crate version, seed,
+    /// and every parameter, as a dict
+    fn metadata(&self, py: Python<'_>) -> PyResult<PyObject> {
+        Ok(self.describe(py, self.seed)?.to_object(py))
+    }
+
+    /// Where the time went in the last call to repeat_simulate_demand,
+    /// repeat_simulate_demand_vectorized or repeat_simulate_demand_batch, or None before the
+    /// first
+    ///
+    /// Returns a dict of seconds spent in "precompute" (tables built before the first
+    /// repetition), "seed_generation", "upload", "kernel" (the repetitions themselves),
+    /// "readback" and "reduction" (adding up the results), in that order. rustoclsim reports
+    /// the same phases, so the two line up. There's no device here, so upload and readback
+    /// are always 0.
+    fn last_timings(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let breakdown = match self.timings.get() {
+            Some(breakdown) => breakdown,
+            None => return Ok(py.None()),
+        };
+        let dict = PyDict::new(py);
+        for &(name, phase) in &Phase::ALL {
+            dict.set_item(name, breakdown.seconds(phase))?;
+        }
+        Ok(dict.to_object(py))
+    }
+
+    /// Do exactly the same search Python does
+    ///
+    /// This method performs all it's conversions automatically
+    fn simulate_demand_inner(&self, starting_quantity: usize) -> Summary {
+        self.simulate_summary(starting_quantity, &mut self.rng())
+    }
+
+    /// You can also perform the conversions manually, and you can get access to the Python GIL, which necessary in many cases
+    fn simulate_demand(&self, py: Python<'_>, starting_quantity: usize) -> PyResult<PyObject> {
+        Ok(self.simulate_demand_inner(starting_quantity).into_py(py))
+    }
+
+    /// Repeat the simulation many times
+    ///
+    /// `sampling` is either "random" (plain Monte Carlo), or "sobol", which draws each day's
+    /// demand from a randomly shifted Sobol sequence instead. Sobol points cover the space of
+    /// possible years more evenly than random ones, so the totals usually converge faster,
+    /// especially when `count` is a power of two.
+    ///
+    /// With a ResultStore as `store`, the run and its totals are recorded there too.
+    ///
+    /// `callbacks` is a list of objects with on_run_start(config) and on_run_end(config,
+    /// metrics) methods, called before and after the run, for forwarding runs to an
+    /// experiment tracker.
+    ///
+    /// `backend` is "cpu", "opencl" to run on rustoclsim's default device instead, or "auto"
+    /// to use the GPU only from the count where it's faster on this machine. That count comes
+    /// from benchmark(), or a short probe the first time "auto" needs it, and is saved under
+    /// $RUSTSIM_CACHE_DIR (or ~/.cache/rustsim). The GPU only does random sampling, from plain
+    /// zipf demand with a perfect forecast, so "auto" stays on the CPU for anything else.
+    #[args(
+        sampling = "\"random\"",
+        store = "None",
+        callbacks = "vec![]",
+        backend = "\"cpu\""
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn repeat_simulate_demand(
+        &self,
+        py: Python<'_>,
+        starting_quantity: usize,
+        count: usize,
+        sampling: &str,
+        store: Option<&ResultStore>,
+        callbacks: Vec<&PyAny>,
+        backend: &str,
+    ) -> PyResult<Summary> {
+        let method = self.sampling_method(sampling)?;
+        let mut cpu_only = self.cpu_only_settings();
+        if sampling != "random" {
+            cpu_only.push("sampling");
+        }
+        let gpu_only = cpu_only.is_empty();
+        let on_gpu = match backend {
+            "cpu" => false,
+            "opencl" if !gpu_only => {
+                return Err(ValueError::py_err(format!(
+                    "backend=\"opencl\" only does plain zipf demand with a perfect forecast \
+                     and sampling=\"random\", but this call uses {}",
+                    cpu_only.join(", ")
+                )))
+            }
+            "opencl" => true,
+            "auto" => gpu_only && backends::prefer_gpu(py, count)?,
+            _ => {
+                return Err(ValueError::py_err(format!(
+                    "backend must be \"cpu\", \"opencl\" or \"auto\", not {:?}",
+                    backend
+                )))
+            }
+        };
+        let gpu_sim = if on_gpu {
+            backends::gpu_simulation(py, self)?
+        } else {
+            None
+        };
+        if backend == "opencl" && gpu_sim.is_none() {
+            return Err(RuntimeError::py_err(
+                "backend=\"opencl\" needs rustoclsim and a working OpenCL device; \
+                 see rustoclsim.doctor()",
+            ));
+        }
+        // The CPU draws a seed for an unseeded call, so its config can say which; the GPU
+        // copy keeps its own, and says so in rustoclsim's last_metadata()
+        let seed = self.call_seed();
+        let config = self.describe(
+            py,
+            if gpu_sim.is_some() {
+                self.seed
+            } else {
+                Some(seed)
+            },
+        )?;
+        config.set_item("method", method)?;
+        config.set_item("starting_quantity", starting_quantity)?;
+        config.set_item("count", count)?;
+        let callbacks = Callbacks::start(callbacks, config)?;
+        if let Some(gpu_sim) = gpu_sim {
+            let (summary, breakdown) =
+                backends::repeat_on_gpu(py, &gpu_sim, starting_quantity, count)?;
+            if let Some(breakdown) = breakdown {
+                self.timings.set(breakdown);
+            }
+            if let Some(store) = store {
+                store.add(py, self, method, starting_quantity, count, summary)?;
+            }
+            callbacks.end(py, summary, &[])?;
+            return Ok(summary);
+        }
+        let (summary, _, _) = self.repeat_on_cpu(py, starting_quantity, count, sampling, seed);
+        if let Some(store) = store {
+            store.add(py, self, method, starting_quantity, count, summary)?;
+        }
+        callbacks.end(py, summary, &[])?;
+        Ok(summary)
+    }
+
+    /// Like repeat_simulate_demand, but also estimates the fill rates with control variates
+    ///
+    /// Returns the usual totals along with (transaction fill rate, unit fill rate, failed
+    /// sales per year), corrected using how far the simulated customers and demand strayed
+    /// from what the distributions should give on average. Those corrections often cut the
+    /// error a lot for the same `count`. The metrics passed to `callbacks` include the
+    /// corrected estimates, prefixed with "controlled_". This only runs on the CPU.
+    #[args(sampling = "\"random\"", store = "None", callbacks = "vec![]")]
+    fn repeat_simulate_demand_controlled(
+        &self,
+        py: Python<'_>,
+        starting_quantity: usize,
+        count: usize,
+        sampling: &str,
+        store: Option<&ResultStore>,
+        callbacks: Vec<&PyAny>,
+    ) -> PyResult<(Summary, (f64, f64, f64))> {
+        let method = self.sampling_method(sampling)?;
+        let seed = self.call_seed();
+        let config = self.describe(py, Some(seed))?;
+        config.set_item("method", method)?;
+        config.set_item("starting_quantity", starting_quantity)?;
+        config.set_item("count", count)?;
+        let callbacks = Callbacks::start(callbacks, config)?;
+        let (summary, transaction_control, sales_control) =
+            self.repeat_on_cpu(py, starting_quantity, count, sampling, seed);
+        if let Some(store) = store {
+            store.add(py, self, method, starting_quantity, count, summary)?;
+        }
+        // We know exactly how many customers and units to expect each year on average
+        let expected_customers = 365.0 * self.mean_customers();
+        let expected_demand = expected_customers * self.mean_request();
+        let expected_sales = sales_control.estimate(expected_demand);
+        let controlled = (
+            transaction_control.estimate(expected_customers) / expected_customers,
+            expected_sales / expected_demand,
+            expected_demand - expected_sales,
+        );
+        callbacks.end(
+            py,
+            summary,
+            &[
+                ("controlled_transaction_fill_rate", controlled.0),
+                ("controlled_unit_fill_rate", controlled.1),
+                ("controlled_failed_sales", controlled.2),
+            ],
+        )?;
+        Ok((summary, controlled))
+    }
+
+    /// Like repeat_simulate_demand, with the policy changing on set days of every year
+    ///
+    /// `schedule` is a list of (day, param, value), like [(300, "safety_stock", 40)] to raise
+    /// the safety stock ahead of a holiday peak. Each change takes effect at the start of its
+    /// day (counting from 0) and lasts until another change to the same parameter. Any
+    /// parameter with_params() takes can change, but for lead_time, since the trucks already
+    /// on the way are laid out by it, and seed. Changes on the same day apply in order.
+    fn repeat_simulate_demand_scheduled(
+        &self,
+        py: Python<'_>,
+        starting_quantity: usize,
+        count: usize,
+        schedule: Vec<(usize, String, &PyAny)>,
+    ) -> PyResult<Summary> {
+        let mut schedule = schedule;
+        // A stable sort, so changes on the same day keep their order
+        schedule.sort_by_key(|&(day, _, _)| day);
+        let mut policies: Vec<(usize, Simulation)> = vec![];
+        let mut sim = self.clone();
+        for (day, param, value) in schedule {
+            if param == "lead_time" || param == "seed" {
+                return Err(ValueError::py_err(format!(
+                    "{} can't change mid-year",
+                    param
+                )));
+            }
+            let params = PyDict::new(py);
+            params.set_item(param, value)?;
+            sim = sim.with_params(Some(params))?;
+            match policies.last_mut() {
+                Some((last_day, last)) if *last_day == day => *last = sim.clone(),
+                _ => policies.push((day, sim.clone())),
+            }
+        }
+        let (mut st, mut ss, mut ft, mut fs) = (0, 0, 0, 0);
+        py.allow_threads(|| {
+            let mut rng = self.rng();
+            for _ in 0..count {
+                let (xst, xss, xft, xfs, _, _) =
+                    self.simulate_scheduled_summary(starting_quantity, &policies, &mut rng);
+                st += xst;
+                ss += xss;
+                ft += xft;
+                fs += xfs;
+            }
+        });
+        Ok(summarize(st, ss, ft, fs))
+    }
+
+    /// Like repeat_simulate_demand, on the discrete-event engine, where lead times can vary
+    ///
+    /// Each order's lead time is drawn uniformly from lead_time - lead_time_spread to
+    /// lead_time + lead_time_spread days, fractions and all, counting from the end of the day
+    /// it's placed. Customers come at random times of day, and trucks can arrive in the
+    /// middle of one, or overtake each other when the spread is wide. Each truck arrives just
+    /// once, so even with no spread the years aren't the same as repeat_simulate_demand's.
+    #[args(lead_time_spread = "0.0")]
+    fn repeat_simulate_demand_events(
+        &self,
+        py: Python<'_>,
+        starting_quantity: usize,
+        count: usize,
+        lead_time_spread: f64,
+    ) -> PyResult<Summary> {
+        let lead_time = self.lead_time as f64;
+        if !(0.0..=lead_time).contains(&lead_time_spread) {
+            return Err(ValueError::py_err(format!(
+                "lead_time_spread must be between 0 and lead_time ({}), not {}",
+                lead_time, lead_time_spread
+            )));
+        }
+        let lead_times = rand::distributions::Uniform::new_inclusive(
+            lead_time - lead_time_spread,
+            lead_time + lead_time_spread,
+        );
+        let it_zipf = self.traffic();
+        let jl_zipf = self.job_lots();
+        let (mut st, mut ss, mut ft, mut fs) = (0, 0, 0, 0);
+        py.allow_threads(|| {
+            let mut rng = self.rng();
+            for _ in 0..count {
+                let (xst, xss, xft, xfs, _, _) = events::simulate_year(
+                    self,
+                    starting_quantity,
+                    &lead_times,
+                    &it_zipf,
+                    &jl_zipf,
+                    &mut rng,
+                );
+                st += xst;
+                ss += xss;
+                ft += xft;
+                fs += xfs;
+            }
+        });
+        Ok(summarize(st, ss, ft, fs))
+    }
+
+    /// Like repeat_simulate_demand, but with the customers coming from Python
+    ///
+    /// `demand` is a callable, asked for `batch` years of days at a time as demand(days), or
+    /// an iterable (a generator, say) of batches of as many days as it likes. A batch is a
+    /// list with a list of request sizes for each day, like [[3, 1], [], [12]]. The years are
+    /// 365 of those days each, in order, and the job lot and traffic exponents go unused.
+    /// It's slow next to the built-in distributions, but it's a quick way to see what an
+    /// unusual demand model does before writing it in Rust. Raises whatever the callable
+    /// raises, and ValueError if the days run out before `count` years.
+    #[args(batch = "1")]
+    fn repeat_simulate_demand_python(
+        &self,
+        starting_quantity: usize,
+        count: usize,
+        demand: &PyAny,
+        batch: isize,
+    ) -> PyResult<Summary> {
+        let batch = at_least("batch", batch, 1)?;
+        let mut demand = PythonDemand::new(demand, 365 * batch)?;
+        let mut rng = self.rng();
+        let (mut st, mut ss, mut ft, mut fs) = (0, 0, 0, 0);
+        for _ in 0..count {
+            let year = demand.year()?;
+            let replay = year.replay();
+            let (xst, xss, xft, xfs, _, _) = self
+                .simulate_year_with(starting_quantity, &replay, &replay, &mut rng, |_, _, _| {})
+                .summary();
+            st += xst;
+            ss += xss;
+            ft += xft;
+            fs += xfs;
+        }
+        Ok(summarize(st, ss, ft, fs))
+    }
+
+    /// Like repeat_simulate_demand, along with custom metrics of each year
+    ///
+    /// `metrics` is a list like ["days_below(5)", "max(stock)", "mean(lost)"], each an
+    /// aggregate over the days of a year of an expression over the day's state; see metric.rs
+    /// for what they can say. They're worked out as each year is simulated, so there's no
+    /// trace to keep. Returns the usual summary and a dict from each metric to its mean over
+    /// the years. rustoclsim's method of the same name works them out on the device.
+    fn repeat_simulate_demand_metrics(
+        &self,
+        py: Python<'_>,
+        starting_quantity: usize,
+        count: usize,
+        metrics: Vec<&str>,
+    ) -> PyResult<(Summary, PyObject)> {
+        let parsed = metrics
+            .iter()
+            .map(|source| metric::Metric::parse(source))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(ValueError::py_err)?;
+        let it_zipf = self.traffic();
+        let jl_zipf = self.job_lots();
+        let (mut st, mut ss, mut ft, mut fs) = (0, 0, 0, 0);
+        let mut totals = vec![0.0; parsed.len()];
+        py.allow_threads(|| {
+            let mut rng = self.rng();
+            for _ in 0..count {
+                let ((xst, xss, xft, xfs, _, _), years) = metric::simulate_year(
+                    self,
+                    starting_quantity,
+                    &parsed,
+                    &it_zipf,
+                    &jl_zipf,
+                    &mut rng,
+                );
+                st += xst;
+                ss += xss;
+                ft += xft;
+                fs += xfs;
+                for (total, year) in totals.iter_mut().zip(years) {
+                    *total += year as f64;
+                }
+            }
+        });
+        let means = PyDict::new(py);
+        for (source, total) in metrics.iter().zip(totals) {
+            means.set_item(source, total / count.max(1) as f64)?;
+        }
+        Ok((summarize(st, ss, ft, fs), means.to_object(py)))
+    }
+
+    /// Repeat the simulation, eight years at a time in SIMD lanes, spread across all your cores
+    ///
+    /// This is much faster than repeat_simulate_demand without a GPU. Like rustoclsim, it
+    /// draws customers from large precomputed zipf tables with xorshift rather than sampling
+    /// the zipf distributions directly, so results differ from repeat_simulate_demand for the
+    /// same seed, though they agree in distribution. It only has the built-in ordering rule and
+    /// plain zipfs, so simulations with a policy, a mixture or max_request raise ValueError.
+    fn repeat_simulate_demand_vectorized(
+        &self,
+        py: Python<'_>,
+        starting_quantity: usize,
+        count: usize,
+    ) -> PyResult<Summary> {
+        if self.policy.is_some() {
+            return Err(ValueError::py_err(
+                "repeat_simulate_demand_vectorized only has the built-in ordering rule; use \
+                 repeat_simulate_demand for a policy",
+            ));
+        }
+        if self.custom_demand() {
+            return Err(ValueError::py_err(
+                "repeat_simulate_demand_vectorized only draws from plain zipfs; use \
+                 repeat_simulate_demand for a mixture or max_request",
+            ));
+        }
+        self.vectorized(py, starting_quantity, count)
+    }
+
+    /// Repeat the simulation `count` times from each of `starting_quantities`, for an
+    /// initial-buy curve in one call
+    ///
+    /// `starting_quantities` is a 1-D numpy array or a list. Every starting quantity sees
+    /// exactly the same `count` years of customers (common random numbers), so the curve is
+    /// smooth and the differences along it are down to the stock rather than luck. They're
+    /// spread across all your cores without the GIL. Returns one summary per starting
+    /// quantity, in order, which `numpy.asarray()` turns into a matrix.
+    fn repeat_simulate_demand_batch(
+        &self,
+        py: Python<'_>,
+        starting_quantities: Vec<usize>,
+        count: usize,
+    ) -> Vec<Summary> {
+        let mut stopwatch = Stopwatch::start();
+        let seed = self.rng().gen();
+        stopwatch.lap(Phase::Seeds);
+        let summaries = py.allow_threads(|| {
+            starting_quantities
+                .par_iter()
+                .map(|&starting_quantity| {
+                    let mut rng = StdRng::seed_from_u64(seed);
+                    let (mut st, mut ss, mut ft, mut fs) = (0, 0, 0, 0);
+                    for _ in 0..count {
+                        let (xst, xss, xft, xfs, _, _) =
+                            self.simulate_summary(starting_quantity, &mut rng);
+                        st += xst;
+                        ss += xss;
+                        ft += xft;
+                        fs += xfs;
+                    }
+                    summarize(st, ss, ft, fs)
+                })
+                .collect()
+        });
+        stopwatch.lap(Phase::Kernel);
+        self.timings.set(stopwatch.breakdown());
+        summaries
+    }
+
+    /// Repeat the simulation, and also track the spread of the fill rates between repetitions
+    ///
+    /// Returns the same totals as repeat_simulate_demand, plus (mean, variance) of the
+    /// per-repetition transaction fill rate and unit fill rate. These are accumulated as we
+    /// go, so this works for any `count` without storing each repetition.
+    fn repeat_simulate_demand_stats(
+        &self,
+        starting_quantity: usize,
+        count: usize,
+    ) -> (Summary, (f64, f64), (f64, f64)) {
+        let (mut st, mut ss, mut ft, mut fs) = (0, 0, 0, 0);
+        let mut transaction_rate = Welford::new();
+        let mut unit_rate = Welford::new();
+        let mut rng = self.rng();
+        for _ in 0..count {
+            let (xst, xss, xft, xfs, xtr, xur) = self.simulate_summary(starting_quantity, &mut rng);
+            st += xst;
+            ss += xss;
+            ft += xft;
+            fs += xfs;
+            // A year with no customers at all has no fill rate to speak of
+            if xst + xft > 0 {
+                transaction_rate.push(xtr);
+                unit_rate.push(xur);
+            }
+        }
+        (
+            summarize(st, ss, ft, fs),
+            (transaction_rate.mean(), transaction_rate.variance()),
+            (unit_rate.mean(), unit_rate.variance()),
+        )
+    }
+
+    /// Repeat the simulation in batches until we're confident enough about the unit fill rate
+    ///
+    /// Stops once the 95% confidence interval of the mean per-repetition unit fill rate is
+    /// narrower than `target_ci_width`, or after `max_count` repetitions, whichever is first.
+    /// Returns the totals (as in repeat_simulate_demand), how many repetitions it took,
+    /// and the final interval width.
+    #[args(max_count = "1_000_000")]
+    fn repeat_until(
+        &self,
+        starting_quantity: usize,
+        target_ci_width: f64,
+        max_count: usize,
+    ) -> (Summary, usize, f64) {
+        self.repeat_summary_until(starting_quantity, target_ci_width, max_count)
+    }
+
+    /// Find out which of two configurations has the better unit fill rate, as quickly as possible
+    ///
+    /// Both simulations see exactly the same customers in each repetition (common random
+    /// numbers), so we only count which one did better, and run Wald's sequential probability
+    /// ratio test on those counts: is self better in at least 50% + `indifference` of the
+    /// repetitions, or is other? We stop as soon as either is accepted with error rate `alpha`.
+    ///
+    /// Returns ("self", "other" or None if `max_count` ran out first), how many repetitions it
+    /// took, and how many repetitions each one won (ties don't count). Raises ValueError
+    /// unless 0 < `alpha` < 1 and 0 < `indifference` < 0.5.
+    #[args(alpha = "0.05", indifference = "0.05", max_count = "1_000_000")]
+    fn compare_sequential(
+        &self,
+        other: &Simulation,
+        starting_quantity: usize,
+        alpha: f64,
+        indifference: f64,
+        max_count: usize,
+    ) -> PyResult<(Option<String>, usize, usize, usize)> {
+        self.sequential_comparison(other, starting_quantity, alpha, indifference, max_count)
+            .map_err(ValueError::py_err)
+    }
+
+    /// Estimate the probability that a year has at least one stockout, using importance sampling
+    ///
+    /// When stockouts are rare, almost every plain repetition is wasted. Instead, we simulate
+    /// with both zipf exponents lowered by `tilt`, which makes big days and big baskets (and so
+    /// stockouts) much more common, and then weight each year by how much less likely it was
+    /// under the real distributions. Returns (probability, standard error, effective sample
+    /// size); if the effective sample size is tiny compared to `count`, the tilt is too strong.
+    #[args(tilt = "0.5")]
+    fn stockout_probability(
+        &self,
+        starting_quantity: usize,
+        count: usize,
+        tilt: f64,
+    ) -> PyResult<(f64, f64, f64)> {
+        if self.custom_demand() {
+            return Err(ValueError::py_err(
+                "stockout_probability only tilts plain zipfs, not mixtures or max_request",
+            ));
+        }
+        if tilt >= self.job_lot_zipf.min(self.itemwise_traffic_zipf) {
+            return Err(ValueError::py_err(
+                "tilt must be smaller than both zipf exponents",
+            ));
+        }
+        let it_dist = Tilted::new(1000, self.itemwise_traffic_zipf, tilt);
+        let jl_dist = Tilted::new(1000, self.job_lot_zipf, tilt);
+        let mut rng = self.rng();
+        let mut weighted = Welford::new();
+        let (mut sum_weights, mut sum_squared_weights) = (0.0, 0.0);
+        for _ in 0..count {
+            it_dist.reset();
+            jl_dist.reset();
+            let year = self.simulate_year_with(
+                starting_quantity,
+                &it_dist,
+                &jl_dist,
+                &mut rng,
+                |_, _, _| {},
+            );
+            let weight = (it_dist.log_weight() + jl_dist.log_weight()).exp();
+            sum_weights += weight;
+            sum_squared_weights += weight * weight;
+            weighted.push(if year.failed_transactions > 0 {
+                weight
+            } else {
+                0.0
+            });
+        }
+        Ok((
+            weighted.mean(),
+            (weighted.variance() / count as f64).sqrt(),
+            sum_weights * sum_weights / sum_squared_weights,
+        ))
+    }
+
+    /// Repeat the simulation, spreading repetitions evenly over low, medium and high demand years
+    ///
+    /// Most of the randomness in the fill rate comes from how much demand there was that
+    /// year. So we first draw `pilot` years of demand to find the quantiles of total annual
+    /// demand, which split years into `strata` equally likely groups. Then we draw years of
+    /// demand, keeping each only if its group isn't full yet, until every group has its share
+    /// of `count`. Because no group can end up over- or under-represented by chance, the
+    /// estimate varies less than plain repetition does. (Ties in demand can merge groups.)
+    ///
+    /// Returns the totals (as in repeat_simulate_demand), the stratified estimate of the
+    /// mean per-repetition unit fill rate, and its standard error.
+    #[args(strata = "10", pilot = "1000")]
+    fn repeat_simulate_demand_stratified(
+        &self,
+        starting_quantity: usize,
+        count: usize,
+        strata: usize,
+        pilot: usize,
+    ) -> PyResult<(Summary, f64, f64)> {
+        if strata == 0 || pilot < strata || count < strata {
+            return Err(ValueError::py_err(
+                "Need at least one stratum, and at least as many pilot years and repetitions as strata",
+            ));
+        }
+        let jl_zipf = self.job_lots();
+        let it_zipf = self.traffic();
+        let mut rng = self.rng();
+        let (boundaries, weights) = stratified::boundaries(
+            (0..pilot)
+                .map(|_| DemandYear::draw(&mut rng, &it_zipf, &jl_zipf).total)
+                .collect(),
+            strata,
+        );
+
+        let wanted = stratified::allocate(&weights, count);
+        let mut unit_rates = vec![Welford::new(); weights.len()];
+        let mut filled = vec![0; weights.len()];
+        let (mut st, mut ss, mut ft, mut fs) = (0, 0, 0, 0);
+        let mut remaining = count;
+        while remaining > 0 {
+            let demand = DemandYear::draw(&mut rng, &it_zipf, &jl_zipf);
+            let h = stratified::stratum_of(&boundaries, demand.total);
+            if filled[h] == wanted[h] {
+                continue;
+            }
+            let replay = demand.replay();
+            let (xst, xss, xft, xfs, _, xur) = self
+                .simulate_year_with(starting_quantity, &replay, &replay, &mut rng, |_, _, _| {})
+                .summary();
+            st += xst;
+            ss += xss;
+            ft += xft;
+            fs += xfs;
+            if xst + xft > 0 {
+                unit_rates[h].push(xur);
+            }
+            filled[h] += 1;
+            remaining -= 1;
+        }
+
+        // Weigh each stratum by how likely it is, not by how many repetitions it got.
+        // A tiny stratum might have rounded down to no repetitions, so leave it out.
+        let sampled: Vec<(&Welford, f64, usize)> = unit_rates
+            .iter()
+            .zip(weights)
+            .zip(filled)
+            .filter(|&(_, n)| n > 0)
+            .map(|((rates, weight), n)| (rates, weight, n))
+            .collect();
+        let total_weight: f64 = sampled.iter().map(|&(_, weight, _)| weight).sum();
+        let mean = sampled
+            .iter()
+            .map(|&(rates, weight, _)| weight / total_weight * rates.mean())
+            .sum();
+        let variance: f64 = sampled
+            .iter()
+            .map(|&(rates, weight, n)| {
+                (weight / total_weight).powi(2) * rates.variance() / n as f64
+            })
+            .sum();
+        Ok((summarize(st, ss, ft, fs), mean, variance.sqrt()))
+    }
+
+    /// Evaluate many variations of this simulation at once, for external optimizers
+    ///
+    /// Each row of `param_matrix` (a 2-D numpy array or list of lists) is one candidate, and
+    /// its columns are the parameters named in `params`, by default all of
+    /// rustsim.optimize.PARAMETERS. Anything not in `params` stays as it is in this simulation,
+    /// except starting_quantity, which follows the safety stock. Candidates are simulated
+    /// `count` times each, all in parallel without the GIL.
+    ///
+    /// Returns one row per candidate, with the columns in rustsim.optimize.METRICS, which
+    /// `numpy.asarray()` will happily turn into a matrix. This suits scipy.optimize, Optuna,
+    /// Ax and friends, which can hand over a whole population of candidates at once.
+    #[args(
+        params = "None",
+        count = "1000",
+        holding_cost = "0.01",
+        order_cost = "1.0",
+        shortage_cost = "1.0"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn evaluate(
+        &self,
+        py: Python<'_>,
+        param_matrix: &PyAny,
+        params: Option<Vec<String>>,
+        count: usize,
+        holding_cost: f64,
+        order_cost: f64,
+        shortage_cost: f64,
+    ) -> PyResult<Vec<Vec<f64>>> {
+        let params =
+            params.unwrap_or_else(|| optimize::PARAMETERS.iter().map(|p| p.to_string()).collect());
+        let follows_safety_stock = !params.iter().any(|p| p == "starting_quantity");
+        let base = Candidate {
+            sim: self.clone(),
+            starting_quantity: self.safety_stock,
+        };
+        let mut candidates = vec![];
+        for row in optimize::extract_matrix(py, param_matrix)? {
+            if row.len() != params.len() {
+                return Err(ValueError::py_err(format!(
+                    "Each row needs {} columns ({:?}), not {}",
+                    params.len(),
+                    params,
+                    row.len()
+                )));
+            }
+            let mut candidate = base.clone();
+            for (name, &value) in params.iter().zip(&row) {
+                candidate = candidate.with(name, value)?;
+            }
+            if follows_safety_stock {
+                candidate.starting_quantity = candidate.sim.safety_stock;
+            }
+            candidates.push(candidate);
+        }
+        let costs = Costs {
+            holding: holding_cost,
+            order: order_cost,
+            shortage: shortage_cost,
+        };
+        Ok(optimize::evaluate_all(py, &candidates, count, costs)
+            .iter()
+            .map(|metrics| metrics.to_row())
+            .collect())
+    }
+
+    /// Find the smallest starting quantity that reaches `target_fill_rate` (unit fill rate)
+    ///
+    /// More starting stock never hurts the fill rate, so we double the starting quantity until
+    /// the target is met and then bisect. Every trial sees exactly the same `count` years of
+    /// customers (common random numbers), so noise between trials can't send the search the
+    /// wrong way. Returns (starting quantity, its fill rate), or None if even `max_quantity`
+    /// isn't enough.
+    #[args(count = "1000", max_quantity = "1 << 32")]
+    fn solve_starting_quantity(
+        &self,
+        py: Python<'_>,
+        target_fill_rate: f64,
+        count: usize,
+        max_quantity: usize,
+    ) -> Option<(usize, f64)> {
+        py.allow_threads(|| self.smallest_starting_quantity(target_fill_rate, count, max_quantity))
+    }
+
+    /// How much safety stock this simulation's forecast error costs
+    ///
+    /// We find the smallest safety stock that reaches `target_fill_rate` (unit fill rate) with
+    /// forecast_bias and forecast_mape as they are, and again with a perfect forecast, each
+    /// year starting with the safety stock on hand. Both searches see the same `count` years
+    /// of customers. Returns a dict with "safety_stock", "perfect_forecast_safety_stock" and
+    /// "extra_safety_stock", the difference; each is None if even `max_quantity` isn't enough.
+    #[args(count = "1000", max_quantity = "1 << 32")]
+    fn forecast_error_safety_stock(
+        &self,
+        py: Python<'_>,
+        target_fill_rate: f64,
+        count: usize,
+        max_quantity: usize,
+    ) -> PyResult<PyObject> {
+        let seed = self.rng().gen();
+        let mut perfect = self.clone();
+        perfect.forecast_bias = 0.0;
+        perfect.forecast_mape = 0.0;
+        let (noisy, perfect) = py.allow_threads(|| {
+            rayon::join(
+                || self.smallest_safety_stock(target_fill_rate, count, max_quantity, seed),
+                || perfect.smallest_safety_stock(target_fill_rate, count, max_quantity, seed),
+            )
+        });
+        let (noisy, perfect) = (noisy.map(|(s, _)| s), perfect.map(|(s, _)| s));
+        let result = PyDict::new(py);
+        result.set_item("safety_stock", noisy)?;
+        result.set_item("perfect_forecast_safety_stock", perfect)?;
+        result.set_item(
+            "extra_safety_stock",
+            match (noisy, perfect) {
+                (Some(noisy), Some(perfect)) => Some(noisy as isize - perfect as isize),
+                _ => None,
+            },
+        )?;
+        Ok(result.to_object(py))
+    }
+
+    /// Estimate how much the fill rates and cost change per unit change of each parameter
+    ///
+    /// For each name in `params` (see optimize.grid_search for the choices) we simulate
+    /// `count` years with that parameter nudged up and down by the matching entry of
+    /// `epsilons`, and take the slope between them. Both sides see exactly the same customers
+    /// (common random numbers), so the slope reflects the parameter rather than luck. Where
+    /// nudging down isn't possible (like a lead time of 0), we compare to the current value.
+    ///
+    /// Returns a dict of {param: {"unit_fill_rate": slope, "transaction_fill_rate": slope,
+    /// "total_cost": slope}}, with costs per year as in optimize.grid_search.
+    #[args(holding_cost = "0.01", order_cost = "1.0", shortage_cost = "1.0")]
+    #[allow(clippy::too_many_arguments)]
+    fn sensitivity(
+        &self,
+        py: Python<'_>,
+        starting_quantity: usize,
+        params: Vec<String>,
+        epsilons: Vec<f64>,
+        count: usize,
+        holding_cost: f64,
+        order_cost: f64,
+        shortage_cost: f64,
+    ) -> PyResult<PyObject> {
+        if params.len() != epsilons.len() {
+            return Err(ValueError::py_err(
+                "params and epsilons must be the same length",
+            ));
+        }
+        let costs = Costs {
+            holding: holding_cost,
+            order: order_cost,
+            shortage: shortage_cost,
+        };
+        let center = Candidate {
+            sim: self.clone(),
+            starting_quantity,
+        };
+        let result = PyDict::new(py);
+        for (name, &epsilon) in params.iter().zip(&epsilons) {
+            let x = center.get(name)?;
+            let up = center.with(name, x + epsilon)?;
+            let down = center.with(name, x - epsilon);
+            let (low, step) = match &down {
+                Ok(down) => (down, 2.0 * epsilon),
+                Err(_) => (&center, epsilon),
+            };
+            let seed = self.rng().gen();
+            let (high, low) = py.allow_threads(|| {
+                (
+                    up.evaluate_with(count, costs, &mut StdRng::seed_from_u64(seed)),
+                    low.evaluate_with(count, costs, &mut StdRng::seed_from_u64(seed)),
+                )
+            });
+            let slopes = PyDict::new(py);
+            slopes.set_item(
+                "unit_fill_rate",
+                (high.unit_fill_rate - low.unit_fill_rate) / step,
+            )?;
+            slopes.set_item(
+                "transaction_fill_rate",
+                (high.transaction_fill_rate - low.transaction_fill_rate) / step,
+            )?;
+            slopes.set_item("total_cost", (high.total_cost - low.total_cost) / step)?;
+            result.set_item(name, slopes)?;
+        }
+        Ok(result.to_object(py))
+    }
+
+    /// Repeat the simulation, keeping every repetition's result for later analysis
+    ///
+    /// The results remember the seed they were simulated with, even if this simulation
+    /// doesn't have one, so they can always be reproduced. `callbacks` are called as in
+    /// repeat_simulate_demand.
+    #[args(callbacks = "vec![]")]
+    fn repeat_simulate_demand_stored(
+        &self,
+        py: Python<'_>,
+        starting_quantity: usize,
+        count: usize,
+        callbacks: Vec<&PyAny>,
+    ) -> PyResult<Repetitions> {
+        let seed = self.call_seed();
+        let mut rng = StdRng::seed_from_u64(seed);
+        let metadata = self.describe(py, Some(seed))?;
+        metadata.set_item("starting_quantity", starting_quantity)?;
+        metadata.set_item("count", count)?;
+        let config = metadata.copy()?;
+        config.set_item("method", "repeat_simulate_demand_stored")?;
+        let callbacks = Callbacks::start(callbacks, config)?;
+        let mut stock = DailyStock(vec![0.0; 365]);
+        let years: Vec<Summary> = (0..count)
+            .map(|_| {
+                self.simulate_year(starting_quantity, &mut rng, &mut stock)
+                    .summary()
+            })
+            .collect();
+        stock
+            .0
+            .iter_mut()
+            .for_each(|day| *day /= count.max(1) as f64);
+        let (st, ss, ft, fs) = years.iter().fold((0, 0, 0, 0), |(st, ss, ft, fs), year| {
+            (st + year.0, ss + year.1, ft + year.2, fs + year.3)
+        });
+        callbacks.end(py, summarize(st, ss, ft, fs), &[])?;
+        Ok(Repetitions {
+            years,
+            daily_stock: stock.0,
+            caption: format!(
+                "{} years of safety stock {}, lead time {}, order quantity {}, starting with {} \
+                 (seed {})",
+                count,
+                self.safety_stock,
+                self.lead_time,
+                self.order_quantity,
+                starting_quantity,
+                seed
+            ),
+            metadata: metadata.to_object(py),
+            seed,
+        })
+    }
+
+    /// Repeat the simulation, and also estimate quantiles of the per-repetition outcomes
+    ///
+    /// Returns the same totals as repeat_simulate_demand, plus the requested quantiles
+    /// (by default P01, P50 and P99) of the transaction fill rate, the unit fill rate, and the
+    /// stock left at the end of the year. These come from t-digest sketches, so memory stays
+    /// bounded no matter how large `count` is, at the cost of being approximate.
+    #[args(quantiles = "vec![0.01, 0.5, 0.99]")]
+    fn repeat_simulate_demand_quantiles(
+        &self,
+        starting_quantity: usize,
+        count: usize,
+        quantiles: Vec<f64>,
+    ) -> (Summary, Vec<f64>, Vec<f64>, Vec<f64>) {
+        let mut rng = self.rng();
+        let (mut st, mut ss, mut ft, mut fs) = (0, 0, 0, 0);
+        let mut transaction_rate = Sketch::new();
+        let mut unit_rate = Sketch::new();
+        let mut ending_stock = Sketch::new();
+        for _ in 0..count {
+            let year = self.simulate_year(starting_quantity, &mut rng, |_, _, _| {});
+            let (xst, xss, xft, xfs, xtr, xur) = year.summary();
+            st += xst;
+            ss += xss;
+            ft += xft;
+            fs += xfs;
+            // A year with no customers at all has no fill rate to speak of
+            if xst + xft > 0 {
+                transaction_rate.push(xtr);
+                unit_rate.push(xur);
+            }
+            ending_stock.push(year.ending_stock as f64);
+        }
+        (
+            summarize(st, ss, ft, fs),
+            transaction_rate.quantiles(&quantiles),
+            unit_rate.quantiles(&quantiles),
+            ending_stock.quantiles(&quantiles),
+        )
+    }
+
+    /// Simulate `count` years and write every day of them (customers, demand, sales, failed
+    /// sales and ending stock) to a new HDF5 file at `path`, `chunk` repetitions at a time
+    ///
+    /// Each is a (count, 365) dataset, compressed, with the config and seed to reproduce it
+    /// in the file's attributes. This needs h5py. Returns the same totals as
+    /// repeat_simulate_demand.
+    #[args(chunk = "1024")]
+    fn export_trace_hdf5(
+        &self,
+        py: Python<'_>,
+        path: &str,
+        starting_quantity: usize,
+        count: usize,
+        chunk: usize,
+    ) -> PyResult<Summary> {
+        trace::export_hdf5(py, self, path, starting_quantity, count, chunk)
+    }
+
+    /// Repeat the simulation, and also keep a uniform sample of the individual transactions
+    ///
+    /// Returns the same totals as repeat_simulate_demand, plus up to `sample_size` transactions
+    /// as (repetition, day, request, filled). Every transaction across every repetition is
+    /// equally likely to be in the sample, but we never hold more than `sample_size` of them.
+    fn repeat_simulate_demand_sampled(
+        &self,
+        starting_quantity: usize,
+        count: usize,
+        sample_size: usize,
+    ) -> (Summary, Vec<(usize, usize, usize, bool)>) {
+        let mut rng = self.rng();
+        // The reservoir needs its own random numbers while the simulation is using `rng`
+        let mut reservoir_rng = StdRng::seed_from_u64(rng.gen());
+        let mut reservoir = Reservoir::new(sample_size);
+        let (mut st, mut ss, mut ft, mut fs) = (0, 0, 0, 0);
+        for repetition in 0..count {
+            let year = self.simulate_year(starting_quantity, &mut rng, |day, request, filled| {
+                reservoir.offer((repetition, day, request, filled), &mut reservoir_rng)
+            });
+            st += year.successful_transactions;
+            ss += year.successful_sales;
+            ft += year.failed_transactions;
+            fs += year.failed_sales;
+        }
+        (summarize(st, ss, ft, fs), reservoir.into_vec())
+    }
+
+    /// Simulate the distribution of demand during the lead time
+    ///
+    /// Each of `count` years is cut into back-to-back windows of lead_time days (a partial
+    /// window at the end of the year is dropped), and we add up the units customers asked for
+    /// in each window, whether or not they got them. That's the quantity analytic safety-stock
+    /// formulas reason about, so this is the place to check them against the simulation.
+    ///
+    /// Returns a dict with "histogram" ({units demanded: number of windows}, in increasing
+    /// order of demand), "windows", "mean", "std_dev", and the "metadata" to reproduce it.
+    fn lead_time_demand(&self, py: Python<'_>, count: usize) -> PyResult<PyObject> {
+        let windows_per_year = 365 / self.lead_time;
+        let mut histogram = std::collections::BTreeMap::new();
+        let mut moments = Welford::new();
+        let seed = self.call_seed();
+        let mut rng = StdRng::seed_from_u64(seed);
+        for _ in 0..count {
+            let mut demand = vec![0usize; windows_per_year];
+            self.simulate_year(self.safety_stock, &mut rng, |day, request, _| {
+                if let Some(window) = demand.get_mut(day / self.lead_time) {
+                    *window += request;
+                }
+            });
+            for window in demand {
+                *histogram.entry(window).or_insert(0usize) += 1;
+                moments.push(window as f64);
+            }
+        }
+
+        let result = PyDict::new(py);
+        let bins = PyDict::new(py);
+        for (units, windows) in histogram {
+            bins.set_item(units, windows)?;
+        }
+        result.set_item("histogram", bins)?;
+        result.set_item("windows", count * windows_per_year)?;
+        result.set_item("mean", moments.mean())?;
+        result.set_item("std_dev", moments.variance().sqrt())?;
+        let metadata = self.describe(py, Some(seed))?;
+        metadata.set_item("count", count)?;
+        result.set_item("metadata", metadata)?;
+        Ok(result.to_object(py))
+    }
+
+    /// Lazily yield the result of each repetition, one at a time
+    ///
+    /// The simulations run in a background thread, which stays at most a few results ahead
+    /// of whoever is consuming them, so `count` can be as large as you like.
+    fn iter_simulations(&self, starting_quantity: usize, count: usize) -> SimulationIter {
+        // A bounded channel makes the producer wait once it's this far ahead
+        let (sender, receiver) = sync_channel(64);
+        let sim = self.clone();
+        thread::spawn(move || {
+            let mut rng = sim.rng();
+            for _ in 0..count {
+                // If the iterator was dropped, nobody is listening anymore, so stop early
+                if sender
+                    .send(sim.simulate_summary(starting_quantity, &mut rng))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+        SimulationIter {
+            receiver: Mutex::new(receiver),
+        }
+    }
+}
+
+/// Simulations compare by their parameters and seed, not by identity, so they work as dict
+/// keys and in sets
+#[cfg(feature = "python")]
+#[pyproto]
+impl PyObjectProtocol for Simulation {
+    fn __richcmp__(&self, other: &PyAny, op: CompareOp) -> PyResult<PyObject> {
+        let py = other.py();
+        let other = match other.downcast_ref::<Simulation>() {
+            Ok(other) => other,
+            Err(_) => return Ok(py.NotImplemented()),
+        };
+        Ok(match op {
+            CompareOp::Eq => (self == other).into_py(py),
+            CompareOp::Ne => (self != other).into_py(py),
+            _ => py.NotImplemented(),
+        })
+    }
+
+    fn __hash__(&self) -> PyResult<isize> {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        Ok(hasher.finish() as isize)
+    }
+}
+
+/// The results of each of many repetitions, kept around for analysis
+///
+/// Created by Simulation.repeat_simulate_demand_stored()
+#[cfg(feature = "python")]
+#[pyclass(module = "rustsim")]
+struct Repetitions {
+    years: Vec<Summary>,
+    /// Stock on the shelf at the end of each day of the year, averaged over the repetitions
+    daily_stock: Vec<f64>,
+    /// What these are, in a few words, for the top of the notebook table
+    caption: String,
+    metadata: PyObject,
+    /// The seed the years were simulated with, which the bootstrap's resampling comes from too
+    seed: u64,
+}
+
+/// Adds up the stock on the shelf at the end of each day, over many years
+#[cfg(feature = "python")]
+struct DailyStock(Vec<f64>);
+
+#[cfg(feature = "python")]
+impl Observer for &mut DailyStock {
+    fn transaction(&mut self, _day: usize, _request: usize, _filled: bool) {}
+
+    fn end_of_day(&mut self, day: usize, stock: usize) {
+        self.0[day] += stock as f64;
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl Repetitions {
+    /// How these results were made, as in Simulation.metadata(), plus the starting
+    /// quantity and count
+    fn metadata(&self, py: Python<'_>) -> PyObject {
+        self.metadata.clone_ref(py)
+    }
+
+    /// Each repetition's result, as returned by simulate_demand
+    fn results(&self) -> Vec<Summary> {
+        self.years.clone()
+    }
+
+    /// The stock on the shelf at the end of each of the 365 days, averaged over the
+    /// repetitions
+    fn daily_stock(&self) -> Vec<f64> {
+        self.daily_stock.clone()
+    }
+
+    /// A table of the metrics bootstrap_ci() knows, plus a sparkline of the daily stock, for
+    /// Jupyter
+    fn _repr_html_(&self) -> String {
+        let mut rows: Vec<Vec<String>> = Metric::ALL
+            .iter()
+            .map(|&(name, metric)| {
+                let estimate = metric.estimate(&self.years);
+                let estimate = match metric {
+                    Metric::TransactionFillRate | Metric::UnitFillRate => {
+                        format!("{:.4}", estimate)
+                    }
+                    _ => format!("{:.1}", estimate),
+                };
+                vec![name.to_string(), estimate]
+            })
+            .collect();
+        let low = self
+            .daily_stock
+            .iter()
+            .cloned()
+            .fold(f64::INFINITY, f64::min);
+        let high = self.daily_stock.iter().cloned().fold(0.0, f64::max);
+        rows.push(vec![
+            "daily stock, by week".to_string(),
+            format!(
+                "{:.1} <span style=\"font-family: monospace\">{}</span> {:.1}",
+                low,
+                html::sparkline(&self.daily_stock, 52),
+                high
+            ),
+        ]);
+        html::table(
+            &html::escape(&self.caption),
+            &["metric", "per year (rates pooled)"],
+            &rows,
+        )
+    }
 
+    /// Each repetition's result as a row of a pandas DataFrame, with the metadata in its
+    /// `attrs`
+    fn to_pandas(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let counter = |field: fn(&Summary) -> usize| {
+            self.years
+                .iter()
+                .map(field)
+                .collect::<Vec<_>>()
+                .to_object(py)
+        };
+        let rate = |field: fn(&Summary) -> f64| {
+            self.years
+                .iter()
+                .map(field)
+                .collect::<Vec<_>>()
+                .to_object(py)
+        };
+        let frame = data_frame(
+            py,
+            vec![
+                ("successful_transactions", counter(|y| y.0)),
+                ("successful_sales", counter(|y| y.1)),
+                ("failed_transactions", counter(|y| y.2)),
+                ("failed_sales", counter(|y| y.3)),
+                ("transaction_fill_rate", rate(|y| y.4)),
+                ("unit_fill_rate", rate(|y| y.5)),
+            ],
+        )?;
+        frame.getattr(py, "attrs")?.call_method1(
+            py,
+            "__setitem__",
+            ("metadata", self.metadata.clone_ref(py)),
+        )?;
+        Ok(frame)
+    }
+
+    /// Bias-corrected bootstrap confidence interval of a metric
+    ///
+    /// `metric` is one of successful_transactions, successful_sales, failed_transactions,
+    /// failed_sales (averaged per repetition), or transaction_fill_rate, unit_fill_rate
+    /// (pooled over all repetitions). Returns (estimate, low, high).
+    #[args(n_boot = "1000", confidence = "0.95")]
+    fn bootstrap_ci(
+        &self,
+        metric: &str,
+        n_boot: usize,
+        confidence: f64,
+    ) -> PyResult<(f64, f64, f64)> {
+        let metric = Metric::parse(metric)
+            .ok_or_else(|| ValueError::py_err(format!("Unknown metric {:?}", metric)))?;
+        Ok(bootstrap::bootstrap_ci(
+            &self.years,
+            metric,
+            n_boot,
+            confidence,
+            &mut streams::stream(self.seed, streams::BOOTSTRAP),
+        ))
+    }
+}
+
+/// `value` as a count, or a ValueError naming it if it's below `least`
+///
+/// Counts come from Python as signed, so a negative gets this rather than an OverflowError.
+#[cfg(feature = "python")]
+fn at_least(name: &str, value: isize, least: isize) -> PyResult<usize> {
+    if value < least {
+        Err(ValueError::py_err(format!(
+            "{} must be >= {}, not {}",
+            name, least, value
+        )))
+    } else {
+        Ok(value as usize)
+    }
+}
+
+/// The mixture of `components`, or a ValueError saying what's wrong with them
+#[cfg(feature = "python")]
+fn mixture(components: Option<Vec<(f64, f64)>>) -> PyResult<Option<Mixture>> {
+    components
+        .map(|components| Mixture::new(&components))
+        .transpose()
+        .map_err(ValueError::py_err)
+}
+
+/// A pandas DataFrame of `columns`, in that order
+#[cfg(feature = "python")]
+fn data_frame(py: Python<'_>, columns: Vec<(&str, PyObject)>) -> PyResult<PyObject> {
+    let data = PyDict::new(py);
+    let names: Vec<&str> = columns.iter().map(|&(name, _)| name).collect();
+    for (name, values) in columns {
+        data.set_item(name, values)?;
+    }
+    let options = PyDict::new(py);
+    options.set_item("columns", names)?;
+    let frame = py
+        .import("pandas")?
+        .call("DataFrame", (data,), Some(options))?;
+    Ok(frame.to_object(py))
+}
+
+/// Python iterator over the results of repeated simulations
+///
+/// Created by Simulation.iter_simulations()
+#[cfg(feature = "python")]
 #[pyclass(module = "rustsim")]
-struct Simulation {
-    safety_stock: usize,
-    lead_time: usize,
-    order_quantity: usize,
-    job_lot_zipf: f64,
-    itemwise_traffic_zipf: f64,
+struct SimulationIter {
+    // Receivers can't be shared between threads, but we need to lend it to a thread that
+    // runs without the GIL, so it's wrapped in a Mutex.
+    receiver: Mutex<Receiver<Summary>>,
 }
 
-#[pymethods]
+#[cfg(feature = "python")]
+#[pyproto]
+impl PyIterProtocol for SimulationIter {
+    fn __iter__(slf: PyRefMut<Self>) -> PyResult<Py<SimulationIter>> {
+        Ok(slf.into())
+    }
+
+    fn __next__(slf: PyRefMut<Self>) -> PyResult<Option<Summary>> {
+        let gil = Python::acquire_gil();
+        let receiver = &slf.receiver;
+        // Let other Python threads run while we wait on the simulation thread.
+        // An error here means the simulation thread finished, so the iterator is done.
+        Ok(gil
+            .python()
+            .allow_threads(|| receiver.lock().unwrap().recv().ok()))
+    }
+}
+
+/// Simulation Implementation, continued
+///
+/// This group doesn't mention pymethods, and isn't visible from Python, but it's the Rust
+/// API for anything that embeds the simulator without Python, like rustwasmsim
 impl Simulation {
-    #[new]
-    fn new(
-        obj: &PyRawObject,
+    /// A simulation with the default zipf exponents for anything left as None
+    pub fn new(
         safety_stock: usize,
         lead_time: usize,
         order_quantity: usize,
         job_lot_zipf: Option<f64>,
         itemwise_traffic_zipf: Option<f64>,
-    ) {
-        obj.init(Simulation {
+        seed: Option<u64>,
+    ) -> Simulation {
+        Simulation {
             safety_stock,
             lead_time,
             order_quantity,
             job_lot_zipf: job_lot_zipf.unwrap_or(2.75),
             itemwise_traffic_zipf: itemwise_traffic_zipf.unwrap_or(4.0),
+            seed,
+            policy: None,
+            job_lot_mixture: None,
+            itemwise_traffic_mixture: None,
+            max_request: None,
+            max_request_mode: Truncation::Clip,
+            compound_poisson: None,
+            forecast_bias: 0.0,
+            forecast_mape: 0.0,
+            timings: LastTimings::default(),
+        }
+    }
+
+    /// Why these parameters can't be simulated, if they can't
+    ///
+    /// Simulation::new() takes anything, so whatever builds one from outside input should
+    /// check it with this.
+    pub fn check_parameters(&self) -> Result<(), String> {
+        if self.lead_time < 1 {
+            return Err(format!("lead_time must be >= 1, not {}", self.lead_time));
+        }
+        if self.order_quantity < 1 {
+            return Err(format!(
+                "order_quantity must be >= 1, not {}",
+                self.order_quantity
+            ));
+        }
+        for &(name, exponent) in &[
+            ("job_lot_zipf", self.job_lot_zipf),
+            ("itemwise_traffic_zipf", self.itemwise_traffic_zipf),
+        ] {
+            // Written this way around so NaN fails too
+            if !(exponent > 0.0 && exponent.is_finite()) {
+                return Err(format!("{} must be > 0, not {}", name, exponent));
+            }
+        }
+        if self.compound_poisson.is_some()
+            && (self.job_lot_mixture.is_some() || self.itemwise_traffic_mixture.is_some())
+        {
+            return Err(
+                "compound_poisson replaces the zipfs, so it can't have mixtures".to_string(),
+            );
+        }
+        if self.max_request == Some(0) {
+            return Err("max_request must be >= 1, not 0".to_string());
+        }
+        if !(self.forecast_bias > -1.0 && self.forecast_bias.is_finite()) {
+            return Err(format!(
+                "forecast_bias must be > -1, not {}",
+                self.forecast_bias
+            ));
+        }
+        if !(self.forecast_mape >= 0.0 && self.forecast_mape.is_finite()) {
+            return Err(format!(
+                "forecast_mape must be >= 0, not {}",
+                self.forecast_mape
+            ));
+        }
+        if self.noisy_forecast() && self.policy.is_none() {
+            return Err(
+                "forecast_bias and forecast_mape need a policy; the built-in rule doesn't \
+                 forecast"
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Repeat the simulation `count` times and add it all up, like repeat_simulate_demand
+    /// with its defaults
+    ///
+    /// This is backend::Scalar, in one batch seeded from the simulation's generator.
+    pub fn repeat_summary(&self, starting_quantity: usize, count: usize) -> Summary {
+        let mut scalar = backend::Scalar::default();
+        backend::repeat(
+            &mut scalar,
+            self,
+            starting_quantity,
+            count,
+            count,
+            &mut self.rng(),
+        )
+        .expect("The scalar backend runs any simulation")
+    }
+
+    /// Repeat the simulation in batches until the unit fill rate's 95% confidence interval is
+    /// narrower than `target_ci_width`, or `max_count` repetitions are done, like repeat_until
+    ///
+    /// Each batch is seeded the way backend::repeat seeds them, so the first batch is exactly
+    /// repeat_summary of the same size.
+    pub fn repeat_summary_until(
+        &self,
+        starting_quantity: usize,
+        target_ci_width: f64,
+        max_count: usize,
+    ) -> (Summary, usize, f64) {
+        const BATCH_SIZE: usize = 1000;
+        let (mut st, mut ss, mut ft, mut fs) = (0, 0, 0, 0);
+        let mut unit_rate = Welford::new();
+        let mut done = 0;
+        let mut rng = self.rng();
+        while done < max_count && unit_rate.ci_width() > target_ci_width {
+            let mut batch = StdRng::seed_from_u64(rng.gen());
+            for _ in 0..BATCH_SIZE.min(max_count - done) {
+                let (xst, xss, xft, xfs, _, xur) =
+                    self.simulate_summary(starting_quantity, &mut batch);
+                st += xst;
+                ss += xss;
+                ft += xft;
+                fs += xfs;
+                if xst + xft > 0 {
+                    unit_rate.push(xur);
+                }
+                done += 1;
+            }
+        }
+        (summarize(st, ss, ft, fs), done, unit_rate.ci_width())
+    }
+
+    /// Simulate years `years` of a run seeded with `master`, each year with its own stream
+    /// (see streams.rs)
+    ///
+    /// The totals depend only on the master seed and which years are in the range, not on how
+    /// the run is split up, so a big run can be farmed out in pieces and added back up to
+    /// exactly the same result.
+    pub fn repeat_summary_streams(
+        &self,
+        starting_quantity: usize,
+        master: u64,
+        years: Range<u64>,
+    ) -> Summary {
+        let (mut st, mut ss, mut ft, mut fs) = (0, 0, 0, 0);
+        for year in years {
+            let (xst, xss, xft, xfs, _, _) =
+                self.simulate_summary(starting_quantity, &mut streams::stream(master, year));
+            st += xst;
+            ss += xss;
+            ft += xft;
+            fs += xfs;
+        }
+        summarize(st, ss, ft, fs)
+    }
+
+    /// Find out which of two configurations has the better unit fill rate, as quickly as
+    /// possible, like compare_sequential
+    ///
+    /// Fails unless 0 < `alpha` < 1 and 0 < `indifference` < 0.5, since the test's thresholds
+    /// are meaningless outside them.
+    pub fn sequential_comparison(
+        &self,
+        other: &Simulation,
+        starting_quantity: usize,
+        alpha: f64,
+        indifference: f64,
+        max_count: usize,
+    ) -> Result<(Option<String>, usize, usize, usize), String> {
+        // Written this way around so NaN fails too
+        if !(alpha > 0.0 && alpha < 1.0) {
+            return Err(format!("alpha must be between 0 and 1, not {}", alpha));
+        }
+        if !(indifference > 0.0 && indifference < 0.5) {
+            return Err(format!(
+                "indifference must be between 0 and 0.5, not {}",
+                indifference
+            ));
+        }
+        // Each win moves the log likelihood ratio by this much, one way or the other
+        let step = ((0.5 + indifference) / (0.5 - indifference)).ln();
+        let threshold = ((1.0 - alpha) / alpha).ln();
+        let mut log_likelihood_ratio = 0.0;
+        let (mut self_wins, mut other_wins) = (0, 0);
+        let mut seeds = self.rng();
+        for repetition in 1..=max_count {
+            let seed = seeds.gen();
+            let mine = self
+                .simulate_year(
+                    starting_quantity,
+                    &mut StdRng::seed_from_u64(seed),
+                    |_, _, _| {},
+                )
+                .summary()
+                .5;
+            let theirs = other
+                .simulate_year(
+                    starting_quantity,
+                    &mut StdRng::seed_from_u64(seed),
+                    |_, _, _| {},
+                )
+                .summary()
+                .5;
+            if mine > theirs {
+                self_wins += 1;
+                log_likelihood_ratio += step;
+            } else if theirs > mine {
+                other_wins += 1;
+                log_likelihood_ratio -= step;
+            }
+            if log_likelihood_ratio >= threshold {
+                return Ok((Some("self".into()), repetition, self_wins, other_wins));
+            } else if log_likelihood_ratio <= -threshold {
+                return Ok((Some("other".into()), repetition, self_wins, other_wins));
+            }
+        }
+        Ok((None, max_count, self_wins, other_wins))
+    }
+
+    /// Find the smallest starting quantity that reaches `target_fill_rate` (unit fill rate),
+    /// like solve_starting_quantity
+    ///
+    /// Every trial sees the same `count` years of customers, so noise between trials can't
+    /// send the search the wrong way. Returns None if even `max_quantity` isn't enough.
+    pub fn smallest_starting_quantity(
+        &self,
+        target_fill_rate: f64,
+        count: usize,
+        max_quantity: usize,
+    ) -> Option<(usize, f64)> {
+        let seed = self.rng().gen();
+        search::smallest_reaching(target_fill_rate, max_quantity, |starting_quantity| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let (mut ss, mut fs) = (0, 0);
+            for _ in 0..count {
+                let (_, xss, _, xfs, _, _) = self.simulate_summary(starting_quantity, &mut rng);
+                ss += xss;
+                fs += xfs;
+            }
+            fill_rate(ss, fs)
+        })
+    }
+
+    /// Find the smallest safety stock that reaches `target_fill_rate` (unit fill rate), each
+    /// year starting with the safety stock on hand, drawing every trial's years from `seed`
+    ///
+    /// Returns None if even `max_quantity` isn't enough.
+    pub fn smallest_safety_stock(
+        &self,
+        target_fill_rate: f64,
+        count: usize,
+        max_quantity: usize,
+        seed: u64,
+    ) -> Option<(usize, f64)> {
+        let (it_zipf, jl_zipf) = (self.traffic(), self.job_lots());
+        search::smallest_reaching(target_fill_rate, max_quantity, |safety_stock| {
+            let mut sim = self.clone();
+            sim.safety_stock = safety_stock;
+            let mut rng = StdRng::seed_from_u64(seed);
+            // Forecast errors come from a stream of their own, so however noisy the forecast
+            // is, every trial sees the same customers
+            let mut forecast = Some(streams::stream(seed, streams::FORECAST));
+            let (mut ss, mut fs) = (0, 0);
+            for _ in 0..count {
+                let mut shelf = Shelf::new(safety_stock, sim.lead_time);
+                shelf.forecast = forecast.take();
+                let year =
+                    sim.simulate_year_from(&mut shelf, &it_zipf, &jl_zipf, &mut rng, |_, _, _| {});
+                forecast = shelf.forecast.take();
+                ss += year.successful_sales;
+                fs += year.failed_sales;
+            }
+            fill_rate(ss, fs)
+        })
+    }
+
+    /// A random number generator for one call
+    ///
+    /// Seeded simulations start from the same place every time. We use StdRng rather than
+    /// thread_rng either way, so with a fixed seed the CPU engine gives bit-identical results
+    /// on every platform, as long as the rand version stays put (StdRng's algorithm may change
+    /// between releases). That doesn't carry over to the GPU backends: they draw their seeds
+    /// from this too, but float rounding, the work item layout and the driver's compiler all
+    /// differ from device to device, so there a seed only repeats on the same device.
+    pub fn rng(&self) -> StdRng {
+        match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        }
+    }
+
+    #[cfg(feature = "python")]
+    /// The seed for one call: the simulation's own, or a fresh one to record with the results
+    fn call_seed(&self) -> u64 {
+        self.seed.unwrap_or_else(streams::fresh_seed)
+    }
+
+    /// repeat_simulate_demand_vectorized, once it's checked the simulation can run in lanes
+    #[cfg(all(feature = "python", feature = "simd"))]
+    fn vectorized(
+        &self,
+        py: Python<'_>,
+        starting_quantity: usize,
+        count: usize,
+    ) -> PyResult<Summary> {
+        use backend::SimBackend;
+        let mut stopwatch = Stopwatch::start();
+        let mut lanes = backend::Vectorized::default();
+        let summary = py
+            .allow_threads(|| {
+                // The tables come from the simulation's generator, so the batch seeds come from
+                // a stream of their own rather than the numbers the tables were drawn from
+                lanes.configure(self)?;
+                stopwatch.lap(Phase::Precompute);
+                let mut seeds = streams::stream(self.rng().gen(), streams::BATCHES);
+                stopwatch.lap(Phase::Seeds);
+                // Already configured for this simulation, so this is just the lanes
+                let summary = backend::repeat(
+                    &mut lanes,
+                    self,
+                    starting_quantity,
+                    count,
+                    count,
+                    &mut seeds,
+                );
+                stopwatch.lap(Phase::Kernel);
+                summary
+            })
+            .map_err(ValueError::py_err)?;
+        stopwatch.lap(Phase::Reduction);
+        self.timings.set(stopwatch.breakdown());
+        Ok(summary)
+    }
+
+    /// Without the simd feature there are no lanes to run in
+    #[cfg(all(feature = "python", not(feature = "simd")))]
+    fn vectorized(
+        &self,
+        _py: Python<'_>,
+        _starting_quantity: usize,
+        _count: usize,
+    ) -> PyResult<Summary> {
+        Err(RuntimeError::py_err(
+            "rustsim was built without the simd feature, so repeat_simulate_demand_vectorized \
+             isn't available; use repeat_simulate_demand",
+        ))
+    }
+
+    /// Reproducibility metadata for results simulated with `seed`
+    #[cfg(feature = "python")]
+    fn describe<'p>(&self, py: Python<'p>, seed: Option<u64>) -> PyResult<&'p PyDict> {
+        let dict = PyDict::new(py);
+        dict.set_item("backend", "cpu")?;
+        dict.set_item(
+            "device",
+            format!("{}-{}", std::env::consts::ARCH, std::env::consts::OS),
+        )?;
+        dict.set_item("version", env!("CARGO_PKG_VERSION"))?;
+        dict.set_item("seed", seed)?;
+        let params = PyDict::new(py);
+        params.set_item("safety_stock", self.safety_stock)?;
+        params.set_item("lead_time", self.lead_time)?;
+        params.set_item("order_quantity", self.order_quantity)?;
+        params.set_item("job_lot_zipf", self.job_lot_zipf)?;
+        params.set_item("itemwise_traffic_zipf", self.itemwise_traffic_zipf)?;
+        params.set_item("policy", self.policy.as_ref().map(Policy::source))?;
+        params.set_item("job_lot_mixture", self.job_lot_mixture())?;
+        params.set_item("itemwise_traffic_mixture", self.itemwise_traffic_mixture())?;
+        params.set_item("max_request", self.max_request)?;
+        params.set_item("max_request_mode", self.max_request_mode.name())?;
+        params.set_item("compound_poisson", self.compound_poisson(py)?)?;
+        params.set_item("forecast_bias", self.forecast_bias)?;
+        params.set_item("forecast_mape", self.forecast_mape)?;
+        dict.set_item("params", params)?;
+        Ok(dict)
+    }
+
+    #[cfg(any(feature = "python", test))]
+    /// Simulate a year, and check that everything in it adds up
+    ///
+    /// Returns a description of the first thing that doesn't.
+    fn check_invariants<R: Rng>(
+        &self,
+        starting_quantity: usize,
+        rng: &mut R,
+    ) -> Result<(), String> {
+        let (mut transactions, mut demand) = (0, 0);
+        let year = self.simulate_year(starting_quantity, rng, |_, request, _| {
+            transactions += 1;
+            demand += request;
         });
+        let (st, ss, ft, fs, transaction_rate, unit_rate) = year.summary();
+        if st + ft != transactions {
+            return Err(format!(
+                "{} transactions were counted, but {} happened",
+                st + ft,
+                transactions
+            ));
+        }
+        if ss + fs != demand {
+            return Err(format!(
+                "{} units of demand were counted, but {} were requested",
+                ss + fs,
+                demand
+            ));
+        }
+        if ss > demand {
+            return Err(format!("Sold {} units with only {} demanded", ss, demand));
+        }
+        // Stock only comes in on trucks and only leaves in sales; if it had ever gone
+        // negative this couldn't balance
+        if starting_quantity + year.units_received != ss + year.ending_stock {
+            return Err(format!(
+                "Started with {} and received {}, but sold {} and ended with {}",
+                starting_quantity, year.units_received, ss, year.ending_stock
+            ));
+        }
+        if year.ending_stock > year.peak_stock {
+            return Err(format!(
+                "Ended with {} but the peak was only {}",
+                year.ending_stock, year.peak_stock
+            ));
+        }
+        for rate in &[transaction_rate, unit_rate] {
+            if !(0.0..=1.0).contains(rate) {
+                return Err(format!("Fill rate {} is outside [0, 1]", rate));
+            }
+        }
+        Ok(())
     }
 
-    /// Do exactly the same search Python does
+    /// Simulate one year and summarize it
+    pub fn simulate_summary<R: Rng>(&self, starting_quantity: usize, rng: &mut R) -> Summary {
+        self.simulate_year(starting_quantity, rng, |_, _, _| {})
+            .summary()
+    }
+
+    /// Simulate one year, telling `observe` about every transaction as (day, request, filled)
+    fn simulate_year<R: Rng, O: Observer>(
+        &self,
+        starting_quantity: usize,
+        rng: &mut R,
+        observe: O,
+    ) -> Year {
+        let jl_zipf = self.job_lots();
+        let it_zipf = self.traffic();
+        self.simulate_year_with(starting_quantity, &it_zipf, &jl_zipf, rng, observe)
+    }
+
+    /// Simulate one year like simulate_year, but drawing customers from other distributions
     ///
-    /// This method performs all it's conversions automatically
-    fn simulate_demand_inner(
+    /// `it_dist` is how many customers arrive each day and `jl_dist` is how many each wants.
+    fn simulate_year_with<R, O, IT, JL>(
         &self,
         starting_quantity: usize,
-    ) -> (usize, usize, usize, usize, f64, f64) {
-        let mut successful_transactions = 0;
-        let mut successful_sales = 0;
-        let mut failed_transactions = 0;
-        let mut failed_sales = 0;
-        let mut stock = starting_quantity;
-        let mut trucks = vec![0; self.lead_time];
-        let mut rng = rand::thread_rng();
-        let jl_zipf = zipf::ZipfDistribution::new(1000, self.job_lot_zipf).unwrap();
-        let it_zipf = zipf::ZipfDistribution::new(1000, self.itemwise_traffic_zipf).unwrap();
+        it_dist: &IT,
+        jl_dist: &JL,
+        rng: &mut R,
+        observe: O,
+    ) -> Year
+    where
+        R: Rng,
+        O: Observer,
+        IT: Distribution<usize>,
+        JL: Distribution<usize>,
+    {
+        let mut shelf = Shelf::new(starting_quantity, self.lead_time);
+        self.simulate_year_from(&mut shelf, it_dist, jl_dist, rng, observe)
+    }
+
+    /// Simulate one year like simulate_year_with, starting from `shelf` and leaving it as the
+    /// year ends
+    fn simulate_year_from<R, O, IT, JL>(
+        &self,
+        shelf: &mut Shelf,
+        it_dist: &IT,
+        jl_dist: &JL,
+        rng: &mut R,
+        mut observe: O,
+    ) -> Year
+    where
+        R: Rng,
+        O: Observer,
+        IT: Distribution<usize>,
+        JL: Distribution<usize>,
+    {
+        let starting_quantity = shelf.stock;
+        let mut year = Year {
+            successful_transactions: 0,
+            successful_sales: 0,
+            failed_transactions: 0,
+            failed_sales: 0,
+            ending_stock: starting_quantity,
+            stock_days: 0,
+            trucks_ordered: 0,
+            peak_stock: starting_quantity,
+            units_received: 0,
+        };
+        for _ in 0..365 {
+            let day = self.simulate_day_with(shelf, it_dist, jl_dist, rng, &mut observe);
+            year.successful_transactions += day.successful_transactions;
+            year.successful_sales += day.successful_sales;
+            year.failed_transactions += day.failed_transactions;
+            year.failed_sales += day.failed_sales;
+            year.stock_days += day.closing_stock;
+            year.trucks_ordered += (day.ordered > 0) as usize;
+            year.peak_stock = year.peak_stock.max(day.opening_stock);
+            year.units_received += day.received;
+        }
+        year.ending_stock = shelf.stock;
+        year
+    }
 
+    #[cfg(feature = "python")]
+    /// Simulate one year and summarize it, switching to each of `policies` on its day
+    ///
+    /// `policies` is sorted by day. They all have this simulation's lead time, since the
+    /// trucks on the way can't be laid out again mid-year.
+    fn simulate_scheduled_summary<R: Rng>(
+        &self,
+        starting_quantity: usize,
+        policies: &[(usize, Simulation)],
+        rng: &mut R,
+    ) -> Summary {
+        let zipfs = |sim: &Simulation| (sim.traffic(), sim.job_lots());
+        let mut shelf = Shelf::new(starting_quantity, self.lead_time);
+        let (mut sim, mut next) = (self, 0);
+        let (mut it_zipf, mut jl_zipf) = zipfs(sim);
+        let (mut st, mut ss, mut ft, mut fs) = (0, 0, 0, 0);
         for day in 0..365 {
-            // A truck arrived
-            stock += trucks[day % self.lead_time];
-            // This many customers arrive
-            for _customer in 0..it_zipf.sample(&mut rng) {
-                // This customer wants this many
-                let request = jl_zipf.sample(&mut rng);
-                if stock >= request {
-                    // There are enough.
-                    successful_transactions += 1;
-                    successful_sales += request;
-                    stock -= request;
-                } else {
-                    // There are not enough
-                    failed_transactions += 1;
-                    failed_sales += request;
-                }
+            while next < policies.len() && policies[next].0 <= day {
+                sim = &policies[next].1;
+                let (it, jl) = zipfs(sim);
+                it_zipf = it;
+                jl_zipf = jl;
+                next += 1;
             }
-            // The day is over. Start making orders.
-            if stock < self.safety_stock {
-                let short = max(self.safety_stock - stock, 0);
-                let orders = (short + self.order_quantity - 1) / self.order_quantity;
-                trucks[(day + self.lead_time - 1) % self.lead_time] = orders * self.order_quantity;
+            let day = sim.simulate_day_with(&mut shelf, &it_zipf, &jl_zipf, rng, &mut |_, _, _| {});
+            st += day.successful_transactions;
+            ss += day.successful_sales;
+            ft += day.failed_transactions;
+            fs += day.failed_sales;
+        }
+        summarize(st, ss, ft, fs)
+    }
+
+    /// Simulate the day `shelf` is up to, and move it on to the next
+    ///
+    /// Every engine that goes a day at a time comes through here, so a year stepped through
+    /// by SimulationState plays out exactly like one from simulate_year_with.
+    fn simulate_day_with<R, O, IT, JL>(
+        &self,
+        shelf: &mut Shelf,
+        it_dist: &IT,
+        jl_dist: &JL,
+        rng: &mut R,
+        observe: &mut O,
+    ) -> Day
+    where
+        R: Rng,
+        O: Observer,
+        IT: Distribution<usize>,
+        JL: Distribution<usize>,
+    {
+        let day = shelf.day;
+        let mut result = Day {
+            day,
+            ..Day::default()
+        };
+        // A truck arrived
+        result.received = shelf.trucks[day % self.lead_time];
+        shelf.stock += result.received;
+        result.opening_stock = shelf.stock;
+        // This many customers arrive
+        for _customer in 0..it_dist.sample(rng) {
+            // This customer wants this many
+            let request = jl_dist.sample(rng);
+            if serve(&mut shelf.stock, day, request, observe) {
+                result.successful_transactions += 1;
+                result.successful_sales += request;
+            } else {
+                result.failed_transactions += 1;
+                result.failed_sales += request;
             }
         }
-        (
-            successful_transactions,
-            successful_sales,
-            failed_transactions,
-            failed_sales,
-            // Rust enforces that floats and integers stay separate
-            successful_transactions as f64
-                / (successful_transactions as f64 + failed_transactions as f64),
-            successful_sales as f64 / (successful_sales as f64 + failed_sales as f64),
+        // The day is over. Start making orders.
+        observe.end_of_day(day, shelf.stock);
+        result.closing_stock = shelf.stock;
+        let pipeline = shelf.trucks.iter().sum();
+        result.ordered = match shelf.forecast.as_mut() {
+            Some(forecast) => self.order(shelf.stock, pipeline, day, forecast),
+            None => self.order(shelf.stock, pipeline, day, rng),
+        };
+        if result.ordered > 0 {
+            shelf.trucks[(day + self.lead_time - 1) % self.lead_time] = result.ordered;
+        }
+        shelf.day += 1;
+        result
+    }
+}
+
+impl Simulation {
+    /// How many units to order at the end of `day`, with `on_hand` on the shelf and
+    /// `pipeline` on the way
+    ///
+    /// The built-in rule orders whole multiples of order_quantity to get back up to
+    /// safety_stock, and only looks at the shelf. A policy sees the forecast from
+    /// review_forecast(), which only draws from `rng` if the forecast is noisy. That's the
+    /// shelf's own forecast stream where it has one, and the customers' generator otherwise.
+    fn order<R: Rng + ?Sized>(
+        &self,
+        on_hand: usize,
+        pipeline: usize,
+        day: usize,
+        rng: &mut R,
+    ) -> usize {
+        match &self.policy {
+            None if on_hand < self.safety_stock => {
+                let short = max(self.safety_stock - on_hand, 0);
+                short.div_ceil(self.order_quantity) * self.order_quantity
+            }
+            None => 0,
+            Some(policy) => policy.order(&Inputs {
+                on_hand: on_hand as f32,
+                pipeline: pipeline as f32,
+                day: day as f32,
+                forecast: self.review_forecast(rng),
+                safety_stock: self.safety_stock as f32,
+                order_quantity: self.order_quantity as f32,
+                lead_time: self.lead_time as f32,
+            }),
+        }
+    }
+}
+
+impl Simulation {
+    /// Where each day's customer count comes from
+    fn traffic(&self) -> Demand {
+        if let Some(compound) = &self.compound_poisson {
+            return compound.customers();
+        }
+        Demand::new(
+            self.itemwise_traffic_zipf,
+            self.itemwise_traffic_mixture.as_ref(),
+            None,
         )
     }
 
-    /// You can also perform the conversions manually, and you can get access to the Python GIL, which necessary in many cases
-    fn simulate_demand(&self, py: Python<'_>, starting_quantity: usize) -> PyResult<PyObject> {
-        Ok(self.simulate_demand_inner(starting_quantity).into_py(py))
+    /// Where each customer's request comes from
+    fn job_lots(&self) -> Demand {
+        let max_request = self.max_request.map(|limit| (limit, self.max_request_mode));
+        match &self.compound_poisson {
+            Some(compound) => compound.basket(max_request),
+            None => Demand::new(
+                self.job_lot_zipf,
+                self.job_lot_mixture.as_ref(),
+                max_request,
+            ),
+        }
     }
 
-    /// Repeat the simulation many times
-    fn repeat_simulate_demand(
+    /// Whether demand is anything but the two plain zipfs, with a mixture, a cap or compound
+    /// Poisson, which only the CPU engines can draw
+    pub fn custom_demand(&self) -> bool {
+        self.job_lot_mixture.is_some()
+            || self.itemwise_traffic_mixture.is_some()
+            || self.max_request.is_some()
+            || self.compound_poisson.is_some()
+    }
+
+    /// The customers that come in a day, on average
+    fn mean_customers(&self) -> f64 {
+        if self.custom_demand() {
+            self.traffic().mean()
+        } else {
+            controls::zipf_mean(1000, self.itemwise_traffic_zipf)
+        }
+    }
+
+    /// The units a customer asks for, on average
+    fn mean_request(&self) -> f64 {
+        if self.custom_demand() {
+            self.job_lots().mean()
+        } else {
+            controls::zipf_mean(1000, self.job_lot_zipf)
+        }
+    }
+
+    /// The units customers want in a day on average, as a policy sees it
+    ///
+    /// For plain zipfs this is policy::forecast(), which rustoclsim works out the same way.
+    fn forecast(&self) -> f32 {
+        if self.custom_demand() {
+            (self.mean_customers() * self.mean_request()) as f32
+        } else {
+            policy::forecast(self.job_lot_zipf, self.itemwise_traffic_zipf)
+        }
+    }
+
+    /// Whether the forecast a policy sees is off, by forecast_bias or forecast_mape, which
+    /// only the CPU engines can draw
+    pub fn noisy_forecast(&self) -> bool {
+        self.forecast_bias != 0.0 || self.forecast_mape != 0.0
+    }
+
+    /// The name a run with `sampling` is recorded under, or why `sampling` can't run here
+    #[cfg(feature = "python")]
+    fn sampling_method(&self, sampling: &str) -> PyResult<&'static str> {
+        let method = match sampling {
+            "random" => "repeat_simulate_demand",
+            "sobol" => "repeat_simulate_demand/sobol",
+            _ => {
+                return Err(ValueError::py_err(format!(
+                    "sampling must be \"random\" or \"sobol\", not {:?}",
+                    sampling
+                )))
+            }
+        };
+        if sampling == "sobol" && self.custom_demand() {
+            return Err(ValueError::py_err(
+                "sampling=\"sobol\" only draws from plain zipfs, not mixtures or max_request",
+            ));
+        }
+        Ok(method)
+    }
+
+    /// repeat_simulate_demand on the CPU, once `sampling` is checked, along with the control
+    /// variates of successful transactions by customers and successful sales by demand
+    #[cfg(feature = "python")]
+    fn repeat_on_cpu(
         &self,
+        py: Python<'_>,
         starting_quantity: usize,
         count: usize,
-    ) -> (usize, usize, usize, usize, f64, f64) {
+        sampling: &str,
+        seed: u64,
+    ) -> (Summary, ControlVariate, ControlVariate) {
         let (mut st, mut ss, mut ft, mut fs) = (0, 0, 0, 0);
-        for _ in 0..count {
-            let (xst, xss, xft, xfs, _, _) = self.simulate_demand_inner(starting_quantity);
+        let mut transaction_control = ControlVariate::new();
+        let mut sales_control = ControlVariate::new();
+        let mut tally = |(xst, xss, xft, xfs, _, _): Summary| {
             st += xst;
             ss += xss;
             ft += xft;
             fs += xfs;
+            transaction_control.push(xst as f64, (xst + xft) as f64);
+            sales_control.push(xss as f64, (xss + xfs) as f64);
+        };
+        let mut stopwatch = Stopwatch::start();
+        // Other Python threads can run their own simulations meanwhile
+        py.allow_threads(|| match sampling {
+            "random" => {
+                let mut rng = StdRng::seed_from_u64(seed);
+                stopwatch.lap(Phase::Seeds);
+                for _ in 0..count {
+                    tally(self.simulate_summary(starting_quantity, &mut rng));
+                }
+                stopwatch.lap(Phase::Kernel);
+            }
+            _ => {
+                let mut rng = StdRng::seed_from_u64(seed);
+                stopwatch.lap(Phase::Seeds);
+                let mut demand = QuasiDemand::new(
+                    &mut rng,
+                    InverseZipf::new(1000, self.itemwise_traffic_zipf),
+                    InverseZipf::new(1000, self.job_lot_zipf),
+                );
+                stopwatch.lap(Phase::Precompute);
+                for _ in 0..count {
+                    demand.next_year();
+                    tally(
+                        self.simulate_year_with(
+                            starting_quantity,
+                            &demand.traffic(),
+                            &demand.job_lot(),
+                            &mut rng,
+                            |_, _, _| {},
+                        )
+                        .summary(),
+                    );
+                }
+                stopwatch.lap(Phase::Kernel);
+            }
+        });
+        let summary = summarize(st, ss, ft, fs);
+        stopwatch.lap(Phase::Reduction);
+        self.timings.set(stopwatch.breakdown());
+        (summary, transaction_control, sales_control)
+    }
+
+    /// The settings in use that only the CPU engines can simulate, named as Python knows them
+    #[cfg(any(feature = "python", test))]
+    fn cpu_only_settings(&self) -> Vec<&'static str> {
+        let settings = [
+            ("job_lot_mixture", self.job_lot_mixture.is_some()),
+            (
+                "itemwise_traffic_mixture",
+                self.itemwise_traffic_mixture.is_some(),
+            ),
+            ("max_request", self.max_request.is_some()),
+            ("compound_poisson", self.compound_poisson.is_some()),
+            ("forecast_bias", self.forecast_bias != 0.0),
+            ("forecast_mape", self.forecast_mape != 0.0),
+        ];
+        settings
+            .iter()
+            .filter(|&&(_, used)| used)
+            .map(|&(name, _)| name)
+            .collect()
+    }
+
+    /// The forecast a policy sees at one review: forecast(), or with a noisy forecast, that
+    /// times 1 + forecast_bias + a normal error with mean absolute value forecast_mape
+    ///
+    /// It never goes below 0. A perfect forecast doesn't touch `rng`, so simulations without
+    /// forecast error see the same customers they always have.
+    #[allow(deprecated)]
+    fn review_forecast<R: Rng + ?Sized>(&self, rng: &mut R) -> f32 {
+        let forecast = self.forecast();
+        if !self.noisy_forecast() {
+            return forecast;
         }
-        (
-            st,
-            ss,
-            ft,
-            fs,
-            st as f64 / (st as f64 + ft as f64),
-            ss as f64 / (ss as f64 + fs as f64),
+        // E|N(0, σ)| = σ sqrt(2 / π)
+        let sigma = self.forecast_mape * (std::f64::consts::PI / 2.0).sqrt();
+        let noise: f64 = rand::distributions::StandardNormal.sample(rng);
+        let error = self.forecast_bias + sigma * noise;
+        (forecast as f64 * (1.0 + error)).max(0.0) as f32
+    }
+}
+
+/// A customer asks for `request` units on `day`: sell them if they're on the shelf, and say
+/// whether we did
+fn serve<O: Observer>(stock: &mut usize, day: usize, request: usize, observe: &mut O) -> bool {
+    let filled = *stock >= request;
+    if filled {
+        // There are enough.
+        *stock -= request;
+    }
+    observe.transaction(day, request, filled);
+    filled
+}
+
+/// Where a simulation stands between two days: what's on the shelf and what's on the way
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct Shelf {
+    /// The next day to simulate, counting from 0
+    day: usize,
+    /// Units on the shelf
+    stock: usize,
+    /// Units on trucks, in the slot for the day they arrive, modulo the lead time
+    trucks: Vec<usize>,
+    /// Where a noisy forecast's errors come from, if not from the customers' generator
+    #[serde(skip)]
+    forecast: Option<ChaCha20Rng>,
+}
+
+impl Shelf {
+    fn new(stock: usize, lead_time: usize) -> Shelf {
+        Shelf {
+            day: 0,
+            stock,
+            trucks: vec![0; lead_time],
+            forecast: None,
+        }
+    }
+
+    #[cfg(feature = "python")]
+    /// Units on the way, by how many days from now they arrive, starting with today's truck
+    fn pipeline(&self) -> Vec<usize> {
+        let lead_time = self.trucks.len();
+        (0..lead_time)
+            .map(|ahead| self.trucks[(self.day + ahead) % lead_time])
+            .collect()
+    }
+}
+
+/// What happened on one simulated day
+#[derive(Clone, Copy, Debug, Default)]
+struct Day {
+    day: usize,
+    /// Units that arrived by truck that morning
+    received: usize,
+    /// Units on the shelf once the truck was unloaded
+    opening_stock: usize,
+    successful_transactions: usize,
+    successful_sales: usize,
+    failed_transactions: usize,
+    failed_sales: usize,
+    /// Units left on the shelf at the end of the day
+    closing_stock: usize,
+    /// Units ordered that evening, or 0 if we didn't order
+    ordered: usize,
+}
+
+/// Something watching a simulated year as it happens
+///
+/// Any FnMut(day, request, filled) closure is one, for the many callers that only care about
+/// transactions.
+trait Observer {
+    /// A customer asked for `request` units on `day`, and got them if `filled`
+    fn transaction(&mut self, day: usize, request: usize, filled: bool);
+
+    /// `day` is over, with `stock` left on the shelf
+    fn end_of_day(&mut self, _day: usize, _stock: usize) {}
+}
+
+impl<F: FnMut(usize, usize, bool)> Observer for F {
+    fn transaction(&mut self, day: usize, request: usize, filled: bool) {
+        self(day, request, filled)
+    }
+}
+
+/// The raw outcome of one simulated year
+struct Year {
+    successful_transactions: usize,
+    successful_sales: usize,
+    failed_transactions: usize,
+    failed_sales: usize,
+    /// What was left on the shelf after the last day
+    ending_stock: usize,
+    /// Stock on the shelf at the end of each day, added up over the year
+    stock_days: usize,
+    /// How many times we sent off an order
+    trucks_ordered: usize,
+    /// The most we ever had on the shelf, right after a truck arrived
+    peak_stock: usize,
+    /// Units that arrived on trucks
+    units_received: usize,
+}
+
+impl Year {
+    /// The counters, plus the rates computed from them
+    fn summary(&self) -> Summary {
+        summarize(
+            self.successful_transactions,
+            self.successful_sales,
+            self.failed_transactions,
+            self.failed_sales,
         )
     }
 }
 
+/// Simulate `count` years in one call, for trying things out
+///
+/// Every parameter has a default, so rustsim.simulate() alone runs something sensible, and
+/// rustsim.simulate(safety_stock=20) changes just the one thing. `starting_quantity` defaults
+/// to the safety stock. Returns the Repetitions, as Simulation.repeat_simulate_demand_stored
+/// does, which show as a table in Jupyter and go to pandas with to_pandas().
+#[cfg(feature = "python")]
+#[pyfunction(
+    safety_stock = "10",
+    lead_time = "3",
+    order_quantity = "7",
+    starting_quantity = "None",
+    count = "1000",
+    job_lot_zipf = "None",
+    itemwise_traffic_zipf = "None",
+    seed = "None"
+)]
+#[allow(clippy::too_many_arguments)]
+fn simulate(
+    py: Python<'_>,
+    safety_stock: usize,
+    lead_time: usize,
+    order_quantity: usize,
+    starting_quantity: Option<usize>,
+    count: usize,
+    job_lot_zipf: Option<f64>,
+    itemwise_traffic_zipf: Option<f64>,
+    seed: Option<u64>,
+) -> PyResult<Repetitions> {
+    let sim = <Simulation as TryFrom<_>>::try_from(schema::Config {
+        version: schema::VERSION,
+        safety_stock,
+        lead_time,
+        order_quantity,
+        job_lot_zipf,
+        itemwise_traffic_zipf,
+        seed,
+        policy: None,
+        job_lot_mixture: None,
+        itemwise_traffic_mixture: None,
+        max_request: None,
+        max_request_mode: None,
+        compound_poisson: None,
+        forecast_bias: None,
+        forecast_mape: None,
+    })
+    .map_err(ValueError::py_err)?;
+    sim.repeat_simulate_demand_stored(py, starting_quantity.unwrap_or(safety_stock), count, vec![])
+}
+
+/// Time repeat_simulate_demand here and in rustoclsim at each of `counts`, to see which
+/// backend suits which size of run on this machine
+///
+/// Both simulate `sim` (by default, Simulation()) from `starting_quantity` (by default, its
+/// safety stock). The GPU gets one untimed call first, since its first call in a process
+/// compiles the kernel; what's left is the cost of every call, uploading the tables and
+/// launching. The GPU is skipped if rustoclsim isn't installed or has no working device.
+///
+/// Returns a dict with "counts", "cpu" and "opencl" (repetitions per second at each count,
+/// or None for the GPU if it was skipped), "device" (the GPU's name, or None), and
+/// "crossover": the count from which the GPU is faster, or None if it never is here.
+///
+/// The crossover is saved for the device, and repeat_simulate_demand(backend="auto") goes
+/// by it from then on, in this process and later ones.
+#[cfg(feature = "python")]
+#[pyfunction(
+    counts = "vec![1_000, 10_000, 100_000, 1_000_000]",
+    sim = "None",
+    starting_quantity = "None"
+)]
+fn benchmark(
+    py: Python<'_>,
+    counts: Vec<usize>,
+    sim: Option<&Simulation>,
+    starting_quantity: Option<usize>,
+) -> PyResult<PyObject> {
+    if counts.len() < 2 || counts.contains(&0) {
+        return Err(ValueError::py_err(
+            "counts needs at least two sizes, all above 0, to fit a line through",
+        ));
+    }
+    let default = Simulation::new(10, 3, 7, None, None, None);
+    let sim = sim.unwrap_or(&default);
+    let starting_quantity = starting_quantity.unwrap_or(sim.safety_stock);
+
+    let cpu_seconds = backends::time_cpu(py, sim, starting_quantity, &counts);
+    let device = backends::device(py)?;
+    let gpu_seconds = match backends::gpu_simulation(py, sim)? {
+        Some(gpu_sim) => Some(backends::time_gpu(
+            py,
+            &gpu_sim,
+            starting_quantity,
+            &counts,
+        )?),
+        None => None,
+    };
+    let crossover = gpu_seconds
+        .as_ref()
+        .and_then(|gpu_seconds| backends::crossover(&counts, &cpu_seconds, gpu_seconds));
+    if let (Some(device), Some(_)) = (&device, &gpu_seconds) {
+        backends::remember(device, crossover);
+    }
+
+    let throughput = |seconds: &[f64]| -> Vec<f64> {
+        counts
+            .iter()
+            .zip(seconds)
+            .map(|(&count, &seconds)| count as f64 / seconds)
+            .collect()
+    };
+    let result = PyDict::new(py);
+    result.set_item("counts", &counts)?;
+    result.set_item("cpu", throughput(&cpu_seconds))?;
+    result.set_item("opencl", gpu_seconds.as_deref().map(throughput))?;
+    result.set_item("device", gpu_seconds.as_ref().and(device))?;
+    result.set_item("crossover", crossover)?;
+    Ok(result.to_object(py))
+}
+
+/// Check that another backend (like rustoclsim) simulates the same thing as this crate
+///
+/// `ocl_sim` can be any object with a repeat_simulate_demand_each(starting_quantity, count)
+/// method returning one summary per repetition, and should be built with the same parameters
+/// as `cpu_sim`. We simulate as many repetitions on the CPU as the other backend returned, and
+/// compare the two with Kolmogorov-Smirnov tests on the per-repetition transaction and unit
+/// fill rates, and a chi-squared test on the units demanded per year.
+///
+/// Returns a dict of {test: (statistic, p-value)}, or raises AssertionError if any p-value is
+/// below `alpha`. The tests are exact for the same model, so with a small `alpha` a failure
+/// means the implementations have drifted apart rather than bad luck.
+#[cfg(feature = "python")]
+#[pyfunction(count = "100_000", alpha = "0.001")]
+fn crosscheck(
+    py: Python<'_>,
+    cpu_sim: &Simulation,
+    ocl_sim: &PyAny,
+    starting_quantity: usize,
+    count: usize,
+    alpha: f64,
+) -> PyResult<PyObject> {
+    let other: Vec<Summary> = ocl_sim
+        .call_method1("repeat_simulate_demand_each", (starting_quantity, count))?
+        .extract()?;
+    if other.is_empty() {
+        return Err(ValueError::py_err(
+            "The other backend returned no repetitions; try a larger count",
+        ));
+    }
+    let ours: Vec<Summary> = py.allow_threads(|| {
+        let mut rng = cpu_sim.rng();
+        (0..other.len())
+            .map(|_| cpu_sim.simulate_summary(starting_quantity, &mut rng))
+            .collect()
+    });
+    let results = hypothesis::compare_years(&ours, &other);
+
+    let failed: Vec<String> = results
+        .iter()
+        .filter(|(_, _, p)| *p < alpha)
+        .map(|(name, statistic, p)| format!("{} (statistic {}, p = {})", name, statistic, p))
+        .collect();
+    if !failed.is_empty() {
+        return Err(AssertionError::py_err(format!(
+            "The backends disagree: {}",
+            failed.join(", ")
+        )));
+    }
+    let dict = PyDict::new(py);
+    for (name, statistic, p) in results {
+        dict.set_item(name, (statistic, p))?;
+    }
+    Ok(dict.to_object(py))
+}
+
+/// Fit demand models to `samples`, a list of observed counts like each day's customers, and
+/// say how well each fits
+///
+/// `distribution` is "zipf", "poisson" or "negative_binomial" to fit just that one, or None
+/// for all of them. Each is fitted by maximum likelihood; see fit.rs for the scoring. Returns
+/// a dict with an entry per distribution, None if it can't produce the samples at all (like a
+/// zipf and a 0), otherwise a dict of its "parameters", "log_likelihood", "aic", and "ks" and
+/// "chi_squared" as (statistic, p-value). "best" is the one with the lowest AIC.
+#[cfg(feature = "python")]
+#[pyfunction(distribution = "None")]
+fn fit_report(
+    py: Python<'_>,
+    samples: Vec<usize>,
+    distribution: Option<&str>,
+) -> PyResult<PyObject> {
+    let families = match distribution {
+        Some(name) => vec![fit::Family::parse(name).map_err(ValueError::py_err)?],
+        None => fit::FAMILIES.to_vec(),
+    };
+    let fits: Vec<(fit::Family, Result<fit::Fit, String>)> = py.allow_threads(|| {
+        families
+            .iter()
+            .map(|&family| (family, fit::fit(&samples, family)))
+            .collect()
+    });
+    let result = PyDict::new(py);
+    let mut best: Option<(&str, f64)> = None;
+    for (family, fitted) in fits {
+        let fitted = match fitted {
+            Ok(fitted) => fitted,
+            // Asked for by name, it has to fit
+            Err(problem) if distribution.is_some() || samples.is_empty() => {
+                return Err(ValueError::py_err(problem))
+            }
+            Err(_) => {
+                result.set_item(family.name(), py.None())?;
+                continue;
+            }
+        };
+        let parameters = PyDict::new(py);
+        for (name, value) in fitted.model.parameters() {
+            parameters.set_item(name, value)?;
+        }
+        let dict = PyDict::new(py);
+        dict.set_item("parameters", parameters)?;
+        dict.set_item("log_likelihood", fitted.log_likelihood)?;
+        dict.set_item("aic", fitted.aic)?;
+        dict.set_item("ks", fitted.ks)?;
+        dict.set_item("chi_squared", (fitted.chi_squared.0, fitted.chi_squared.2))?;
+        result.set_item(family.name(), dict)?;
+        if best.is_none_or(|(_, aic)| fitted.aic < aic) {
+            best = Some((family.name(), fitted.aic));
+        }
+    }
+    if best.is_none() {
+        return Err(ValueError::py_err(
+            "None of the distributions can produce these samples",
+        ));
+    }
+    result.set_item("best", best.map(|(name, _)| name))?;
+    Ok(result.to_object(py))
+}
+
+/// Check the simulation's invariants over `count` random configurations
+///
+/// Each configuration has random parameters and a random starting quantity, and we simulate
+/// one year of it, checking that sales never exceed demand, every unit that arrived is
+/// either sold or still on the shelf (so stock never went negative), the counters agree with
+/// what actually happened, and the fill rates are between 0 and 1. Worth running after any
+/// change to the simulation itself.
+///
+/// Returns the number of configurations checked, or raises AssertionError describing the
+/// first one that broke an invariant. With a `seed`, the configurations are the same every
+/// time.
+#[cfg(feature = "python")]
+#[pyfunction(count = "100", seed = "None")]
+fn validate(count: usize, seed: Option<u64>) -> PyResult<usize> {
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    check_random_configurations(count, &mut rng).map_err(AssertionError::py_err)?;
+    Ok(count)
+}
+
+#[cfg(any(feature = "python", test))]
+/// The checks behind validate(): `count` random configurations from `rng`, each simulated for
+/// a year with check_invariants
+///
+/// Returns a description of the first configuration that broke an invariant, and how.
+fn check_random_configurations<R: Rng>(count: usize, rng: &mut R) -> Result<(), String> {
+    for _ in 0..count {
+        let sim = Simulation {
+            safety_stock: rng.gen_range(0, 200),
+            lead_time: rng.gen_range(1, 11),
+            order_quantity: rng.gen_range(1, 100),
+            job_lot_zipf: rng.gen_range(1.5, 5.0),
+            itemwise_traffic_zipf: rng.gen_range(1.5, 5.0),
+            seed: None,
+            policy: None,
+            job_lot_mixture: None,
+            itemwise_traffic_mixture: None,
+            max_request: None,
+            max_request_mode: Truncation::Clip,
+            compound_poisson: None,
+            forecast_bias: 0.0,
+            forecast_mape: 0.0,
+            timings: LastTimings::default(),
+        };
+        let starting_quantity = rng.gen_range(0, 300);
+        if let Err(problem) = sim.check_invariants(starting_quantity, rng) {
+            return Err(format!(
+                "{} (safety_stock={}, lead_time={}, order_quantity={}, job_lot_zipf={}, \
+                 itemwise_traffic_zipf={}, starting_quantity={})",
+                problem,
+                sim.safety_stock,
+                sim.lead_time,
+                sim.order_quantity,
+                sim.job_lot_zipf,
+                sim.itemwise_traffic_zipf,
+                starting_quantity
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Every class can be shared between Python threads (see the README on threads): Simulation
+// is plain data, SimulationIter locks its receiver, and ResultStore serializes its queries
+#[cfg(feature = "python")]
+const _: fn() = || {
+    fn shareable<T: Send + Sync>() {}
+    shareable::<Simulation>();
+    shareable::<SimulationIter>();
+    shareable::<Repetitions>();
+    shareable::<ResultStore>();
+    shareable::<SimulationState>();
+    shareable::<DayResult>();
+    shareable::<surface::ResponseSurface>();
+};
+
 /// This module is a python module implemented in Rust.
+#[cfg(feature = "python")]
 #[pymodule]
 fn rustsim(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<Simulation>()?;
+    m.add_class::<SimulationIter>()?;
+    m.add_class::<Repetitions>()?;
+    m.add_class::<ResultStore>()?;
+    m.add_class::<SimulationState>()?;
+    m.add_class::<DayResult>()?;
+    m.add_wrapped(wrap_pyfunction!(simulate))?;
+    m.add_wrapped(wrap_pyfunction!(crosscheck))?;
+    m.add_wrapped(wrap_pyfunction!(fit_report))?;
+    m.add_wrapped(wrap_pyfunction!(validate))?;
+    m.add_wrapped(wrap_pyfunction!(benchmark))?;
+    m.add_wrapped(wrap_pymodule!(optimize))?;
+    m.add_wrapped(wrap_pymodule!(portfolio))?;
 
     Ok(())
 }
+
+#[test]
+fn test_validate() {
+    // Random configurations and the defaults keep their invariants, at the edges too, and
+    // the ones that can't be simulated at all are turned away before they're tried
+    let mut rng = StdRng::seed_from_u64(3);
+    check_random_configurations(50, &mut rng).unwrap();
+    let defaults = Simulation::new(10, 3, 7, None, None, Some(3));
+    assert!(defaults.check_parameters().is_ok());
+    for &starting_quantity in &[0, 10, 10_000] {
+        defaults
+            .check_invariants(starting_quantity, &mut rng)
+            .unwrap();
+    }
+    for &(safety_stock, lead_time, order_quantity) in &[(0, 1, 1), (200, 1, 1), (0, 30, 500)] {
+        let sim = Simulation::new(safety_stock, lead_time, order_quantity, None, None, Some(3));
+        assert!(sim.check_parameters().is_ok());
+        sim.check_invariants(0, &mut rng).unwrap();
+    }
+    assert!(Simulation::new(10, 0, 7, None, None, None)
+        .check_parameters()
+        .unwrap_err()
+        .contains("lead_time"));
+    assert!(Simulation::new(10, 3, 0, None, None, None)
+        .check_parameters()
+        .unwrap_err()
+        .contains("order_quantity"));
+}
+
+#[test]
+fn test_repeat_until() {
+    // A loose target stops after the first batch, one nobody can reach runs to max_count,
+    // and a seeded simulation gives the same answer each time
+    let sim = Simulation::new(10, 3, 7, None, None, Some(12));
+    let (summary, count, width) = sim.repeat_summary_until(10, 0.5, 100_000);
+    assert_eq!(count, 1000);
+    assert!(width <= 0.5);
+    assert_eq!(summary, sim.repeat_summary(10, 1000));
+    let (_, count, width) = sim.repeat_summary_until(10, 0.0, 2500);
+    assert_eq!(count, 2500);
+    assert!(width > 0.0);
+    assert_eq!(
+        sim.repeat_summary_until(10, 0.01, 100_000),
+        sim.repeat_summary_until(10, 0.01, 100_000)
+    );
+}
+
+#[test]
+fn test_cpu_only_settings() {
+    // Each setting the GPU can't simulate is named on its own, and a plain simulation has none
+    use compound::Basket;
+    let plain = Simulation::new(10, 3, 7, None, None, Some(1));
+    assert!(plain.cpu_only_settings().is_empty());
+    let mut sim = plain.clone();
+    sim.max_request = Some(50);
+    sim.forecast_mape = 0.2;
+    assert_eq!(
+        sim.cpu_only_settings(),
+        vec!["max_request", "forecast_mape"]
+    );
+    let mut sim = plain;
+    sim.compound_poisson = Some(CompoundPoisson::new(40.0, 0.5, Basket::Geometric).unwrap());
+    sim.forecast_bias = -0.1;
+    assert_eq!(
+        sim.cpu_only_settings(),
+        vec!["compound_poisson", "forecast_bias"]
+    );
+}
+
+#[test]
+fn test_repeat_streams() {
+    // However a run is split, its pieces add up to the whole, and the master seed matters
+    let sim = Simulation::new(10, 3, 7, None, None, None);
+    let whole = sim.repeat_summary_streams(10, 5, 0..300);
+    let (a, b) = (
+        sim.repeat_summary_streams(10, 5, 0..120),
+        sim.repeat_summary_streams(10, 5, 120..300),
+    );
+    assert_eq!(summarize(a.0 + b.0, a.1 + b.1, a.2 + b.2, a.3 + b.3), whole);
+    assert_ne!(sim.repeat_summary_streams(10, 6, 0..300), whole);
+}
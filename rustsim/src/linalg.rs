@@ -0,0 +1,117 @@
+//! Just enough linear algebra for the fitting helpers
+
+/// Solve a x = b by Gaussian elimination with partial pivoting
+///
+/// Returns None if `a` is singular (or close enough that the answer would be garbage), or
+/// anything in it isn't finite.
+pub fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        // NaN sorts above everything, so a NaN anywhere in the column ends up here
+        if !a[pivot][col].is_finite() || a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let (done, rest) = a.split_at_mut(col + 1);
+        let pivot_row = &done[col];
+        for (offset, row) in rest.iter_mut().enumerate() {
+            let factor = row[col] / pivot_row[col];
+            for (x, p) in row[col..].iter_mut().zip(&pivot_row[col..]) {
+                *x -= factor * p;
+            }
+            b[col + 1 + offset] -= factor * b[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let rest: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - rest) / a[row][row];
+    }
+    Some(x)
+}
+
+/// Whether a symmetric matrix is positive definite, by attempting a Cholesky factorization
+pub fn is_positive_definite(a: &[Vec<f64>]) -> bool {
+    let n = a.len();
+    let mut l = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in 0..=i {
+            let sum: f64 = (0..j).map(|k| l[i][k] * l[j][k]).sum();
+            if i == j {
+                let diagonal = a[i][i] - sum;
+                if diagonal <= 0.0 {
+                    return false;
+                }
+                l[i][i] = diagonal.sqrt();
+            } else {
+                l[i][j] = (a[i][j] - sum) / l[j][j];
+            }
+        }
+    }
+    true
+}
+
+/// The lower triangular L with L Lᵀ = a, for a symmetric positive semidefinite `a`
+///
+/// Returns None if `a` isn't. Where a pivot is 0, as with two perfectly correlated variables,
+/// its column is left 0 rather than giving up.
+pub fn cholesky(a: &[Vec<f64>]) -> Option<Vec<Vec<f64>>> {
+    let n = a.len();
+    let mut l = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in 0..=i {
+            let sum: f64 = (0..j).map(|k| l[i][k] * l[j][k]).sum();
+            if i == j {
+                let diagonal = a[i][i] - sum;
+                if diagonal < -1e-9 {
+                    return None;
+                }
+                l[i][i] = diagonal.max(0.0).sqrt();
+            } else if l[j][j] > 1e-9 {
+                l[i][j] = (a[i][j] - sum) / l[j][j];
+            } else if (a[i][j] - sum).abs() > 1e-9 {
+                // Nothing left in column j to match what's asked of it
+                return None;
+            }
+        }
+    }
+    Some(l)
+}
+
+#[test]
+fn test_linalg() {
+    // Solutions check out, a singular system has none, and Cholesky agrees with the
+    // positive definite test and multiplies back out
+    let a = vec![
+        vec![4.0, 2.0, 0.6],
+        vec![2.0, 3.0, 0.4],
+        vec![0.6, 0.4, 2.0],
+    ];
+    let x = solve(a.clone(), vec![1.0, 2.0, 3.0]).unwrap();
+    for (row, b) in a.iter().zip(&[1.0, 2.0, 3.0]) {
+        let ax: f64 = row.iter().zip(&x).map(|(r, x)| r * x).sum();
+        assert!((ax - b).abs() < 1e-9);
+    }
+    assert!(solve(vec![vec![1.0, 2.0], vec![2.0, 4.0]], vec![1.0, 2.0]).is_none());
+    assert!(solve(vec![vec![1.0, 2.0], vec![f64::NAN, 4.0]], vec![1.0, 2.0]).is_none());
+    assert!(solve(
+        vec![vec![f64::INFINITY, 2.0], vec![2.0, 4.0]],
+        vec![1.0, 2.0]
+    )
+    .is_none());
+    assert!(is_positive_definite(&a));
+    assert!(!is_positive_definite(&[vec![1.0, 2.0], vec![2.0, 1.0]]));
+    let l = cholesky(&a).unwrap();
+    for i in 0..3 {
+        for j in 0..3 {
+            let product: f64 = (0..3).map(|k| l[i][k] * l[j][k]).sum();
+            assert!((product - a[i][j]).abs() < 1e-9);
+        }
+    }
+    // Perfectly correlated is only semidefinite, but still factors
+    assert!(!is_positive_definite(&[vec![1.0, 1.0], vec![1.0, 1.0]]));
+    assert!(cholesky(&[vec![1.0, 1.0], vec![1.0, 1.0]]).is_some());
+    assert!(cholesky(&[vec![1.0, 2.0], vec![2.0, 1.0]]).is_none());
+}
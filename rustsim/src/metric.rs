@@ -0,0 +1,250 @@
+//! Custom metrics, each a number per year accumulated from every day's state
+//!
+//! A metric is an aggregate around an expression over the day's state, like "max(stock)" or
+//! "mean(lost)", where the aggregate is one of
+//!
+//! - sum(x), the total over the year
+//! - mean(x), the average day
+//! - max(x) and min(x), the highest and lowest day
+//! - days(x), how many days x isn't 0, like days(stock < 5)
+//!
+//! and days_below(n) and days_above(n) are short for days(stock < n) and days(stock > n). The
+//! expression is in the arithmetic of expression.rs, over the names in DayState. The daily
+//! engine adds them up here, and rustoclsim compiles the same thing into its kernel, so
+//! neither has to hand back every day of every year to answer them.
+use crate::expression::{self, Expr, Op};
+use crate::{summarize, Shelf, Simulation, Summary};
+use rand::distributions::Distribution;
+use rand::Rng;
+
+/// What a metric can see of a day, once it's over and the order is in, all as floats
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DayState {
+    /// Units on the shelf at the end of the day
+    pub stock: f32,
+    /// Units on the shelf once the morning's truck was unloaded
+    pub opening_stock: f32,
+    /// Units that arrived that morning
+    pub received: f32,
+    /// Units ordered that evening
+    pub ordered: f32,
+    /// Units on the truck ring, that evening's order included
+    pub pipeline: f32,
+    /// Customers who came
+    pub customers: f32,
+    /// Customers who left with nothing
+    pub turned_away: f32,
+    /// Units customers asked for
+    pub demand: f32,
+    /// Units sold
+    pub sold: f32,
+    /// Units customers asked for that we didn't have
+    pub lost: f32,
+    /// The day, counting from 0
+    pub day: f32,
+}
+
+/// The names a metric can use, in the order of DayState
+pub const NAMES: [&str; 11] = [
+    "stock",
+    "opening_stock",
+    "received",
+    "ordered",
+    "pipeline",
+    "customers",
+    "turned_away",
+    "demand",
+    "sold",
+    "lost",
+    "day",
+];
+
+impl DayState {
+    fn values(&self) -> [f32; 11] {
+        [
+            self.stock,
+            self.opening_stock,
+            self.received,
+            self.ordered,
+            self.pipeline,
+            self.customers,
+            self.turned_away,
+            self.demand,
+            self.sold,
+            self.lost,
+            self.day,
+        ]
+    }
+}
+
+/// How a metric turns days into a year, in the order of AGGREGATES
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Aggregate {
+    Sum,
+    Mean,
+    Max,
+    Min,
+    Days,
+}
+
+const AGGREGATES: [&str; 5] = ["sum", "mean", "max", "min", "days"];
+
+/// A custom metric, parsed from its source
+#[derive(Clone, Debug)]
+pub struct Metric {
+    source: String,
+    aggregate: Aggregate,
+    expr: Expr,
+}
+
+impl Metric {
+    /// The metric `source` describes, or what's wrong with it and where
+    pub fn parse(source: &str) -> Result<Metric, String> {
+        let (aggregate, expr) = match expression::parse_call(source, &AGGREGATES, &NAMES)? {
+            Some((aggregate, expr)) => (
+                [
+                    Aggregate::Sum,
+                    Aggregate::Mean,
+                    Aggregate::Max,
+                    Aggregate::Min,
+                    Aggregate::Days,
+                ][aggregate],
+                expr,
+            ),
+            None => match expression::parse_call(source, &["days_below", "days_above"], &NAMES)? {
+                Some((shorthand, threshold)) => {
+                    let op = [Op::Less, Op::Greater][shorthand];
+                    let stock = Box::new(Expr::Input(0));
+                    (
+                        Aggregate::Days,
+                        Expr::Binary(op, stock, Box::new(threshold)),
+                    )
+                }
+                None => {
+                    return Err(format!(
+                        "{:?} isn't a metric; a metric is sum(x), mean(x), max(x), min(x), \
+                         days(x), days_below(n) or days_above(n), where x can use {}",
+                        source,
+                        NAMES.join(", ")
+                    ))
+                }
+            },
+        };
+        Ok(Metric {
+            source: source.to_string(),
+            aggregate,
+            expr,
+        })
+    }
+
+    /// The metric, as it was written
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Where a year starts, before any days
+    pub fn start(&self) -> f32 {
+        match self.aggregate {
+            Aggregate::Sum | Aggregate::Mean | Aggregate::Days => 0.0,
+            Aggregate::Max => f32::NEG_INFINITY,
+            Aggregate::Min => f32::INFINITY,
+        }
+    }
+
+    /// The year so far, `year`, with one more day
+    pub fn day(&self, year: f32, state: &DayState) -> f32 {
+        let x = self.expr.evaluate(&state.values());
+        match self.aggregate {
+            Aggregate::Sum | Aggregate::Mean => year + x,
+            Aggregate::Max => year.max(x),
+            Aggregate::Min => year.min(x),
+            Aggregate::Days => year + if x != 0.0 { 1.0 } else { 0.0 },
+        }
+    }
+
+    /// The year's value, after `days` days
+    pub fn finish(&self, year: f32, days: usize) -> f32 {
+        match self.aggregate {
+            Aggregate::Mean => year / days as f32,
+            _ => year,
+        }
+    }
+
+    /// OpenCL C for start(), as a float
+    pub fn opencl_start(&self) -> &'static str {
+        match self.aggregate {
+            Aggregate::Sum | Aggregate::Mean | Aggregate::Days => "0.0f",
+            Aggregate::Max => "-INFINITY",
+            Aggregate::Min => "INFINITY",
+        }
+    }
+
+    /// OpenCL C for day(), as a float, given the year so far in `year` and the day's state in
+    /// metric_stock, metric_opening_stock and so on
+    pub fn opencl_day(&self, year: &str) -> String {
+        let mut x = String::new();
+        self.expr.write_opencl(&mut x, "metric_", &NAMES);
+        match self.aggregate {
+            Aggregate::Sum | Aggregate::Mean => format!("({} + {})", year, x),
+            Aggregate::Max => format!("fmax({}, {})", year, x),
+            Aggregate::Min => format!("fmin({}, {})", year, x),
+            Aggregate::Days => format!("({} + (float)({} != 0.0f))", year, x),
+        }
+    }
+
+    /// OpenCL C for finish(), as a float, given the year in `year` and the days in `days`
+    pub fn opencl_finish(&self, year: &str, days: &str) -> String {
+        match self.aggregate {
+            Aggregate::Mean => format!("({} / (float){})", year, days),
+            _ => year.to_string(),
+        }
+    }
+}
+
+/// Simulate a year a day at a time, and summarize it along with each of `metrics`
+pub fn simulate_year<R, IT, JL>(
+    sim: &Simulation,
+    starting_quantity: usize,
+    metrics: &[Metric],
+    it_dist: &IT,
+    jl_dist: &JL,
+    rng: &mut R,
+) -> (Summary, Vec<f32>)
+where
+    R: Rng,
+    IT: Distribution<usize>,
+    JL: Distribution<usize>,
+{
+    let mut shelf = Shelf::new(starting_quantity, sim.lead_time);
+    let mut years: Vec<f32> = metrics.iter().map(Metric::start).collect();
+    let (mut st, mut ss, mut ft, mut fs) = (0, 0, 0, 0);
+    for _ in 0..365 {
+        let day = sim.simulate_day_with(&mut shelf, it_dist, jl_dist, rng, &mut |_, _, _| {});
+        st += day.successful_transactions;
+        ss += day.successful_sales;
+        ft += day.failed_transactions;
+        fs += day.failed_sales;
+        let state = DayState {
+            stock: day.closing_stock as f32,
+            opening_stock: day.opening_stock as f32,
+            received: day.received as f32,
+            ordered: day.ordered as f32,
+            pipeline: shelf.trucks.iter().sum::<usize>() as f32,
+            customers: (day.successful_transactions + day.failed_transactions) as f32,
+            turned_away: day.failed_transactions as f32,
+            demand: (day.successful_sales + day.failed_sales) as f32,
+            sold: day.successful_sales as f32,
+            lost: day.failed_sales as f32,
+            day: day.day as f32,
+        };
+        for (year, metric) in years.iter_mut().zip(metrics) {
+            *year = metric.day(*year, &state);
+        }
+    }
+    let years = years
+        .iter()
+        .zip(metrics)
+        .map(|(&year, metric)| metric.finish(year, 365))
+        .collect();
+    (summarize(st, ss, ft, fs), years)
+}
@@ -0,0 +1,278 @@
+//! Demand beyond a single zipf: mixtures of them, and a cap on how much one customer asks for
+//!
+//! A mixture is a list of (weight, exponent) components, like [(0.9, 4.0), (0.1, 2.0)] for
+//! mostly quiet days with the odd burst. Each draw picks a component by weight and then draws
+//! from its zipf over 1..=1000, so a mixture of traffic picks afresh each day and a mixture
+//! of job lots picks afresh for each customer. The weights needn't add up to 1; they're
+//! scaled to.
+//!
+//! A cap, max_request, keeps the zipf's long tail from sending in the odd customer who wants
+//! 1000 units of something you stock 20 of. Requests over it are either clipped to it, which
+//! piles the tail up on the cap, or drawn again until they fit, which spreads the tail over
+//! everything below it.
+use crate::importance::harmonic;
+#[allow(deprecated)]
+use rand::distributions::{Distribution, Poisson};
+use rand::Rng;
+use zipf::ZipfDistribution;
+
+/// A weighted mixture of zipf exponents
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mixture {
+    /// (weight, exponent), with the weights adding up to 1
+    components: Vec<(f64, f64)>,
+}
+
+impl Mixture {
+    /// The mixture of `components`, each (weight, exponent), or why it can't be one
+    pub fn new(components: &[(f64, f64)]) -> Result<Mixture, String> {
+        if components.is_empty() {
+            return Err("A mixture needs at least one component".to_string());
+        }
+        for &(weight, exponent) in components {
+            // Written this way around so NaN fails too
+            if !(weight > 0.0 && weight.is_finite()) {
+                return Err(format!("Mixture weights must be > 0, not {}", weight));
+            }
+            if !(exponent > 0.0 && exponent.is_finite()) {
+                return Err(format!("Mixture exponents must be > 0, not {}", exponent));
+            }
+        }
+        let total: f64 = components.iter().map(|&(weight, _)| weight).sum();
+        Ok(Mixture {
+            components: components
+                .iter()
+                .map(|&(weight, exponent)| (weight / total, exponent))
+                .collect(),
+        })
+    }
+
+    /// The components as (weight, exponent), with the weights scaled to add up to 1
+    pub fn components(&self) -> &[(f64, f64)] {
+        &self.components
+    }
+}
+
+/// What happens to a request over max_request
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Truncation {
+    /// It asks for max_request instead
+    #[default]
+    Clip,
+    /// It's drawn again until it's no more than max_request
+    Resample,
+}
+
+impl Truncation {
+    /// The truncation called `mode`, "clip" or "resample"
+    pub fn parse(mode: &str) -> Result<Truncation, String> {
+        match mode {
+            "clip" => Ok(Truncation::Clip),
+            "resample" => Ok(Truncation::Resample),
+            _ => Err(format!(
+                "max_request_mode must be \"clip\" or \"resample\", not {:?}",
+                mode
+            )),
+        }
+    }
+
+    /// The name parse() takes
+    pub fn name(self) -> &'static str {
+        match self {
+            Truncation::Clip => "clip",
+            Truncation::Resample => "resample",
+        }
+    }
+}
+
+/// Draws from a zipf over 1..=1000, or from a mixture of them, capped or not
+///
+/// With a single zipf and no cap, it uses the generator exactly as the zipf alone would, so a
+/// simulation without either sees the same customers it always has. compound.rs adds the
+/// Poisson and geometric shapes.
+#[derive(Clone, Debug)]
+pub struct Demand {
+    shape: Shape,
+    max_request: Option<(usize, Truncation)>,
+}
+
+/// What a Demand draws from, before any cap
+#[derive(Clone, Debug)]
+enum Shape {
+    /// A zipf over 1..=1000 or a mixture of them
+    Zipfs {
+        /// (weight, exponent), as in Mixture
+        components: Vec<(f64, f64)>,
+        zipfs: Vec<ZipfDistribution>,
+        /// Where each component's share of [0, 1) ends
+        cumulative: Vec<f64>,
+    },
+    /// A Poisson with this mean
+    #[allow(deprecated)]
+    Poisson(f64, Poisson),
+    /// A geometric over 1, 2, 3 and so on with this mean
+    Geometric(f64),
+}
+
+impl Demand {
+    /// Draws from `mixture` if there is one, otherwise from a zipf with `exponent`, with
+    /// anything over `max_request`'s limit truncated its way
+    pub fn new(
+        exponent: f64,
+        mixture: Option<&Mixture>,
+        max_request: Option<(usize, Truncation)>,
+    ) -> Demand {
+        let components = match mixture {
+            Some(mixture) => mixture.components.clone(),
+            None => vec![(1.0, exponent)],
+        };
+        let mut total = 0.0;
+        let shape = Shape::Zipfs {
+            zipfs: components
+                .iter()
+                .map(|&(_, exponent)| ZipfDistribution::new(1000, exponent).unwrap())
+                .collect(),
+            cumulative: components
+                .iter()
+                .map(|&(weight, _)| {
+                    total += weight;
+                    total
+                })
+                .collect(),
+            components,
+        };
+        Demand { shape, max_request }
+    }
+
+    /// Draws from a Poisson with mean `mean`, which has to be above 0
+    #[allow(deprecated)]
+    pub fn poisson(mean: f64) -> Demand {
+        Demand {
+            shape: Shape::Poisson(mean, Poisson::new(mean)),
+            max_request: None,
+        }
+    }
+
+    /// Draws from a geometric over 1, 2, 3 and so on with mean `mean`, at least 1, truncated
+    /// like new()
+    pub fn geometric(mean: f64, max_request: Option<(usize, Truncation)>) -> Demand {
+        Demand {
+            shape: Shape::Geometric(mean),
+            max_request,
+        }
+    }
+
+    /// The mean draw, worked out exactly from the probability of each of 1..=1000 (or from
+    /// the parameter, for an uncapped Poisson or geometric)
+    pub fn mean(&self) -> f64 {
+        let limit = match self.max_request {
+            Some((limit, _)) => limit.min(1000),
+            None => 1000,
+        };
+        let mut probabilities = vec![0.0; limit];
+        match &self.shape {
+            Shape::Zipfs { components, .. } => {
+                for &(weight, exponent) in components {
+                    let total = harmonic(1000, exponent);
+                    for (k, probability) in probabilities.iter_mut().enumerate() {
+                        *probability += weight * ((k + 1) as f64).powf(-exponent) / total;
+                    }
+                }
+            }
+            // Nothing caps customers
+            Shape::Poisson(mean, _) => return *mean,
+            Shape::Geometric(mean) if self.max_request.is_none() => return *mean,
+            Shape::Geometric(mean) => {
+                let p = 1.0 / mean;
+                for (k, probability) in probabilities.iter_mut().enumerate() {
+                    *probability = p * (1.0 - p).powi(k as i32);
+                }
+            }
+        }
+        let below: f64 = probabilities.iter().sum();
+        let mean: f64 = (1..=limit)
+            .zip(&probabilities)
+            .map(|(k, probability)| k as f64 * probability)
+            .sum();
+        match self.max_request {
+            Some((_, Truncation::Resample)) => mean / below,
+            _ => mean + limit as f64 * (1.0 - below),
+        }
+    }
+
+    /// A draw before any cap
+    fn sample_uncapped<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        match &self.shape {
+            Shape::Zipfs { zipfs, .. } if zipfs.len() == 1 => zipfs[0].sample(rng),
+            Shape::Zipfs {
+                zipfs, cumulative, ..
+            } => {
+                let pick: f64 = rng.gen();
+                // Rounding can leave the last boundary a hair under 1
+                let component = cumulative
+                    .iter()
+                    .position(|&end| pick < end)
+                    .unwrap_or(zipfs.len() - 1);
+                zipfs[component].sample(rng)
+            }
+            Shape::Poisson(_, poisson) => Distribution::<u64>::sample(poisson, rng) as usize,
+            Shape::Geometric(mean) if *mean <= 1.0 => 1,
+            Shape::Geometric(mean) => {
+                // Inverting the CDF, with 1 - gen() so the log never sees 0
+                let failures = (1.0 - rng.gen::<f64>()).ln() / (1.0 - 1.0 / mean).ln();
+                1 + failures as usize
+            }
+        }
+    }
+}
+
+impl Distribution<usize> for Demand {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        match self.max_request {
+            None => self.sample_uncapped(rng),
+            Some((limit, Truncation::Clip)) => self.sample_uncapped(rng).min(limit),
+            Some((limit, Truncation::Resample)) => loop {
+                // The limit is at least 1, so this takes a couple of tries at worst
+                let request = self.sample_uncapped(rng);
+                if request <= limit {
+                    break request;
+                }
+            },
+        }
+    }
+}
+
+#[test]
+fn test_demand_mean() {
+    // Caps keep every request under them, and their means fall where they should
+    use crate::controls::zipf_mean;
+    use rand::SeedableRng;
+    let mixture = Mixture::new(&[(9.0, 4.0), (1.0, 1.5)]).unwrap();
+    assert_eq!(mixture.components()[0].0, 0.9);
+    let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+    let mut means = vec![];
+    for max_request in &[
+        None,
+        Some((20, Truncation::Clip)),
+        Some((20, Truncation::Resample)),
+    ] {
+        let demand = Demand::new(2.75, Some(&mixture), *max_request);
+        if max_request.is_some() {
+            assert!((0..100_000).all(|_| demand.sample(&mut rng) <= 20));
+        }
+        means.push(demand.mean());
+    }
+    // Clipping piles the tail up on the cap, and resampling spreads it out below
+    assert!(means[0] > means[1] && means[1] > means[2]);
+    assert!(zipf_mean(1000, 4.0) < means[2] && means[0] < zipf_mean(1000, 1.5));
+    let plain = Demand::new(2.75, None, None).mean();
+    assert!((plain - zipf_mean(1000, 2.75)).abs() < 1e-9);
+    for broken in &[
+        vec![],
+        vec![(0.0, 2.0)],
+        vec![(1.0, -1.0)],
+        vec![(f64::NAN, 2.0)],
+    ] {
+        assert!(Mixture::new(broken).is_err());
+    }
+}
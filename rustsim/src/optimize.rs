@@ -0,0 +1,1086 @@
+//! Searching parameter space for the best ordering policy
+//!
+//! Available from Python as `rustsim.optimize`
+
+use crate::backends;
+use crate::parquet::{self, Column};
+use crate::surface::ResponseSurface;
+use crate::{data_frame, fill_rate, Simulation, Summary};
+use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::{RuntimeError, ValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyDict};
+use pyo3::wrap_pyfunction;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+/// Every parameter you can search over, in the order they're reported
+pub const PARAMETERS: [&str; 6] = [
+    "safety_stock",
+    "lead_time",
+    "order_quantity",
+    "job_lot_zipf",
+    "itemwise_traffic_zipf",
+    "starting_quantity",
+];
+
+/// Every metric evaluate() reports, in the order of its columns
+pub const METRICS: [&str; 6] = [
+    "unit_fill_rate",
+    "transaction_fill_rate",
+    "total_cost",
+    "average_stock",
+    "orders_per_year",
+    "peak_stock",
+];
+
+/// The metrics the GPU measures; its kernel doesn't count stock or orders
+pub const GPU_METRICS: [&str; 2] = ["unit_fill_rate", "transaction_fill_rate"];
+
+/// What it costs to run a policy, in whatever currency you like
+#[derive(Clone, Copy)]
+pub struct Costs {
+    /// Per unit on the shelf at the end of each day
+    pub holding: f64,
+    /// Per order sent off
+    pub order: f64,
+    /// Per unit a customer wanted but couldn't get
+    pub shortage: f64,
+}
+
+/// One point in parameter space: a policy, and how much stock it starts with
+#[derive(Clone)]
+pub struct Candidate {
+    pub sim: Simulation,
+    pub starting_quantity: usize,
+}
+
+impl Candidate {
+    /// Read a parameter by name
+    pub fn get(&self, name: &str) -> PyResult<f64> {
+        match name {
+            "safety_stock" => Ok(self.sim.safety_stock as f64),
+            "lead_time" => Ok(self.sim.lead_time as f64),
+            "order_quantity" => Ok(self.sim.order_quantity as f64),
+            "job_lot_zipf" => Ok(self.sim.job_lot_zipf),
+            "itemwise_traffic_zipf" => Ok(self.sim.itemwise_traffic_zipf),
+            "starting_quantity" => Ok(self.starting_quantity as f64),
+            _ => Err(ValueError::py_err(format!(
+                "Unknown parameter {:?}, expected one of {:?}",
+                name, PARAMETERS
+            ))),
+        }
+    }
+
+    /// A copy of this candidate with one parameter changed
+    pub fn with(&self, name: &str, value: f64) -> PyResult<Candidate> {
+        let mut candidate = self.clone();
+        let whole = || -> PyResult<usize> {
+            if value < 0.0 || value.fract() != 0.0 {
+                Err(ValueError::py_err(format!(
+                    "{} must be a non-negative whole number, not {}",
+                    name, value
+                )))
+            } else {
+                Ok(value as usize)
+            }
+        };
+        match name {
+            "safety_stock" => candidate.sim.safety_stock = whole()?,
+            "lead_time" => candidate.sim.lead_time = whole()?,
+            "order_quantity" => candidate.sim.order_quantity = whole()?,
+            "job_lot_zipf" => candidate.sim.job_lot_zipf = value,
+            "itemwise_traffic_zipf" => candidate.sim.itemwise_traffic_zipf = value,
+            "starting_quantity" => candidate.starting_quantity = whole()?,
+            _ => {
+                return Err(ValueError::py_err(format!(
+                    "Unknown parameter {:?}, expected one of {:?}",
+                    name, PARAMETERS
+                )))
+            }
+        }
+        candidate
+            .sim
+            .check_parameters()
+            .map_err(ValueError::py_err)?;
+        Ok(candidate)
+    }
+
+    /// The parameters, as a Python dict
+    pub fn to_dict<'p>(&self, py: Python<'p>) -> PyResult<&'p PyDict> {
+        let dict = PyDict::new(py);
+        dict.set_item("safety_stock", self.sim.safety_stock)?;
+        dict.set_item("lead_time", self.sim.lead_time)?;
+        dict.set_item("order_quantity", self.sim.order_quantity)?;
+        dict.set_item("job_lot_zipf", self.sim.job_lot_zipf)?;
+        dict.set_item("itemwise_traffic_zipf", self.sim.itemwise_traffic_zipf)?;
+        dict.set_item("starting_quantity", self.starting_quantity)?;
+        Ok(dict)
+    }
+
+    /// Simulate `count` years of this candidate and measure how it did
+    pub fn evaluate(&self, count: usize, costs: Costs) -> Metrics {
+        self.evaluate_with(count, costs, &mut self.sim.rng())
+    }
+
+    /// Like evaluate, with your choice of random number generator
+    ///
+    /// Two candidates evaluated with identically seeded generators see the same customers,
+    /// so the difference between them is down to the policy rather than luck.
+    pub fn evaluate_with<R: Rng>(&self, count: usize, costs: Costs, rng: &mut R) -> Metrics {
+        let (mut st, mut ss, mut ft, mut fs) = (0, 0, 0, 0);
+        let (mut stock_days, mut trucks_ordered, mut peak_stock) = (0, 0, 0);
+        for _ in 0..count {
+            let year = self
+                .sim
+                .simulate_year(self.starting_quantity, rng, |_, _, _| {});
+            st += year.successful_transactions;
+            ss += year.successful_sales;
+            ft += year.failed_transactions;
+            fs += year.failed_sales;
+            stock_days += year.stock_days;
+            trucks_ordered += year.trucks_ordered;
+            peak_stock = peak_stock.max(year.peak_stock);
+        }
+        let years = count as f64;
+        Metrics {
+            transaction_fill_rate: fill_rate(st, ft),
+            unit_fill_rate: fill_rate(ss, fs),
+            total_cost: (costs.holding * stock_days as f64
+                + costs.order * trucks_ordered as f64
+                + costs.shortage * fs as f64)
+                / years,
+            average_stock: stock_days as f64 / (365.0 * years),
+            orders_per_year: trucks_ordered as f64 / years,
+            peak_stock: peak_stock as f64,
+        }
+    }
+}
+
+/// How a candidate did, averaged over its repetitions
+///
+/// From the GPU, only the fill rates are measured, and everything else is NaN.
+pub struct Metrics {
+    pub transaction_fill_rate: f64,
+    pub unit_fill_rate: f64,
+    /// Per year
+    pub total_cost: f64,
+    /// Stock on the shelf at the end of an average day
+    pub average_stock: f64,
+    pub orders_per_year: f64,
+    /// The most ever on the shelf in any year, which is how much space the policy needs
+    pub peak_stock: f64,
+}
+
+impl Metrics {
+    /// What the GPU measures from a summary, which is only the fill rates
+    pub fn from_summary(summary: Summary) -> Metrics {
+        let (_, _, _, _, transaction_fill_rate, unit_fill_rate) = summary;
+        Metrics {
+            transaction_fill_rate,
+            unit_fill_rate,
+            total_cost: f64::NAN,
+            average_stock: f64::NAN,
+            orders_per_year: f64::NAN,
+            peak_stock: f64::NAN,
+        }
+    }
+
+    /// The metrics in the same order as METRICS
+    pub fn to_row(&self) -> Vec<f64> {
+        vec![
+            self.unit_fill_rate,
+            self.transaction_fill_rate,
+            self.total_cost,
+            self.average_stock,
+            self.orders_per_year,
+            self.peak_stock,
+        ]
+    }
+
+    /// Look up a metric by name
+    pub fn get(&self, name: &str) -> PyResult<f64> {
+        METRICS
+            .iter()
+            .position(|&metric| metric == name)
+            .map(|i| self.to_row()[i])
+            .ok_or_else(|| {
+                ValueError::py_err(format!(
+                    "Unknown metric {:?}, expected one of {:?}",
+                    name, METRICS
+                ))
+            })
+    }
+
+    /// Add the metrics to a Python dict
+    pub fn fill_dict(&self, dict: &PyDict) -> PyResult<()> {
+        dict.set_item("transaction_fill_rate", self.transaction_fill_rate)?;
+        dict.set_item("unit_fill_rate", self.unit_fill_rate)?;
+        dict.set_item("total_cost", self.total_cost)?;
+        dict.set_item("average_stock", self.average_stock)?;
+        dict.set_item("orders_per_year", self.orders_per_year)?;
+        dict.set_item("peak_stock", self.peak_stock)?;
+        Ok(())
+    }
+}
+
+/// What we're trying to achieve
+#[derive(Clone, Copy)]
+pub enum Objective {
+    MaximizeUnitFillRate,
+    MaximizeTransactionFillRate,
+    MinimizeTotalCost,
+    MinimizeAverageStock,
+    MinimizeOrdersPerYear,
+}
+
+impl Objective {
+    pub fn parse(name: &str) -> PyResult<Objective> {
+        match name {
+            "fill_rate" | "unit_fill_rate" => Ok(Objective::MaximizeUnitFillRate),
+            "transaction_fill_rate" => Ok(Objective::MaximizeTransactionFillRate),
+            "total_cost" => Ok(Objective::MinimizeTotalCost),
+            "average_stock" => Ok(Objective::MinimizeAverageStock),
+            "orders_per_year" => Ok(Objective::MinimizeOrdersPerYear),
+            _ => Err(ValueError::py_err(format!(
+                "objective must be one of fill_rate, transaction_fill_rate, total_cost, \
+                 average_stock or orders_per_year, not {:?}",
+                name
+            ))),
+        }
+    }
+
+    /// The metric it's scored by
+    pub fn metric(self) -> &'static str {
+        match self {
+            Objective::MaximizeUnitFillRate => "unit_fill_rate",
+            Objective::MaximizeTransactionFillRate => "transaction_fill_rate",
+            Objective::MinimizeTotalCost => "total_cost",
+            Objective::MinimizeAverageStock => "average_stock",
+            Objective::MinimizeOrdersPerYear => "orders_per_year",
+        }
+    }
+
+    /// A single number where higher is always better
+    pub fn score(self, metrics: &Metrics) -> f64 {
+        match self {
+            Objective::MaximizeUnitFillRate => metrics.unit_fill_rate,
+            Objective::MaximizeTransactionFillRate => metrics.transaction_fill_rate,
+            Objective::MinimizeTotalCost => -metrics.total_cost,
+            Objective::MinimizeAverageStock => -metrics.average_stock,
+            Objective::MinimizeOrdersPerYear => -metrics.orders_per_year,
+        }
+    }
+}
+
+/// A limit on one metric, like "max_average_stock": 500 or "min_unit_fill_rate": 0.95
+pub struct Constraint {
+    pub name: String,
+    pub metric: String,
+    pub bound: f64,
+    pub upper: bool,
+}
+
+impl Constraint {
+    /// Read constraints from a dict of {"max_<metric>" or "min_<metric>": bound}
+    pub fn parse_all(constraints: Option<&PyDict>) -> PyResult<Vec<Constraint>> {
+        let mut parsed = vec![];
+        for (name, bound) in constraints.iter().flat_map(|dict| dict.iter()) {
+            let name: String = name.extract()?;
+            let (upper, metric) = if let Some(metric) = name.strip_prefix("max_") {
+                (true, metric)
+            } else if let Some(metric) = name.strip_prefix("min_") {
+                (false, metric)
+            } else {
+                return Err(ValueError::py_err(format!(
+                    "Constraints must start with max_ or min_, not {:?}",
+                    name
+                )));
+            };
+            if !METRICS.contains(&metric) {
+                return Err(ValueError::py_err(format!(
+                    "Unknown metric in constraint {:?}, expected one of {:?}",
+                    name, METRICS
+                )));
+            }
+            parsed.push(Constraint {
+                metric: metric.to_string(),
+                name,
+                bound: bound.extract()?,
+                upper,
+            });
+        }
+        Ok(parsed)
+    }
+
+    pub fn satisfied(&self, metrics: &Metrics) -> PyResult<bool> {
+        let value = metrics.get(&self.metric)?;
+        Ok(if self.upper {
+            value <= self.bound
+        } else {
+            value >= self.bound
+        })
+    }
+}
+
+/// The names of the constraints a candidate breaks
+pub fn violations(constraints: &[Constraint], metrics: &Metrics) -> PyResult<Vec<String>> {
+    let mut broken = vec![];
+    for constraint in constraints {
+        if !constraint.satisfied(metrics)? {
+            broken.push(constraint.name.clone());
+        }
+    }
+    Ok(broken)
+}
+
+/// Where to start from when a parameter isn't being searched over
+///
+/// Without a base simulation, the grid has to cover safety_stock, lead_time and
+/// order_quantity. When starting_quantity isn't given, each candidate starts at its safety stock.
+pub fn base_candidate(base: Option<&Simulation>, grid: &PyDict) -> PyResult<Candidate> {
+    let sim = match base {
+        Some(sim) => sim.clone(),
+        None => {
+            for &required in &["safety_stock", "lead_time", "order_quantity"] {
+                if grid.get_item(required).is_none() {
+                    return Err(ValueError::py_err(format!(
+                        "Without a base simulation, the grid needs {}",
+                        required
+                    )));
+                }
+            }
+            // These placeholders are always overwritten by the grid
+            Simulation {
+                safety_stock: 0,
+                lead_time: 1,
+                order_quantity: 1,
+                job_lot_zipf: 2.75,
+                itemwise_traffic_zipf: 4.0,
+                seed: None,
+                policy: None,
+                job_lot_mixture: None,
+                itemwise_traffic_mixture: None,
+                max_request: None,
+                max_request_mode: crate::mixture::Truncation::Clip,
+                compound_poisson: None,
+                forecast_bias: 0.0,
+                forecast_mape: 0.0,
+                timings: Default::default(),
+            }
+        }
+    };
+    let starting_quantity = sim.safety_stock;
+    Ok(Candidate {
+        sim,
+        starting_quantity,
+    })
+}
+
+/// Every combination of the values in the grid
+pub fn expand_grid(base: &Candidate, grid: &PyDict) -> PyResult<Vec<Candidate>> {
+    let mut axes: Vec<(String, Vec<f64>)> = vec![];
+    for (name, values) in grid.iter() {
+        axes.push((name.extract()?, values.extract()?));
+    }
+    let follows_safety_stock = grid.get_item("starting_quantity").is_none();
+    let mut candidates = vec![base.clone()];
+    for (name, values) in &axes {
+        let mut next = Vec::with_capacity(candidates.len() * values.len());
+        for candidate in &candidates {
+            for &value in values {
+                next.push(candidate.with(name, value)?);
+            }
+        }
+        candidates = next;
+    }
+    if follows_safety_stock {
+        for candidate in &mut candidates {
+            candidate.starting_quantity = candidate.sim.safety_stock;
+        }
+    }
+    Ok(candidates)
+}
+
+/// Read a 2-D array (numpy, or a list of lists) into rows
+///
+/// A contiguous float64 numpy array is copied in one go through the buffer protocol,
+/// anything else is read element by element.
+pub fn extract_matrix(py: Python<'_>, matrix: &PyAny) -> PyResult<Vec<Vec<f64>>> {
+    if let Ok(buffer) = PyBuffer::get(py, matrix) {
+        if buffer.dimensions() == 2 {
+            let columns = buffer.shape()[1];
+            let flat = buffer.to_vec::<f64>(py);
+            buffer.release(py);
+            if let Ok(flat) = flat {
+                return Ok(flat
+                    .chunks(columns.max(1))
+                    .map(|row| row.to_vec())
+                    .collect());
+            }
+        } else {
+            buffer.release(py);
+        }
+    }
+    matrix.extract()
+}
+
+/// Evaluate all the candidates, in parallel, without holding the GIL
+pub fn evaluate_all(
+    py: Python<'_>,
+    candidates: &[Candidate],
+    count: usize,
+    costs: Costs,
+) -> Vec<Metrics> {
+    py.allow_threads(|| {
+        candidates
+            .par_iter()
+            .map(|candidate| candidate.evaluate(count, costs))
+            .collect()
+    })
+}
+
+/// Evaluate all the candidates on rustoclsim's default device, a launch per program
+///
+/// The lead time, zipf exponents, seed and policy are compiled into the GPU's program, so
+/// candidates that share them go to a single repeat_simulate_demand_sweep, which runs every
+/// (starting quantity, safety stock, order quantity) among them in one kernel launch.
+pub fn evaluate_all_on_gpu(
+    py: Python<'_>,
+    candidates: &[Candidate],
+    count: usize,
+) -> PyResult<Vec<Metrics>> {
+    // The simulation each group compiles to, with the parameters the sweep passes in zeroed
+    let program = |candidate: &Candidate| Simulation {
+        safety_stock: 0,
+        order_quantity: 1,
+        ..candidate.sim.clone()
+    };
+    let mut groups: Vec<(Simulation, Vec<usize>)> = vec![];
+    for (i, candidate) in candidates.iter().enumerate() {
+        let key = program(candidate);
+        match groups.iter_mut().find(|(sim, _)| *sim == key) {
+            Some((_, members)) => members.push(i),
+            None => groups.push((key, vec![i])),
+        }
+    }
+    let mut metrics: Vec<Option<Metrics>> = candidates.iter().map(|_| None).collect();
+    for (sim, members) in &groups {
+        let cpu_only = sim.cpu_only_settings();
+        if !cpu_only.is_empty() {
+            return Err(ValueError::py_err(format!(
+                "backend=\"opencl\" only does plain zipf demand with a perfect forecast, \
+                 but the base simulation uses {}",
+                cpu_only.join(", ")
+            )));
+        }
+        let gpu_sim = backends::gpu_simulation(py, sim)?.ok_or_else(|| {
+            RuntimeError::py_err(
+                "backend=\"opencl\" needs rustoclsim and a working OpenCL device; \
+                 see rustoclsim.doctor()",
+            )
+        })?;
+        let policies: Vec<(usize, usize, usize)> = members
+            .iter()
+            .map(|&i| {
+                let candidate = &candidates[i];
+                (
+                    candidate.starting_quantity,
+                    candidate.sim.safety_stock,
+                    candidate.sim.order_quantity,
+                )
+            })
+            .collect();
+        let summaries: Vec<Summary> = gpu_sim
+            .call_method1(py, "repeat_simulate_demand_sweep", (policies, count))?
+            .extract(py)?;
+        for (&i, summary) in members.iter().zip(summaries) {
+            metrics[i] = Some(Metrics::from_summary(summary));
+        }
+    }
+    Ok(metrics.into_iter().flatten().collect())
+}
+
+/// Try every combination of parameters in `param_grid` and find the best one
+///
+/// `param_grid` maps parameter names (safety_stock, lead_time, order_quantity, job_lot_zipf,
+/// itemwise_traffic_zipf, starting_quantity) to lists of values to try. Anything not in the
+/// grid comes from `base`. Each combination is simulated `count` times, and the combinations
+/// are spread across all your cores.
+///
+/// With `backend="opencl"`, the combinations run on rustoclsim's default device instead,
+/// batched so that all those sharing a lead time and zipf exponents go in one kernel launch.
+/// The GPU only measures the fill rates, so the objective and constraints have to be about
+/// those, and the other metrics come back as NaN.
+///
+/// `objective` is fill_rate, transaction_fill_rate (both maximized) or total_cost (minimized).
+/// Costs per year are holding_cost per unit left on the shelf each night, order_cost per order
+/// and shortage_cost per unit of unmet demand. If `max_cost` is given, only combinations that
+/// cost at most that much per year are eligible.
+///
+/// `constraints` limits any other metric, as a dict like {"max_average_stock": 500,
+/// "max_peak_stock": 2000, "min_unit_fill_rate": 0.95}. Use average_stock for a budget on
+/// inventory held (multiply your budget by the unit value to get it in units) and peak_stock
+/// for warehouse space.
+///
+/// Returns (best, surface): best is a dict of the winning parameters and metrics (or None if
+/// nothing was eligible), and surface is a list of such dicts, one for every combination,
+/// each with a "feasible" flag and a list of the constraints it "violates".
+#[pyfunction(
+    objective = "\"fill_rate\"",
+    count = "1000",
+    base = "None",
+    max_cost = "None",
+    constraints = "None",
+    holding_cost = "0.01",
+    order_cost = "1.0",
+    shortage_cost = "1.0",
+    backend = "\"cpu\""
+)]
+#[allow(clippy::too_many_arguments)]
+fn grid_search(
+    py: Python<'_>,
+    param_grid: &PyDict,
+    objective: &str,
+    count: usize,
+    base: Option<&Simulation>,
+    max_cost: Option<f64>,
+    constraints: Option<&PyDict>,
+    holding_cost: f64,
+    order_cost: f64,
+    shortage_cost: f64,
+    backend: &str,
+) -> PyResult<(PyObject, Vec<PyObject>)> {
+    let objective = Objective::parse(objective)?;
+    let mut constraints = Constraint::parse_all(constraints)?;
+    if let Some(cap) = max_cost {
+        constraints.push(Constraint {
+            name: "max_total_cost".to_string(),
+            metric: "total_cost".to_string(),
+            bound: cap,
+            upper: true,
+        });
+    }
+    let costs = Costs {
+        holding: holding_cost,
+        order: order_cost,
+        shortage: shortage_cost,
+    };
+    let candidates = expand_grid(&base_candidate(base, param_grid)?, param_grid)?;
+    let metrics = match backend {
+        "cpu" => evaluate_all(py, &candidates, count, costs),
+        "opencl" => {
+            let unmeasured = std::iter::once(objective.metric())
+                .chain(constraints.iter().map(|constraint| &constraint.metric[..]))
+                .find(|metric| !GPU_METRICS.contains(metric));
+            if let Some(metric) = unmeasured {
+                return Err(ValueError::py_err(format!(
+                    "backend=\"opencl\" only measures {:?}, not {}",
+                    GPU_METRICS, metric
+                )));
+            }
+            evaluate_all_on_gpu(py, &candidates, count)?
+        }
+        _ => {
+            return Err(ValueError::py_err(format!(
+                "backend must be \"cpu\" or \"opencl\", not {:?}",
+                backend
+            )))
+        }
+    };
+
+    let mut best: Option<(f64, PyObject)> = None;
+    let mut surface = Vec::with_capacity(candidates.len());
+    for (candidate, metrics) in candidates.iter().zip(&metrics) {
+        let violated = violations(&constraints, metrics)?;
+        let feasible = violated.is_empty();
+        let dict = candidate.to_dict(py)?;
+        metrics.fill_dict(dict)?;
+        dict.set_item("feasible", feasible)?;
+        dict.set_item("violates", violated)?;
+        let score = objective.score(metrics);
+        if feasible
+            && best
+                .as_ref()
+                .is_none_or(|(best_score, _)| score > *best_score)
+        {
+            best = Some((score, dict.to_object(py)));
+        }
+        surface.push(dict.to_object(py));
+    }
+    Ok((best.map_or_else(|| py.None(), |(_, dict)| dict), surface))
+}
+
+/// Simulate every combination in `param_grid` and write the results straight to a Parquet
+/// file at `path`, one row per combination
+///
+/// The grid, base, count and costs work just like grid_search. The columns are
+/// rustsim.optimize.PARAMETERS followed by METRICS, with the integer parameters as int64, and
+/// the file's metadata records the count and costs. Nothing goes through Python objects on
+/// the way, so this suits sweeps far too big to look at row by row. Returns the number of
+/// rows written.
+#[pyfunction(
+    count = "1000",
+    base = "None",
+    holding_cost = "0.01",
+    order_cost = "1.0",
+    shortage_cost = "1.0"
+)]
+#[allow(clippy::too_many_arguments)]
+fn sweep_to_parquet(
+    py: Python<'_>,
+    param_grid: &PyDict,
+    path: &str,
+    count: usize,
+    base: Option<&Simulation>,
+    holding_cost: f64,
+    order_cost: f64,
+    shortage_cost: f64,
+) -> PyResult<usize> {
+    let costs = Costs {
+        holding: holding_cost,
+        order: order_cost,
+        shortage: shortage_cost,
+    };
+    let candidates = expand_grid(&base_candidate(base, param_grid)?, param_grid)?;
+    let metrics = evaluate_all(py, &candidates, count, costs);
+
+    let columns = sweep_columns(&candidates, &metrics);
+    let count = count.to_string();
+    let costs = format!("{} {} {}", holding_cost, order_cost, shortage_cost);
+    let metadata = [
+        ("rustsim.count", count.as_str()),
+        ("rustsim.holding_order_shortage_costs", costs.as_str()),
+    ];
+    let file = std::fs::File::create(path)?;
+    parquet::write(std::io::BufWriter::new(file), &columns, &metadata)?;
+    Ok(candidates.len())
+}
+
+/// Like sweep_to_parquet, but returning the table as a pandas DataFrame
+#[pyfunction(
+    count = "1000",
+    base = "None",
+    holding_cost = "0.01",
+    order_cost = "1.0",
+    shortage_cost = "1.0"
+)]
+fn sweep_to_pandas(
+    py: Python<'_>,
+    param_grid: &PyDict,
+    count: usize,
+    base: Option<&Simulation>,
+    holding_cost: f64,
+    order_cost: f64,
+    shortage_cost: f64,
+) -> PyResult<PyObject> {
+    let costs = Costs {
+        holding: holding_cost,
+        order: order_cost,
+        shortage: shortage_cost,
+    };
+    let candidates = expand_grid(&base_candidate(base, param_grid)?, param_grid)?;
+    let metrics = evaluate_all(py, &candidates, count, costs);
+    let columns = sweep_columns(&candidates, &metrics)
+        .into_iter()
+        .map(|(name, column)| {
+            let values = match column {
+                Column::Int64(values) => values.to_object(py),
+                Column::Double(values) => values.to_object(py),
+                Column::Text(values) => values.to_object(py),
+            };
+            (name, values)
+        })
+        .collect();
+    data_frame(py, columns)
+}
+
+/// A sweep's results as a table: PARAMETERS, then METRICS, with a row per candidate
+fn sweep_columns(candidates: &[Candidate], metrics: &[Metrics]) -> Vec<(&'static str, Column)> {
+    let integers = |value: fn(&Candidate) -> usize| {
+        Column::Int64(candidates.iter().map(|c| value(c) as i64).collect())
+    };
+    let doubles =
+        |value: fn(&Candidate) -> f64| Column::Double(candidates.iter().map(value).collect());
+    let mut columns = vec![
+        ("safety_stock", integers(|c| c.sim.safety_stock)),
+        ("lead_time", integers(|c| c.sim.lead_time)),
+        ("order_quantity", integers(|c| c.sim.order_quantity)),
+        ("job_lot_zipf", doubles(|c| c.sim.job_lot_zipf)),
+        (
+            "itemwise_traffic_zipf",
+            doubles(|c| c.sim.itemwise_traffic_zipf),
+        ),
+        ("starting_quantity", integers(|c| c.starting_quantity)),
+    ];
+    for (i, &name) in METRICS.iter().enumerate() {
+        columns.push((
+            name,
+            Column::Double(metrics.iter().map(|m| m.to_row()[i]).collect()),
+        ));
+    }
+    columns
+}
+
+/// Find the policies in `param_grid` that can't be improved in one objective without
+/// giving up some of another
+///
+/// The grid, base and costs work just like grid_search. `objectives` can be any of fill_rate,
+/// transaction_fill_rate (maximized), total_cost, average_stock or orders_per_year (minimized).
+/// Returns the non-dominated combinations as a list of dicts of parameters and metrics, in
+/// grid order. Nothing else in the grid is at least as good at everything.
+///
+/// With `constraints` (as in grid_search), only the feasible combinations are considered.
+#[pyfunction(
+    objectives = "vec![\"fill_rate\".to_string(), \"total_cost\".to_string()]",
+    count = "1000",
+    base = "None",
+    constraints = "None",
+    holding_cost = "0.01",
+    order_cost = "1.0",
+    shortage_cost = "1.0"
+)]
+#[allow(clippy::too_many_arguments)]
+fn pareto(
+    py: Python<'_>,
+    param_grid: &PyDict,
+    objectives: Vec<String>,
+    count: usize,
+    base: Option<&Simulation>,
+    constraints: Option<&PyDict>,
+    holding_cost: f64,
+    order_cost: f64,
+    shortage_cost: f64,
+) -> PyResult<Vec<PyObject>> {
+    let constraints = Constraint::parse_all(constraints)?;
+    let objectives = objectives
+        .iter()
+        .map(|name| Objective::parse(name))
+        .collect::<PyResult<Vec<Objective>>>()?;
+    let costs = Costs {
+        holding: holding_cost,
+        order: order_cost,
+        shortage: shortage_cost,
+    };
+    let candidates = expand_grid(&base_candidate(base, param_grid)?, param_grid)?;
+    let metrics = evaluate_all(py, &candidates, count, costs);
+    let mut feasible = Vec::with_capacity(metrics.len());
+    for m in &metrics {
+        feasible.push(violations(&constraints, m)?.is_empty());
+    }
+    let (candidates, metrics): (Vec<Candidate>, Vec<Metrics>) = candidates
+        .into_iter()
+        .zip(metrics)
+        .zip(&feasible)
+        .filter(|(_, &ok)| ok)
+        .map(|(pair, _)| pair)
+        .unzip();
+    let scores: Vec<Vec<f64>> = metrics
+        .iter()
+        .map(|m| objectives.iter().map(|o| o.score(m)).collect())
+        .collect();
+
+    let mut frontier = vec![];
+    for (i, candidate) in candidates.iter().enumerate() {
+        if !scores.iter().any(|other| dominates(other, &scores[i])) {
+            let dict = candidate.to_dict(py)?;
+            metrics[i].fill_dict(dict)?;
+            frontier.push(dict.to_object(py));
+        }
+    }
+    Ok(frontier)
+}
+
+/// Find the policy in `param_grid` that holds up best across several demand scenarios
+///
+/// The fitted zipf exponents are only estimates, so rather than trusting one pair of them,
+/// `scenarios` lists the plausible alternatives as dicts like {"job_lot_zipf": 2.5,
+/// "itemwise_traffic_zipf": 3.8}. Every combination in the grid is simulated under every
+/// scenario, each scenario seeing the same customers for every combination. The grid, base
+/// and costs work just like grid_search, but shouldn't include the scenario parameters.
+///
+/// `criterion` decides how the per-scenario scores are combined: "worst_case" takes the worst
+/// scenario, and "cvar" takes the average of the worst `alpha` fraction of scenarios (at least
+/// one), which is less at the mercy of a single pessimistic scenario.
+///
+/// Returns (best, surface) like grid_search, where each dict also has the "robust_score" and
+/// the "scenario_scores" in scenario order. Scores are the objective, negated for metrics that
+/// are minimized, so higher is always better.
+#[pyfunction(
+    objective = "\"fill_rate\"",
+    criterion = "\"worst_case\"",
+    alpha = "0.2",
+    count = "1000",
+    base = "None",
+    holding_cost = "0.01",
+    order_cost = "1.0",
+    shortage_cost = "1.0"
+)]
+#[allow(clippy::too_many_arguments)]
+fn robust_search(
+    py: Python<'_>,
+    param_grid: &PyDict,
+    scenarios: Vec<&PyDict>,
+    objective: &str,
+    criterion: &str,
+    alpha: f64,
+    count: usize,
+    base: Option<&Simulation>,
+    holding_cost: f64,
+    order_cost: f64,
+    shortage_cost: f64,
+) -> PyResult<(PyObject, Vec<PyObject>)> {
+    let objective = Objective::parse(objective)?;
+    let worst = match criterion {
+        "worst_case" => 1,
+        "cvar" if alpha > 0.0 && alpha <= 1.0 => {
+            ((alpha * scenarios.len() as f64).ceil() as usize).max(1)
+        }
+        "cvar" => return Err(ValueError::py_err("alpha must be in (0, 1]")),
+        _ => {
+            return Err(ValueError::py_err(format!(
+                "criterion must be worst_case or cvar, not {:?}",
+                criterion
+            )))
+        }
+    };
+    if scenarios.is_empty() {
+        return Err(ValueError::py_err("scenarios needs at least one scenario"));
+    }
+    let costs = Costs {
+        holding: holding_cost,
+        order: order_cost,
+        shortage: shortage_cost,
+    };
+    let start = base_candidate(base, param_grid)?;
+    let candidates = expand_grid(&start, param_grid)?;
+
+    // Every candidate under every scenario, candidate-major
+    let mut runs = Vec::with_capacity(candidates.len() * scenarios.len());
+    for candidate in &candidates {
+        for (i, scenario) in scenarios.iter().enumerate() {
+            let mut run = candidate.clone();
+            for (name, value) in scenario.iter() {
+                run = run.with(name.extract()?, value.extract()?)?;
+            }
+            runs.push((i, run));
+        }
+    }
+    let mut seeds_rng = start.sim.rng();
+    let seeds: Vec<u64> = scenarios.iter().map(|_| seeds_rng.gen()).collect();
+    let metrics: Vec<Metrics> = py.allow_threads(|| {
+        runs.par_iter()
+            .map(|(i, run)| run.evaluate_with(count, costs, &mut StdRng::seed_from_u64(seeds[*i])))
+            .collect()
+    });
+
+    let mut best: Option<(f64, PyObject)> = None;
+    let mut surface = Vec::with_capacity(candidates.len());
+    for (candidate, metrics) in candidates.iter().zip(metrics.chunks(scenarios.len())) {
+        let scores: Vec<f64> = metrics.iter().map(|m| objective.score(m)).collect();
+        let mut sorted = scores.clone();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let score = sorted[..worst].iter().sum::<f64>() / worst as f64;
+
+        let dict = candidate.to_dict(py)?;
+        dict.set_item("robust_score", score)?;
+        dict.set_item("scenario_scores", scores)?;
+        if best
+            .as_ref()
+            .is_none_or(|(best_score, _)| score > *best_score)
+        {
+            best = Some((score, dict.to_object(py)));
+        }
+        surface.push(dict.to_object(py));
+    }
+    Ok((best.map_or_else(|| py.None(), |(_, dict)| dict), surface))
+}
+
+/// Whether `a` is at least as good as `b` at everything, and better at something
+fn dominates(a: &[f64], b: &[f64]) -> bool {
+    a.iter().zip(b).all(|(x, y)| x >= y) && a.iter().zip(b).any(|(x, y)| x > y)
+}
+
+/// The parameters simulated annealing can search over, since it takes whole steps
+const INTEGER_PARAMETERS: [&str; 4] = [
+    "safety_stock",
+    "lead_time",
+    "order_quantity",
+    "starting_quantity",
+];
+
+/// Search integer policy parameters with simulated annealing
+///
+/// `bounds` maps each parameter to search (safety_stock, lead_time, order_quantity or
+/// starting_quantity) to an inclusive (low, high) range; the rest come from `base`.
+/// We start in the middle of the ranges, and at each of `steps` steps we nudge one parameter
+/// up or down. Better neighbors are always accepted, and worse ones with probability
+/// exp(-loss / temperature), where the temperature starts at `initial_temperature` and is
+/// multiplied by `cooling` every step. That lets the search wander out of local optima early
+/// on and settle down later.
+///
+/// `objective` is either one of the names grid_search accepts, or any Python callable that
+/// takes the dict of parameters and metrics and returns a number to maximize.
+/// Returns the dict of parameters and metrics of the best policy seen.
+#[pyfunction(
+    objective = "None",
+    count = "1000",
+    base = "None",
+    steps = "1000",
+    initial_temperature = "0.1",
+    cooling = "0.995",
+    holding_cost = "0.01",
+    order_cost = "1.0",
+    shortage_cost = "1.0"
+)]
+#[allow(clippy::too_many_arguments)]
+fn anneal(
+    py: Python<'_>,
+    bounds: &PyDict,
+    objective: Option<&PyAny>,
+    count: usize,
+    base: Option<&Simulation>,
+    steps: usize,
+    initial_temperature: f64,
+    cooling: f64,
+    holding_cost: f64,
+    order_cost: f64,
+    shortage_cost: f64,
+) -> PyResult<PyObject> {
+    let costs = Costs {
+        holding: holding_cost,
+        order: order_cost,
+        shortage: shortage_cost,
+    };
+    let mut ranges: Vec<(String, i64, i64)> = vec![];
+    for (name, range) in bounds.iter() {
+        let name: String = name.extract()?;
+        let (low, high): (i64, i64) = range.extract()?;
+        if !INTEGER_PARAMETERS.contains(&name.as_str()) {
+            return Err(ValueError::py_err(format!(
+                "Can only anneal over {:?}, not {:?}",
+                INTEGER_PARAMETERS, name
+            )));
+        }
+        if low > high {
+            return Err(ValueError::py_err(format!("Empty range for {}", name)));
+        }
+        ranges.push((name, low, high));
+    }
+    if ranges.is_empty() {
+        return Err(ValueError::py_err("bounds needs at least one parameter"));
+    }
+    let follows_safety_stock = bounds.get_item("starting_quantity").is_none();
+    let place = |values: &[i64]| -> PyResult<Candidate> {
+        let mut candidate = base_candidate(base, bounds)?;
+        for ((name, _, _), &value) in ranges.iter().zip(values) {
+            candidate = candidate.with(name, value as f64)?;
+        }
+        if follows_safety_stock {
+            candidate.starting_quantity = candidate.sim.safety_stock;
+        }
+        Ok(candidate)
+    };
+    // Score a candidate, and keep its dict around in case it turns out to be the best
+    let score = |candidate: &Candidate| -> PyResult<(f64, PyObject)> {
+        let metrics = py.allow_threads(|| candidate.evaluate(count, costs));
+        let dict = candidate.to_dict(py)?;
+        metrics.fill_dict(dict)?;
+        let score = match objective {
+            None => Objective::MaximizeUnitFillRate.score(&metrics),
+            Some(objective) => match objective.extract::<&str>() {
+                Ok(name) => Objective::parse(name)?.score(&metrics),
+                Err(_) => objective.call1((dict,))?.extract()?,
+            },
+        };
+        Ok((score, dict.to_object(py)))
+    };
+
+    let mut rng = base_candidate(base, bounds)?.sim.rng();
+    let mut current: Vec<i64> = ranges
+        .iter()
+        .map(|(_, low, high)| (low + high) / 2)
+        .collect();
+    let (mut current_score, current_dict) = score(&place(&current)?)?;
+    let mut best = (current_score, current_dict);
+    let mut temperature = initial_temperature;
+    for _ in 0..steps {
+        // Nudge one parameter by up to a tenth of its range
+        let mut neighbor = current.clone();
+        let i = rng.gen_range(0, ranges.len());
+        let (_, low, high) = ranges[i];
+        let reach = ((high - low) / 10).max(1);
+        let step = rng.gen_range(1, reach + 1) * if rng.gen() { 1 } else { -1 };
+        neighbor[i] = (neighbor[i] + step).max(low).min(high);
+        if neighbor == current {
+            continue;
+        }
+
+        let (neighbor_score, neighbor_dict) = score(&place(&neighbor)?)?;
+        let accept = neighbor_score >= current_score
+            || rng.gen::<f64>() < ((neighbor_score - current_score) / temperature).exp();
+        if accept {
+            current = neighbor;
+            current_score = neighbor_score;
+            if current_score > best.0 {
+                best = (neighbor_score, neighbor_dict);
+            }
+        }
+        temperature *= cooling;
+    }
+    Ok(best.1)
+}
+
+/// Fit a quadratic response surface to the results of a sweep
+///
+/// `points` is a list of dicts like the surface from grid_search, `params` names the
+/// parameters to fit over (they should all vary in the sweep), and `metric` is the metric to
+/// model. The fitted surface can interpolate between grid points, and estimate where the
+/// optimum lies and how sharply the metric falls off around it.
+#[pyfunction(metric = "\"unit_fill_rate\"")]
+fn fit_surface(
+    points: Vec<&PyDict>,
+    params: Vec<String>,
+    metric: &str,
+) -> PyResult<ResponseSurface> {
+    let mut xs = Vec::with_capacity(points.len());
+    let mut ys = Vec::with_capacity(points.len());
+    let missing = |name: &str| ValueError::py_err(format!("A point is missing {:?}", name));
+    for point in points {
+        let mut x = Vec::with_capacity(params.len());
+        for name in &params {
+            x.push(
+                point
+                    .get_item(name.as_str())
+                    .ok_or_else(|| missing(name))?
+                    .extract()?,
+            );
+        }
+        xs.push(x);
+        ys.push(
+            point
+                .get_item(metric)
+                .ok_or_else(|| missing(metric))?
+                .extract()?,
+        );
+    }
+    ResponseSurface::fit(params, &xs, &ys)
+}
+
+/// Optimization tools, available as rustsim.optimize
+#[pymodule]
+fn optimize(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_wrapped(wrap_pyfunction!(grid_search))?;
+    m.add_wrapped(wrap_pyfunction!(sweep_to_parquet))?;
+    m.add_wrapped(wrap_pyfunction!(sweep_to_pandas))?;
+    m.add_wrapped(wrap_pyfunction!(pareto))?;
+    m.add_wrapped(wrap_pyfunction!(anneal))?;
+    m.add_wrapped(wrap_pyfunction!(robust_search))?;
+    m.add_wrapped(wrap_pyfunction!(fit_surface))?;
+    m.add_class::<ResponseSurface>()?;
+    m.add("PARAMETERS", PARAMETERS.to_vec())?;
+    m.add("METRICS", METRICS.to_vec())?;
+
+    Ok(())
+}
@@ -0,0 +1,418 @@
+//! Writing tables to Parquet, so sweep results go straight into Spark, DuckDB or pandas
+//!
+//! This is just enough of the format for flat tables of numbers and text: every column is
+//! required, PLAIN encoded and uncompressed, and the whole table is one row group with one
+//! page per column. Anything that reads Parquet reads that, and a sweep is small enough that
+//! compression wouldn't buy much. The metadata is Thrift's compact protocol, written by hand
+//! below, so we don't need the whole Arrow stack to produce a few columns.
+use std::convert::TryFrom;
+use std::io::{self, Write};
+
+/// One column's values
+pub enum Column {
+    Int64(Vec<i64>),
+    Double(Vec<f64>),
+    Text(Vec<String>),
+}
+
+impl Column {
+    fn len(&self) -> usize {
+        match self {
+            Column::Int64(values) => values.len(),
+            Column::Double(values) => values.len(),
+            Column::Text(values) => values.len(),
+        }
+    }
+
+    /// Parquet's physical type
+    fn physical_type(&self) -> i32 {
+        match self {
+            Column::Int64(_) => 2,
+            Column::Double(_) => 5,
+            Column::Text(_) => 6,
+        }
+    }
+
+    /// The values, PLAIN encoded
+    fn plain(&self) -> Vec<u8> {
+        let mut out = vec![];
+        match self {
+            Column::Int64(values) => values.iter().for_each(|v| out.extend(&v.to_le_bytes())),
+            Column::Double(values) => values.iter().for_each(|v| out.extend(&v.to_le_bytes())),
+            Column::Text(values) => {
+                for value in values {
+                    out.extend(&(value.len() as u32).to_le_bytes());
+                    out.extend(value.as_bytes());
+                }
+            }
+        }
+        out
+    }
+}
+
+// Parquet's enums, as far as we use them
+const PLAIN: i32 = 0;
+const RLE: i32 = 3;
+const DATA_PAGE: i32 = 0;
+const REQUIRED: i32 = 0;
+const UTF8: i32 = 0;
+const UNCOMPRESSED: i32 = 0;
+
+/// Write `columns` (all the same length) as a Parquet file, with `metadata` as its key-value
+/// metadata
+pub fn write<W: Write>(
+    mut out: W,
+    columns: &[(&str, Column)],
+    metadata: &[(&str, &str)],
+) -> io::Result<()> {
+    let rows = columns.first().map_or(0, |(_, column)| column.len());
+    if columns.iter().any(|(_, column)| column.len() != rows) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "every column must be the same length",
+        ));
+    }
+    out.write_all(b"PAR1")?;
+    let mut offset = 4;
+    let mut chunks = vec![];
+    for (name, column) in columns {
+        let data = column.plain();
+        let size = i32::try_from(data.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "a column is over 2GB"))?;
+        let mut header = Thrift::default();
+        header.i32(1, DATA_PAGE);
+        header.i32(2, size);
+        header.i32(3, size);
+        header.begin(5);
+        header.i32(1, rows as i32);
+        header.i32(2, PLAIN);
+        header.i32(3, RLE);
+        header.i32(4, RLE);
+        header.end();
+        header.stop();
+        out.write_all(&header.out)?;
+        out.write_all(&data)?;
+        let total = (header.out.len() + data.len()) as i64;
+        chunks.push((*name, column.physical_type(), offset, total));
+        offset += total;
+    }
+
+    let mut footer = Thrift::default();
+    footer.i32(1, 1);
+    footer.list(2, STRUCT, columns.len() + 1);
+    footer.begin_element();
+    footer.string(4, "schema");
+    footer.i32(5, columns.len() as i32);
+    footer.end();
+    for (name, column) in columns {
+        footer.begin_element();
+        footer.i32(1, column.physical_type());
+        footer.i32(3, REQUIRED);
+        footer.string(4, name);
+        if let Column::Text(_) = column {
+            footer.i32(6, UTF8);
+        }
+        footer.end();
+    }
+    footer.i64(3, rows as i64);
+    footer.list(4, STRUCT, 1);
+    footer.begin_element();
+    footer.list(1, STRUCT, chunks.len());
+    for &(name, physical_type, offset, total) in &chunks {
+        footer.begin_element();
+        footer.i64(2, offset);
+        footer.begin(3);
+        footer.i32(1, physical_type);
+        footer.list(2, I32, 2);
+        footer.varint(zigzag(PLAIN.into()));
+        footer.varint(zigzag(RLE.into()));
+        footer.list(3, BINARY, 1);
+        footer.bytes(name.as_bytes());
+        footer.i32(4, UNCOMPRESSED);
+        footer.i64(5, rows as i64);
+        footer.i64(6, total);
+        footer.i64(7, total);
+        footer.i64(9, offset);
+        footer.end();
+        footer.end();
+    }
+    footer.i64(2, chunks.iter().map(|chunk| chunk.3).sum());
+    footer.i64(3, rows as i64);
+    footer.end();
+    if !metadata.is_empty() {
+        footer.list(5, STRUCT, metadata.len());
+        for (key, value) in metadata {
+            footer.begin_element();
+            footer.string(1, key);
+            footer.string(2, value);
+            footer.end();
+        }
+    }
+    footer.string(6, concat!("rustsim ", env!("CARGO_PKG_VERSION")));
+    footer.stop();
+    out.write_all(&footer.out)?;
+    out.write_all(&(footer.out.len() as u32).to_le_bytes())?;
+    out.write_all(b"PAR1")
+}
+
+// Thrift compact protocol types
+const I32: u8 = 5;
+const I64: u8 = 6;
+const BINARY: u8 = 8;
+const LIST: u8 = 9;
+const STRUCT: u8 = 12;
+
+fn zigzag(n: i64) -> u64 {
+    ((n << 1) ^ (n >> 63)) as u64
+}
+
+/// A Thrift compact protocol encoder, for the few types Parquet's metadata needs
+#[derive(Default)]
+struct Thrift {
+    out: Vec<u8>,
+    /// The last field id in each struct we're inside, since ids are written as deltas
+    last: Vec<i16>,
+    current: i16,
+}
+
+impl Thrift {
+    fn varint(&mut self, mut n: u64) {
+        while n >= 0x80 {
+            self.out.push(n as u8 | 0x80);
+            n >>= 7;
+        }
+        self.out.push(n as u8);
+    }
+
+    fn field(&mut self, id: i16, kind: u8) {
+        let delta = id - self.current;
+        if 0 < delta && delta <= 15 {
+            self.out.push((delta as u8) << 4 | kind);
+        } else {
+            self.out.push(kind);
+            self.varint(zigzag(id.into()));
+        }
+        self.current = id;
+    }
+
+    fn i32(&mut self, id: i16, value: i32) {
+        self.field(id, I32);
+        self.varint(zigzag(value.into()));
+    }
+
+    fn i64(&mut self, id: i16, value: i64) {
+        self.field(id, I64);
+        self.varint(zigzag(value));
+    }
+
+    fn bytes(&mut self, value: &[u8]) {
+        self.varint(value.len() as u64);
+        self.out.extend(value);
+    }
+
+    fn string(&mut self, id: i16, value: &str) {
+        self.field(id, BINARY);
+        self.bytes(value.as_bytes());
+    }
+
+    /// Start a list field of `size` elements of type `kind`
+    fn list(&mut self, id: i16, kind: u8, size: usize) {
+        self.field(id, LIST);
+        if size < 15 {
+            self.out.push((size as u8) << 4 | kind);
+        } else {
+            self.out.push(0xf0 | kind);
+            self.varint(size as u64);
+        }
+    }
+
+    /// Start a struct field; end() finishes it
+    fn begin(&mut self, id: i16) {
+        self.field(id, STRUCT);
+        self.begin_element();
+    }
+
+    /// Start a struct that's an element of a list; end() finishes it
+    fn begin_element(&mut self) {
+        self.last.push(self.current);
+        self.current = 0;
+    }
+
+    fn end(&mut self) {
+        self.stop();
+        self.current = self.last.pop().unwrap_or(0);
+    }
+
+    fn stop(&mut self) {
+        self.out.push(0);
+    }
+}
+
+#[test]
+fn test_parquet() {
+    // The file starts and ends with PAR1, the length before the last one is the footer's, and
+    // the footer decodes to metadata whose offsets lead to pages holding the values we wrote
+    #[derive(Debug)]
+    enum Value {
+        Int(i64),
+        Bytes(Vec<u8>),
+        List(Vec<Value>),
+        Struct(Vec<(i16, Value)>),
+    }
+    impl Value {
+        fn field(&self, id: i16) -> &Value {
+            match self {
+                Value::Struct(fields) => &fields.iter().find(|field| field.0 == id).unwrap().1,
+                _ => panic!("{:?} isn't a struct", self),
+            }
+        }
+        fn int(&self) -> i64 {
+            match self {
+                Value::Int(n) => *n,
+                _ => panic!("{:?} isn't an integer", self),
+            }
+        }
+        fn text(&self) -> &str {
+            match self {
+                Value::Bytes(bytes) => std::str::from_utf8(bytes).unwrap(),
+                _ => panic!("{:?} isn't text", self),
+            }
+        }
+        fn list(&self) -> &[Value] {
+            match self {
+                Value::List(values) => values,
+                _ => panic!("{:?} isn't a list", self),
+            }
+        }
+    }
+    // A Thrift compact protocol decoder, for the types Thrift above encodes
+    fn varint(input: &mut &[u8]) -> u64 {
+        let (mut n, mut shift) = (0, 0);
+        loop {
+            let byte = input[0];
+            *input = &input[1..];
+            n |= u64::from(byte & 0x7f) << shift;
+            if byte < 0x80 {
+                return n;
+            }
+            shift += 7;
+        }
+    }
+    fn signed(input: &mut &[u8]) -> i64 {
+        let n = varint(input);
+        (n >> 1) as i64 ^ -((n & 1) as i64)
+    }
+    fn value(input: &mut &[u8], kind: u8) -> Value {
+        match kind {
+            I32 | I64 => Value::Int(signed(input)),
+            BINARY => {
+                let len = varint(input) as usize;
+                let bytes = input[..len].to_vec();
+                *input = &input[len..];
+                Value::Bytes(bytes)
+            }
+            LIST => {
+                let header = input[0];
+                *input = &input[1..];
+                let size = match header >> 4 {
+                    15 => varint(input) as usize,
+                    size => size as usize,
+                };
+                Value::List((0..size).map(|_| value(input, header & 0xf)).collect())
+            }
+            STRUCT => {
+                let (mut fields, mut id) = (vec![], 0);
+                loop {
+                    let header = input[0];
+                    *input = &input[1..];
+                    if header == 0 {
+                        return Value::Struct(fields);
+                    }
+                    id = match header >> 4 {
+                        0 => signed(input) as i16,
+                        delta => id + i16::from(delta),
+                    };
+                    fields.push((id, value(input, header & 0xf)));
+                }
+            }
+            _ => panic!("Unexpected Thrift type {}", kind),
+        }
+    }
+
+    let mut file = vec![];
+    write(
+        &mut file,
+        &[
+            ("quantity", Column::Int64(vec![3, -1, 7])),
+            ("rate", Column::Double(vec![0.5, 0.25, 1.0])),
+            (
+                "policy",
+                Column::Text(vec!["a".into(), "bc".into(), "".into()]),
+            ),
+        ],
+        &[("seed", "42")],
+    )
+    .unwrap();
+    assert_eq!(&file[..4], b"PAR1");
+    assert_eq!(&file[file.len() - 4..], b"PAR1");
+    let mut length = [0; 4];
+    length.copy_from_slice(&file[file.len() - 8..file.len() - 4]);
+    let footer_start = file.len() - 8 - u32::from_le_bytes(length) as usize;
+    let mut footer = &file[footer_start..file.len() - 8];
+    let metadata = value(&mut footer, STRUCT);
+    assert!(
+        footer.is_empty(),
+        "The footer length doesn't match the footer"
+    );
+
+    assert_eq!(metadata.field(1).int(), 1);
+    assert_eq!(metadata.field(3).int(), 3);
+    let schema = metadata.field(2).list();
+    assert_eq!(schema[0].field(5).int(), 3);
+    let names: Vec<&str> = schema[1..]
+        .iter()
+        .map(|element| element.field(4).text())
+        .collect();
+    assert_eq!(names, ["quantity", "rate", "policy"]);
+    assert_eq!(schema[3].field(6).int(), i64::from(UTF8));
+    let key_value = &metadata.field(5).list()[0];
+    assert_eq!(
+        (key_value.field(1).text(), key_value.field(2).text()),
+        ("seed", "42")
+    );
+
+    let row_groups = metadata.field(4).list();
+    assert_eq!(row_groups.len(), 1);
+    assert_eq!(row_groups[0].field(3).int(), 3);
+    let chunks = row_groups[0].field(1).list();
+    let mut pages = vec![];
+    let mut end = 4;
+    for (chunk, name) in chunks.iter().zip(&names) {
+        let meta = chunk.field(3);
+        assert_eq!(meta.field(3).list()[0].text(), *name);
+        assert_eq!(meta.field(5).int(), 3);
+        let offset = meta.field(9).int() as usize;
+        assert_eq!(offset, end, "Column chunks should follow one another");
+        let mut page = &file[offset..];
+        let header = value(&mut page, STRUCT);
+        let size = header.field(2).int() as usize;
+        assert_eq!(header.field(5).field(1).int(), 3);
+        pages.push(page[..size].to_vec());
+        end = offset + meta.field(7).int() as usize;
+    }
+    assert_eq!(
+        end, footer_start,
+        "The footer should follow the last column chunk"
+    );
+    let mut values: Vec<u8> = vec![];
+    [3i64, -1, 7]
+        .iter()
+        .for_each(|v| values.extend(&v.to_le_bytes()));
+    assert_eq!(pages[0], values);
+    values.clear();
+    [0.5f64, 0.25, 1.0]
+        .iter()
+        .for_each(|v| values.extend(&v.to_le_bytes()));
+    assert_eq!(pages[1], values);
+    assert_eq!(pages[2], b"\x01\0\0\0a\x02\0\0\0bc\0\0\0\0");
+}
@@ -0,0 +1,228 @@
+//! A tiny expression language for ordering policies
+//!
+//! The built-in rule orders whole multiples of order_quantity to get back up to safety_stock.
+//! A policy replaces it with an expression, evaluated at the end of each day, whose value is
+//! how many units to order, like
+//!
+//! ```text
+//! max(0, 2 * forecast * lead_time + safety_stock - on_hand - pipeline)
+//! ```
+//!
+//! It's written in the arithmetic of expression.rs, over the names in Inputs.
+//!
+//! The CPU interprets the expression, and rustoclsim pastes to_opencl() into its kernel,
+//! where it runs as fast as the built-in rule. Both work in single precision, with the same
+//! operations in the same order, so a policy orders the same on either, as long as its
+//! numbers stay where a float is exact (below 16 million or so). Whatever the expression
+//! comes to is cut down to a whole number of units, with anything negative or NaN ordering
+//! nothing.
+use crate::expression::{self, Expr};
+use std::cell::Cell;
+
+/// What a policy knows when it decides, all as floats
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Inputs {
+    /// Units on the shelf at the end of the day
+    pub on_hand: f32,
+    /// Units on trucks that haven't arrived yet
+    pub pipeline: f32,
+    /// The day, counting from 0
+    pub day: f32,
+    /// The units we expect customers to want in a day, from the zipf exponents
+    pub forecast: f32,
+    /// The simulation's own parameters, so a policy can follow with_params()
+    pub safety_stock: f32,
+    pub order_quantity: f32,
+    pub lead_time: f32,
+}
+
+/// The names a policy can use, in the order of Inputs
+const NAMES: [&str; 7] = [
+    "on_hand",
+    "pipeline",
+    "day",
+    "forecast",
+    "safety_stock",
+    "order_quantity",
+    "lead_time",
+];
+
+impl Inputs {
+    fn values(&self) -> [f32; 7] {
+        [
+            self.on_hand,
+            self.pipeline,
+            self.day,
+            self.forecast,
+            self.safety_stock,
+            self.order_quantity,
+            self.lead_time,
+        ]
+    }
+}
+
+/// An ordering policy, parsed from its source
+#[derive(Clone, Debug)]
+pub struct Policy {
+    source: String,
+    expr: Expr,
+}
+
+/// Policies are the same if they're written the same
+impl PartialEq for Policy {
+    fn eq(&self, other: &Policy) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Policy {
+    /// The policy `source` describes, or what's wrong with it and where
+    pub fn parse(source: &str) -> Result<Policy, String> {
+        Ok(Policy {
+            source: source.to_string(),
+            expr: expression::parse(source, &NAMES)?,
+        })
+    }
+
+    /// The expression, as it was written
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// What the expression comes to, before it's cut down to whole units
+    pub fn evaluate(&self, inputs: &Inputs) -> f32 {
+        self.expr.evaluate(&inputs.values())
+    }
+
+    /// How many units to order
+    ///
+    /// The float is truncated and saturates at 0 and u32::MAX, with NaN as 0, which is what
+    /// convert_uint_sat() does in the kernel.
+    pub fn order(&self, inputs: &Inputs) -> usize {
+        self.evaluate(inputs) as u32 as usize
+    }
+
+    /// The expression in OpenCL C, as a float reading policy_on_hand, policy_pipeline,
+    /// policy_day, policy_forecast, policy_safety_stock, policy_order_quantity and
+    /// policy_lead_time
+    pub fn to_opencl(&self) -> String {
+        let mut out = String::new();
+        self.expr.write_opencl(&mut out, "policy_", &NAMES);
+        out
+    }
+}
+
+/// The mean of a zipf distribution over 1..=1000, which is what every engine samples
+fn zipf_mean(exponent: f64) -> f64 {
+    let (mut weights, mut total) = (0.0, 0.0);
+    for k in 1..=1000 {
+        let weight = (k as f64).powf(-exponent);
+        weights += weight;
+        total += k as f64 * weight;
+    }
+    total / weights
+}
+
+thread_local! {
+    /// The last forecast worked out on this thread, and the exponents it was for
+    static LAST_FORECAST: Cell<(u64, u64, f32)> = const { Cell::new((0, 0, 0.0)) };
+}
+
+/// The units customers want in a day on average: customers a day times units a customer
+///
+/// This is a couple thousand powf()s, and every review asks for it, so each thread remembers
+/// the last one.
+pub fn forecast(job_lot_zipf: f64, itemwise_traffic_zipf: f64) -> f32 {
+    let key = (job_lot_zipf.to_bits(), itemwise_traffic_zipf.to_bits());
+    LAST_FORECAST.with(|last| {
+        let (job_lot_bits, traffic_bits, forecast) = last.get();
+        if (job_lot_bits, traffic_bits) == key && forecast > 0.0 {
+            return forecast;
+        }
+        let forecast = (zipf_mean(itemwise_traffic_zipf) * zipf_mean(job_lot_zipf)) as f32;
+        last.set((key.0, key.1, forecast));
+        forecast
+    })
+}
+
+#[test]
+fn test_builtin_rule_as_policy() {
+    // The built-in rule, written as a policy, gives the same years
+    use crate::Simulation;
+    let builtin = Simulation::new(40, 4, 15, None, Some(2.0), Some(3));
+    let mut written = builtin.clone();
+    written.policy = Some(
+        Policy::parse(
+            "if(on_hand < safety_stock, \
+             ceil((safety_stock - on_hand) / order_quantity) * order_quantity, 0)",
+        )
+        .unwrap(),
+    );
+    assert_eq!(
+        written.repeat_summary(40, 200),
+        builtin.repeat_summary(40, 200)
+    );
+    assert_eq!(
+        written.policy.unwrap().to_opencl(),
+        "((float)(policy_on_hand < policy_safety_stock) != 0.0f ? \
+         (ceil(((policy_safety_stock - policy_on_hand) / policy_order_quantity)) \
+         * policy_order_quantity) : 0.0f)"
+    );
+    for broken in &[
+        "on_hand +",
+        "max(1)",
+        "stock",
+        "1 < 2 < 3",
+        "(1",
+        "1e39",
+        "1e-40",
+        "1e-50",
+    ] {
+        assert!(Policy::parse(broken).is_err(), "{} parsed", broken);
+    }
+}
+
+#[test]
+fn test_forecast_error() {
+    // A policy on a low forecast runs out more, and forecast error needs a policy to matter
+    use crate::Simulation;
+    let mut sim = Simulation::new(10, 3, 7, None, None, Some(4));
+    assert!(sim.check_parameters().is_ok());
+    sim.forecast_bias = -0.5;
+    assert!(sim.check_parameters().is_err());
+    sim.policy = Some(
+        Policy::parse("max(0, 2 * forecast * lead_time + safety_stock - on_hand - pipeline)")
+            .unwrap(),
+    );
+    let low = sim.repeat_summary(10, 500);
+    sim.forecast_bias = 0.0;
+    let perfect = sim.repeat_summary(10, 500);
+    sim.forecast_mape = 0.3;
+    let noisy = sim.repeat_summary(10, 500);
+    assert!(low.5 < perfect.5, "{} >= {}", low.5, perfect.5);
+    assert!(noisy != perfect);
+    sim.forecast_mape = -0.1;
+    assert!(sim.check_parameters().is_err());
+}
+
+#[test]
+fn test_forecast_error_safety_stock() {
+    // Forecast errors don't take from the customers, so the same forecast finds the same
+    // safety stock with noise as without, and so does a policy that never looks at it
+    use crate::Simulation;
+    let mut perfect = Simulation::new(10, 3, 7, None, None, Some(4));
+    perfect.policy = Some(
+        Policy::parse("max(0, 2 * forecast * lead_time + safety_stock - on_hand - pipeline)")
+            .unwrap(),
+    );
+    let mut noisy = perfect.clone();
+    // Far too small to move an f32 forecast, but still a draw at every review
+    noisy.forecast_mape = 1e-12;
+    let search = |sim: &Simulation| sim.smallest_safety_stock(0.999, 50, 1 << 12, 9);
+    assert!(search(&perfect).is_some_and(|(safety_stock, _)| safety_stock > 0));
+    assert_eq!(search(&noisy), search(&perfect));
+    perfect.policy = Some(Policy::parse("max(0, safety_stock - on_hand - pipeline)").unwrap());
+    noisy.policy = perfect.policy.clone();
+    noisy.forecast_mape = 0.3;
+    assert_eq!(search(&noisy), search(&perfect));
+}
@@ -0,0 +1,422 @@
+//! Running many items (SKUs) together and summarizing the results the way a
+//! portfolio is usually looked at
+//!
+//! Available from Python as `rustsim.portfolio`
+
+use crate::copula::Copula;
+use crate::mixture::Demand;
+use crate::sketch::Sketch;
+use crate::{fill_rate, search, streams, Shelf, Simulation};
+use pyo3::exceptions::ValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use pyo3::wrap_pyfunction;
+use rand::distributions::Distribution;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+/// What one item did, added up over all its repetitions
+#[derive(Clone, Default)]
+struct ItemTotals {
+    successful_sales: usize,
+    failed_sales: usize,
+    stock_days: usize,
+}
+
+impl ItemTotals {
+    fn simulate(sim: &Simulation, starting_quantity: usize, count: usize) -> ItemTotals {
+        let mut rng = sim.rng();
+        let mut totals = ItemTotals {
+            successful_sales: 0,
+            failed_sales: 0,
+            stock_days: 0,
+        };
+        for _ in 0..count {
+            let year = sim.simulate_year(starting_quantity, &mut rng, |_, _, _| {});
+            totals.successful_sales += year.successful_sales;
+            totals.failed_sales += year.failed_sales;
+            totals.stock_days += year.stock_days;
+        }
+        totals
+    }
+}
+
+/// Classify items A, B or C by simulated sales value, and summarize each class
+///
+/// `items` is one Simulation per item, each starting with the matching entry of
+/// `starting_quantities`, and each simulated `count` times in parallel. `unit_values` is what
+/// one unit of each item is worth (all 1.0 by default). Sales value is units sold per year
+/// times unit value.
+///
+/// Ranked by sales value, the items making up the first `a_share` of the total are class A,
+/// those up to `b_share` are class B, and the rest are class C. An item straddling a boundary
+/// goes in the higher class.
+///
+/// Returns a dict with "classes", the class of each item in the order given, and one entry per
+/// class with its "items" (count), "sales_value" per year, "unit_fill_rate", and
+/// "inventory_investment" (average stock on hand times unit value).
+#[pyfunction(
+    count = "1000",
+    unit_values = "None",
+    a_share = "0.8",
+    b_share = "0.95"
+)]
+fn classify_abc(
+    py: Python<'_>,
+    items: Vec<&Simulation>,
+    starting_quantities: Vec<usize>,
+    count: usize,
+    unit_values: Option<Vec<f64>>,
+    a_share: f64,
+    b_share: f64,
+) -> PyResult<PyObject> {
+    let unit_values = unit_values.unwrap_or_else(|| vec![1.0; items.len()]);
+    if starting_quantities.len() != items.len() || unit_values.len() != items.len() {
+        return Err(ValueError::py_err(
+            "items, starting_quantities and unit_values must be the same length",
+        ));
+    }
+    if let Some(value) = unit_values
+        .iter()
+        .find(|value| !(value.is_finite() && **value >= 0.0))
+    {
+        return Err(ValueError::py_err(format!(
+            "unit_values must be finite and non-negative, not {}",
+            value
+        )));
+    }
+    if !(0.0 < a_share && a_share <= b_share && b_share <= 1.0) {
+        return Err(ValueError::py_err("Expected 0 < a_share <= b_share <= 1"));
+    }
+    if count == 0 {
+        return Err(ValueError::py_err("count must be at least 1"));
+    }
+
+    let sims: Vec<Simulation> = items.iter().map(|&sim| sim.clone()).collect();
+    let totals: Vec<ItemTotals> = py.allow_threads(|| {
+        sims.par_iter()
+            .zip(&starting_quantities)
+            .map(|(sim, &starting_quantity)| ItemTotals::simulate(sim, starting_quantity, count))
+            .collect()
+    });
+    let years = count as f64;
+    let sales_value: Vec<f64> = totals
+        .iter()
+        .zip(&unit_values)
+        .map(|(t, value)| t.successful_sales as f64 * value / years)
+        .collect();
+
+    // Rank by sales value, and cut where the running share crosses each boundary
+    let mut ranked: Vec<usize> = (0..sims.len()).collect();
+    ranked.sort_by(|&a, &b| sales_value[b].total_cmp(&sales_value[a]));
+    let grand_total: f64 = sales_value.iter().sum();
+    let mut classes = vec!["C"; sims.len()];
+    let mut running = 0.0;
+    for &i in &ranked {
+        let share_before = if grand_total > 0.0 {
+            running / grand_total
+        } else {
+            1.0
+        };
+        classes[i] = if share_before < a_share {
+            "A"
+        } else if share_before < b_share {
+            "B"
+        } else {
+            "C"
+        };
+        running += sales_value[i];
+    }
+
+    let result = PyDict::new(py);
+    result.set_item("classes", classes.clone())?;
+    for &class in &["A", "B", "C"] {
+        let members: Vec<usize> = (0..sims.len()).filter(|&i| classes[i] == class).collect();
+        let sold: usize = members.iter().map(|&i| totals[i].successful_sales).sum();
+        let missed: usize = members.iter().map(|&i| totals[i].failed_sales).sum();
+        let summary = PyDict::new(py);
+        summary.set_item("items", members.len())?;
+        summary.set_item(
+            "sales_value",
+            members.iter().map(|&i| sales_value[i]).sum::<f64>(),
+        )?;
+        summary.set_item("unit_fill_rate", fill_rate(sold, missed))?;
+        summary.set_item(
+            "inventory_investment",
+            members
+                .iter()
+                .map(|&i| totals[i].stock_days as f64 / (365.0 * years) * unit_values[i])
+                .sum::<f64>(),
+        )?;
+        result.set_item(class, summary)?;
+    }
+    Ok(result.to_object(py))
+}
+
+/// Customers from another distribution, each of whom shows up here with probability `share`
+///
+/// That's what one of several equally busy locations sees of the total demand.
+struct Thinned<D> {
+    dist: D,
+    share: f64,
+}
+
+impl<D: Distribution<usize>> Distribution<usize> for Thinned<D> {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        (0..self.dist.sample(rng))
+            .filter(|_| rng.gen::<f64>() < self.share)
+            .count()
+    }
+}
+
+/// How much safety stock pooling saves, compared to splitting demand across `locations`
+///
+/// We take the demand `sim` describes and either serve all of it from one location, or spread
+/// the customers evenly at random over `locations` locations that each run `sim`'s policy on
+/// their own. For each arrangement we find the smallest safety stock per location (each
+/// location starting with its safety stock on hand) that reaches `target_fill_rate`, using
+/// `count` years of the same customers for every trial.
+///
+/// Returns a dict with "pooled_safety_stock", "split_safety_stock" (per location),
+/// "split_total_safety_stock" and "reduction", the fraction of the split total that pooling
+/// saves. The safety stocks are None if `max_quantity` isn't enough.
+#[pyfunction(count = "1000", max_quantity = "1 << 32")]
+fn risk_pooling(
+    py: Python<'_>,
+    sim: &Simulation,
+    locations: usize,
+    target_fill_rate: f64,
+    count: usize,
+    max_quantity: usize,
+) -> PyResult<PyObject> {
+    if locations == 0 {
+        return Err(ValueError::py_err("locations must be at least 1"));
+    }
+    let sim = sim.clone();
+    let seed = sim.rng().gen();
+    let solve = |share: f64| {
+        let it_dist = Thinned {
+            dist: sim.traffic(),
+            share,
+        };
+        let jl_dist = sim.job_lots();
+        search::smallest_reaching(target_fill_rate, max_quantity, |safety_stock| {
+            let mut local = sim.clone();
+            local.safety_stock = safety_stock;
+            let mut rng = StdRng::seed_from_u64(seed);
+            let (mut sold, mut missed) = (0, 0);
+            for _ in 0..count {
+                let year = local.simulate_year_with(
+                    safety_stock,
+                    &it_dist,
+                    &jl_dist,
+                    &mut rng,
+                    |_, _, _| {},
+                );
+                sold += year.successful_sales;
+                missed += year.failed_sales;
+            }
+            fill_rate(sold, missed)
+        })
+        .map(|(safety_stock, _)| safety_stock)
+    };
+    let (pooled, split) =
+        py.allow_threads(|| rayon::join(|| solve(1.0), || solve(1.0 / locations as f64)));
+
+    let result = PyDict::new(py);
+    result.set_item("pooled_safety_stock", pooled)?;
+    result.set_item("split_safety_stock", split)?;
+    result.set_item("split_total_safety_stock", split.map(|s| s * locations))?;
+    result.set_item(
+        "reduction",
+        match (pooled, split) {
+            (Some(pooled), Some(split)) if split > 0 => {
+                Some(1.0 - pooled as f64 / (split * locations) as f64)
+            }
+            _ => None,
+        },
+    )?;
+    Ok(result.to_object(py))
+}
+
+/// A customer count the copula already drew, for simulate_day_with to take as its draw
+struct Drawn(usize);
+
+impl Distribution<usize> for Drawn {
+    fn sample<R: Rng + ?Sized>(&self, _: &mut R) -> usize {
+        self.0
+    }
+}
+
+/// What every item did in one year of linked demand
+struct LinkedYear {
+    items: Vec<ItemTotals>,
+    /// Days on which at least two items turned a customer away
+    shared_stockout_days: usize,
+}
+
+/// Simulate one year of every item in `sims` a day at a time, with each day's customer counts
+/// from `copula`
+fn simulate_linked_year<R: Rng>(
+    sims: &[Simulation],
+    starting_quantities: &[usize],
+    copula: &Copula,
+    job_lots: &[Demand],
+    rng: &mut R,
+) -> LinkedYear {
+    let mut shelves: Vec<Shelf> = sims
+        .iter()
+        .zip(starting_quantities)
+        .map(|(sim, &starting_quantity)| Shelf::new(starting_quantity, sim.lead_time))
+        .collect();
+    let mut year = LinkedYear {
+        items: vec![ItemTotals::default(); sims.len()],
+        shared_stockout_days: 0,
+    };
+    let mut counts = vec![0; sims.len()];
+    for _ in 0..365 {
+        copula.sample(rng, &mut counts);
+        let mut stockouts = 0;
+        for i in 0..sims.len() {
+            let day = sims[i].simulate_day_with(
+                &mut shelves[i],
+                &Drawn(counts[i]),
+                &job_lots[i],
+                rng,
+                &mut |_, _, _| {},
+            );
+            let totals = &mut year.items[i];
+            totals.successful_sales += day.successful_sales;
+            totals.failed_sales += day.failed_sales;
+            totals.stock_days += day.closing_stock;
+            stockouts += (day.failed_transactions > 0) as usize;
+        }
+        year.shared_stockout_days += (stockouts >= 2) as usize;
+    }
+    year
+}
+
+/// Simulate items together with correlated daily demand, and summarize the risk to the
+/// portfolio as a whole
+///
+/// `items`, `starting_quantities`, `count` and `unit_values` are as in classify_abc.
+/// `correlation` is an items by items matrix linking each day's customer counts through a
+/// Gaussian copula (see copula.rs), so items that get busy together run out together; None
+/// leaves them independent. Each item keeps its own traffic zipf. Mixtures, max_request and
+/// compound Poisson demand aren't supported. The years are seeded from the first item.
+///
+/// Returns a dict with "unit_fill_rate", a list with each item's, "portfolio_fill_rate" for
+/// all their units together, "lost_value", the sales value lost in an average year,
+/// "lost_value_quantiles", the `quantiles` of the value lost in a year, and
+/// "shared_stockout_days", the days in an average year on which at least two items turned
+/// customers away.
+#[pyfunction(
+    correlation = "None",
+    count = "1000",
+    unit_values = "None",
+    quantiles = "vec![0.5, 0.95, 0.99]"
+)]
+fn portfolio_risk(
+    py: Python<'_>,
+    items: Vec<&Simulation>,
+    starting_quantities: Vec<usize>,
+    correlation: Option<Vec<Vec<f64>>>,
+    count: usize,
+    unit_values: Option<Vec<f64>>,
+    quantiles: Vec<f64>,
+) -> PyResult<PyObject> {
+    let unit_values = unit_values.unwrap_or_else(|| vec![1.0; items.len()]);
+    if items.is_empty() {
+        return Err(ValueError::py_err("items must have at least one item"));
+    }
+    if starting_quantities.len() != items.len() || unit_values.len() != items.len() {
+        return Err(ValueError::py_err(
+            "items, starting_quantities and unit_values must be the same length",
+        ));
+    }
+    if count == 0 {
+        return Err(ValueError::py_err("count must be at least 1"));
+    }
+    if items.iter().any(|sim| sim.custom_demand()) {
+        return Err(ValueError::py_err(
+            "portfolio_risk only links plain zipf traffic, not mixtures, max_request or \
+             compound Poisson demand",
+        ));
+    }
+    let correlation = correlation.unwrap_or_else(|| {
+        (0..items.len())
+            .map(|i| {
+                (0..items.len())
+                    .map(|j| if i == j { 1.0 } else { 0.0 })
+                    .collect()
+            })
+            .collect()
+    });
+    let exponents: Vec<f64> = items.iter().map(|sim| sim.itemwise_traffic_zipf).collect();
+    let copula = Copula::new(&correlation, &exponents).map_err(ValueError::py_err)?;
+
+    let sims: Vec<Simulation> = items.iter().map(|&sim| sim.clone()).collect();
+    let job_lots: Vec<Demand> = sims.iter().map(Simulation::job_lots).collect();
+    let master = sims[0].call_seed();
+    let linked: Vec<LinkedYear> = py.allow_threads(|| {
+        (0..count as u64)
+            .into_par_iter()
+            .map(|year| {
+                let mut rng = streams::stream(master, year);
+                simulate_linked_year(&sims, &starting_quantities, &copula, &job_lots, &mut rng)
+            })
+            .collect()
+    });
+
+    let mut lost = Sketch::new();
+    let mut totals = vec![ItemTotals::default(); sims.len()];
+    for year in &linked {
+        let mut lost_value = 0.0;
+        for ((total, item), value) in totals.iter_mut().zip(&year.items).zip(&unit_values) {
+            total.successful_sales += item.successful_sales;
+            total.failed_sales += item.failed_sales;
+            lost_value += item.failed_sales as f64 * value;
+        }
+        lost.push(lost_value);
+    }
+    let (sold, missed) = totals.iter().fold((0, 0), |(sold, missed), t| {
+        (sold + t.successful_sales, missed + t.failed_sales)
+    });
+    let years = count as f64;
+
+    let result = PyDict::new(py);
+    result.set_item(
+        "unit_fill_rate",
+        totals
+            .iter()
+            .map(|t| fill_rate(t.successful_sales, t.failed_sales))
+            .collect::<Vec<f64>>(),
+    )?;
+    result.set_item("portfolio_fill_rate", fill_rate(sold, missed))?;
+    result.set_item(
+        "lost_value",
+        totals
+            .iter()
+            .zip(&unit_values)
+            .map(|(t, value)| t.failed_sales as f64 * value)
+            .sum::<f64>()
+            / years,
+    )?;
+    result.set_item("lost_value_quantiles", lost.quantiles(&quantiles))?;
+    result.set_item(
+        "shared_stockout_days",
+        linked.iter().map(|y| y.shared_stockout_days).sum::<usize>() as f64 / years,
+    )?;
+    Ok(result.to_object(py))
+}
+
+/// Tools for many items at once, available as rustsim.portfolio
+#[pymodule]
+fn portfolio(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_wrapped(wrap_pyfunction!(classify_abc))?;
+    m.add_wrapped(wrap_pyfunction!(risk_pooling))?;
+    m.add_wrapped(wrap_pyfunction!(portfolio_risk))?;
+
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use rand::distributions::Distribution;
+use rand::Rng;
+use sobol::params::JoeKuoD6;
+use sobol::Sobol;
+use std::cell::Cell;
+
+/// Sobol dimensions used per year: one for each day's customer count, one for its first request
+pub const DIMENSIONS: usize = 2 * 365;
+
+/// A zipf distribution over 1..=n sampled by inverting its CDF
+///
+/// The zipf crate uses rejection sampling, which needs an unknown number of uniform draws.
+/// Quasi-random points only help if each draw maps to exactly one coordinate, so we do it
+/// the old fashioned way instead.
+pub struct InverseZipf {
+    cdf: Vec<f64>,
+}
+
+impl InverseZipf {
+    pub fn new(num_elements: usize, exponent: f64) -> InverseZipf {
+        let mut total = 0.0;
+        let mut cdf: Vec<f64> = (1..=num_elements)
+            .map(|k| {
+                total += (k as f64).powf(-exponent);
+                total
+            })
+            .collect();
+        for p in cdf.iter_mut() {
+            *p /= total;
+        }
+        InverseZipf { cdf }
+    }
+
+    /// The smallest k whose cumulative probability reaches u
+    pub fn quantile(&self, u: f64) -> usize {
+        (self.cdf.partition_point(|&p| p < u) + 1).min(self.cdf.len())
+    }
+}
+
+/// Generates one year of demand per Sobol point
+///
+/// Each repetition uses the next point of a randomly shifted Sobol sequence for each day's
+/// customer count and that day's first request. Later requests on the same day are rare and
+/// matter less, so they just use ordinary random numbers.
+pub struct QuasiDemand {
+    points: Sobol<f64>,
+    shift: Vec<f64>,
+    point: Vec<f64>,
+    it_inverse: InverseZipf,
+    jl_inverse: InverseZipf,
+    day: Cell<usize>,
+    first_request: Cell<bool>,
+}
+
+impl QuasiDemand {
+    pub fn new<R: Rng>(
+        rng: &mut R,
+        it_inverse: InverseZipf,
+        jl_inverse: InverseZipf,
+    ) -> QuasiDemand {
+        QuasiDemand {
+            points: Sobol::new(DIMENSIONS, &JoeKuoD6::standard()),
+            // A random shift (Cranley-Patterson rotation) keeps the points just as even,
+            // but makes each run an independent, unbiased estimate
+            shift: (0..DIMENSIONS).map(|_| rng.gen()).collect(),
+            point: vec![],
+            it_inverse,
+            jl_inverse,
+            day: Cell::new(0),
+            first_request: Cell::new(false),
+        }
+    }
+
+    /// Move on to the next year
+    pub fn next_year(&mut self) {
+        self.point = self.points.next().unwrap();
+        self.day.set(0);
+    }
+
+    /// The coordinate of the current point, shifted and wrapped back into [0, 1)
+    fn coordinate(&self, dimension: usize) -> f64 {
+        (self.point[dimension] + self.shift[dimension]).fract()
+    }
+
+    /// Distribution of how many customers arrive each day
+    pub fn traffic(&self) -> QuasiTraffic<'_> {
+        QuasiTraffic(self)
+    }
+
+    /// Distribution of how many items each customer wants
+    pub fn job_lot(&self) -> QuasiJobLot<'_> {
+        QuasiJobLot(self)
+    }
+}
+
+pub struct QuasiTraffic<'a>(&'a QuasiDemand);
+
+impl<'a> Distribution<usize> for QuasiTraffic<'a> {
+    fn sample<R: Rng + ?Sized>(&self, _rng: &mut R) -> usize {
+        let day = self.0.day.get();
+        self.0.day.set(day + 1);
+        self.0.first_request.set(true);
+        self.0.it_inverse.quantile(self.0.coordinate(2 * day))
+    }
+}
+
+pub struct QuasiJobLot<'a>(&'a QuasiDemand);
+
+impl<'a> Distribution<usize> for QuasiJobLot<'a> {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        let u = if self.0.first_request.get() {
+            self.0.first_request.set(false);
+            self.0.coordinate(2 * (self.0.day.get() - 1) + 1)
+        } else {
+            rng.gen()
+        };
+        self.0.jl_inverse.quantile(u)
+    }
+}
+
+#[test]
+fn test_sobol_points() {
+    // Shifted points stay in [0, 1) and never repeat, the first 1024 cover each 1/1024th of
+    // a dimension exactly once, and draws map back into 1..=n
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    let mut rng = StdRng::seed_from_u64(21);
+    let mut demand = QuasiDemand::new(
+        &mut rng,
+        InverseZipf::new(50, 2.0),
+        InverseZipf::new(20, 1.5),
+    );
+    let mut seen = std::collections::HashSet::new();
+    let mut bins = vec![0; 1024];
+    for _ in 0..1024 {
+        demand.next_year();
+        let point: Vec<f64> = (0..DIMENSIONS).map(|d| demand.coordinate(d)).collect();
+        assert!(point.iter().all(|&x| (0.0..1.0).contains(&x)));
+        assert!(seen.insert(point.iter().map(|x| x.to_bits()).collect::<Vec<_>>()));
+        bins[(point[0] * 1024.0) as usize] += 1;
+        let (traffic, job_lot) = (demand.traffic(), demand.job_lot());
+        for _day in 0..365 {
+            assert!((1..=50).contains(&traffic.sample(&mut rng)));
+            assert!((1..=20).contains(&job_lot.sample(&mut rng)));
+        }
+    }
+    assert!(bins.iter().all(|&n| n == 1));
+    let inverse = InverseZipf::new(20, 1.5);
+    assert_eq!(inverse.quantile(0.0), 1);
+    assert_eq!(inverse.quantile(1.0), 20);
+}
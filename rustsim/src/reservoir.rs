@@ -0,0 +1,63 @@
+use rand::Rng;
+
+/// A fixed-size uniform sample of a stream of unknown length
+///
+/// This is Vitter's "Algorithm R": keep the first `capacity` items, then replace a random
+/// slot with the n-th item with probability capacity/n. At every point, every item seen
+/// so far had the same chance of being kept.
+pub struct Reservoir<T> {
+    capacity: usize,
+    seen: usize,
+    items: Vec<T>,
+}
+
+impl<T> Reservoir<T> {
+    pub fn new(capacity: usize) -> Reservoir<T> {
+        Reservoir {
+            capacity,
+            seen: 0,
+            items: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Consider one more item for the sample
+    pub fn offer<R: Rng>(&mut self, item: T, rng: &mut R) {
+        self.seen += 1;
+        if self.items.len() < self.capacity {
+            self.items.push(item);
+        } else {
+            let slot = rng.gen_range(0, self.seen);
+            if slot < self.capacity {
+                self.items[slot] = item;
+            }
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.items
+    }
+}
+
+#[test]
+fn test_reservoir() {
+    // A long stream is cut down to the capacity, drawn from all of it, and a short one is
+    // kept whole, in order
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    let mut rng = StdRng::seed_from_u64(8);
+    let mut sample = Reservoir::new(100);
+    (0..100_000).for_each(|i| sample.offer(i, &mut rng));
+    let sample = sample.into_vec();
+    assert_eq!(sample.len(), 100);
+    let mut distinct = sample.clone();
+    distinct.sort_unstable();
+    distinct.dedup();
+    assert_eq!(distinct.len(), 100);
+    // Uniform over the stream, so the mean lands near the middle and some come from the end
+    let mean = sample.iter().sum::<usize>() as f64 / 100.0;
+    assert!((mean - 50_000.0).abs() < 10_000.0, "{}", mean);
+    assert!(sample.iter().any(|&i| i >= 50_000));
+    let mut small = Reservoir::new(100);
+    (0..30).for_each(|i| small.offer(i, &mut rng));
+    assert_eq!(small.into_vec(), (0..30).collect::<Vec<_>>());
+}
@@ -0,0 +1,203 @@
+//! Scenario files: base parameters plus named variations on them, so a big suite of
+//! experiments can live in version control
+//!
+//! A file has a `base` table of Simulation parameters (the fields of schema::Config; the
+//! version is optional) and a `scenarios` table of named overrides. A scenario can say
+//! `inherits = "other"` to start from another scenario instead of the base. A file can also
+//! say `include = ["common.toml"]` (relative to itself) to start from other files; tables
+//! merge key by key, and the including file wins where they disagree.
+//!
+//! ```toml
+//! include = "defaults.toml"
+//!
+//! [base]
+//! safety_stock = 10
+//! lead_time = 3
+//! order_quantity = 7
+//!
+//! [scenarios.holidays]
+//! itemwise_traffic_zipf = 3.0
+//!
+//! [scenarios.peak_season]
+//! inherits = "holidays"
+//! order_quantity = 14
+//! ```
+//!
+//! Reading a file is up to the caller, who turns it into a serde_json::Value. That way JSON
+//! works out of the box, and Python reads TOML and YAML with its own libraries, so we don't
+//! carry a parser for every format.
+use crate::schema::{self, Config};
+use crate::Simulation;
+use serde_json::{Map, Value};
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+
+/// Something that reads one file into a tree of values
+pub type Parser<'a> = dyn Fn(&Path) -> Result<Value, String> + 'a;
+
+/// A scenario file and everything it includes, merged
+pub struct Scenarios {
+    base: Map<String, Value>,
+    scenarios: Map<String, Value>,
+}
+
+impl Scenarios {
+    pub fn load(path: &Path, parse: &Parser) -> Result<Scenarios, String> {
+        let mut document = match load(path, parse, &mut vec![])? {
+            Value::Object(document) => document,
+            _ => return Err(format!("{} must be a table", path.display())),
+        };
+        let mut table = |key| match document.remove(key) {
+            None => Ok(Map::new()),
+            Some(Value::Object(table)) => Ok(table),
+            Some(_) => Err(format!("{} in {} must be a table", key, path.display())),
+        };
+        Ok(Scenarios {
+            base: table("base")?,
+            scenarios: table("scenarios")?,
+        })
+    }
+
+    /// Every scenario's name, in alphabetical order
+    pub fn names(&self) -> Vec<&str> {
+        self.scenarios.keys().map(String::as_str).collect()
+    }
+
+    /// The parameters for `scenario`, or for the base if it's None
+    pub fn config(&self, scenario: Option<&str>) -> Result<Config, String> {
+        // Follow `inherits` back to the base, then apply the overrides from there forward
+        let mut chain: Vec<&Map<String, Value>> = vec![];
+        let mut seen: Vec<&str> = vec![];
+        let mut next = scenario;
+        while let Some(name) = next {
+            if seen.contains(&name) {
+                return Err(format!("Scenario {:?} inherits from itself", name));
+            }
+            let overrides = match self.scenarios.get(name) {
+                Some(Value::Object(overrides)) => overrides,
+                Some(_) => return Err(format!("Scenario {:?} must be a table", name)),
+                None => {
+                    return Err(format!(
+                        "There's no scenario {:?}; there are {:?}",
+                        name,
+                        self.names()
+                    ))
+                }
+            };
+            next = match overrides.get("inherits") {
+                None => None,
+                Some(Value::String(parent)) => Some(parent),
+                Some(_) => return Err(format!("{:?} inherits must be a scenario name", name)),
+            };
+            seen.push(name);
+            chain.push(overrides);
+        }
+        let mut merged = Value::Object(self.base.clone());
+        for overrides in chain.into_iter().rev() {
+            let mut overrides = overrides.clone();
+            overrides.remove("inherits");
+            merge(&mut merged, Value::Object(overrides));
+        }
+        if let Value::Object(table) = &mut merged {
+            table
+                .entry("version")
+                .or_insert_with(|| schema::VERSION.into());
+        }
+        serde_json::from_value(merged).map_err(|error| match scenario {
+            Some(name) => format!("Scenario {:?}: {}", name, error),
+            None => format!("The base: {}", error),
+        })
+    }
+
+    /// The simulation for `scenario`, or for the base if it's None
+    pub fn simulation(&self, scenario: Option<&str>) -> Result<Simulation, String> {
+        Simulation::try_from(self.config(scenario)?)
+    }
+}
+
+/// Read a JSON file, for the Parser everyone has
+pub fn parse_json(path: &Path) -> Result<Value, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|error| format!("Can't read {}: {}", path.display(), error))?;
+    serde_json::from_str(&text).map_err(|error| format!("{}: {}", path.display(), error))
+}
+
+/// Read a file and everything it includes, with the file itself on top
+///
+/// `including` is the files we're in the middle of reading, to catch a file including itself.
+fn load(path: &Path, parse: &Parser, including: &mut Vec<PathBuf>) -> Result<Value, String> {
+    let canonical = path
+        .canonicalize()
+        .map_err(|error| format!("Can't read {}: {}", path.display(), error))?;
+    if including.contains(&canonical) {
+        return Err(format!("{} includes itself", path.display()));
+    }
+    let mut document = parse(path)?;
+    let includes = match document
+        .as_object_mut()
+        .and_then(|table| table.remove("include"))
+    {
+        None => vec![],
+        Some(Value::String(include)) => vec![include],
+        Some(Value::Array(includes)) => includes
+            .into_iter()
+            .map(|include| match include {
+                Value::String(include) => Ok(include),
+                _ => Err(format!("include in {} must be file names", path.display())),
+            })
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err(format!("include in {} must be file names", path.display())),
+    };
+    including.push(canonical);
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut merged = Value::Object(Map::new());
+    for include in includes {
+        merge(
+            &mut merged,
+            load(&directory.join(include), parse, including)?,
+        );
+    }
+    including.pop();
+    merge(&mut merged, document);
+    Ok(merged)
+}
+
+/// Lay `top` over `bottom`: tables merge key by key, and anything else replaces what was there
+fn merge(bottom: &mut Value, top: Value) {
+    match (bottom, top) {
+        (Value::Object(bottom), Value::Object(top)) => {
+            for (key, value) in top {
+                merge(bottom.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (bottom, top) => *bottom = top,
+    }
+}
+
+/// Read a file with Python's own parsers: tomllib (or tomli, before Python 3.11) for .toml,
+/// PyYAML for .yaml and .yml, and our own for anything else
+#[cfg(feature = "python")]
+pub fn parse_with_python(py: pyo3::Python<'_>, path: &Path) -> Result<Value, String> {
+    use pyo3::prelude::*;
+    let message = |error: PyErr| format!("{}: {}", path.display(), *error.to_object(py).as_ref(py));
+    let loads = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("toml") => py
+            .import("tomllib")
+            .or_else(|_| py.import("tomli"))
+            .and_then(|module| module.getattr("loads")),
+        Some("yaml") | Some("yml") => py
+            .import("yaml")
+            .and_then(|module| module.getattr("safe_load")),
+        _ => return parse_json(path),
+    }
+    .map_err(message)?;
+    let text = std::fs::read_to_string(path)
+        .map_err(|error| format!("Can't read {}: {}", path.display(), error))?;
+    // Round trip through Python's json rather than walking Python objects by hand
+    let json: String = loads
+        .call1((text,))
+        .and_then(|tree| py.import("json")?.call1("dumps", (tree,)))
+        .and_then(|json| json.extract())
+        .map_err(message)?;
+    serde_json::from_str(&json).map_err(|error| format!("{}: {}", path.display(), error))
+}
@@ -0,0 +1,172 @@
+//! The one JSON format for simulations and their results, shared by Python's to_json() and
+//! from_json(), simserver, and anything else that saves or sends them
+//!
+//! A Simulation serializes as a Config, which carries the schema version, so a file written
+//! today can be read (or clearly rejected) by a later build. Results serialize as Totals, the
+//! Summary tuple with its fields named.
+use crate::compound::CompoundPoisson;
+use crate::mixture::{Mixture, Truncation};
+use crate::policy::Policy;
+use crate::{Simulation, Summary};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+/// The schema version we write. Bump it when a field changes meaning or goes away; adding an
+/// optional field doesn't need it.
+pub const VERSION: u32 = 1;
+
+/// A Simulation's parameters, as they appear on disk or on the wire
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Config {
+    pub version: u32,
+    pub safety_stock: usize,
+    pub lead_time: usize,
+    pub order_quantity: usize,
+    /// Leave it out for the default
+    #[serde(default)]
+    pub job_lot_zipf: Option<f64>,
+    /// Leave it out for the default
+    #[serde(default)]
+    pub itemwise_traffic_zipf: Option<f64>,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// An ordering policy expression, or left out for the built-in rule
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<String>,
+    /// (weight, exponent) pairs to draw job lots from instead of job_lot_zipf, or left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_lot_mixture: Option<Vec<(f64, f64)>>,
+    /// (weight, exponent) pairs to draw traffic from instead of itemwise_traffic_zipf, or
+    /// left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub itemwise_traffic_mixture: Option<Vec<(f64, f64)>>,
+    /// The most one customer asks for, or left out for no cap
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_request: Option<usize>,
+    /// "clip" or "resample", or left out for "clip"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_request_mode: Option<String>,
+    /// {"mean_daily_units", "cv", "basket"} for compound Poisson demand in place of the zipfs,
+    /// or left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compound_poisson: Option<CompoundPoisson>,
+    /// The forecast's average relative error, or left out for 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forecast_bias: Option<f64>,
+    /// The mean absolute percentage error of the forecast's noise, or left out for 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forecast_mape: Option<f64>,
+}
+
+impl From<Simulation> for Config {
+    fn from(sim: Simulation) -> Config {
+        // Write the exponents out, so a change of defaults can't change a saved simulation
+        let mode = sim.max_request_mode;
+        Config {
+            version: VERSION,
+            safety_stock: sim.safety_stock,
+            lead_time: sim.lead_time,
+            order_quantity: sim.order_quantity,
+            job_lot_zipf: Some(sim.job_lot_zipf),
+            itemwise_traffic_zipf: Some(sim.itemwise_traffic_zipf),
+            seed: sim.seed,
+            policy: sim.policy.map(|policy| policy.source().to_string()),
+            job_lot_mixture: sim
+                .job_lot_mixture
+                .map(|mixture| mixture.components().to_vec()),
+            itemwise_traffic_mixture: sim
+                .itemwise_traffic_mixture
+                .map(|mixture| mixture.components().to_vec()),
+            max_request: sim.max_request,
+            max_request_mode: sim.max_request.map(|_| mode.name().to_string()),
+            compound_poisson: sim.compound_poisson,
+            forecast_bias: Some(sim.forecast_bias).filter(|&bias| bias != 0.0),
+            forecast_mape: Some(sim.forecast_mape).filter(|&mape| mape != 0.0),
+        }
+    }
+}
+
+impl TryFrom<Config> for Simulation {
+    type Error = String;
+
+    /// The simulation, or why the config can't be one
+    fn try_from(config: Config) -> Result<Simulation, String> {
+        if config.version != VERSION {
+            return Err(format!(
+                "This is schema version {}, but we only read version {}",
+                config.version, VERSION
+            ));
+        }
+        let mut sim = Simulation::new(
+            config.safety_stock,
+            config.lead_time,
+            config.order_quantity,
+            config.job_lot_zipf,
+            config.itemwise_traffic_zipf,
+            config.seed,
+        );
+        sim.policy = config.policy.as_deref().map(Policy::parse).transpose()?;
+        sim.job_lot_mixture = config
+            .job_lot_mixture
+            .as_deref()
+            .map(Mixture::new)
+            .transpose()?;
+        sim.itemwise_traffic_mixture = config
+            .itemwise_traffic_mixture
+            .as_deref()
+            .map(Mixture::new)
+            .transpose()?;
+        sim.max_request = config.max_request;
+        if let Some(mode) = &config.max_request_mode {
+            sim.max_request_mode = Truncation::parse(mode)?;
+        }
+        // Only the parameters were written, so work out the rest again
+        sim.compound_poisson = config
+            .compound_poisson
+            .map(|compound| {
+                CompoundPoisson::new(compound.mean_daily_units, compound.cv, compound.basket)
+            })
+            .transpose()?;
+        sim.forecast_bias = config.forecast_bias.unwrap_or(0.0);
+        sim.forecast_mape = config.forecast_mape.unwrap_or(0.0);
+        sim.check_parameters()?;
+        Ok(sim)
+    }
+}
+
+/// A Summary with its fields named
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Totals {
+    pub successful_transactions: usize,
+    pub successful_sales: usize,
+    pub failed_transactions: usize,
+    pub failed_sales: usize,
+    pub transaction_fill_rate: f64,
+    pub unit_fill_rate: f64,
+}
+
+impl From<Summary> for Totals {
+    fn from((st, ss, ft, fs, tr, ur): Summary) -> Totals {
+        Totals {
+            successful_transactions: st,
+            successful_sales: ss,
+            failed_transactions: ft,
+            failed_sales: fs,
+            transaction_fill_rate: tr,
+            unit_fill_rate: ur,
+        }
+    }
+}
+
+impl From<Totals> for Summary {
+    fn from(totals: Totals) -> Summary {
+        (
+            totals.successful_transactions,
+            totals.successful_sales,
+            totals.failed_transactions,
+            totals.failed_sales,
+            totals.transaction_fill_rate,
+            totals.unit_fill_rate,
+        )
+    }
+}
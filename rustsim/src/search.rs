@@ -0,0 +1,58 @@
+/// Find the smallest quantity where `rate` (which never falls as the quantity rises) reaches
+/// `target`, by doubling and then bisecting
+///
+/// Returns (quantity, its rate), or None if even `max_quantity` isn't enough.
+pub fn smallest_reaching<F: Fn(usize) -> f64>(
+    target: f64,
+    max_quantity: usize,
+    rate: F,
+) -> Option<(usize, f64)> {
+    let zero_rate = rate(0);
+    if zero_rate >= target {
+        return Some((0, zero_rate));
+    }
+    if max_quantity == 0 {
+        return None;
+    }
+    // Find a quantity that's enough, and remember the last one that wasn't
+    let mut low = 0;
+    let mut high = 1;
+    let mut high_rate = rate(high);
+    while high_rate < target {
+        if high >= max_quantity {
+            return None;
+        }
+        low = high;
+        high = (high * 2).min(max_quantity);
+        high_rate = rate(high);
+    }
+    // Now low isn't enough and high is, so narrow it down
+    while high - low > 1 {
+        let middle = low + (high - low) / 2;
+        let middle_rate = rate(middle);
+        if middle_rate >= target {
+            high = middle;
+            high_rate = middle_rate;
+        } else {
+            low = middle;
+        }
+    }
+    Some((high, high_rate))
+}
+
+#[test]
+fn test_smallest_reaching() {
+    // The search lands on the first quantity that's enough, wherever that is, and never
+    // answers with more than max_quantity
+    let step = |threshold: usize| move |quantity: usize| (quantity >= threshold) as u8 as f64;
+    for threshold in [0, 1, 2, 3, 7, 64, 65, 1000] {
+        assert_eq!(
+            smallest_reaching(1.0, 1000, step(threshold)),
+            Some((threshold, 1.0))
+        );
+    }
+    assert_eq!(smallest_reaching(1.0, 999, step(1000)), None);
+    assert_eq!(smallest_reaching(1.0, 1000, step(1000)), Some((1000, 1.0)));
+    assert_eq!(smallest_reaching(1.0, 0, step(1)), None);
+    assert_eq!(smallest_reaching(1.0, 0, step(0)), Some((0, 1.0)));
+}
@@ -0,0 +1,170 @@
+use crate::{streams, Simulation};
+use rand::distributions::Distribution;
+use rand::Rng;
+use std::simd::prelude::*;
+
+/// How many years we simulate side by side, one in each SIMD lane
+///
+/// Eight 32-bit lanes fill an AVX2 register; the 64-bit counters take two, which is still
+/// much cheaper than eight separate passes.
+pub const LANES: usize = 8;
+
+/// Entries in each precomputed zipf table. A power of two, so picking an entry is a mask.
+const TABLE_SIZE: usize = 1 << 20;
+
+type State = Simd<u32, LANES>;
+type Counter = Simd<u64, LANES>;
+
+/// Zipf draws, precomputed so every lane can look one up at once
+///
+/// Lanes can't each run the zipf crate's rejection sampler, so like rustoclsim we draw a big
+/// table once and pick entries with xorshift. A million entries keeps both tables in cache
+/// on most CPUs while still reaching far into the tails.
+pub struct Tables {
+    traffic: Vec<u32>,
+    job_lot: Vec<u32>,
+}
+
+impl Tables {
+    pub fn new<R: Rng>(sim: &Simulation, rng: &mut R) -> Tables {
+        let mut precompute = |exponent| -> Vec<u32> {
+            let z = zipf::ZipfDistribution::new(1000, exponent).unwrap();
+            (0..TABLE_SIZE).map(|_| z.sample(rng) as u32).collect()
+        };
+        Tables {
+            traffic: precompute(sim.itemwise_traffic_zipf),
+            job_lot: precompute(sim.job_lot_zipf),
+        }
+    }
+}
+
+/// The counters from one simulated year, per lane:
+/// (successful transactions, successful sales, failed transactions, failed sales)
+pub type Counters = [(u64, u64, u64, u64); LANES];
+
+/// Seeds for batch `batch` of lanes in a run seeded with `master`, one stream per lane
+pub fn seeds(master: u64, batch: u64) -> [u32; LANES] {
+    let mut seeds = [0; LANES];
+    for (lane, seed) in seeds.iter_mut().enumerate() {
+        *seed = streams::xorshift_seed(master, batch * LANES as u64 + lane as u64);
+    }
+    seeds
+}
+
+/// Marsaglia's xorshift32, in every lane at once
+fn xorshift(x: State) -> State {
+    let x = x ^ (x << 13);
+    let x = x ^ (x >> 17);
+    x ^ (x << 5)
+}
+
+/// Simulate one year in each lane, each with its own xorshift seed
+///
+/// This is simulate_year, except that lanes can't branch separately, so every lane steps
+/// through the day's customers together, and lanes that have already seen all of theirs
+/// just sit out the rest. The work per day is set by the busiest lane, which is why a
+/// batch of eight isn't quite eight times faster.
+pub fn simulate_lanes(
+    sim: &Simulation,
+    starting_quantity: usize,
+    tables: &Tables,
+    seeds: [u32; LANES],
+) -> Counters {
+    let mask = State::splat(TABLE_SIZE as u32 - 1);
+    let safety_stock = Counter::splat(sim.safety_stock as u64);
+    let order_quantity = Counter::splat(sim.order_quantity as u64);
+    let zero = Counter::splat(0);
+    let one = Counter::splat(1);
+    let (mut st, mut ss, mut ft, mut fs) = (zero, zero, zero, zero);
+    let mut stock = Counter::splat(starting_quantity as u64);
+    let mut trucks = vec![zero; sim.lead_time];
+    let mut state = State::from_array(seeds);
+
+    for day in 0..365 {
+        // A truck arrived
+        stock += trucks[day % sim.lead_time];
+        // This many customers arrive, in each lane
+        state = xorshift(state);
+        let mut customers = State::gather_or_default(&tables.traffic, (state & mask).cast());
+        loop {
+            let waiting = customers.simd_gt(State::splat(0));
+            if !waiting.any() {
+                break;
+            }
+            // Only the lanes with customers left draw another one
+            state = waiting.select(xorshift(state), state);
+            let request: Counter = State::gather_select(
+                &tables.job_lot,
+                waiting.cast(),
+                (state & mask).cast(),
+                State::splat(0),
+            )
+            .cast();
+            customers -= waiting.select(State::splat(1), State::splat(0));
+            let active: Mask<i64, LANES> = waiting.cast();
+            // There are enough.
+            let filled = active & stock.simd_ge(request);
+            st += filled.select(one, zero);
+            ss += filled.select(request, zero);
+            stock -= filled.select(request, zero);
+            // There are not enough
+            let failed = active & !filled;
+            ft += failed.select(one, zero);
+            fs += failed.select(request, zero);
+        }
+        // The day is over. Start making orders.
+        let short = stock.simd_lt(safety_stock);
+        let orders = (safety_stock.saturating_sub(stock) + order_quantity - one) / order_quantity;
+        let slot = (day + sim.lead_time - 1) % sim.lead_time;
+        trucks[slot] = short.select(orders * order_quantity, trucks[slot]);
+    }
+
+    let mut counters = [(0, 0, 0, 0); LANES];
+    for (lane, counter) in counters.iter_mut().enumerate() {
+        *counter = (st[lane], ss[lane], ft[lane], fs[lane]);
+    }
+    counters
+}
+
+#[test]
+fn test_lanes_match_scalar() {
+    // The lanes draw from a table and their own xorshift streams, so their years can't match
+    // simulate_summary's one for one. They should come from the same distribution, though.
+    use crate::hypothesis::compare_years;
+    use crate::{summarize, Summary};
+    const YEARS: usize = 2000;
+    for &(safety_stock, lead_time, order_quantity, starting_quantity) in &[
+        (10, 10, 7, 10),
+        (150, 3, 40, 0),
+        (0, 1, 1, 300),
+        (80, 6, 95, 80),
+    ] {
+        let sim = Simulation::new(
+            safety_stock,
+            lead_time,
+            order_quantity,
+            Some(2.75),
+            Some(2.5),
+            Some(7),
+        );
+        let tables = Tables::new(&sim, &mut sim.rng());
+        let lanes: Vec<Summary> = (0..(YEARS / LANES) as u64)
+            .flat_map(|batch| simulate_lanes(&sim, starting_quantity, &tables, seeds(7, batch)))
+            .map(|(st, ss, ft, fs)| summarize(st as usize, ss as usize, ft as usize, fs as usize))
+            .collect();
+        let mut rng = sim.rng();
+        let scalar: Vec<Summary> = (0..YEARS)
+            .map(|_| sim.simulate_summary(starting_quantity, &mut rng))
+            .collect();
+        for (name, statistic, p) in compare_years(&lanes, &scalar) {
+            assert!(
+                p > 1e-4,
+                "{} differs for {:?}: statistic {}, p = {}",
+                name,
+                (safety_stock, lead_time, order_quantity, starting_quantity),
+                statistic,
+                p
+            );
+        }
+    }
+}
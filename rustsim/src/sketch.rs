@@ -0,0 +1,75 @@
+use tdigest::TDigest;
+
+/// How many values to collect before folding them into the digest
+const BUFFER_SIZE: usize = 4096;
+
+/// An approximate, bounded-memory summary of a stream of values, good for quantiles
+///
+/// t-digest wants values in batches, so we buffer a few thousand at a time before merging.
+pub struct Sketch {
+    digest: TDigest,
+    buffer: Vec<f64>,
+}
+
+impl Sketch {
+    pub fn new() -> Sketch {
+        Sketch {
+            digest: TDigest::new_with_size(100),
+            buffer: Vec::with_capacity(BUFFER_SIZE),
+        }
+    }
+
+    /// Add one value
+    pub fn push(&mut self, x: f64) {
+        self.buffer.push(x);
+        if self.buffer.len() >= BUFFER_SIZE {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        if !self.buffer.is_empty() {
+            let values = std::mem::replace(&mut self.buffer, Vec::with_capacity(BUFFER_SIZE));
+            self.digest = self.digest.merge_unsorted(values);
+        }
+    }
+
+    /// Estimate each of these quantiles (between 0 and 1). NaN if nothing was pushed.
+    pub fn quantiles(&mut self, qs: &[f64]) -> Vec<f64> {
+        self.flush();
+        qs.iter()
+            .map(|&q| {
+                if self.digest.is_empty() {
+                    f64::NAN
+                } else {
+                    self.digest.estimate_quantile(q)
+                }
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_quantiles() {
+    // Quantiles of a few buffers' worth of uniform values land near the exact ones, and an
+    // empty sketch has none
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    let mut rng = StdRng::seed_from_u64(11);
+    let mut sketch = Sketch::new();
+    assert!(sketch.quantiles(&[0.5]).iter().all(|q| q.is_nan()));
+    let mut values: Vec<f64> = (0..10_000).map(|_| rng.gen::<f64>()).collect();
+    values.iter().for_each(|&x| sketch.push(x));
+    values.sort_by(f64::total_cmp);
+    let qs = [0.01, 0.25, 0.5, 0.75, 0.99];
+    for (&q, estimate) in qs.iter().zip(sketch.quantiles(&qs)) {
+        let exact = values[(q * values.len() as f64) as usize];
+        assert!(
+            (estimate - exact).abs() < 0.01,
+            "q{}: {} vs {}",
+            q,
+            estimate,
+            exact
+        );
+    }
+}
@@ -0,0 +1,379 @@
+//! Driving a simulation one day at a time, for interventions and interactive tools
+//!
+//! SimulationState is a year in progress: the shelf, the trucks on the way, the random
+//! stream and what's been sold so far. Each step_day() plays one day exactly the way
+//! repeat_simulate_demand does, and in between, anything about the state can be looked at
+//! or changed. It can also be written down with to_bytes() and picked up again later, or in
+//! another process, from exactly where it was.
+use crate::mixture::Demand;
+use crate::{streams, summarize, Day, Shelf, Simulation, Summary};
+use pyo3::exceptions::ValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use pyo3::PyObjectProtocol;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+/// The version of the checkpoint format we write, and the only one we read
+const CHECKPOINT_VERSION: u32 = 1;
+
+/// A simulated year, paused between days
+///
+/// Created with SimulationState(sim, starting_quantity, seed=None). The seed defaults to the
+/// simulation's, and with neither, the year is different every time.
+#[pyclass(module = "rustsim")]
+#[derive(Clone)]
+pub struct SimulationState {
+    sim: Simulation,
+    shelf: Shelf,
+    rng: ChaCha20Rng,
+    /// What rng was seeded with, which with its position is all it takes to rebuild it
+    key: [u8; 32],
+    /// (successful transactions, successful sales, failed transactions, failed sales) so far
+    totals: (usize, usize, usize, usize),
+    it_zipf: Demand,
+    jl_zipf: Demand,
+}
+
+#[pymethods]
+impl SimulationState {
+    #[new]
+    #[args(seed = "None")]
+    fn init(
+        obj: &PyRawObject,
+        sim: &Simulation,
+        starting_quantity: usize,
+        seed: Option<u64>,
+    ) -> PyResult<()> {
+        obj.init(SimulationState::new(sim, starting_quantity, seed)?);
+        Ok(())
+    }
+
+    /// Start the year over from day 0, with `starting_quantity` on the shelf and nothing on
+    /// the way
+    #[args(seed = "None")]
+    fn reset(&mut self, starting_quantity: usize, seed: Option<u64>) -> PyResult<()> {
+        *self = SimulationState::new(&self.sim, starting_quantity, seed)?;
+        Ok(())
+    }
+
+    /// Simulate the next day, and say what happened in it
+    fn step_day(&mut self) -> DayResult {
+        let day = self.sim.simulate_day_with(
+            &mut self.shelf,
+            &self.it_zipf,
+            &self.jl_zipf,
+            &mut self.rng,
+            &mut |_, _, _| {},
+        );
+        self.totals.0 += day.successful_transactions;
+        self.totals.1 += day.successful_sales;
+        self.totals.2 += day.failed_transactions;
+        self.totals.3 += day.failed_sales;
+        DayResult::from(day)
+    }
+
+    /// The next day to simulate, counting from 0, so also how many have been simulated
+    #[getter]
+    fn day(&self) -> usize {
+        self.shelf.day
+    }
+
+    /// Units on the shelf
+    ///
+    /// Setting it stands for anything that changes the shelf outside the simulation, like a
+    /// recount or an emergency delivery.
+    #[getter]
+    fn stock(&self) -> usize {
+        self.shelf.stock
+    }
+
+    #[setter]
+    fn set_stock(&mut self, stock: usize) -> PyResult<()> {
+        self.shelf.stock = stock;
+        Ok(())
+    }
+
+    /// Units on the way, as a list with one entry per day of lead time, starting with the
+    /// truck due at the start of the next day
+    ///
+    /// The last entry is what tonight's order replaces, if there is one.
+    fn pipeline(&self) -> Vec<usize> {
+        self.shelf.pipeline()
+    }
+
+    /// The policy the rest of the year runs under
+    ///
+    /// Setting it changes the policy from the next day on, like
+    /// state.simulation = state.simulation.with_params(safety_stock=20). The lead time can't
+    /// change, since the trucks already on the way are laid out by it.
+    #[getter]
+    fn simulation(&self) -> Simulation {
+        self.sim.clone()
+    }
+
+    #[setter]
+    fn set_simulation(&mut self, sim: &Simulation) -> PyResult<()> {
+        if sim.lead_time != self.sim.lead_time {
+            return Err(ValueError::py_err(format!(
+                "lead_time can't change mid-year, from {} to {}",
+                self.sim.lead_time, sim.lead_time
+            )));
+        }
+        self.sim = sim.clone();
+        self.it_zipf = sim.traffic();
+        self.jl_zipf = sim.job_lots();
+        Ok(())
+    }
+
+    #[getter]
+    fn successful_transactions(&self) -> usize {
+        self.totals.0
+    }
+
+    #[getter]
+    fn successful_sales(&self) -> usize {
+        self.totals.1
+    }
+
+    #[getter]
+    fn failed_transactions(&self) -> usize {
+        self.totals.2
+    }
+
+    #[getter]
+    fn failed_sales(&self) -> usize {
+        self.totals.3
+    }
+
+    /// Everything so far, as a Summary like simulate_demand returns
+    fn summary(&self) -> Summary {
+        let (st, ss, ft, fs) = self.totals;
+        summarize(st, ss, ft, fs)
+    }
+
+    /// Step through to the start of `day` (by default, the end of the year), and return
+    /// the summary of everything so far
+    ///
+    /// Does nothing if the state is already that far along.
+    #[args(day = "365")]
+    fn run_until(&mut self, py: Python<'_>, day: usize) -> Summary {
+        py.allow_threads(|| {
+            while self.shelf.day < day {
+                self.step_day();
+            }
+        });
+        self.summary()
+    }
+
+    /// `n` copies of this state that each go on with a random stream of their own, for
+    /// what-ifs from a common point, like "given where we are on July 1, what if we change
+    /// order_quantity?"
+    ///
+    /// The copies start with this state's shelf, trucks, policy and totals. `seeds` seeds
+    /// their streams, one each; without it, they're seeded from this state's stream, without
+    /// moving it, so a seeded state branches the same way every time. This state carries on
+    /// unchanged.
+    #[args(seeds = "None")]
+    fn branch(&self, n: usize, seeds: Option<Vec<u64>>) -> PyResult<Vec<SimulationState>> {
+        let keys: Vec<[u8; 32]> = match seeds {
+            Some(seeds) if seeds.len() != n => {
+                return Err(ValueError::py_err(format!(
+                    "Asked for {} branches, but gave {} seeds",
+                    n,
+                    seeds.len()
+                )))
+            }
+            Some(seeds) => seeds
+                .into_iter()
+                .map(|seed| StdRng::seed_from_u64(seed).gen())
+                .collect(),
+            None => {
+                let mut rng = self.rng.clone();
+                (0..n).map(|_| rng.gen()).collect()
+            }
+        };
+        Ok(keys
+            .into_iter()
+            .map(|key| SimulationState {
+                rng: ChaCha20Rng::from_seed(key),
+                key,
+                ..self.clone()
+            })
+            .collect())
+    }
+}
+
+/// Everything in a SimulationState, as to_bytes() writes it
+#[derive(Deserialize, Serialize)]
+struct Checkpoint {
+    version: u32,
+    simulation: Simulation,
+    shelf: Shelf,
+    key: [u8; 32],
+    /// How far into its stream the generator is, in 32-bit words
+    word_pos: u64,
+    totals: (usize, usize, usize, usize),
+}
+
+/// SimulationState, continued: checkpoints
+#[pymethods]
+impl SimulationState {
+    /// Everything about this state, as bytes that from_bytes() turns back into it
+    ///
+    /// That includes where the random stream is, so the copy goes on exactly as this would
+    /// have. The bytes are JSON, with a version number, and pickle uses them too.
+    fn to_bytes(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let checkpoint = Checkpoint {
+            version: CHECKPOINT_VERSION,
+            simulation: self.sim.clone(),
+            shelf: self.shelf.clone(),
+            key: self.key,
+            word_pos: u64::try_from(self.rng.get_word_pos())
+                .map_err(|_| ValueError::py_err("The random stream is too far along to save"))?,
+            totals: self.totals,
+        };
+        let bytes = serde_json::to_vec(&checkpoint)
+            .map_err(|error| ValueError::py_err(error.to_string()))?;
+        Ok(PyBytes::new(py, &bytes).into())
+    }
+
+    /// A state from bytes written by to_bytes(), ready to go on from where it was
+    #[staticmethod]
+    fn from_bytes(data: &PyBytes) -> PyResult<SimulationState> {
+        let checkpoint: Checkpoint = serde_json::from_slice(data.as_bytes())
+            .map_err(|error| ValueError::py_err(error.to_string()))?;
+        if checkpoint.version != CHECKPOINT_VERSION {
+            return Err(ValueError::py_err(format!(
+                "This is checkpoint version {}, but we only read version {}",
+                checkpoint.version, CHECKPOINT_VERSION
+            )));
+        }
+        let sim = checkpoint.simulation;
+        if checkpoint.shelf.trucks.len() != sim.lead_time {
+            return Err(ValueError::py_err(format!(
+                "The checkpoint has {} trucks on the way for a lead time of {}",
+                checkpoint.shelf.trucks.len(),
+                sim.lead_time
+            )));
+        }
+        let mut rng = ChaCha20Rng::from_seed(checkpoint.key);
+        rng.set_word_pos(checkpoint.word_pos.into());
+        Ok(SimulationState {
+            it_zipf: sim.traffic(),
+            jl_zipf: sim.job_lots(),
+            sim,
+            shelf: checkpoint.shelf,
+            rng,
+            key: checkpoint.key,
+            totals: checkpoint.totals,
+        })
+    }
+
+    /// For pickle, which goes through to_bytes()
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (PyObject,))> {
+        let from_bytes = py
+            .import("rustsim")?
+            .getattr("SimulationState")?
+            .getattr("from_bytes")?;
+        Ok((from_bytes.to_object(py), (self.to_bytes(py)?,)))
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for SimulationState {
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!(
+            "SimulationState(day={}, stock={}, pipeline={:?})",
+            self.shelf.day,
+            self.shelf.stock,
+            self.shelf.pipeline()
+        ))
+    }
+}
+
+impl SimulationState {
+    fn new(sim: &Simulation, starting_quantity: usize, seed: Option<u64>) -> PyResult<Self> {
+        sim.check_parameters().map_err(ValueError::py_err)?;
+        // Seeded from a ChaCha key we make ourselves, rather than an StdRng, so the whole
+        // stream can be written down and picked up again
+        let seed = seed.or(sim.seed).unwrap_or_else(streams::fresh_seed);
+        let key = StdRng::seed_from_u64(seed).gen();
+        Ok(SimulationState {
+            sim: sim.clone(),
+            shelf: Shelf::new(starting_quantity, sim.lead_time),
+            rng: ChaCha20Rng::from_seed(key),
+            key,
+            totals: (0, 0, 0, 0),
+            it_zipf: sim.traffic(),
+            jl_zipf: sim.job_lots(),
+        })
+    }
+}
+
+/// What happened on one day of a SimulationState
+#[pyclass(module = "rustsim")]
+pub struct DayResult {
+    /// Which day it was, counting from 0
+    #[pyo3(get)]
+    day: usize,
+    /// Units that arrived by truck that morning
+    #[pyo3(get)]
+    received: usize,
+    /// Units on the shelf once the truck was unloaded
+    #[pyo3(get)]
+    opening_stock: usize,
+    #[pyo3(get)]
+    successful_transactions: usize,
+    #[pyo3(get)]
+    successful_sales: usize,
+    #[pyo3(get)]
+    failed_transactions: usize,
+    #[pyo3(get)]
+    failed_sales: usize,
+    /// Units left at the end of the day
+    #[pyo3(get)]
+    closing_stock: usize,
+    /// Units ordered that evening, or 0 if we didn't order
+    #[pyo3(get)]
+    ordered: usize,
+}
+
+impl From<Day> for DayResult {
+    fn from(day: Day) -> DayResult {
+        DayResult {
+            day: day.day,
+            received: day.received,
+            opening_stock: day.opening_stock,
+            successful_transactions: day.successful_transactions,
+            successful_sales: day.successful_sales,
+            failed_transactions: day.failed_transactions,
+            failed_sales: day.failed_sales,
+            closing_stock: day.closing_stock,
+            ordered: day.ordered,
+        }
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for DayResult {
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!(
+            "DayResult(day={}, received={}, opening_stock={}, successful_transactions={}, \
+             successful_sales={}, failed_transactions={}, failed_sales={}, closing_stock={}, \
+             ordered={})",
+            self.day,
+            self.received,
+            self.opening_stock,
+            self.successful_transactions,
+            self.successful_sales,
+            self.failed_transactions,
+            self.failed_sales,
+            self.closing_stock,
+            self.ordered
+        ))
+    }
+}
@@ -0,0 +1,289 @@
+//! A record of past runs in a local SQLite database, for keeping track of experiments
+//! without running a tracking server
+//!
+//! This goes through Python's own sqlite3 module, so there's nothing else to install, and the
+//! file opens in any SQLite tool.
+use crate::{html, Simulation, Summary};
+use pyo3::exceptions::ValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyFloat, PyTuple};
+use pyo3::PyNativeType;
+
+const CREATE: &str = "CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    recorded_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    method TEXT NOT NULL,
+    config TEXT NOT NULL,
+    seed TEXT,
+    safety_stock INTEGER NOT NULL,
+    lead_time INTEGER NOT NULL,
+    order_quantity INTEGER NOT NULL,
+    job_lot_zipf REAL NOT NULL,
+    itemwise_traffic_zipf REAL NOT NULL,
+    starting_quantity INTEGER NOT NULL,
+    count INTEGER NOT NULL,
+    successful_transactions INTEGER NOT NULL,
+    successful_sales INTEGER NOT NULL,
+    failed_transactions INTEGER NOT NULL,
+    failed_sales INTEGER NOT NULL,
+    transaction_fill_rate REAL NOT NULL,
+    unit_fill_rate REAL NOT NULL
+)";
+
+/// Every column, in order
+const COLUMNS: [&str; 18] = [
+    "id",
+    "recorded_at",
+    "method",
+    "config",
+    "seed",
+    "safety_stock",
+    "lead_time",
+    "order_quantity",
+    "job_lot_zipf",
+    "itemwise_traffic_zipf",
+    "starting_quantity",
+    "count",
+    "successful_transactions",
+    "successful_sales",
+    "failed_transactions",
+    "failed_sales",
+    "transaction_fill_rate",
+    "unit_fill_rate",
+];
+
+/// The columns _repr_html_() shows, leaving out the ones too wide for a notebook
+const SHOWN: [&str; 10] = [
+    "id",
+    "recorded_at",
+    "method",
+    "safety_stock",
+    "lead_time",
+    "order_quantity",
+    "starting_quantity",
+    "count",
+    "transaction_fill_rate",
+    "unit_fill_rate",
+];
+
+/// How many runs _repr_html_() shows
+const LATEST: usize = 10;
+
+/// A SQLite file of past runs: each one's parameters, seed and totals
+///
+/// Pass one as `store` to Simulation.repeat_simulate_demand() and it records the run, or
+/// record runs yourself with record(). `config` is the simulation as Simulation.to_json()
+/// writes it, so Simulation.from_json(run["config"]) gets you the same simulation back. The
+/// seed is kept as text, since SQLite's integers stop at 2^63, but runs() hands it back as a
+/// number.
+///
+/// Any thread can use a store. Each query holds a lock, so they run one at a time.
+#[pyclass(module = "rustsim")]
+pub struct ResultStore {
+    connection: PyObject,
+    /// A threading.Lock, which lets the GIL go while it waits, unlike a Mutex
+    lock: PyObject,
+}
+
+#[pymethods]
+impl ResultStore {
+    /// Open the database at `path`, creating it if it isn't there
+    #[new]
+    fn init(obj: &PyRawObject, path: &str) -> PyResult<()> {
+        let py = obj.py();
+        // We do the locking, so sqlite3 needn't insist on the thread that opened it
+        let options = PyDict::new(py);
+        options.set_item("check_same_thread", false)?;
+        let connection = py
+            .import("sqlite3")?
+            .call("connect", (path,), Some(options))?;
+        connection.call_method1("execute", (CREATE,))?;
+        connection.call_method0("commit")?;
+        obj.init(ResultStore {
+            connection: connection.to_object(py),
+            lock: py.import("threading")?.call0("Lock")?.to_object(py),
+        });
+        Ok(())
+    }
+
+    /// Add a run, returning its id
+    ///
+    /// `method` says how the summary was simulated, so runs made different ways don't get
+    /// mixed up.
+    #[args(method = "\"repeat_simulate_demand\"")]
+    fn record(
+        &self,
+        py: Python<'_>,
+        sim: &Simulation,
+        starting_quantity: usize,
+        count: usize,
+        summary: Summary,
+        method: &str,
+    ) -> PyResult<i64> {
+        self.add(py, sim, method, starting_quantity, count, summary)
+    }
+
+    /// Past runs, oldest first, each a dict with a key for every column
+    ///
+    /// Keyword arguments pick out runs by column, like
+    /// runs(safety_stock=10, method="repeat_simulate_demand").
+    #[args(filters = "**")]
+    fn runs(&self, py: Python<'_>, filters: Option<&PyDict>) -> PyResult<Vec<PyObject>> {
+        let mut conditions = vec![];
+        let mut values = vec![];
+        for (column, value) in filters.into_iter().flatten() {
+            let column: &str = column.extract()?;
+            // Column names can't be parameters, so only ever put our own into the query
+            if !COLUMNS.contains(&column) || column == "config" {
+                return Err(ValueError::py_err(format!(
+                    "Can't pick runs by {:?}; try one of {:?}",
+                    column, COLUMNS
+                )));
+            }
+            conditions.push(format!("{} = ?", column));
+            values.push(value.to_object(py));
+        }
+        let mut query = format!("SELECT {} FROM runs", COLUMNS.join(", "));
+        if !conditions.is_empty() {
+            query += " WHERE ";
+            query += &conditions.join(" AND ");
+        }
+        query += " ORDER BY id";
+        let rows: Vec<Vec<PyObject>> = self.locked(py, || {
+            self.connection
+                .call_method1(py, "execute", (query, PyTuple::new(py, values)))?
+                .call_method0(py, "fetchall")?
+                .extract(py)
+        })?;
+        rows.into_iter()
+            .map(|row| {
+                let run = PyDict::new(py);
+                for (column, value) in COLUMNS.iter().zip(row) {
+                    match (*column, value.extract::<String>(py)) {
+                        ("seed", Ok(seed)) => run.set_item(column, seed.parse::<u64>().ok())?,
+                        _ => run.set_item(column, value)?,
+                    }
+                }
+                Ok(run.to_object(py))
+            })
+            .collect()
+    }
+
+    /// The same runs as runs(), as a pandas DataFrame with a column for every column
+    #[args(filters = "**")]
+    fn to_pandas(&self, py: Python<'_>, filters: Option<&PyDict>) -> PyResult<PyObject> {
+        let options = PyDict::new(py);
+        options.set_item("columns", COLUMNS.to_vec())?;
+        let frame = py.import("pandas")?.getattr("DataFrame")?.call_method(
+            "from_records",
+            (self.runs(py, filters)?,),
+            Some(options),
+        )?;
+        Ok(frame.to_object(py))
+    }
+
+    /// How many runs there are and a table of the latest few, for Jupyter
+    fn _repr_html_(&self, py: Python<'_>) -> PyResult<String> {
+        let query = format!(
+            "SELECT {} FROM runs ORDER BY id DESC LIMIT {}",
+            SHOWN.join(", "),
+            LATEST
+        );
+        let (total, latest): (i64, Vec<Vec<PyObject>>) = self.locked(py, || {
+            let total = self
+                .connection
+                .call_method1(py, "execute", ("SELECT count(*) FROM runs",))?
+                .call_method0(py, "fetchone")?
+                .extract::<(i64,)>(py)?
+                .0;
+            let latest = self
+                .connection
+                .call_method1(py, "execute", (query,))?
+                .call_method0(py, "fetchall")?
+                .extract(py)?;
+            Ok((total, latest))
+        })?;
+        let rows = latest
+            .iter()
+            .rev()
+            .map(|row| {
+                row.iter()
+                    .map(|value| {
+                        Ok(match value.cast_as::<PyFloat>(py) {
+                            Ok(rate) => format!("{:.4}", rate.value()),
+                            Err(_) => html::escape(&value.as_ref(py).str()?.to_string()?),
+                        })
+                    })
+                    .collect()
+            })
+            .collect::<PyResult<Vec<Vec<String>>>>()?;
+        let caption = match total {
+            1 => "1 run".to_string(),
+            _ if total as usize > LATEST => format!("{} runs, the latest {} shown", total, LATEST),
+            _ => format!("{} runs", total),
+        };
+        Ok(html::table(&caption, &SHOWN, &rows))
+    }
+
+    /// Close the database. The store can't be used after this.
+    fn close(&self, py: Python<'_>) -> PyResult<()> {
+        self.locked(py, || self.connection.call_method0(py, "close"))?;
+        Ok(())
+    }
+}
+
+impl ResultStore {
+    /// What `query` returns, run while no other thread is using the connection
+    fn locked<T>(&self, py: Python<'_>, query: impl FnOnce() -> PyResult<T>) -> PyResult<T> {
+        self.lock.call_method0(py, "acquire")?;
+        let result = query();
+        self.lock.call_method0(py, "release")?;
+        result
+    }
+
+    /// Insert a run and commit it straight away, so a crash later can't lose it
+    pub fn add(
+        &self,
+        py: Python<'_>,
+        sim: &Simulation,
+        method: &str,
+        starting_quantity: usize,
+        count: usize,
+        (st, ss, ft, fs, tr, ur): Summary,
+    ) -> PyResult<i64> {
+        let config =
+            serde_json::to_string(sim).map_err(|error| ValueError::py_err(error.to_string()))?;
+        let values = vec![
+            method.to_object(py),
+            config.to_object(py),
+            sim.seed.map(|seed| seed.to_string()).to_object(py),
+            sim.safety_stock.to_object(py),
+            sim.lead_time.to_object(py),
+            sim.order_quantity.to_object(py),
+            sim.job_lot_zipf.to_object(py),
+            sim.itemwise_traffic_zipf.to_object(py),
+            starting_quantity.to_object(py),
+            count.to_object(py),
+            st.to_object(py),
+            ss.to_object(py),
+            ft.to_object(py),
+            fs.to_object(py),
+            tr.to_object(py),
+            ur.to_object(py),
+        ];
+        // Everything but the id and the time, which SQLite fills in
+        let columns = &COLUMNS[2..];
+        let query = format!(
+            "INSERT INTO runs ({}) VALUES ({})",
+            columns.join(", "),
+            vec!["?"; columns.len()].join(", ")
+        );
+        self.locked(py, || {
+            let cursor =
+                self.connection
+                    .call_method1(py, "execute", (query, PyTuple::new(py, values)))?;
+            self.connection.call_method0(py, "commit")?;
+            cursor.getattr(py, "lastrowid")?.extract(py)
+        })
+    }
+}
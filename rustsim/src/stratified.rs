@@ -0,0 +1,159 @@
+use rand::distributions::Distribution;
+use rand::Rng;
+use std::cell::Cell;
+
+/// A whole year of customer demand, drawn ahead of time
+///
+/// The draws are stored in the same order the simulation asks for them: for each day, the
+/// number of customers followed by each of their requests.
+pub struct DemandYear {
+    draws: Vec<usize>,
+    /// Total units requested over the year
+    pub total: usize,
+}
+
+impl DemandYear {
+    pub fn draw<R, IT, JL>(rng: &mut R, it_dist: &IT, jl_dist: &JL) -> DemandYear
+    where
+        R: Rng,
+        IT: Distribution<usize>,
+        JL: Distribution<usize>,
+    {
+        let mut draws = vec![];
+        let mut total = 0;
+        for _day in 0..365 {
+            let customers = it_dist.sample(rng);
+            draws.push(customers);
+            for _customer in 0..customers {
+                let request = jl_dist.sample(rng);
+                draws.push(request);
+                total += request;
+            }
+        }
+        DemandYear { draws, total }
+    }
+
+    /// A year of demand given day by day, each day being every customer's request
+    pub fn from_days<I: IntoIterator<Item = Vec<usize>>>(days: I) -> DemandYear {
+        let mut draws = vec![];
+        let mut total = 0;
+        for requests in days {
+            draws.push(requests.len());
+            total += requests.iter().sum::<usize>();
+            draws.extend(requests);
+        }
+        DemandYear { draws, total }
+    }
+
+    /// A "distribution" that hands back these draws one after another
+    ///
+    /// Pass it as both the traffic and the job lot distribution to replay this exact year.
+    pub fn replay(&self) -> Replay<'_> {
+        Replay {
+            draws: &self.draws,
+            next: Cell::new(0),
+        }
+    }
+}
+
+pub struct Replay<'a> {
+    draws: &'a [usize],
+    next: Cell<usize>,
+}
+
+impl<'a> Distribution<usize> for Replay<'a> {
+    fn sample<R: Rng + ?Sized>(&self, _rng: &mut R) -> usize {
+        let i = self.next.get();
+        self.next.set(i + 1);
+        self.draws[i]
+    }
+}
+
+/// Which of the strata (numbered from 0) a value falls in, given the boundaries between them
+pub fn stratum_of(boundaries: &[usize], value: usize) -> usize {
+    boundaries.iter().take_while(|&&b| value > b).count()
+}
+
+/// Boundaries that would split these values into (at most) `strata` equally likely groups,
+/// and the fraction of the values that actually landed in each group.
+///
+/// Ties can make some groups bigger than others, or merge them, so use the fractions rather
+/// than assuming they are all the same.
+pub fn boundaries(mut values: Vec<usize>, strata: usize) -> (Vec<usize>, Vec<f64>) {
+    values.sort_unstable();
+    let mut boundaries: Vec<usize> = (1..strata)
+        .map(|h| values[h * values.len() / strata])
+        .collect();
+    boundaries.dedup();
+    let mut weights = vec![0.0; boundaries.len() + 1];
+    for &value in &values {
+        weights[stratum_of(&boundaries, value)] += 1.0 / values.len() as f64;
+    }
+    (boundaries, weights)
+}
+
+/// How many of `count` repetitions each stratum gets, in proportion to its weight
+///
+/// Every stratum gets its share, with any rounding remainder going to the largest.
+pub fn allocate(weights: &[f64], count: usize) -> Vec<usize> {
+    let mut wanted: Vec<usize> = weights
+        .iter()
+        .map(|w| (w * count as f64).floor() as usize)
+        .collect();
+    let largest = (0..weights.len())
+        .max_by(|&a, &b| weights[a].partial_cmp(&weights[b]).unwrap())
+        .unwrap();
+    wanted[largest] += count - wanted.iter().sum::<usize>();
+    wanted
+}
+
+#[test]
+fn test_allocate() {
+    // Allocations always add up to the count, even when ties merge strata or the shares
+    // don't divide evenly, and each stratum gets about its share
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    let mut rng = StdRng::seed_from_u64(3);
+    for _ in 0..100 {
+        let values: Vec<usize> = (0..1000).map(|_| rng.gen_range(0, 20)).collect();
+        let strata = rng.gen_range(1, 15);
+        let (boundaries, weights) = boundaries(values, strata);
+        assert_eq!(weights.len(), boundaries.len() + 1);
+        assert!((weights.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        let count = rng.gen_range(strata, 5000);
+        let wanted = allocate(&weights, count);
+        assert_eq!(wanted.iter().sum::<usize>(), count);
+        for (&n, &w) in wanted.iter().zip(&weights) {
+            assert!((n as f64 - w * count as f64).abs() <= weights.len() as f64);
+        }
+    }
+    assert_eq!(allocate(&[0.25, 0.5, 0.25], 10), vec![2, 6, 2]);
+}
+
+#[test]
+fn test_replay() {
+    // Replaying a year hands back the same draws in the same order as drawing it did, and a
+    // year given day by day adds up the same way
+    use crate::mixture::Demand;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    let (it_dist, jl_dist) = (Demand::new(1.5, None, None), Demand::new(2.0, None, None));
+    let year = DemandYear::draw(&mut StdRng::seed_from_u64(4), &it_dist, &jl_dist);
+    let mut rng = StdRng::seed_from_u64(4);
+    let replay = year.replay();
+    let mut total = 0;
+    for _day in 0..365 {
+        let customers = it_dist.sample(&mut rng);
+        assert_eq!(replay.sample(&mut rng), customers);
+        for _customer in 0..customers {
+            let request = jl_dist.sample(&mut rng);
+            assert_eq!(replay.sample(&mut rng), request);
+            total += request;
+        }
+    }
+    assert_eq!(year.total, total);
+    assert_eq!(
+        DemandYear::from_days(vec![vec![3, 4], vec![], vec![5]]).total,
+        12
+    );
+}
@@ -0,0 +1,73 @@
+//! Independent random number streams, all derived from one master seed
+//!
+//! Anything that splits a run up, over threads, batches, GPU work items or farm workers, asks
+//! here for the generator of each piece by its index rather than drawing seeds one after
+//! another from a shared generator. A piece's numbers then depend only on the master seed and
+//! its index, so the run gives the same years however it's split, and pieces never share
+//! numbers with each other.
+//!
+//! There are two kinds of stream:
+//!
+//! - stream() is ChaCha20 keyed by the master seed, with the index as its stream number. Each
+//!   stream is its own 2^64 blocks of keystream, so no two can overlap, however long they run.
+//! - stream_seed() is SplitMix64 run to the index, for the small seeds handed to the xorshift
+//!   generators in the kernels and SIMD lanes, which can't take a whole ChaCha key.
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+
+/// The increment SplitMix64 adds each step, 2^64 / φ
+const GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// Streams kept for a purpose, far above any index a run splits into
+pub const BOOTSTRAP: u64 = u64::MAX;
+pub const FORECAST: u64 = u64::MAX - 1;
+pub const BATCHES: u64 = u64::MAX - 2;
+
+/// One step of SplitMix64: advance `state` and return its next output
+pub fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(GAMMA);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// The `index`th output of SplitMix64 started from `master`, without the steps before it
+pub fn stream_seed(master: u64, index: u64) -> u64 {
+    let mut state = master.wrapping_add(index.wrapping_mul(GAMMA));
+    splitmix64(&mut state)
+}
+
+/// A seed for a xorshift32 generator, which gets stuck at 0 and so is never given one
+pub fn xorshift_seed(master: u64, index: u64) -> u32 {
+    let seed = stream_seed(master, index);
+    // Fold both halves in, so nothing of the mixing is thrown away
+    ((seed >> 32) as u32 ^ seed as u32).max(1)
+}
+
+/// The generator for piece `index` of a run seeded with `master`
+pub fn stream(master: u64, index: u64) -> ChaCha20Rng {
+    let mut rng = ChaCha20Rng::seed_from_u64(master);
+    rng.set_stream(index);
+    rng
+}
+
+/// A master seed for a run that wasn't given one, from the operating system
+pub fn fresh_seed() -> u64 {
+    rand::random()
+}
+
+#[test]
+fn test_streams() {
+    // Streams are fixed by their index alone, and different indices differ
+    use rand::Rng;
+    let first: Vec<u64> = (0..4).map(|i| stream(7, i).gen()).collect();
+    let again: Vec<u64> = (0..4).rev().map(|i| stream(7, i).gen()).collect();
+    assert_eq!(first, again.into_iter().rev().collect::<Vec<u64>>());
+    assert!(first.windows(2).all(|pair| pair[0] != pair[1]));
+    let mut state = 7;
+    let sequential: Vec<u64> = (0..4).map(|_| splitmix64(&mut state)).collect();
+    let skipped: Vec<u64> = (0..4).map(|i| stream_seed(7, i)).collect();
+    assert_eq!(sequential, skipped);
+    assert!((0..1000).all(|i| xorshift_seed(0, i) != 0));
+}
@@ -0,0 +1,197 @@
+use crate::linalg;
+use pyo3::exceptions::ValueError;
+use pyo3::prelude::*;
+
+/// A quadratic fitted to how a metric responds to some parameters
+///
+/// The model is f(x) = c + sum(b_i z_i) + sum over i <= j of (a_ij z_i z_j), where each z is
+/// a parameter standardized to mean 0 and standard deviation 1 over the data it was fit to.
+/// Standardizing keeps the least squares problem well conditioned when parameters have very
+/// different scales (say, a safety stock of 500 and a zipf exponent of 2.75). Everything that
+/// comes out of here is converted back to the original units.
+#[pyclass(module = "rustsim.optimize")]
+pub struct ResponseSurface {
+    params: Vec<String>,
+    means: Vec<f64>,
+    scales: Vec<f64>,
+    /// Constant, then linear terms, then the upper triangle of the quadratic terms by row
+    coefficients: Vec<f64>,
+    /// Fraction of the metric's variance the fit explains
+    #[pyo3(get)]
+    r_squared: f64,
+}
+
+impl ResponseSurface {
+    /// Least squares fit of a quadratic to `ys` as a function of the rows of `xs`
+    pub fn fit(params: Vec<String>, xs: &[Vec<f64>], ys: &[f64]) -> PyResult<ResponseSurface> {
+        let k = params.len();
+        let n = xs.len() as f64;
+        let means: Vec<f64> = (0..k)
+            .map(|i| xs.iter().map(|x| x[i]).sum::<f64>() / n)
+            .collect();
+        let scales: Vec<f64> = (0..k)
+            .map(|i| {
+                let variance = xs.iter().map(|x| (x[i] - means[i]).powi(2)).sum::<f64>() / n;
+                if variance > 0.0 {
+                    variance.sqrt()
+                } else {
+                    1.0
+                }
+            })
+            .collect();
+        let mut surface = ResponseSurface {
+            params,
+            means,
+            scales,
+            coefficients: vec![],
+            r_squared: 0.0,
+        };
+        let features: Vec<Vec<f64>> = xs.iter().map(|x| surface.features(x)).collect();
+        let width = features.first().map_or(0, |f| f.len());
+        if features.len() < width {
+            return Err(ValueError::py_err(format!(
+                "A quadratic in {} parameters needs at least {} points, not {}",
+                k,
+                width,
+                features.len()
+            )));
+        }
+
+        // Normal equations: (F^T F) c = F^T y
+        let mut gram = vec![vec![0.0; width]; width];
+        let mut moment = vec![0.0; width];
+        for (f, &y) in features.iter().zip(ys) {
+            for i in 0..width {
+                moment[i] += f[i] * y;
+                for j in 0..width {
+                    gram[i][j] += f[i] * f[j];
+                }
+            }
+        }
+        surface.coefficients = linalg::solve(gram, moment).ok_or_else(|| {
+            ValueError::py_err("The points don't vary enough in every parameter to fit a quadratic")
+        })?;
+
+        let mean_y = ys.iter().sum::<f64>() / n;
+        let total: f64 = ys.iter().map(|y| (y - mean_y).powi(2)).sum();
+        let residual: f64 = xs
+            .iter()
+            .zip(ys)
+            .map(|(x, y)| (y - surface.value(x)).powi(2))
+            .sum();
+        surface.r_squared = if total > 0.0 {
+            1.0 - residual / total
+        } else {
+            1.0
+        };
+        Ok(surface)
+    }
+
+    /// 1, then z, then z_i z_j for i <= j
+    fn features(&self, x: &[f64]) -> Vec<f64> {
+        let z: Vec<f64> = x
+            .iter()
+            .zip(&self.means)
+            .zip(&self.scales)
+            .map(|((x, mean), scale)| (x - mean) / scale)
+            .collect();
+        let mut features = vec![1.0];
+        features.extend(&z);
+        for i in 0..z.len() {
+            for j in i..z.len() {
+                features.push(z[i] * z[j]);
+            }
+        }
+        features
+    }
+
+    fn value(&self, x: &[f64]) -> f64 {
+        self.features(x)
+            .iter()
+            .zip(&self.coefficients)
+            .map(|(f, c)| f * c)
+            .sum()
+    }
+
+    /// Linear coefficients, and the Hessian, both in standardized units
+    fn gradient_and_hessian(&self) -> (Vec<f64>, Vec<Vec<f64>>) {
+        let k = self.params.len();
+        let linear = self.coefficients[1..=k].to_vec();
+        let mut hessian = vec![vec![0.0; k]; k];
+        let pairs = (0..k).flat_map(|i| (i..k).map(move |j| (i, j)));
+        for ((i, j), &c) in pairs.zip(&self.coefficients[k + 1..]) {
+            if i == j {
+                hessian[i][i] = 2.0 * c;
+            } else {
+                hessian[i][j] = c;
+                hessian[j][i] = c;
+            }
+        }
+        (linear, hessian)
+    }
+}
+
+#[pymethods]
+impl ResponseSurface {
+    /// The parameters, in the order the other methods expect and return them
+    fn params(&self) -> Vec<String> {
+        self.params.clone()
+    }
+
+    /// Predicted metric at a point (one value per parameter)
+    fn predict(&self, point: Vec<f64>) -> PyResult<f64> {
+        if point.len() != self.params.len() {
+            return Err(ValueError::py_err(format!(
+                "Expected {} values ({:?})",
+                self.params.len(),
+                self.params
+            )));
+        }
+        Ok(self.value(&point))
+    }
+
+    /// Second derivatives of the metric with respect to each pair of parameters
+    fn curvature(&self) -> Vec<Vec<f64>> {
+        let (_, hessian) = self.gradient_and_hessian();
+        hessian
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                row.iter()
+                    .enumerate()
+                    .map(|(j, h)| h / (self.scales[i] * self.scales[j]))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The point where the fitted surface is flat, and what kind of point that is
+    ///
+    /// Returns (point, predicted metric, kind) where kind is "maximum", "minimum" or
+    /// "saddle"; or None if the surface has no single flat point (say, it's a ridge).
+    /// The point may well be outside the grid, where the quadratic is only a guess.
+    fn optimum(&self) -> Option<(Vec<f64>, f64, &'static str)> {
+        let (linear, hessian) = self.gradient_and_hessian();
+        // The gradient, linear + hessian * z, is zero here
+        let z = linalg::solve(hessian.clone(), linear.iter().map(|b| -b).collect())?;
+        let point: Vec<f64> = z
+            .iter()
+            .zip(&self.means)
+            .zip(&self.scales)
+            .map(|((z, mean), scale)| mean + z * scale)
+            .collect();
+        let negated: Vec<Vec<f64>> = hessian
+            .iter()
+            .map(|row| row.iter().map(|h| -h).collect())
+            .collect();
+        let kind = if linalg::is_positive_definite(&negated) {
+            "maximum"
+        } else if linalg::is_positive_definite(&hessian) {
+            "minimum"
+        } else {
+            "saddle"
+        };
+        let value = self.value(&point);
+        Some((point, value, kind))
+    }
+}
@@ -0,0 +1,118 @@
+//! Where the time went in a simulation's last call, for Simulation.last_timings()
+//!
+//! Every backend reports the same phases, so a CPU run and a GPU run of the same call line up
+//! side by side. Phases a backend doesn't have (there's nothing to upload on the CPU) stay
+//! at zero.
+#[cfg(feature = "python")]
+use std::sync::Mutex;
+#[cfg(feature = "python")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "python")]
+/// The phases, in the order a call goes through them
+#[derive(Clone, Copy)]
+pub enum Phase {
+    /// Anything prepared before the first repetition, like zipf tables
+    Precompute,
+    /// Seeding the random number generators
+    Seeds,
+    /// Copying inputs to the device
+    Upload,
+    /// The repetitions themselves
+    Kernel,
+    /// Copying results back from the device
+    Readback,
+    /// Adding the results up into a summary
+    Reduction,
+}
+
+#[cfg(feature = "python")]
+impl Phase {
+    pub const ALL: [(&'static str, Phase); 6] = [
+        ("precompute", Phase::Precompute),
+        ("seed_generation", Phase::Seeds),
+        ("upload", Phase::Upload),
+        ("kernel", Phase::Kernel),
+        ("readback", Phase::Readback),
+        ("reduction", Phase::Reduction),
+    ];
+}
+
+#[cfg(feature = "python")]
+/// Wall-clock time spent in each phase
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Breakdown([Duration; 6]);
+
+#[cfg(feature = "python")]
+impl Breakdown {
+    pub fn add(&mut self, phase: Phase, time: Duration) {
+        self.0[phase as usize] += time;
+    }
+
+    /// Seconds spent in `phase`
+    pub fn seconds(&self, phase: Phase) -> f64 {
+        self.0[phase as usize].as_secs_f64()
+    }
+}
+
+#[cfg(feature = "python")]
+/// Times a call one phase after another
+pub struct Stopwatch {
+    lap: Instant,
+    breakdown: Breakdown,
+}
+
+#[cfg(feature = "python")]
+impl Stopwatch {
+    pub fn start() -> Stopwatch {
+        Stopwatch {
+            lap: Instant::now(),
+            breakdown: Breakdown::default(),
+        }
+    }
+
+    /// Charge everything since the last lap to `phase`
+    pub fn lap(&mut self, phase: Phase) {
+        let now = Instant::now();
+        self.breakdown.add(phase, now - self.lap);
+        self.lap = now;
+    }
+
+    /// The time charged to each phase so far
+    pub fn breakdown(&self) -> Breakdown {
+        self.breakdown
+    }
+}
+
+/// The breakdown of a simulation's last timed call
+///
+/// This isn't part of what the simulation is: copies start without one, and it never makes
+/// two simulations unequal.
+#[derive(Debug, Default)]
+pub struct LastTimings(
+    // Without Python there's nobody to ask for the timings, so there's nothing to keep
+    #[cfg(feature = "python")] Mutex<Option<Breakdown>>,
+);
+
+#[cfg(feature = "python")]
+impl LastTimings {
+    pub fn set(&self, breakdown: Breakdown) {
+        *self.0.lock().unwrap() = Some(breakdown);
+    }
+
+    pub fn get(&self) -> Option<Breakdown> {
+        *self.0.lock().unwrap()
+    }
+}
+
+impl Clone for LastTimings {
+    fn clone(&self) -> LastTimings {
+        LastTimings::default()
+    }
+}
+
+impl PartialEq for LastTimings {
+    fn eq(&self, _other: &LastTimings) -> bool {
+        true
+    }
+}
@@ -0,0 +1,119 @@
+//! Day by day traces of many simulated years, exported to HDF5
+//!
+//! A million years is 365 million days, far too many for CSV, so each count gets its own
+//! (repetition, day) dataset of 32-bit integers, chunked by repetition and gzip-compressed.
+//! We simulate and write a chunk at a time, so memory stays flat however many years there
+//! are. The writing goes through h5py, so the module builds without libhdf5; h5py and numpy
+//! only need to be installed when you export.
+use crate::{summarize, Observer, Simulation, Summary};
+use pyo3::exceptions::ValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PySlice};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// Every dataset we write, each one value per repetition per day
+pub const DATASETS: [&str; 5] = [
+    "customers",
+    "demand",
+    "sales",
+    "failed_sales",
+    "ending_stock",
+];
+
+/// A chunk of repetitions, day by day, each dataset flattened a repetition at a time
+struct Days {
+    arrays: [Vec<u32>; 5],
+    /// The repetition we're on, within the chunk
+    row: usize,
+}
+
+impl Days {
+    fn new(rows: usize) -> Days {
+        let zeros = || vec![0; rows * 365];
+        Days {
+            arrays: [zeros(), zeros(), zeros(), zeros(), zeros()],
+            row: 0,
+        }
+    }
+}
+
+impl Observer for &mut Days {
+    fn transaction(&mut self, day: usize, request: usize, filled: bool) {
+        let at = self.row * 365 + day;
+        let request = request as u32;
+        self.arrays[0][at] += 1;
+        self.arrays[1][at] += request;
+        self.arrays[if filled { 2 } else { 3 }][at] += request;
+    }
+
+    fn end_of_day(&mut self, day: usize, stock: usize) {
+        self.arrays[4][self.row * 365 + day] = stock.min(u32::MAX as usize) as u32;
+    }
+}
+
+/// Simulate `count` years of `sim` and write every day of them to a new HDF5 file at `path`
+///
+/// The file's attributes say how to make it again: the simulation's JSON config, the seed
+/// (as text, since HDF5 integers stop at 2^63), the starting quantity and count. Returns the
+/// totals, which match repeat_simulate_demand for the same seed.
+pub fn export_hdf5(
+    py: Python<'_>,
+    sim: &Simulation,
+    path: &str,
+    starting_quantity: usize,
+    count: usize,
+    chunk: usize,
+) -> PyResult<Summary> {
+    if count == 0 || chunk == 0 {
+        return Err(ValueError::py_err("count and chunk must be at least 1"));
+    }
+    let h5py = py.import("h5py")?;
+    let numpy = py.import("numpy")?;
+    let seed = sim.call_seed();
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let file = h5py.call1("File", (path, "w"))?;
+    let attrs = file.getattr("attrs")?;
+    let config =
+        serde_json::to_string(sim).map_err(|error| ValueError::py_err(error.to_string()))?;
+    attrs.set_item("config", config)?;
+    attrs.set_item("seed", seed.to_string())?;
+    attrs.set_item("starting_quantity", starting_quantity)?;
+    attrs.set_item("count", count)?;
+    let mut datasets = vec![];
+    for name in DATASETS.iter() {
+        let options = PyDict::new(py);
+        options.set_item("shape", (count, 365))?;
+        options.set_item("dtype", "u4")?;
+        options.set_item("chunks", (chunk.min(count), 365))?;
+        options.set_item("compression", "gzip")?;
+        datasets.push(file.call_method("create_dataset", (*name,), Some(options))?);
+    }
+
+    let (mut st, mut ss, mut ft, mut fs) = (0, 0, 0, 0);
+    for start in (0..count).step_by(chunk) {
+        let rows = chunk.min(count - start);
+        let mut days = Days::new(rows);
+        py.allow_threads(|| {
+            for row in 0..rows {
+                days.row = row;
+                let year = sim.simulate_year(starting_quantity, &mut rng, &mut days);
+                st += year.successful_transactions;
+                ss += year.successful_sales;
+                ft += year.failed_transactions;
+                fs += year.failed_sales;
+            }
+        });
+        let rows_written = PySlice::new(py, start as isize, (start + rows) as isize, 1);
+        for (dataset, array) in datasets.iter().zip(days.arrays.iter()) {
+            let bytes: Vec<u8> = array.iter().flat_map(|value| value.to_ne_bytes()).collect();
+            let array = numpy
+                .call1("frombuffer", (PyBytes::new(py, &bytes), "=u4"))?
+                .call_method1("reshape", (rows, 365))?;
+            dataset.set_item(rows_written, array)?;
+        }
+    }
+    file.call_method0("close")?;
+    Ok(summarize(st, ss, ft, fs))
+}
@@ -0,0 +1,88 @@
+/// Running mean and variance, without keeping the values around
+///
+/// This is Welford's online algorithm, which is numerically stable even after hundreds of
+/// millions of values, unlike the textbook sum-of-squares approach. Summaries computed
+/// apart, like rustoclsim's one per work item, merge with Chan et al.'s parallel variant.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Welford {
+    count: u64,
+    mean: f64,
+    m2: f64,
+}
+
+impl Welford {
+    pub fn new() -> Welford {
+        Welford::default()
+    }
+
+    /// Rebuild the summary of a batch that was computed somewhere else
+    pub fn from_parts(count: u64, mean: f64, m2: f64) -> Welford {
+        Welford { count, mean, m2 }
+    }
+
+    /// Add one value
+    pub fn push(&mut self, x: f64) {
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    /// Fold another summary into this one, as if we had seen all of its values too
+    pub fn merge(&mut self, other: &Welford) {
+        if other.count == 0 {
+            return;
+        }
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+        self.mean += delta * other.count as f64 / count as f64;
+        self.m2 +=
+            other.m2 + delta * delta * (self.count as f64 * other.count as f64) / count as f64;
+        self.count = count;
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Width of the normal-approximation 95% confidence interval for the mean
+    pub fn ci_width(&self) -> f64 {
+        if self.count < 2 {
+            f64::INFINITY
+        } else {
+            2.0 * 1.96 * (self.variance() / self.count as f64).sqrt()
+        }
+    }
+
+    /// Sample variance (dividing by n-1)
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 {
+            0.0
+        } else {
+            self.m2 / (self.count - 1) as f64
+        }
+    }
+}
+
+#[test]
+fn test_merge() {
+    // Merging two halves gives the same summary as pushing every value into one
+    let values: Vec<f64> = (0..1000)
+        .map(|i| ((i * 7919) % 1000) as f64 / 10.0)
+        .collect();
+    let mut whole = Welford::new();
+    values.iter().for_each(|&x| whole.push(x));
+    let (mut first, mut second) = (Welford::new(), Welford::new());
+    values[..300].iter().for_each(|&x| first.push(x));
+    values[300..].iter().for_each(|&x| second.push(x));
+    first.merge(&second);
+    assert_eq!(first.count, whole.count);
+    assert!((first.mean() - whole.mean()).abs() < 1e-9);
+    assert!((first.variance() - whole.variance()).abs() < 1e-9);
+    // An empty summary changes nothing, either way around
+    first.merge(&Welford::new());
+    assert!((first.mean() - whole.mean()).abs() < 1e-9);
+    let mut empty = Welford::new();
+    empty.merge(&whole);
+    assert!((empty.variance() - whole.variance()).abs() < 1e-9);
+}
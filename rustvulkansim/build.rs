@@ -0,0 +1,13 @@
+//! Compile the GLSL kernel to SPIR-V, which is what Vulkan takes
+use std::path::Path;
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/simulation.comp");
+    let source = std::fs::read_to_string("src/simulation.comp").expect("Can't read src/simulation.comp");
+    let compiler = shaderc::Compiler::new().expect("Can't start the shader compiler");
+    let spirv = compiler
+        .compile_into_spirv(&source, shaderc::ShaderKind::Compute, "simulation.comp", "main", None)
+        .unwrap_or_else(|error| panic!("simulation.comp doesn't compile: {}", error));
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    std::fs::write(Path::new(&out_dir).join("simulation.spv"), spirv.as_binary_u8()).unwrap();
+}
@@ -0,0 +1,812 @@
+use ash::vk;
+use pyo3::basic::CompareOp;
+use pyo3::exceptions::{OverflowError, RuntimeError, ValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyDict};
+use pyo3::{PyNativeType, PyObjectProtocol};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rustsim::gpu::{self, work_item_seeds};
+use rustsim::Summary;
+use std::collections::hash_map::DefaultHasher;
+use std::convert::TryFrom;
+use std::ffi::CStr;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+/// The kernel, compiled from simulation.comp by build.rs
+const SPIRV: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/simulation.spv"));
+
+/// The longest lead time the kernel has room for (TRUCK_SLOTS in simulation.comp)
+const MAX_LEAD_TIME: usize = 64;
+
+/// How many threads share the work, like the work items in rustoclsim
+const THREADS: usize = 1000;
+
+/// Threads per workgroup (local_size_x in simulation.comp)
+const WORKGROUP_SIZE: usize = 64;
+
+/// (starting quantity, safety stock, order quantity), the parameters the kernel takes per run
+type Policy = (usize, usize, usize);
+
+/// The push constants, laid out exactly like Params in simulation.comp
+#[repr(C)]
+struct Params {
+    starting_quantity: i32,
+    lead_time: u32,
+    safety_stock: i32,
+    order_quantity: i32,
+    precomp_size: u32,
+    samples: u32,
+    threads: u32,
+}
+
+/// Simulation parameters
+///
+/// This is the Vulkan twin of rustoclsim.Simulation, for machines whose drivers have Vulkan
+/// but no OpenCL. It runs the same simulation with the same random streams, so a seeded run
+/// should agree with rustoclsim's exactly, given the same number of threads.
+#[pyclass(module = "rustvulkansim")]
+struct Simulation {
+    safety_stock: usize,
+    lead_time: usize,
+    order_quantity: usize,
+    job_lot_zipf: f64,
+    itemwise_traffic_zipf: f64,
+    /// Shared between the copies with_params() makes, since they're 64 MB each
+    job_lot_zipf_precomp: Arc<Vec<u32>>,
+    itemwise_traffic_zipf_precomp: Arc<Vec<u32>>,
+    /// If set, the zipf tables and the thread seeds come from a generator seeded with
+    /// this, so the same call on the same device gives identical results
+    #[pyo3(get)]
+    seed: Option<u64>,
+    /// What the zipf tables were drawn with: the seed, or a random one in its place
+    table_seed: u64,
+    /// (starting quantity, count, call seed) of the last repeat_simulate_demand
+    last_call: Mutex<Option<(usize, usize, u64)>>,
+}
+
+/// Simulation implementation
+///
+/// The following methods are all available from Python
+#[pymethods]
+impl Simulation {
+    /// Implementation of python Simulation.__init__() (wraps rust Simulation::checked())
+    ///
+    /// Every argument is a keyword with a default, like Simulation(safety_stock=20). Raises
+    /// ValueError naming the argument that's out of range, including a lead time longer than
+    /// the kernel can track. The zipf exponents default to 2.75 and 4.0.
+    #[new]
+    #[args("*", safety_stock = "10", lead_time = "3", order_quantity = "7", job_lot_zipf = "None",
+        itemwise_traffic_zipf = "None", seed = "None")]
+    fn init(
+        obj: &PyRawObject,
+        safety_stock: isize,
+        lead_time: isize,
+        order_quantity: isize,
+        job_lot_zipf: Option<f64>,
+        itemwise_traffic_zipf: Option<f64>,
+        seed: Option<u64>,
+    ) -> PyResult<()> {
+        let sim = Simulation::checked(safety_stock, lead_time, order_quantity, job_lot_zipf, itemwise_traffic_zipf, seed)
+            .map_err(ValueError::py_err)?;
+        obj.init(sim);
+        Ok(())
+    }
+
+    /// A copy with some parameters changed, given as keywords like the constructor's
+    ///
+    /// The zipf tables are only drawn again if an exponent or the seed changes.
+    #[args(params = "**")]
+    fn with_params(&self, params: Option<&PyDict>) -> PyResult<Simulation> {
+        let mut safety_stock = self.safety_stock as isize;
+        let mut lead_time = self.lead_time as isize;
+        let mut order_quantity = self.order_quantity as isize;
+        let mut job_lot_zipf = self.job_lot_zipf;
+        let mut itemwise_traffic_zipf = self.itemwise_traffic_zipf;
+        let mut seed = self.seed;
+        for (name, value) in params.into_iter().flatten() {
+            match name.extract()? {
+                "safety_stock" => safety_stock = value.extract()?,
+                "lead_time" => lead_time = value.extract()?,
+                "order_quantity" => order_quantity = value.extract()?,
+                "job_lot_zipf" => job_lot_zipf = value.extract()?,
+                "itemwise_traffic_zipf" => itemwise_traffic_zipf = value.extract()?,
+                "seed" => seed = value.extract()?,
+                name => return Err(ValueError::py_err(
+                    format!("with_params() got an unexpected keyword argument {:?}", name))),
+            }
+        }
+        if job_lot_zipf == self.job_lot_zipf && itemwise_traffic_zipf == self.itemwise_traffic_zipf && seed == self.seed {
+            let (safety_stock, lead_time, order_quantity) = Simulation::check(safety_stock, lead_time, order_quantity, job_lot_zipf, itemwise_traffic_zipf)
+                .map_err(ValueError::py_err)?;
+            return Ok(Simulation {
+                safety_stock,
+                lead_time,
+                order_quantity,
+                job_lot_zipf_precomp: self.job_lot_zipf_precomp.clone(),
+                itemwise_traffic_zipf_precomp: self.itemwise_traffic_zipf_precomp.clone(),
+                last_call: Mutex::new(None),
+                ..*self
+            });
+        }
+        Simulation::checked(safety_stock, lead_time, order_quantity, Some(job_lot_zipf), Some(itemwise_traffic_zipf), seed)
+            .map_err(ValueError::py_err)
+    }
+
+    /// Whether this simulation was given a seed, and so gives the same results every time
+    #[getter]
+    fn deterministic(&self) -> bool {
+        self.seed.is_some()
+    }
+
+    /// Everything needed to re-run this simulation: the backend, device, crate version, seed,
+    /// and every parameter, as a dict
+    ///
+    /// An unseeded simulation reports the seed its tables were drawn with, so
+    /// Simulation(seed=metadata["seed"], ...) draws the same ones.
+    fn metadata(&self, py: Python<'_>) -> PyResult<PyObject> {
+        Ok(self.describe(py)?.to_object(py))
+    }
+
+    /// metadata(), plus the "starting_quantity", "count" and "call_seed" of the last
+    /// repeat_simulate_demand, or None before the first
+    fn last_metadata(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let last_call = *self.last_call.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let (starting_quantity, count, seed) = match last_call {
+            Some(call) => call,
+            None => return Ok(py.None()),
+        };
+        let dict = self.describe(py)?;
+        dict.set_item("method", "repeat_simulate_demand")?;
+        dict.set_item("starting_quantity", starting_quantity)?;
+        dict.set_item("count", count)?;
+        dict.set_item("call_seed", seed)?;
+        Ok(dict.to_object(py))
+    }
+
+    /// Run `count` simulated years on the GPU and add them up
+    ///
+    /// The work is split evenly over 1000 threads, and whatever doesn't split evenly runs as
+    /// one more year on that many of them. `seed` seeds the threads in place of the
+    /// simulation's own seed, to re-run a call from its last_metadata(). Raises RuntimeError
+    /// if there's no usable Vulkan device, and OverflowError if the totals don't fit in this
+    /// platform's integers.
+    #[args(seed = "None")]
+    fn repeat_simulate_demand(&self, py: Python<'_>, starting_quantity: usize, count: usize, seed: Option<u64>) -> PyResult<Summary> {
+        let seed = seed.or(self.seed).unwrap_or_else(rand::random);
+        *self.last_call.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some((starting_quantity, count, seed));
+        let policy = (starting_quantity, self.safety_stock, self.order_quantity);
+        let totals = py.allow_threads(|| self.vulkan_run(&[policy], count, seed)).map_err(RuntimeError::py_err)?;
+        checked_summary(totals[0])
+    }
+
+    /// Like repeat_simulate_demand, once for each of `starting_quantities`, for an
+    /// initial-buy curve in one call
+    ///
+    /// Every starting quantity gets the same thread seeds, so they all see the same customers
+    /// and the differences along the curve are down to the stock rather than luck.
+    fn repeat_simulate_demand_batch(&self, py: Python<'_>, starting_quantities: Vec<usize>, count: usize) -> PyResult<Vec<Summary>> {
+        let policies: Vec<Policy> = starting_quantities.iter()
+            .map(|&starting_quantity| (starting_quantity, self.safety_stock, self.order_quantity))
+            .collect();
+        self.sweep(py, &policies, count)
+    }
+
+    /// Like repeat_simulate_demand, once for each of `policies`, a list of
+    /// (starting_quantity, safety_stock, order_quantity) tuples
+    ///
+    /// Like repeat_simulate_demand_batch, every policy sees the same customers. The lead time
+    /// and zipf exponents are this simulation's. Raises ValueError for a policy out of range.
+    fn repeat_simulate_demand_sweep(&self, py: Python<'_>, policies: Vec<(usize, isize, isize)>, count: usize) -> PyResult<Vec<Summary>> {
+        let policies = policies.into_iter()
+            .map(|(starting_quantity, safety_stock, order_quantity)| {
+                let (safety_stock, _, order_quantity) = Simulation::check(safety_stock, self.lead_time as isize, order_quantity, self.job_lot_zipf, self.itemwise_traffic_zipf)?;
+                Ok((starting_quantity, safety_stock, order_quantity))
+            })
+            .collect::<Result<Vec<Policy>, String>>()
+            .map_err(ValueError::py_err)?;
+        self.sweep(py, &policies, count)
+    }
+}
+
+#[pyproto]
+impl<'p> PyObjectProtocol<'p> for Simulation {
+    /// Simulations are equal when they have the same parameters and seed
+    fn __richcmp__(&self, other: &PyAny, op: CompareOp) -> PyResult<PyObject> {
+        let py = other.py();
+        let other = match other.downcast_ref::<Simulation>() {
+            Ok(other) => other,
+            Err(_) => return Ok(py.NotImplemented()),
+        };
+        Ok(match op {
+            CompareOp::Eq => (self.config() == other.config()).into_py(py),
+            CompareOp::Ne => (self.config() != other.config()).into_py(py),
+            _ => py.NotImplemented(),
+        })
+    }
+
+    fn __hash__(&self) -> PyResult<isize> {
+        let mut hasher = DefaultHasher::new();
+        self.config().hash(&mut hasher);
+        Ok(hasher.finish() as isize)
+    }
+}
+
+/// Simulation Implementation, continued
+///
+/// This group doesn't mention pymethods, and isn't visible from Python
+impl Simulation {
+    /// A simulation, or why these parameters can't be simulated here
+    fn checked(
+        safety_stock: isize,
+        lead_time: isize,
+        order_quantity: isize,
+        job_lot_zipf: Option<f64>,
+        itemwise_traffic_zipf: Option<f64>,
+        seed: Option<u64>,
+    ) -> Result<Simulation, String> {
+        let (safety_stock, lead_time, order_quantity) = Simulation::check(
+            safety_stock, lead_time, order_quantity,
+            job_lot_zipf.unwrap_or(2.75), itemwise_traffic_zipf.unwrap_or(4.0))?;
+        Ok(Simulation::new(safety_stock, lead_time, order_quantity, job_lot_zipf, itemwise_traffic_zipf, seed))
+    }
+
+    /// The counts, if rustsim would simulate these parameters and the kernel has room for
+    /// the lead time
+    fn check(
+        safety_stock: isize,
+        lead_time: isize,
+        order_quantity: isize,
+        job_lot_zipf: f64,
+        itemwise_traffic_zipf: f64,
+    ) -> Result<(usize, usize, usize), String> {
+        let count = |name: &str, value: isize| usize::try_from(value)
+            .map_err(|_| format!("{} must be >= 0, not {}", name, value));
+        let counts = (count("safety_stock", safety_stock)?, count("lead_time", lead_time)?, count("order_quantity", order_quantity)?);
+        let (safety_stock, lead_time, order_quantity) = counts;
+        rustsim::Simulation::new(safety_stock, lead_time, order_quantity, Some(job_lot_zipf), Some(itemwise_traffic_zipf), None)
+            .check_parameters()?;
+        if lead_time > MAX_LEAD_TIME {
+            return Err(format!("lead_time must be <= {}, not {}", MAX_LEAD_TIME, lead_time));
+        }
+        Ok(counts)
+    }
+
+    /// A simulation with the default zipf exponents for anything left as None, without
+    /// checking the parameters
+    fn new(
+        safety_stock: usize,
+        lead_time: usize,
+        order_quantity: usize,
+        job_lot_zipf: Option<f64>,
+        itemwise_traffic_zipf: Option<f64>,
+        seed: Option<u64>,
+    ) -> Simulation {
+        let job_lot_zipf = job_lot_zipf.unwrap_or(2.75);
+        let itemwise_traffic_zipf = itemwise_traffic_zipf.unwrap_or(4.0);
+        let table_seed = seed.unwrap_or_else(rand::random);
+        let mut rng = StdRng::seed_from_u64(table_seed);
+        Simulation {
+            safety_stock,
+            lead_time,
+            order_quantity,
+            job_lot_zipf,
+            itemwise_traffic_zipf,
+            job_lot_zipf_precomp: Arc::new(gpu::zipf_table(job_lot_zipf, &mut rng)),
+            itemwise_traffic_zipf_precomp: Arc::new(gpu::zipf_table(itemwise_traffic_zipf, &mut rng)),
+            seed,
+            table_seed,
+            last_call: Mutex::new(None),
+        }
+    }
+
+    /// Everything that makes two simulations the same, for == and hash()
+    fn config(&self) -> (usize, usize, usize, u64, u64, Option<u64>) {
+        (self.safety_stock, self.lead_time, self.order_quantity,
+            self.job_lot_zipf.to_bits(), self.itemwise_traffic_zipf.to_bits(), self.seed)
+    }
+
+    /// The dict behind metadata() and last_metadata()
+    fn describe<'p>(&self, py: Python<'p>) -> PyResult<&'p PyDict> {
+        let dict = PyDict::new(py);
+        dict.set_item("backend", "vulkan")?;
+        dict.set_item("device", Vulkan::new().map(|vulkan| vulkan.device_name.clone()).ok())?;
+        dict.set_item("version", env!("CARGO_PKG_VERSION"))?;
+        dict.set_item("seed", self.table_seed)?;
+        let params = PyDict::new(py);
+        params.set_item("safety_stock", self.safety_stock)?;
+        params.set_item("lead_time", self.lead_time)?;
+        params.set_item("order_quantity", self.order_quantity)?;
+        params.set_item("job_lot_zipf", self.job_lot_zipf)?;
+        params.set_item("itemwise_traffic_zipf", self.itemwise_traffic_zipf)?;
+        dict.set_item("params", params)?;
+        Ok(dict)
+    }
+
+    /// Run every policy with the same seed, and summarize each
+    fn sweep(&self, py: Python<'_>, policies: &[Policy], count: usize) -> PyResult<Vec<Summary>> {
+        let seed = self.seed.unwrap_or_else(rand::random);
+        let totals = py.allow_threads(|| self.vulkan_run(policies, count, seed)).map_err(RuntimeError::py_err)?;
+        totals.into_iter().map(checked_summary).collect()
+    }
+
+    /// The Vulkan side of repeat_simulate_demand: successful transactions, successful sales,
+    /// failed transactions and failed sales, summed over every thread, for each policy
+    ///
+    /// Vulkan makes you spell out everything OpenCL does for you: where each buffer's memory
+    /// comes from, how the buffers are bound to the shader, and the command buffers that fill
+    /// them and run it. The steps are still the same, though: copy the inputs over, run the
+    /// kernel, and copy the outputs back. Every policy's threads are seeded alike from
+    /// `seed`, and draw the same seeds rustoclsim's work items would: the main launch's, then
+    /// the leftover launch's.
+    fn vulkan_run(&self, policies: &[Policy], count: usize, seed: u64) -> Result<Vec<[u128; 4]>, String> {
+        let mut vulkan = Vulkan::new()?;
+        // Bindings 0 to 6, in the shader's order: the seeds, which change every dispatch, the
+        // tables, which don't, and the four counters
+        vulkan.host_buffer(THREADS * std::mem::size_of::<u32>())?;
+        vulkan.upload(as_bytes(&self.job_lot_zipf_precomp))?;
+        vulkan.upload(as_bytes(&self.itemwise_traffic_zipf_precomp))?;
+        for _ in 0..4 {
+            vulkan.host_buffer(THREADS * std::mem::size_of::<u64>())?;
+        }
+        vulkan.pipeline()?;
+
+        let (samples, remainder) = (count / THREADS, count % THREADS);
+        policies.iter().map(|&(starting_quantity, safety_stock, order_quantity)| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let params = |samples: usize, threads: usize| Params {
+                starting_quantity: starting_quantity as i32,
+                lead_time: self.lead_time as u32,
+                safety_stock: safety_stock as i32,
+                order_quantity: order_quantity as i32,
+                precomp_size: self.itemwise_traffic_zipf_precomp.len() as u32,
+                samples: samples as u32,
+                threads: threads as u32,
+            };
+            // The device has no randomness of its own, so each thread gets a seed from us
+            let mut totals = vulkan.dispatch(&work_item_seeds(&mut rng, THREADS), &params(samples, THREADS))?;
+            if remainder > 0 {
+                let seeds = work_item_seeds(&mut rng, THREADS);
+                let tail = vulkan.dispatch(&seeds[..remainder], &params(1, remainder))?;
+                for (total, more) in totals.iter_mut().zip(&tail) {
+                    *total += more;
+                }
+            }
+            Ok(totals)
+        }).collect()
+    }
+}
+
+/// Everything we create in Vulkan, so that dropping it cleans up in the right order, even
+/// when we bail out halfway
+///
+/// Vulkan ignores destroying null handles, so whatever we didn't get around to creating is
+/// harmless to destroy.
+struct Vulkan {
+    // The loader has to outlive everything else
+    _entry: ash::Entry,
+    instance: ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    device: ash::Device,
+    device_name: String,
+    queue_family: u32,
+    queue: vk::Queue,
+    /// Storage buffers, in binding order, with their memory
+    buffers: Vec<(vk::Buffer, vk::DeviceMemory)>,
+    shader: vk::ShaderModule,
+    set_layout: vk::DescriptorSetLayout,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    /// One command buffer, recorded again for every submission, and the fence that says the
+    /// last one is done
+    command_pool: vk::CommandPool,
+    commands: vk::CommandBuffer,
+    fence: vk::Fence,
+}
+
+impl Vulkan {
+    /// Find a device that can run the kernel, and open it
+    ///
+    /// We take the first discrete GPU if there is one, and otherwise the first device of any
+    /// kind, as long as it can do compute and 64-bit integers.
+    fn new() -> Result<Vulkan, String> {
+        let entry = unsafe { ash::Entry::load() }.map_err(|error| format!("Can't load Vulkan: {}", error))?;
+        let app_info = vk::ApplicationInfo::builder().api_version(vk::make_api_version(0, 1, 0, 0));
+        let instance = unsafe {
+            entry.create_instance(&vk::InstanceCreateInfo::builder().application_info(&app_info), None)
+        }.map_err(vk_error)?;
+
+        let candidates = unsafe { instance.enumerate_physical_devices() }.map_err(vk_error)?;
+        let usable = |&physical_device: &vk::PhysicalDevice| -> Option<(vk::PhysicalDevice, u32)> {
+            let features = unsafe { instance.get_physical_device_features(physical_device) };
+            if features.shader_int64 != vk::TRUE {
+                return None;
+            }
+            let families = unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
+            let family = families.iter().position(|family| family.queue_flags.contains(vk::QueueFlags::COMPUTE))?;
+            Some((physical_device, family as u32))
+        };
+        let is_discrete = |&(physical_device, _): &(vk::PhysicalDevice, u32)| unsafe {
+            instance.get_physical_device_properties(physical_device).device_type == vk::PhysicalDeviceType::DISCRETE_GPU
+        };
+        let usable: Vec<_> = candidates.iter().filter_map(usable).collect();
+        let (physical_device, queue_family) = match usable.iter().copied().find(is_discrete).or_else(|| usable.first().copied()) {
+            Some(choice) => choice,
+            None => {
+                unsafe { instance.destroy_instance(None) };
+                return Err("There's no Vulkan device that can do compute with 64-bit integers".to_string());
+            }
+        };
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        let device_name = unsafe { CStr::from_ptr(properties.device_name.as_ptr()) }.to_string_lossy().into_owned();
+
+        let priorities = [1.0];
+        let queue_info = vk::DeviceQueueCreateInfo::builder()
+            .queue_family_index(queue_family)
+            .queue_priorities(&priorities);
+        let features = vk::PhysicalDeviceFeatures::builder().shader_int64(true);
+        let device_info = vk::DeviceCreateInfo::builder()
+            .queue_create_infos(std::slice::from_ref(&queue_info))
+            .enabled_features(&features);
+        let device = match unsafe { instance.create_device(physical_device, &device_info, None) } {
+            Ok(device) => device,
+            Err(error) => {
+                unsafe { instance.destroy_instance(None) };
+                return Err(vk_error(error));
+            }
+        };
+        let queue = unsafe { device.get_device_queue(queue_family, 0) };
+        let mut vulkan = Vulkan {
+            _entry: entry,
+            instance,
+            physical_device,
+            device,
+            device_name,
+            queue_family,
+            queue,
+            buffers: vec![],
+            shader: vk::ShaderModule::null(),
+            set_layout: vk::DescriptorSetLayout::null(),
+            pipeline_layout: vk::PipelineLayout::null(),
+            pipeline: vk::Pipeline::null(),
+            descriptor_pool: vk::DescriptorPool::null(),
+            descriptor_set: vk::DescriptorSet::null(),
+            command_pool: vk::CommandPool::null(),
+            commands: vk::CommandBuffer::null(),
+            fence: vk::Fence::null(),
+        };
+
+        // From here on Drop cleans up whatever we made
+        let device = &vulkan.device;
+        vulkan.command_pool = unsafe {
+            device.create_command_pool(&vk::CommandPoolCreateInfo::builder()
+                .queue_family_index(vulkan.queue_family)
+                .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER), None)
+        }.map_err(vk_error)?;
+        vulkan.commands = unsafe {
+            device.allocate_command_buffers(&vk::CommandBufferAllocateInfo::builder()
+                .command_pool(vulkan.command_pool)
+                .level(vk::CommandBufferLevel::PRIMARY)
+                .command_buffer_count(1))
+        }.map_err(vk_error)?[0];
+        vulkan.fence = unsafe { device.create_fence(&vk::FenceCreateInfo::builder(), None) }.map_err(vk_error)?;
+        Ok(vulkan)
+    }
+
+    /// Make a buffer of `size` bytes for `usage`, in memory with `wanted` properties, and add
+    /// it to the end of the bindings
+    fn buffer(&mut self, size: usize, usage: vk::BufferUsageFlags, wanted: vk::MemoryPropertyFlags) -> Result<(vk::Buffer, vk::DeviceMemory), String> {
+        let info = vk::BufferCreateInfo::builder()
+            .size(size.max(1) as u64)
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+        let buffer = unsafe { self.device.create_buffer(&info, None) }.map_err(vk_error)?;
+        let requirements = unsafe { self.device.get_buffer_memory_requirements(buffer) };
+        let memory_properties = unsafe { self.instance.get_physical_device_memory_properties(self.physical_device) };
+        let memory_type = (0..memory_properties.memory_type_count).find(|&index| {
+            requirements.memory_type_bits & (1 << index) != 0
+                && memory_properties.memory_types[index as usize].property_flags.contains(wanted)
+        });
+        let memory_type = match memory_type {
+            Some(memory_type) => memory_type,
+            None => {
+                unsafe { self.device.destroy_buffer(buffer, None) };
+                return Err(format!("The device has no {:?} memory for this buffer", wanted));
+            }
+        };
+        let allocate = vk::MemoryAllocateInfo::builder()
+            .allocation_size(requirements.size)
+            .memory_type_index(memory_type);
+        let memory = match unsafe { self.device.allocate_memory(&allocate, None) } {
+            Ok(memory) => memory,
+            Err(error) => {
+                unsafe { self.device.destroy_buffer(buffer, None) };
+                return Err(vk_error(error));
+            }
+        };
+        // From here on Drop takes care of both
+        self.buffers.push((buffer, memory));
+        unsafe { self.device.bind_buffer_memory(buffer, memory, 0) }.map_err(vk_error)?;
+        Ok((buffer, memory))
+    }
+
+    /// Add a storage buffer of `size` bytes the host can see, so "copying" to and from it is
+    /// just going through a pointer
+    ///
+    /// This is for what changes every dispatch, the seeds and the counters, which are small.
+    fn host_buffer(&mut self, size: usize) -> Result<(), String> {
+        let wanted = vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT;
+        self.buffer(size, vk::BufferUsageFlags::STORAGE_BUFFER, wanted)?;
+        Ok(())
+    }
+
+    /// Add a storage buffer in the device's own memory, holding a copy of `contents`
+    ///
+    /// This is for the zipf tables, which are 64 MB each and read over and over by every
+    /// thread. On a discrete GPU, memory the host can see is usually across the bus, so they
+    /// go through a staging buffer the host can see into memory the kernel reads quickly.
+    fn upload(&mut self, contents: &[u8]) -> Result<(), String> {
+        let usage = vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST;
+        let (buffer, _) = self.buffer(contents.len(), usage, vk::MemoryPropertyFlags::DEVICE_LOCAL)?;
+        let wanted = vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT;
+        let (staging, _) = self.buffer(contents.len(), vk::BufferUsageFlags::TRANSFER_SRC, wanted)?;
+        self.write(self.buffers.len() - 1, contents)?;
+        let region = vk::BufferCopy::builder().size(contents.len() as u64).build();
+        // Have the copy finish before any shader reads the table
+        let barrier = vk::BufferMemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::SHADER_READ)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .buffer(buffer)
+            .size(vk::WHOLE_SIZE)
+            .build();
+        self.submit(|device, commands| unsafe {
+            device.cmd_copy_buffer(commands, staging, buffer, &[region]);
+            device.cmd_pipeline_barrier(commands, vk::PipelineStageFlags::TRANSFER, vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::DependencyFlags::empty(), &[], &[barrier], &[]);
+        })?;
+        // The staging buffer is the last binding, and done with, so it doesn't stay one
+        let (staging, memory) = self.buffers.pop().unwrap();
+        unsafe {
+            self.device.destroy_buffer(staging, None);
+            self.device.free_memory(memory, None);
+        }
+        Ok(())
+    }
+
+    /// Build the pipeline around the buffers we have, and bind them to it
+    fn pipeline(&mut self) -> Result<(), String> {
+        let device = &self.device;
+        let words = ash::util::read_spv(&mut std::io::Cursor::new(SPIRV)).map_err(|error| error.to_string())?;
+        self.shader = unsafe { device.create_shader_module(&vk::ShaderModuleCreateInfo::builder().code(&words), None) }
+            .map_err(vk_error)?;
+
+        // Every buffer is a storage buffer, bound in the order we made them
+        let bindings: Vec<_> = (0..self.buffers.len()).map(|binding| {
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(binding as u32)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                .build()
+        }).collect();
+        self.set_layout = unsafe {
+            device.create_descriptor_set_layout(&vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings), None)
+        }.map_err(vk_error)?;
+        let push_constants = vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::COMPUTE)
+            .size(std::mem::size_of::<Params>() as u32)
+            .build();
+        let set_layouts = [self.set_layout];
+        self.pipeline_layout = unsafe {
+            device.create_pipeline_layout(&vk::PipelineLayoutCreateInfo::builder()
+                .set_layouts(&set_layouts)
+                .push_constant_ranges(std::slice::from_ref(&push_constants)), None)
+        }.map_err(vk_error)?;
+        let entry_point = CStr::from_bytes_with_nul(b"main\0").unwrap();
+        let stage = vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::COMPUTE)
+            .module(self.shader)
+            .name(entry_point);
+        let pipeline_info = vk::ComputePipelineCreateInfo::builder()
+            .stage(stage.build())
+            .layout(self.pipeline_layout)
+            .build();
+        self.pipeline = unsafe { device.create_compute_pipelines(vk::PipelineCache::null(), &[pipeline_info], None) }
+            .map_err(|(_, error)| vk_error(error))?[0];
+
+        // Point the bindings at the buffers
+        let pool_size = vk::DescriptorPoolSize::builder()
+            .ty(vk::DescriptorType::STORAGE_BUFFER)
+            .descriptor_count(self.buffers.len() as u32)
+            .build();
+        self.descriptor_pool = unsafe {
+            device.create_descriptor_pool(&vk::DescriptorPoolCreateInfo::builder()
+                .max_sets(1)
+                .pool_sizes(std::slice::from_ref(&pool_size)), None)
+        }.map_err(vk_error)?;
+        self.descriptor_set = unsafe {
+            device.allocate_descriptor_sets(&vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(self.descriptor_pool)
+                .set_layouts(&set_layouts))
+        }.map_err(vk_error)?[0];
+        let buffer_infos: Vec<_> = self.buffers.iter().map(|&(buffer, _)| {
+            vk::DescriptorBufferInfo::builder().buffer(buffer).offset(0).range(vk::WHOLE_SIZE).build()
+        }).collect();
+        let writes: Vec<_> = buffer_infos.iter().enumerate().map(|(binding, info)| {
+            vk::WriteDescriptorSet::builder()
+                .dst_set(self.descriptor_set)
+                .dst_binding(binding as u32)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .buffer_info(std::slice::from_ref(info))
+                .build()
+        }).collect();
+        unsafe { device.update_descriptor_sets(&writes, &[]) };
+        Ok(())
+    }
+
+    /// Run one thread for each of `seeds`, wait for them, and add up their counters
+    fn dispatch(&self, seeds: &[u32], params: &Params) -> Result<[u128; 4], String> {
+        self.write(0, as_bytes(seeds))?;
+        let params_bytes = unsafe {
+            std::slice::from_raw_parts(params as *const Params as *const u8, std::mem::size_of::<Params>())
+        };
+        // Have the counters written before the host reads them
+        let barrier = vk::MemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+            .dst_access_mask(vk::AccessFlags::HOST_READ)
+            .build();
+        let workgroups = seeds.len().div_ceil(WORKGROUP_SIZE);
+        self.submit(|device, commands| unsafe {
+            device.cmd_bind_pipeline(commands, vk::PipelineBindPoint::COMPUTE, self.pipeline);
+            device.cmd_bind_descriptor_sets(commands, vk::PipelineBindPoint::COMPUTE, self.pipeline_layout, 0, &[self.descriptor_set], &[]);
+            device.cmd_push_constants(commands, self.pipeline_layout, vk::ShaderStageFlags::COMPUTE, 0, params_bytes);
+            device.cmd_dispatch(commands, workgroups as u32, 1, 1);
+            device.cmd_pipeline_barrier(commands, vk::PipelineStageFlags::COMPUTE_SHADER, vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(), &[barrier], &[], &[]);
+        })?;
+
+        // Add up in u128, for the same reason rustoclsim does: a thousand u64s might not fit
+        let mut totals = [0u128; 4];
+        for (index, total) in totals.iter_mut().enumerate() {
+            *total = self.read_u64s(3 + index, seeds.len())?.iter().map(|&x| u128::from(x)).sum();
+        }
+        Ok(totals)
+    }
+
+    /// Record the command buffer with `record`, submit it, and wait for it
+    fn submit(&self, record: impl FnOnce(&ash::Device, vk::CommandBuffer)) -> Result<(), String> {
+        let (device, commands) = (&self.device, self.commands);
+        let submit = vk::SubmitInfo::builder().command_buffers(std::slice::from_ref(&commands)).build();
+        unsafe {
+            device.begin_command_buffer(commands, &vk::CommandBufferBeginInfo::builder()
+                .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)).map_err(vk_error)?;
+            record(device, commands);
+            device.end_command_buffer(commands).map_err(vk_error)?;
+            device.reset_fences(&[self.fence]).map_err(vk_error)?;
+            device.queue_submit(self.queue, &[submit], self.fence).map_err(vk_error)?;
+            device.wait_for_fences(&[self.fence], true, u64::MAX).map_err(vk_error)?;
+        }
+        Ok(())
+    }
+
+    /// Copy `contents` into the start of the host-visible buffer at `binding`
+    fn write(&self, binding: usize, contents: &[u8]) -> Result<(), String> {
+        let (_, memory) = self.buffers[binding];
+        unsafe {
+            let mapped = self.device.map_memory(memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())
+                .map_err(vk_error)?;
+            std::ptr::copy_nonoverlapping(contents.as_ptr(), mapped as *mut u8, contents.len());
+            self.device.unmap_memory(memory);
+        }
+        Ok(())
+    }
+
+    /// Copy the first `count` u64s out of the host-visible buffer at `binding`, once the
+    /// kernel is done
+    fn read_u64s(&self, binding: usize, count: usize) -> Result<Vec<u64>, String> {
+        let (_, memory) = self.buffers[binding];
+        let size = (count * std::mem::size_of::<u64>()) as u64;
+        unsafe {
+            let mapped = self.device.map_memory(memory, 0, size, vk::MemoryMapFlags::empty()).map_err(vk_error)?;
+            let values = std::slice::from_raw_parts(mapped as *const u64, count).to_vec();
+            self.device.unmap_memory(memory);
+            Ok(values)
+        }
+    }
+}
+
+impl Drop for Vulkan {
+    fn drop(&mut self) {
+        unsafe {
+            // Don't pull anything out from under the device while it's still running
+            let _ = self.device.device_wait_idle();
+            self.device.destroy_fence(self.fence, None);
+            self.device.destroy_command_pool(self.command_pool, None);
+            self.device.destroy_descriptor_pool(self.descriptor_pool, None);
+            self.device.destroy_pipeline(self.pipeline, None);
+            self.device.destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device.destroy_descriptor_set_layout(self.set_layout, None);
+            self.device.destroy_shader_module(self.shader, None);
+            for &(buffer, memory) in &self.buffers {
+                self.device.destroy_buffer(buffer, None);
+                self.device.free_memory(memory, None);
+            }
+            self.device.destroy_device(None);
+            self.instance.destroy_instance(None);
+        }
+    }
+}
+
+/// Describe a Vulkan error for Python
+fn vk_error(error: vk::Result) -> String {
+    format!("Vulkan failed: {}", error)
+}
+
+/// The bytes of a u32 slice, for copying into device memory
+fn as_bytes(values: &[u32]) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(values.as_ptr() as *const u8, std::mem::size_of_val(values)) }
+}
+
+/// The totals as a Summary, or OverflowError rather than totals that are quietly wrong
+fn checked_summary(totals: [u128; 4]) -> PyResult<Summary> {
+    gpu::summarize_totals(totals).ok_or_else(|| OverflowError::py_err(
+        "The totals are too large for this platform's integers; run fewer repetitions per call"))
+}
+
+/// This module is a python module implemented in Rust.
+#[pymodule]
+fn rustvulkansim(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<Simulation>()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_check() {
+    // rustsim's checks, and the kernel's own limit on the lead time
+    assert!(Simulation::check(10, 3, 7, 2.75, 4.0).is_ok());
+    assert!(Simulation::check(-1, 3, 7, 2.75, 4.0).unwrap_err().contains("safety_stock"));
+    assert!(Simulation::check(10, 0, 7, 2.75, 4.0).unwrap_err().contains("lead_time"));
+    assert!(Simulation::check(10, 3, 0, 2.75, 4.0).unwrap_err().contains("order_quantity"));
+    assert!(Simulation::check(10, 3, 7, f64::NAN, 4.0).unwrap_err().contains("job_lot_zipf"));
+    assert!(Simulation::check(10, MAX_LEAD_TIME as isize + 1, 7, 2.75, 4.0).unwrap_err().contains("lead_time"));
+}
+
+#[test]
+fn test_seeded() {
+    // The same seed gives the same years, and another gives different ones
+    let sim = Simulation::new(10, 3, 7, None, None, Some(1));
+    let policy = [(10, 10, 7)];
+    let first = sim.vulkan_run(&policy, 10_000, 5).expect("Vulkan failed");
+    assert_eq!(first, sim.vulkan_run(&policy, 10_000, 5).expect("Vulkan failed"));
+    assert_ne!(first, sim.vulkan_run(&policy, 10_000, 6).expect("Vulkan failed"));
+}
+
+#[test]
+fn test_remainder() {
+    // Fewer years than threads still run, and a year over a multiple of them runs on top of
+    // the same main launch. Every day has customers, so every year has transactions.
+    let sim = Simulation::new(10, 3, 7, None, None, Some(1));
+    let few = sim.vulkan_run(&[(10, 10, 7)], 999, 5).expect("Vulkan failed")[0];
+    assert!(few[0] + few[2] > 0);
+    let even = sim.vulkan_run(&[(10, 10, 7)], 1000, 5).expect("Vulkan failed")[0];
+    let more = sim.vulkan_run(&[(10, 10, 7)], 1001, 5).expect("Vulkan failed")[0];
+    assert!(more.iter().zip(&even).all(|(more, even)| more >= even));
+    assert!(more[0] + more[2] > even[0] + even[2]);
+}
+
+#[test]
+fn test_matches_cpu() {
+    // Close to rustsim's fill rates, which aren't drawn the same way so can't match exactly
+    let sim = Simulation::new(10, 3, 7, None, None, Some(1));
+    let totals = sim.vulkan_run(&[(10, 10, 7)], 100_000, 2).expect("Vulkan failed")[0];
+    let (_, _, _, _, transaction_rate, unit_rate) = gpu::summarize_totals(totals).unwrap();
+    let cpu = rustsim::Simulation::new(10, 3, 7, None, None, Some(2)).repeat_summary(10, 100_000);
+    assert!((transaction_rate - cpu.4).abs() < 0.01, "{} vs {}", transaction_rate, cpu.4);
+    assert!((unit_rate - cpu.5).abs() < 0.01, "{} vs {}", unit_rate, cpu.5);
+}
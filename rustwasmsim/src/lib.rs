@@ -0,0 +1,75 @@
+use wasm_bindgen::prelude::*;
+
+/// Counters and rates from one or many simulated years, as rustsim's Summary tuple
+///
+/// JavaScript has no tuples, so the fields get names instead.
+#[wasm_bindgen]
+#[derive(Clone, Copy)]
+pub struct Summary {
+    pub successful_transactions: usize,
+    pub successful_sales: usize,
+    pub failed_transactions: usize,
+    pub failed_sales: usize,
+    pub transaction_fill_rate: f64,
+    pub unit_fill_rate: f64,
+}
+
+impl From<rustsim::Summary> for Summary {
+    fn from((st, ss, ft, fs, tr, ur): rustsim::Summary) -> Summary {
+        Summary {
+            successful_transactions: st,
+            successful_sales: ss,
+            failed_transactions: ft,
+            failed_sales: fs,
+            transaction_fill_rate: tr,
+            unit_fill_rate: ur,
+        }
+    }
+}
+
+/// Simulation parameters, for JavaScript
+///
+/// This wraps the same engine as the rustsim Python module, so a browser what-if tool gets
+/// the same answers as the notebook that designed it, seed for seed.
+#[wasm_bindgen]
+pub struct Simulation {
+    sim: rustsim::Simulation,
+}
+
+#[wasm_bindgen]
+impl Simulation {
+    /// Like rustsim.Simulation(); leave the zipf exponents undefined for the defaults
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        safety_stock: usize,
+        lead_time: usize,
+        order_quantity: usize,
+        job_lot_zipf: Option<f64>,
+        itemwise_traffic_zipf: Option<f64>,
+        seed: Option<u64>,
+    ) -> Result<Simulation, JsValue> {
+        let sim = rustsim::Simulation::new(
+            safety_stock,
+            lead_time,
+            order_quantity,
+            job_lot_zipf,
+            itemwise_traffic_zipf,
+            seed,
+        );
+        // Python would raise on these as soon as a year started; here it would abort the page
+        sim.check_parameters().map_err(|error| JsValue::from_str(&error))?;
+        Ok(Simulation { sim })
+    }
+
+    /// Simulate one year
+    pub fn simulate_demand(&self, starting_quantity: usize) -> Summary {
+        self.sim.simulate_summary(starting_quantity, &mut self.sim.rng()).into()
+    }
+
+    /// Repeat the simulation `count` times and add it all up
+    ///
+    /// This runs on the page's thread, so keep `count` modest, or call it from a web worker.
+    pub fn repeat_simulate_demand(&self, starting_quantity: usize, count: usize) -> Summary {
+        self.sim.repeat_summary(starting_quantity, count).into()
+    }
+}
@@ -0,0 +1,4 @@
+fn main() {
+    // Generates the server (and a client, for tests and other Rust callers) from the schema
+    tonic_build::compile_protos("proto/simserver.proto").unwrap();
+}
@@ -0,0 +1,137 @@
+//! What every kind of server does with a request, once it's been decoded
+//!
+//! The protocol modules turn their own message types into these and back, so gRPC and any
+//! other front end check parameters and run sweeps the same way.
+//!
+//! Simulate and Sweep run on rustsim's rayon backend, over all the cores, unless the server
+//! was started with --opencl, in which case they run on the default OpenCL device, through
+//! rustoclsim's SimBackend.
+//! Optimize and farmed out shards always stay on the CPU: the search takes a few thousand
+//! years at a time, and a shard gives every year its own stream, neither of which a kernel
+//! launch is any good at.
+#[cfg(feature = "opencl")]
+use crate::metrics;
+use rayon::prelude::*;
+#[cfg(feature = "opencl")]
+use rustoclsim::backend::OpenCl;
+use rustsim::schema::{self, Config};
+use rustsim::streams;
+use rustsim::{Simulation, Summary};
+use std::convert::TryFrom;
+#[cfg(feature = "opencl")]
+use std::sync::{Mutex, OnceLock};
+
+/// The device, once --opencl asks for it. There's only one, so requests take turns; keeping
+/// it configured between them means a sweep builds its zipf tables once, not per candidate.
+#[cfg(feature = "opencl")]
+static OPENCL: OnceLock<Mutex<OpenCl>> = OnceLock::new();
+
+/// Run Simulate and Sweep on the default OpenCL device from now on, or say why there isn't one
+#[cfg(feature = "opencl")]
+pub fn use_opencl() -> Result<(), String> {
+    metrics::set_device_memory(rustoclsim::backend::device_memory()?);
+    OPENCL.get_or_init(Default::default);
+    Ok(())
+}
+
+/// Without the opencl feature there's no device to run on
+#[cfg(not(feature = "opencl"))]
+pub fn use_opencl() -> Result<(), String> {
+    Err("This simserver was built without OpenCL; rebuild it with --features opencl".into())
+}
+
+/// A reorder policy and the customers it faces, like the arguments to Simulation()
+pub struct Policy {
+    pub safety_stock: usize,
+    pub lead_time: usize,
+    pub order_quantity: usize,
+    pub job_lot_zipf: Option<f64>,
+    pub itemwise_traffic_zipf: Option<f64>,
+}
+
+impl Policy {
+    /// The simulation for this policy, or why it makes no sense
+    ///
+    /// The engine itself would panic partway through a year on these, taking a worker thread
+    /// with it, so we check them up front, the same way rustsim checks a saved config.
+    pub fn simulation(&self, seed: Option<u64>) -> Result<Simulation, String> {
+        Simulation::try_from(Config {
+            version: schema::VERSION,
+            safety_stock: self.safety_stock,
+            lead_time: self.lead_time,
+            order_quantity: self.order_quantity,
+            job_lot_zipf: self.job_lot_zipf,
+            itemwise_traffic_zipf: self.itemwise_traffic_zipf,
+            seed,
+            policy: None,
+            job_lot_mixture: None,
+            itemwise_traffic_mixture: None,
+            max_request: None,
+            max_request_mode: None,
+            compound_poisson: None,
+            forecast_bias: None,
+            forecast_mape: None,
+        })
+    }
+}
+
+/// Repeat `sim` `count` times from `starting_quantity` and add it all up, on the device if
+/// there is one. The CPU only fails on parameters a Simulation can't have, which never get
+/// this far; the device can fail if it can't be found or the kernel won't run.
+pub fn repeat(sim: &Simulation, starting_quantity: usize, count: usize) -> Result<Summary, String> {
+    #[cfg(feature = "opencl")]
+    {
+        if let Some(opencl) = OPENCL.get() {
+            let mut opencl = opencl.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            // One batch: the kernel splits it across the device itself
+            let summary = rustsim::backend::repeat(&mut *opencl, sim, starting_quantity, count, count, &mut sim.rng())?;
+            // With no years there was no batch, and the timings are the last request's
+            if count > 0 {
+                if let Some(timings) = opencl.last_timings() {
+                    metrics::record_opencl(&timings);
+                }
+            }
+            return Ok(summary);
+        }
+    }
+    // One batch too: rayon splits it across the cores, the same way however many there are
+    let mut parallel = rustsim::backend::Parallel::default();
+    rustsim::backend::repeat(&mut parallel, sim, starting_quantity, count, count, &mut sim.rng())
+}
+
+/// The simulation for a whole rustsim config, or why there can't be one
+///
+/// This is how rustsim reads a saved config, so it checks everything Simulation.from_json()
+/// would. `seed`, if there is one, wins over the config's own.
+pub fn simulation(config: &Config, seed: Option<u64>) -> Result<Simulation, String> {
+    Simulation::try_from(Config { seed: seed.or(config.seed), ..config.clone() })
+}
+
+/// Simulate each (simulation, starting quantity) `count` times, telling `done` about each
+/// one's totals (with its index) as soon as it finishes
+///
+/// On the CPU they run in parallel. On the device they take turns, since each one already
+/// has the whole device to itself.
+pub fn sweep<F>(candidates: &[(Simulation, usize)], count: usize, done: F)
+where
+    F: Fn(usize, Result<Summary, String>) + Sync,
+{
+    #[cfg(feature = "opencl")]
+    {
+        if OPENCL.get().is_some() {
+            for (index, (sim, starting_quantity)) in candidates.iter().enumerate() {
+                done(index, repeat(sim, *starting_quantity, count));
+            }
+            return;
+        }
+    }
+    candidates
+        .par_iter()
+        .enumerate()
+        .for_each(|(index, (sim, starting_quantity))| done(index, repeat(sim, *starting_quantity, count)));
+}
+
+/// The seed to share between sweep candidates, so they all see the same customers
+pub fn sweep_seed(seed: Option<u64>) -> u64 {
+    seed.unwrap_or_else(streams::fresh_seed)
+}
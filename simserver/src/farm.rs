@@ -0,0 +1,205 @@
+//! Farming one big run out to worker machines, for studies too big for one box
+//!
+//! Workers (`simserver --worker`) take shards over plain TCP, one JSON line each way: a Shard
+//! in, a Summary (or {"error": "..."}) out. The coordinator (`simserver --farm`) splits the
+//! repetitions into shards, hands them to whichever worker is free, and adds up the results.
+//!
+//! Every repetition has its own random number stream, picked out by the run's seed and its
+//! number (see rustsim's streams.rs), and each shard covers a range of those numbers. So the
+//! totals only depend on the seed and the count: they come out the same no matter how many
+//! workers there are, or which one ran what, or whether a shard had to be retried elsewhere
+//! after a worker fell over.
+use crate::engine::simulation;
+use crate::http::{SimulateRequest, Summary};
+use crate::metrics::{Job, Method};
+use rustsim::schema::Config;
+use rustsim::streams;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::ops::Range;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+/// Part of a run, for one worker to simulate
+#[derive(Deserialize, Serialize)]
+struct Shard {
+    params: Config,
+    starting_quantity: usize,
+    /// The run's seed
+    master: u64,
+    /// Which of its repetitions to simulate, one stream each
+    years: Range<u64>,
+}
+
+/// What a worker says back
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum Reply {
+    Done(Summary),
+    Failed { error: String },
+}
+
+/// Serve shards on `address` until killed, each connection on its own thread
+pub fn work(address: SocketAddr) -> std::io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    for connection in listener.incoming() {
+        let connection = connection?;
+        thread::spawn(move || {
+            if let Err(error) = serve(connection) {
+                eprintln!("simserver worker: a connection failed: {}", error);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Answer every shard on one connection, until the coordinator hangs up
+fn serve(connection: TcpStream) -> std::io::Result<()> {
+    let mut writer = connection.try_clone()?;
+    for line in BufReader::new(connection).lines() {
+        let reply = match serde_json::from_str::<Shard>(&line?) {
+            Ok(shard) => match simulation(&shard.params, None) {
+                Ok(sim) => {
+                    let years = shard.years.end.saturating_sub(shard.years.start) as usize;
+                    let summary = Job::new(Method::Shard)
+                        .run(years, || sim.repeat_summary_streams(shard.starting_quantity, shard.master, shard.years));
+                    Reply::Done(summary.into())
+                }
+                Err(error) => Reply::Failed { error },
+            },
+            Err(error) => Reply::Failed { error: format!("Not a shard: {}", error) },
+        };
+        serde_json::to_writer(&mut writer, &reply)?;
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Why a shard didn't come back
+enum Failure {
+    /// We lost the worker, so the shard should go to another
+    Lost(String),
+    /// The worker looked at the shard and said no, and so would any other
+    Refused(String),
+}
+
+/// Send one shard and wait for its totals
+fn send(connection: &mut BufReader<TcpStream>, shard: &Shard) -> Result<rustsim::Summary, Failure> {
+    let lost = |error: std::io::Error| Failure::Lost(error.to_string());
+    let mut line = serde_json::to_string(shard).map_err(|error| Failure::Refused(error.to_string()))?;
+    line.push('\n');
+    connection.get_mut().write_all(line.as_bytes()).map_err(lost)?;
+    let mut reply = String::new();
+    if connection.read_line(&mut reply).map_err(lost)? == 0 {
+        return Err(Failure::Lost("the worker hung up".into()));
+    }
+    match serde_json::from_str(&reply) {
+        Ok(Reply::Done(summary)) => Ok(summary.into()),
+        Ok(Reply::Failed { error }) => Err(Failure::Refused(error)),
+        Err(error) => Err(Failure::Lost(format!("the worker made no sense: {}", error))),
+    }
+}
+
+/// The shards still to simulate, and how many are out with workers
+struct Work {
+    queue: VecDeque<Range<u64>>,
+    /// Shards sent and not yet back. Until this is 0, one of them might come back lost and
+    /// need a worker, so idle workers wait rather than leave.
+    in_flight: usize,
+    /// Why the run failed, if a worker refused a shard
+    refused: Option<String>,
+}
+
+impl Work {
+    /// The next shard to send, waiting while the queue is empty but shards are still out, or
+    /// None once there's nothing left that could need a worker
+    fn next(work: &Mutex<Work>, changed: &Condvar) -> Option<Range<u64>> {
+        let mut work = work.lock().unwrap();
+        loop {
+            if work.refused.is_some() {
+                return None;
+            }
+            if let Some(years) = work.queue.pop_front() {
+                work.in_flight += 1;
+                return Some(years);
+            }
+            if work.in_flight == 0 {
+                return None;
+            }
+            work = changed.wait(work).unwrap();
+        }
+    }
+
+    /// A shard came back (or didn't), so wake the waiting workers to look again
+    fn settle(work: &Mutex<Work>, changed: &Condvar, settle: impl FnOnce(&mut Work)) {
+        let mut work = work.lock().unwrap();
+        work.in_flight -= 1;
+        settle(&mut work);
+        changed.notify_all();
+    }
+}
+
+/// Run `request` across `workers`, in `shards` pieces, and add up the results
+///
+/// A worker that can't be reached or drops a connection is left out from then on, and its
+/// shard goes back in the queue for the others. A shard the worker refuses (because the
+/// parameters make no sense) fails the whole run, since every worker would refuse it.
+pub fn farm(workers: &[String], request: SimulateRequest, shards: usize) -> Result<Summary, String> {
+    simulation(&request.params, None)?;
+    let master = request.seed.unwrap_or_else(streams::fresh_seed);
+    let count = request.count as u64;
+    let shard_size = count.div_ceil(shards.max(1) as u64).max(1);
+    let queue: VecDeque<Range<u64>> =
+        (0..count).step_by(shard_size as usize).map(|start| start..(start + shard_size).min(count)).collect();
+    let work = Mutex::new(Work { queue, in_flight: 0, refused: None });
+    let changed = Condvar::new();
+    let totals = Mutex::new((0, 0, 0, 0));
+
+    thread::scope(|scope| {
+        for worker in workers {
+            let (work, changed, totals, request) = (&work, &changed, &totals, &request);
+            scope.spawn(move || {
+                let mut connection = match TcpStream::connect(worker) {
+                    Ok(connection) => BufReader::new(connection),
+                    Err(error) => return eprintln!("simserver farm: can't reach {}: {}", worker, error),
+                };
+                while let Some(years) = Work::next(work, changed) {
+                    let shard = Shard {
+                        params: request.params.clone(),
+                        starting_quantity: request.starting_quantity,
+                        master,
+                        years: years.clone(),
+                    };
+                    match send(&mut connection, &shard) {
+                        Ok((st, ss, ft, fs, _, _)) => {
+                            let mut totals = totals.lock().unwrap();
+                            *totals = (totals.0 + st, totals.1 + ss, totals.2 + ft, totals.3 + fs);
+                            drop(totals);
+                            Work::settle(work, changed, |_| {});
+                        }
+                        Err(Failure::Lost(error)) => {
+                            eprintln!("simserver farm: lost {}: {}", worker, error);
+                            return Work::settle(work, changed, |work| work.queue.push_back(years));
+                        }
+                        Err(Failure::Refused(error)) => {
+                            let problem = format!("{} refused a shard: {}", worker, error);
+                            return Work::settle(work, changed, |work| work.refused = Some(problem));
+                        }
+                    }
+                }
+            });
+        }
+    });
+
+    let work = work.into_inner().unwrap();
+    if let Some(problem) = work.refused {
+        return Err(problem);
+    }
+    if !work.queue.is_empty() {
+        return Err(format!("Ran out of workers with {} shards still to go", work.queue.len()));
+    }
+    let (st, ss, ft, fs) = totals.into_inner().unwrap();
+    Ok(rustsim::summarize(st, ss, ft, fs).into())
+}
@@ -0,0 +1,119 @@
+//! The gRPC front end, generated from proto/simserver.proto
+use crate::engine::{self, Policy};
+use crate::metrics::{Job, Method};
+use proto::simulator_server::Simulator;
+use proto::{
+    OptimizeReply, OptimizeRequest, Parameters, SimulateRequest, Summary, SweepRequest, SweepResult,
+};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+pub mod proto {
+    tonic::include_proto!("simserver");
+}
+
+pub use proto::simulator_server::SimulatorServer;
+
+impl From<rustsim::Summary> for Summary {
+    fn from((st, ss, ft, fs, tr, ur): rustsim::Summary) -> Summary {
+        Summary {
+            successful_transactions: st as u64,
+            successful_sales: ss as u64,
+            failed_transactions: ft as u64,
+            failed_sales: fs as u64,
+            transaction_fill_rate: tr,
+            unit_fill_rate: ur,
+        }
+    }
+}
+
+/// The simulation for a request's parameters, or INVALID_ARGUMENT explaining what's wrong
+fn simulation(params: Option<Parameters>, seed: Option<u64>) -> Result<rustsim::Simulation, Status> {
+    let params = params.ok_or_else(|| Status::invalid_argument("params is required"))?;
+    Policy {
+        safety_stock: params.safety_stock as usize,
+        lead_time: params.lead_time as usize,
+        order_quantity: params.order_quantity as usize,
+        job_lot_zipf: params.job_lot_zipf,
+        itemwise_traffic_zipf: params.itemwise_traffic_zipf,
+    }
+    .simulation(seed)
+    .map_err(Status::invalid_argument)
+}
+
+/// Run `work` on a thread that's allowed to block, since a simulation can take a while
+async fn blocking<T, F>(work: F) -> Result<T, Status>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|error| Status::internal(format!("The simulation failed: {}", error)))
+}
+
+#[derive(Default)]
+pub struct Service;
+
+#[tonic::async_trait]
+impl Simulator for Service {
+    async fn simulate(&self, request: Request<SimulateRequest>) -> Result<Response<Summary>, Status> {
+        let request = request.into_inner();
+        let sim = simulation(request.params, request.seed)?;
+        let (starting_quantity, count) = (request.starting_quantity as usize, request.count as usize);
+        let job = Job::new(Method::Simulate);
+        let summary = blocking(move || job.run(count, || engine::repeat(&sim, starting_quantity, count)))
+            .await?
+            .map_err(Status::internal)?;
+        Ok(Response::new(summary.into()))
+    }
+
+    type SweepStream = ReceiverStream<Result<SweepResult, Status>>;
+
+    async fn sweep(&self, request: Request<SweepRequest>) -> Result<Response<Self::SweepStream>, Status> {
+        let request = request.into_inner();
+        let seed = engine::sweep_seed(request.seed);
+        // Check every candidate before starting any, so a bad one can't fail a sweep halfway
+        let mut candidates = Vec::with_capacity(request.candidates.len());
+        for candidate in request.candidates {
+            let safety_stock = candidate.params.as_ref().map_or(0, |params| params.safety_stock);
+            let starting_quantity = candidate.starting_quantity.unwrap_or(safety_stock) as usize;
+            candidates.push((simulation(candidate.params, Some(seed))?, starting_quantity));
+        }
+        let count = request.count as usize;
+        let (sender, receiver) = mpsc::channel(64);
+        let job = Job::new(Method::Sweep);
+        tokio::task::spawn_blocking(move || {
+            job.run(count * candidates.len(), || {
+                engine::sweep(&candidates, count, |index, summary| {
+                    // If the client hung up, there's nobody to send to, but the rest still finish
+                    let _ = sender.blocking_send(
+                        summary
+                            .map(|summary| SweepResult { index: index as u32, summary: Some(summary.into()) })
+                            .map_err(Status::internal),
+                    );
+                })
+            });
+        });
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+
+    async fn optimize(&self, request: Request<OptimizeRequest>) -> Result<Response<OptimizeReply>, Status> {
+        let request = request.into_inner();
+        let sim = simulation(request.params, request.seed)?;
+        let (target, count, max_quantity) =
+            (request.target_fill_rate, request.count as usize, request.max_quantity as usize);
+        let job = Job::new(Method::Optimize);
+        let found =
+            blocking(move || job.run(0, || sim.smallest_starting_quantity(target, count, max_quantity))).await?;
+        Ok(Response::new(match found {
+            Some((starting_quantity, unit_fill_rate)) => OptimizeReply {
+                found: true,
+                starting_quantity: starting_quantity as u64,
+                unit_fill_rate,
+            },
+            None => OptimizeReply::default(),
+        }))
+    }
+}
@@ -0,0 +1,108 @@
+//! The HTTP front end: JSON in, JSON out
+//!
+//! The bodies have the same shape as the gRPC messages in proto/simserver.proto (in their
+//! JSON form), except that params is a whole rustsim config, as Simulation.to_json() writes
+//! it, so a policy, mixtures or compound Poisson demand can come along too. Bad parameters
+//! get a 400 with {"error": "..."}, and a device that fails under --opencl a 500.
+use crate::engine::{self, simulation};
+use crate::metrics::{self, Job, Method};
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use rustsim::schema::Config;
+use serde::Deserialize;
+use std::sync::Mutex;
+
+#[derive(Deserialize)]
+pub struct SimulateRequest {
+    pub params: Config,
+    pub starting_quantity: usize,
+    pub count: usize,
+    pub seed: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct Candidate {
+    pub params: Config,
+    /// Leave it out to start at the safety stock
+    pub starting_quantity: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct SweepRequest {
+    pub candidates: Vec<Candidate>,
+    pub count: usize,
+    /// Every candidate uses this seed, so they all see the same customers
+    pub seed: Option<u64>,
+}
+
+/// Counters and rates from many simulated years, in rustsim's own format
+pub use rustsim::schema::Totals as Summary;
+
+/// A status and a JSON body saying what went wrong
+type Error = (StatusCode, Json<serde_json::Value>);
+
+fn bad_request(problem: String) -> Error {
+    (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": problem })))
+}
+
+fn internal(problem: String) -> Error {
+    (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": problem })))
+}
+
+/// Run `work` on a thread that's allowed to block, since a simulation can take a while
+async fn blocking<T, F>(work: F) -> Result<T, Error>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|error| internal(format!("The simulation failed: {}", error)))
+}
+
+/// POST /simulate: repeat one policy and return the totals
+async fn simulate(Json(request): Json<SimulateRequest>) -> Result<Json<Summary>, Error> {
+    let sim = simulation(&request.params, request.seed).map_err(bad_request)?;
+    let (starting_quantity, count) = (request.starting_quantity, request.count);
+    let job = Job::new(Method::Simulate);
+    let summary = blocking(move || job.run(count, || engine::repeat(&sim, starting_quantity, count)))
+        .await?
+        .map_err(internal)?;
+    Ok(Json(summary.into()))
+}
+
+/// POST /sweep: simulate every candidate on the same customers, returning their totals in
+/// the same order as the candidates
+///
+/// Unlike the gRPC Sweep, this waits for all of them; a plain JSON body can't be streamed.
+async fn sweep(Json(request): Json<SweepRequest>) -> Result<Json<Vec<Summary>>, Error> {
+    let seed = engine::sweep_seed(request.seed);
+    let mut candidates = Vec::with_capacity(request.candidates.len());
+    for candidate in &request.candidates {
+        let starting_quantity = candidate.starting_quantity.unwrap_or(candidate.params.safety_stock);
+        candidates.push((simulation(&candidate.params, Some(seed)).map_err(bad_request)?, starting_quantity));
+    }
+    let count = request.count;
+    let job = Job::new(Method::Sweep);
+    let summaries = blocking(move || {
+        let results = Mutex::new(vec![None; candidates.len()]);
+        job.run(count * candidates.len(), || {
+            engine::sweep(&candidates, count, |index, summary| {
+                results.lock().unwrap()[index] = Some(summary);
+            })
+        });
+        results.into_inner().unwrap()
+    })
+    .await?;
+    let summaries: Result<Vec<rustsim::Summary>, String> = summaries.into_iter().flatten().collect();
+    Ok(Json(summaries.map_err(internal)?.into_iter().map(Summary::from).collect()))
+}
+
+/// Every endpoint
+pub fn router() -> Router {
+    Router::new()
+        .route("/simulate", post(simulate))
+        .route("/sweep", post(sweep))
+        .route("/metrics", get(metrics::handler))
+}
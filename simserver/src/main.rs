@@ -0,0 +1,143 @@
+//! simserver: the simulation engine as a network service
+//!
+//! Usage: `simserver [--http | --worker] [--listen ADDRESS] [--opencl]`. By default it serves
+//! gRPC on 0.0.0.0:50051, with the schema in proto/simserver.proto; with --http it serves JSON
+//! on 0.0.0.0:8080 instead (see http.rs), and with --worker it takes shards of a farmed out run
+//! on 0.0.0.0:7070 (see farm.rs). --opencl runs Simulate and Sweep on the default OpenCL device,
+//! in a build with the opencl feature (see engine.rs).
+//!
+//! `simserver --farm HOST:PORT,HOST:PORT,... [--shards N]` is the other end of that: it reads
+//! a /simulate request from stdin, runs it on those workers, and prints the totals.
+//!
+//! Prometheus metrics are at GET /metrics in HTTP mode; in the others, --metrics ADDRESS
+//! serves them there (see metrics.rs).
+mod engine;
+mod farm;
+mod grpc;
+mod http;
+mod metrics;
+
+use std::io::Read;
+use std::net::SocketAddr;
+
+const USAGE: &str = "Usage: simserver [--http | --worker] [--listen ADDRESS] [--metrics ADDRESS] [--opencl]
+       simserver --farm HOST:PORT,... [--shards N] < request.json";
+
+/// What the command line asked for
+enum Mode {
+    Grpc(SocketAddr),
+    Http(SocketAddr),
+    Worker(SocketAddr),
+    Farm { workers: Vec<String>, shards: usize },
+}
+
+fn listen(address: &str) -> Result<SocketAddr, String> {
+    address.parse().map_err(|error| format!("Can't listen on {:?}: {}", address, error))
+}
+
+/// The mode, and where to serve metrics, if anywhere
+fn mode() -> Result<(Mode, Option<SocketAddr>), String> {
+    let mut args = std::env::args().skip(1);
+    let (mut http, mut worker, mut address, mut workers, mut shards) = (false, false, None, None, None);
+    let mut metrics = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--http" => http = true,
+            "--worker" => worker = true,
+            "--opencl" => engine::use_opencl()?,
+            "--listen" => address = Some(args.next().ok_or("--listen needs an address")?),
+            "--metrics" => metrics = Some(args.next().ok_or("--metrics needs an address")?),
+            "--farm" => workers = Some(args.next().ok_or("--farm needs a list of workers")?),
+            "--shards" => {
+                let n = args.next().ok_or("--shards needs a number")?;
+                shards = Some(n.parse().map_err(|_| format!("--shards needs a number, not {:?}", n))?);
+            }
+            _ => return Err(format!("Unknown argument {:?}\n{}", arg, USAGE)),
+        }
+    }
+    let metrics = metrics.as_deref().map(listen).transpose()?;
+    if let Some(workers) = workers {
+        let workers: Vec<String> = workers.split(',').map(String::from).collect();
+        // Enough shards that a slow worker doesn't hold everyone up at the end
+        let shards = shards.unwrap_or(10 * workers.len());
+        return Ok((Mode::Farm { workers, shards }, metrics));
+    }
+    let default = match (http, worker) {
+        (true, true) => return Err(format!("Pick one of --http and --worker\n{}", USAGE)),
+        (true, false) => "0.0.0.0:8080",
+        (false, true) => "0.0.0.0:7070",
+        (false, false) => "0.0.0.0:50051",
+    };
+    let address = listen(address.as_deref().unwrap_or(default))?;
+    let mode = if http {
+        Mode::Http(address)
+    } else if worker {
+        Mode::Worker(address)
+    } else {
+        Mode::Grpc(address)
+    };
+    Ok((mode, metrics))
+}
+
+/// Read a /simulate request from stdin, farm it out, and print the totals as JSON
+fn farm(workers: &[String], shards: usize) -> Result<(), String> {
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input).map_err(|error| error.to_string())?;
+    let request = serde_json::from_str(&input).map_err(|error| format!("Not a /simulate request: {}", error))?;
+    let summary = farm::farm(workers, request, shards)?;
+    println!("{}", serde_json::to_string(&summary).map_err(|error| error.to_string())?);
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    let (mode, metrics) = match mode() {
+        Ok(mode) => mode,
+        Err(problem) => {
+            eprintln!("{}", problem);
+            std::process::exit(2);
+        }
+    };
+    if let Some(address) = metrics {
+        eprintln!("simserver serving metrics on {}", address);
+        let listener = match tokio::net::TcpListener::bind(address).await {
+            Ok(listener) => listener,
+            Err(error) => {
+                eprintln!("simserver can't serve metrics on {}: {}", address, error);
+                std::process::exit(1);
+            }
+        };
+        tokio::spawn(async move { axum::serve(listener, metrics::router()).await });
+    }
+    let served = match mode {
+        Mode::Http(address) => {
+            eprintln!("simserver listening for HTTP on {}", address);
+            match tokio::net::TcpListener::bind(address).await {
+                Ok(listener) => axum::serve(listener, http::router()).await.map_err(|error| error.to_string()),
+                Err(error) => Err(error.to_string()),
+            }
+        }
+        Mode::Grpc(address) => {
+            eprintln!("simserver listening for gRPC on {}", address);
+            tonic::transport::Server::builder()
+                .add_service(grpc::SimulatorServer::new(grpc::Service))
+                .serve(address)
+                .await
+                .map_err(|error| error.to_string())
+        }
+        // These two are plain threads and blocking sockets, so keep them off the async workers
+        Mode::Worker(address) => {
+            eprintln!("simserver taking shards on {}", address);
+            tokio::task::spawn_blocking(move || farm::work(address).map_err(|error| error.to_string()))
+                .await
+                .unwrap_or_else(|error| Err(error.to_string()))
+        }
+        Mode::Farm { workers, shards } => tokio::task::spawn_blocking(move || farm(&workers, shards))
+            .await
+            .unwrap_or_else(|error| Err(error.to_string())),
+    };
+    if let Err(error) = served {
+        eprintln!("simserver stopped: {}", error);
+        std::process::exit(1);
+    }
+}
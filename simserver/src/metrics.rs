@@ -0,0 +1,140 @@
+//! Prometheus metrics, so whoever runs a shared simserver can see how busy it is
+//!
+//! Everything is a plain atomic, rendered by hand in Prometheus' text format on GET /metrics.
+//! Under --opencl there's the device too: the time its runs spent in each phase, as
+//! rustoclsim's last_timings() splits them, and how much memory it has. OpenCL can't say how
+//! much of that is in use, so that's the device's size, not its load. The process's resident
+//! memory, on Linux, is host memory only.
+//!
+//! Simulations per second is `rate(simserver_simulated_years_total[1m])`. Optimize doesn't add
+//! to that, because how many trials its search takes depends on where the answer is, but its
+//! time still counts in simserver_simulation_seconds_total.
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// The kinds of request we count separately
+#[derive(Clone, Copy)]
+pub enum Method {
+    Simulate,
+    Sweep,
+    Optimize,
+    /// A piece of a farmed out run, on a worker
+    Shard,
+}
+
+const METHODS: [&str; 4] = ["simulate", "sweep", "optimize", "shard"];
+
+static REQUESTS: [AtomicU64; 4] = [AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0)];
+static YEARS: AtomicU64 = AtomicU64::new(0);
+static NANOSECONDS: AtomicU64 = AtomicU64::new(0);
+static QUEUED: AtomicI64 = AtomicI64::new(0);
+/// Seconds the device's runs have spent in each phase, by name, in the order they first came
+static OPENCL_PHASES: Mutex<Vec<(&'static str, f64)>> = Mutex::new(Vec::new());
+/// The device's global memory, or 0 without --opencl
+static DEVICE_MEMORY: AtomicU64 = AtomicU64::new(0);
+
+/// Add one device run's time in each phase
+pub fn record_opencl(timings: &[(&'static str, f64)]) {
+    let mut phases = OPENCL_PHASES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    for &(name, seconds) in timings {
+        match phases.iter_mut().find(|(phase, _)| *phase == name) {
+            Some((_, total)) => *total += seconds,
+            None => phases.push((name, seconds)),
+        }
+    }
+}
+
+/// Say how much memory the device has
+pub fn set_device_memory(bytes: u64) {
+    DEVICE_MEMORY.store(bytes, Ordering::Relaxed);
+}
+
+/// One request's simulations, from when it's accepted until it's done
+///
+/// It counts toward the queue depth for as long as it's alive, whether it's still waiting for
+/// a thread or already running.
+pub struct Job;
+
+impl Job {
+    pub fn new(method: Method) -> Job {
+        REQUESTS[method as usize].fetch_add(1, Ordering::Relaxed);
+        QUEUED.fetch_add(1, Ordering::Relaxed);
+        Job
+    }
+
+    /// Run `work`, which simulates `years` years, and count its time
+    pub fn run<T, F: FnOnce() -> T>(&self, years: usize, work: F) -> T {
+        let started = Instant::now();
+        let result = work();
+        NANOSECONDS.fetch_add(started.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        YEARS.fetch_add(years as u64, Ordering::Relaxed);
+        result
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        QUEUED.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// The process's resident memory in bytes, if the OS will say
+fn resident_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// Every metric, in Prometheus' text exposition format
+pub fn render() -> String {
+    let mut out = String::new();
+    // Writing to a String can't fail
+    let _ = writeln!(out, "# HELP simserver_requests_total Requests accepted, by method");
+    let _ = writeln!(out, "# TYPE simserver_requests_total counter");
+    for (name, count) in METHODS.iter().zip(REQUESTS.iter()) {
+        let _ = writeln!(out, "simserver_requests_total{{method=\"{}\"}} {}", name, count.load(Ordering::Relaxed));
+    }
+    let _ = writeln!(out, "# HELP simserver_simulated_years_total Years simulated, counting every repetition");
+    let _ = writeln!(out, "# TYPE simserver_simulated_years_total counter");
+    let _ = writeln!(out, "simserver_simulated_years_total {}", YEARS.load(Ordering::Relaxed));
+    let _ = writeln!(out, "# HELP simserver_simulation_seconds_total Time spent running simulations, per request");
+    let _ = writeln!(out, "# TYPE simserver_simulation_seconds_total counter");
+    let seconds = NANOSECONDS.load(Ordering::Relaxed) as f64 / 1e9;
+    let _ = writeln!(out, "simserver_simulation_seconds_total {}", seconds);
+    let _ = writeln!(out, "# HELP simserver_queue_depth Requests waiting for or running simulations");
+    let _ = writeln!(out, "# TYPE simserver_queue_depth gauge");
+    let _ = writeln!(out, "simserver_queue_depth {}", QUEUED.load(Ordering::Relaxed));
+    if let Some(bytes) = resident_memory() {
+        let _ = writeln!(out, "# HELP simserver_process_resident_memory_bytes Host memory the process is using, not the device's");
+        let _ = writeln!(out, "# TYPE simserver_process_resident_memory_bytes gauge");
+        let _ = writeln!(out, "simserver_process_resident_memory_bytes {}", bytes);
+    }
+    let phases = OPENCL_PHASES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if !phases.is_empty() {
+        let _ = writeln!(out, "# HELP simserver_opencl_phase_seconds_total Time the device's runs spent in each phase");
+        let _ = writeln!(out, "# TYPE simserver_opencl_phase_seconds_total counter");
+        for (name, seconds) in phases.iter() {
+            let _ = writeln!(out, "simserver_opencl_phase_seconds_total{{phase=\"{}\"}} {}", name, seconds);
+        }
+    }
+    let device_memory = DEVICE_MEMORY.load(Ordering::Relaxed);
+    if device_memory > 0 {
+        let _ = writeln!(out, "# HELP simserver_opencl_device_memory_bytes Global memory the device has, used or not");
+        let _ = writeln!(out, "# TYPE simserver_opencl_device_memory_bytes gauge");
+        let _ = writeln!(out, "simserver_opencl_device_memory_bytes {}", device_memory);
+    }
+    out
+}
+
+/// GET /metrics
+pub async fn handler() -> ([(axum::http::HeaderName, &'static str); 1], String) {
+    ([(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")], render())
+}
+
+/// Just the metrics endpoint, for the modes that don't serve HTTP themselves
+pub fn router() -> axum::Router {
+    axum::Router::new().route("/metrics", axum::routing::get(handler))
+}